@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use polymc::meta::DEFAULT_META_SERVERS;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+
+/// Accounts saved with [`polymc::auth::AuthProfile::save_all`] in the config directory.
+pub(crate) const ACCOUNTS_FILE: &str = "accounts.json";
+
+/// Settings shared by all commands, stored in the config directory of the user.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GlobalConfig {
+    /// Directory holding the `lib`, `assets` and `meta` stores, the data directory if not set.
+    #[serde(default)]
+    pub store: Option<PathBuf>,
+    /// Library directory of another launcher used as `lib` store, see `plmc store adopt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adopted_lib: Option<PathBuf>,
+    /// Assets directory of another launcher used as `assets` store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adopted_assets: Option<PathBuf>,
+    /// Meta servers the fastest is chosen from if no base url is given, the known ones if empty.
+    /// They should be mirrors of the same meta data.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meta_servers: Vec<String>,
+}
+
+impl GlobalConfig {
+    /// Directory of the config and other files of the user, like the accounts.
+    pub fn dir() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap();
+        path.push("plmc");
+        path
+    }
+
+    pub fn path() -> PathBuf {
+        Self::dir().join("config.json")
+    }
+
+    /// Load the config, the default one if it does not exist yet.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        match OpenOptions::new().read(true).open(&path) {
+            Ok(file) => {
+                serde_json::from_reader(file).with_context(|| format!("Reading {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Opening {}", path.display())),
+        }
+    }
+
+    /// Keys of the config file, others are ignored when loading it.
+    pub fn keys() -> Vec<String> {
+        let config = Self {
+            store: Some(PathBuf::new()),
+            adopted_lib: Some(PathBuf::new()),
+            adopted_assets: Some(PathBuf::new()),
+            meta_servers: vec![String::new()],
+        };
+        match serde_json::to_value(config) {
+            Ok(serde_json::Value::Object(keys)) => keys.into_iter().map(|(key, _)| key).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Directory of another launcher used as the `lib` or `assets` store.
+    pub fn adopted(&self, sub: &str) -> Option<&PathBuf> {
+        match sub {
+            "lib" => self.adopted_lib.as_ref(),
+            "assets" => self.adopted_assets.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Meta servers to choose from, see [`GlobalConfig::meta_servers`].
+    pub fn meta_servers(&self) -> Vec<String> {
+        if self.meta_servers.is_empty() {
+            DEFAULT_META_SERVERS
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        } else {
+            self.meta_servers.clone()
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .with_context(|| format!("Writing {}", path.display()))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::http::HttpClient;
+use crate::modrinth::json;
+use anyhow::Result;
+use clap::{Arg, ArgMatches};
+use hyper::header::HeaderValue;
+use hyper::Request;
+use polymc::instance::check_file_name;
+use polymc::meta::manifest::{LibraryDownload, Sha1Sum};
+use polymc::meta::DownloadRequest;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) const DEFAULT_API_URL: &str = "https://api.curseforge.com/v1";
+
+/// Hash algorithm of [`FileHash`] for sha1.
+const ALGO_SHA1: u32 = 1;
+
+/// Arguments for the CurseForge API, which is only used with an API key.
+pub(crate) fn args() -> [Arg<'static>; 2] {
+    [
+        Arg::new("curseforge_url")
+            .long("curseforge-url")
+            .env("PLMC_CURSEFORGE_URL")
+            .takes_value(true)
+            .default_value(DEFAULT_API_URL)
+            .help("Url of the CurseForge API"),
+        Arg::new("curseforge_key")
+            .long("curseforge-key")
+            .env("PLMC_CURSEFORGE_API_KEY")
+            .takes_value(true)
+            .help("API key to look up mods on CurseForge"),
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintMatches {
+    exact_matches: Vec<FingerprintMatch>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FingerprintMatch {
+    file: File,
+}
+
+/// File of a mod on CurseForge.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct File {
+    pub id: u64,
+    pub mod_id: u64,
+    pub display_name: String,
+    pub file_name: String,
+    /// Missing if the author does not allow third party launchers to download the file.
+    #[serde(default)]
+    pub download_url: Option<String>,
+    #[serde(default)]
+    pub hashes: Vec<FileHash>,
+    pub file_date: String,
+    pub file_length: i64,
+    pub file_fingerprint: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FileHash {
+    pub value: String,
+    pub algo: u32,
+}
+
+impl File {
+    /// Request to download the file into `dir`, `None` if it may not be downloaded.
+    ///
+    /// Fails if the API returned a file name which is not a plain file name.
+    pub fn request(&self, dir: &Path) -> Result<Option<DownloadRequest>> {
+        check_file_name(&self.file_name)?;
+        let sha1: Option<Sha1Sum> = self
+            .hashes
+            .iter()
+            .find(|h| h.algo == ALGO_SHA1)
+            .and_then(|h| h.value.parse().ok());
+
+        Ok(self.download_url.as_ref().map(|url| {
+            DownloadRequest::new_library(
+                LibraryDownload {
+                    sha1,
+                    size: self.file_length,
+                    url: url.clone(),
+                },
+                dir.join(&self.file_name),
+            )
+        }))
+    }
+}
+
+pub(crate) struct CurseForge {
+    client: HttpClient,
+    api_url: String,
+    key: String,
+}
+
+impl CurseForge {
+    /// Client for the API, `None` without an API key.
+    pub fn new(client: HttpClient, sub_matches: &ArgMatches) -> Option<Self> {
+        Some(Self {
+            client,
+            api_url: sub_matches
+                .value_of("curseforge_url")
+                .unwrap_or(DEFAULT_API_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            key: sub_matches.value_of("curseforge_key")?.to_string(),
+        })
+    }
+
+    /// Files with the [`polymc::instance::curseforge_fingerprint`]s `fingerprints`, by
+    /// fingerprint.
+    pub async fn match_fingerprints(&self, fingerprints: &[u32]) -> Result<HashMap<u32, File>> {
+        let url = format!("{}/fingerprints", self.api_url);
+        let body = json!({ "fingerprints": fingerprints });
+        let request = Request::post(&url).header("content-type", "application/json");
+        let matches: FingerprintMatches = self
+            .send(&url, request, hyper::Body::from(serde_json::to_vec(&body)?))
+            .await?;
+
+        Ok(matches
+            .exact_matches
+            .into_iter()
+            .map(|m| (m.file.file_fingerprint, m.file))
+            .collect())
+    }
+
+    /// Newest file of the mod `mod_id` for the game version and mod loader.
+    pub async fn latest_file(
+        &self,
+        mod_id: u64,
+        game_version: Option<&str>,
+        loader: Option<&str>,
+    ) -> Result<Option<File>> {
+        let mut url = format!("{}/mods/{}/files?", self.api_url, mod_id);
+        if let Some(version) = game_version {
+            url.push_str(&format!("gameVersion={}&", version));
+        }
+        if let Some(loader_type) = loader.and_then(mod_loader_type) {
+            url.push_str(&format!("modLoaderType={}", loader_type));
+        }
+
+        let files: Vec<File> = self
+            .send(&url, Request::get(&url), hyper::Body::empty())
+            .await?;
+        Ok(files
+            .into_iter()
+            .max_by(|a, b| a.file_date.cmp(&b.file_date)))
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        request: hyper::http::request::Builder,
+        body: hyper::Body,
+    ) -> Result<T> {
+        let request = request.header("x-api-key", HeaderValue::from_str(&self.key)?);
+        let data: Data<T> = json(url, self.client.send(request, body).await?).await?;
+        Ok(data.data)
+    }
+}
+
+/// Id of the mod loader `loader` as named by Modrinth, e.g. `fabric`.
+fn mod_loader_type(loader: &str) -> Option<u32> {
+    match loader {
+        "forge" => Some(1),
+        "fabric" => Some(4),
+        "quilt" => Some(5),
+        _ => None,
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+use std::fs::File;
+
+pub(crate) fn app() -> App<'static> {
+    crate::run::args(
+        App::new("debug-bundle")
+            .about(
+                "Collect logs, crash reports and the launch command of an instance for bug reports",
+            )
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .takes_value(true)
+                    .default_value("plmc-debug.zip")
+                    .help("Zip file to write the bundle to"),
+            ),
+    )
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    // Only what is already there is collected, a broken setup is not downloaded again
+    let (instance, java, auth, _) = match crate::run::prepare(sub_matches, true).await? {
+        Some(prepared) => prepared,
+        None => return Ok(1),
+    };
+
+    let output = sub_matches.value_of("output").unwrap();
+    let file = File::create(output).with_context(|| format!("Creating {}", output))?;
+    polymc::debug::write_debug_bundle(&instance, &java, &auth, file)?;
+    println!("Wrote debug bundle to {}", output);
+
+    Ok(0)
+}
@@ -0,0 +1,538 @@
+use crate::config::GlobalConfig;
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches};
+use console::style;
+use polymc::instance::{InstanceInfo, INSTANCE_INFO_FILE};
+use polymc::java_wrapper::Java;
+use polymc::lock::{DirLock, LOCK_FILE_NAME};
+use polymc::meta::manifest::Manifest;
+use polymc::meta::{AssetIndex, MetaIndex, PackageIndex, ASSET_DEFAULT_URL};
+use serde::de::DeserializeOwned;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("doctor")
+        .about("Check the setup of the launcher and print fixes for problems")
+        .arg(
+            Arg::new("meta_url")
+                .long("base-url")
+                .env("PLMC_BASE_URL")
+                .help("Base url of the meta server to check, the configured ones if not given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("assets_url")
+                .long("assets-url")
+                .env("PLMC_ASSETS_URL")
+                .takes_value(true)
+                .help("Base url to download assets from, e.g. a mirror"),
+        )
+        .arg(
+            Arg::new("java")
+                .long("java")
+                .short('j')
+                .env("PLMC_JAVA")
+                .takes_value(true)
+                .help("Path to the java executable")
+                .default_value("java"),
+        )
+        .arg(
+            Arg::new("lib_dir")
+                .long("lib-dir")
+                .takes_value(true)
+                .env("PLMC_LIB_DIR"),
+        )
+        .arg(
+            Arg::new("meta_dir")
+                .long("meta-dir")
+                .takes_value(true)
+                .env("PLMC_META_DIR"),
+        )
+        .arg(
+            Arg::new("mc_dir")
+                .long("mc-dir")
+                .short('d')
+                .env("PLMC_MC_DIR")
+                .takes_value(true)
+                .help("The Minecraft directory"),
+        )
+        .arg(
+            Arg::new("assets_dir")
+                .long("assets-dir")
+                .env("PLMC_ASSETS_DIR")
+                .takes_value(true),
+        );
+    crate::http::args(app)
+}
+
+/// Result of a single check.
+enum Check {
+    Ok(String),
+    /// Not critical, the launcher still works.
+    Warn(String, String),
+    /// The launcher can not work until this is fixed.
+    Fail(String, String),
+}
+
+impl Check {
+    fn print(&self) {
+        match self {
+            Check::Ok(msg) => println!("{} {}", style("ok").green().bold(), msg),
+            Check::Warn(msg, fix) => {
+                println!("{} {}", style("warn").yellow().bold(), msg);
+                println!("     {}", fix);
+            }
+            Check::Fail(msg, fix) => {
+                println!("{} {}", style("fail").red().bold(), msg);
+                println!("     {}", fix);
+            }
+        }
+    }
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = |arg: &str, sub: &str| {
+        sub_matches
+            .value_of(arg)
+            .map(ToString::to_string)
+            .unwrap_or_else(|| crate::run::get_store_dir(sub))
+    };
+    let meta_dir = dir("meta_dir", "meta");
+    let lib_dir = dir("lib_dir", "lib");
+    let assets_dir = dir("assets_dir", "assets");
+
+    let mut checks = check_config();
+    checks.extend(check_servers(sub_matches).await);
+    for (name, path) in [
+        ("meta", &meta_dir),
+        ("library", &lib_dir),
+        ("assets", &assets_dir),
+    ] {
+        checks.push(check_dir(name, path));
+    }
+    checks.push(check_meta_cache(&meta_dir));
+    checks.push(check_asset_store(&assets_dir));
+    checks.push(check_partial_files(&[&meta_dir, &lib_dir, &assets_dir]));
+    let mc_dir = sub_matches
+        .value_of("mc_dir")
+        .map(ToString::to_string)
+        .unwrap_or_else(|| crate::run::get_dir("game"));
+    checks.push(check_instance(&mc_dir));
+    checks.extend(check_java(sub_matches.value_of("java").unwrap()));
+
+    for check in &checks {
+        check.print();
+    }
+
+    if checks.iter().any(|c| matches!(c, Check::Fail(..))) {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Check that the config file parses and its values are usable.
+fn check_config() -> Vec<Check> {
+    let path = GlobalConfig::path();
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return vec![Check::Ok(format!(
+                "No config at {}, using the defaults",
+                path.display()
+            ))]
+        }
+        Err(e) => {
+            return vec![Check::Fail(
+                format!("Could not read the config {}: {}", path.display(), e),
+                format!("Fix the permissions of {}", path.display()),
+            )]
+        }
+    };
+    let config: GlobalConfig = match serde_json::from_slice(&data) {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Check::Fail(
+                format!("Config {} is invalid: {}", path.display(), e),
+                format!("Fix or remove {}", path.display()),
+            )]
+        }
+    };
+
+    let mut checks = Vec::new();
+    let keys = GlobalConfig::keys();
+    let value: serde_json::Value = serde_json::from_slice(&data).unwrap_or_default();
+    let unknown: Vec<_> = value
+        .as_object()
+        .into_iter()
+        .flat_map(|o| o.keys())
+        .filter(|key| !keys.contains(key))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        checks.push(Check::Warn(
+            format!(
+                "Config {} has unknown keys: {}",
+                path.display(),
+                unknown.join(", ")
+            ),
+            format!("They are ignored, known keys are {}", keys.join(", ")),
+        ));
+    }
+
+    for url in &config.meta_servers {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            checks.push(Check::Fail(
+                format!("Meta server {} in the config is not an http(s) url", url),
+                format!("Fix metaServers in {}", path.display()),
+            ));
+        }
+    }
+    if let Some(store) = config.store.as_ref().filter(|s| s.exists() && !s.is_dir()) {
+        checks.push(Check::Fail(
+            format!("Store {} in the config is not a directory", store.display()),
+            "Move the stores again with plmc store move".to_string(),
+        ));
+    }
+    for (key, adopted) in [
+        ("adoptedLib", &config.adopted_lib),
+        ("adoptedAssets", &config.adopted_assets),
+    ] {
+        if let Some(adopted) = adopted.as_ref().filter(|a| !a.is_dir()) {
+            checks.push(Check::Warn(
+                format!("Adopted store {} does not exist", adopted.display()),
+                format!(
+                    "Adopt it again with plmc store adopt or remove {} from {}",
+                    key,
+                    path.display()
+                ),
+            ));
+        }
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::Ok(format!("Config {} is valid", path.display())));
+    }
+    checks
+}
+
+/// Check the meta servers and the server the assets are downloaded from.
+async fn check_servers(sub_matches: &ArgMatches) -> Vec<Check> {
+    let servers = match sub_matches.value_of("meta_url") {
+        Some(url) => vec![url.to_string()],
+        // An invalid config is reported by check_config
+        None => GlobalConfig::load().unwrap_or_default().meta_servers(),
+    };
+    let mut checks = Vec::new();
+    for url in &servers {
+        checks.push(check_meta(sub_matches, url).await);
+    }
+    // The fastest server is used, one reachable is enough
+    if checks.iter().any(|c| matches!(c, Check::Ok(_))) {
+        for check in &mut checks {
+            if let Check::Fail(msg, fix) = check {
+                *check = Check::Warn(std::mem::take(msg), std::mem::take(fix));
+            }
+        }
+    }
+
+    let assets_url = sub_matches
+        .value_of("assets_url")
+        .unwrap_or(ASSET_DEFAULT_URL);
+    checks.push(check_assets_server(sub_matches, assets_url).await);
+    checks
+}
+
+async fn check_meta(sub_matches: &ArgMatches, meta_url: &str) -> Check {
+    let client = match crate::http::client(sub_matches, meta_url) {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::Fail(
+                format!("Could not set up HTTP client: {:#}", e),
+                "Check the --ca-cert and --pin-cert arguments".to_string(),
+            )
+        }
+    };
+
+    let url = format!("{}/index.json", meta_url);
+    let res = match url.parse() {
+        Ok(uri) => client.get(uri).await,
+        Err(e) => {
+            return Check::Fail(
+                format!("Invalid meta url {}: {}", meta_url, e),
+                "Pass the base url of the meta server, e.g. https://meta.polymc.org/v1".to_string(),
+            )
+        }
+    };
+
+    match res {
+        Ok(res) if res.status().is_success() => {
+            match hyper::body::to_bytes(res.into_body()).await {
+                Ok(data) if MetaIndex::from_data(&data).is_ok() => {
+                    Check::Ok(format!("Meta server {} is reachable", meta_url))
+                }
+                Ok(_) => Check::Fail(
+                    format!("{} is not a meta index", url),
+                    "Check that --base-url points to the meta server".to_string(),
+                ),
+                Err(e) => Check::Fail(
+                    format!("Could not download {}: {}", url, e),
+                    "Check your internet connection and proxy settings".to_string(),
+                ),
+            }
+        }
+        Ok(res) => Check::Fail(
+            format!("Meta server returned {} for {}", res.status(), url),
+            "Check that --base-url points to the meta server".to_string(),
+        ),
+        Err(e) => Check::Fail(
+            format!("Could not connect to {}: {}", meta_url, e),
+            "Check your internet connection and proxy settings".to_string(),
+        ),
+    }
+}
+
+async fn check_assets_server(sub_matches: &ArgMatches, assets_url: &str) -> Check {
+    let fix =
+        "Check your internet connection and proxy settings, or pass a mirror with --assets-url";
+    let client = match crate::http::client(sub_matches, assets_url) {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::Fail(
+                format!("Could not set up HTTP client: {:#}", e),
+                "Check the --ca-cert and --pin-cert arguments".to_string(),
+            )
+        }
+    };
+    let uri = match assets_url.parse() {
+        Ok(uri) => uri,
+        Err(e) => {
+            return Check::Fail(
+                format!("Invalid assets url {}: {}", assets_url, e),
+                fix.to_string(),
+            )
+        }
+    };
+
+    // The base url has no index, any response means the server is reachable
+    match client.get(uri).await {
+        Ok(_) => Check::Ok(format!("Assets server {} is reachable", assets_url)),
+        Err(e) => Check::Fail(
+            format!("Could not connect to {}: {}", assets_url, e),
+            fix.to_string(),
+        ),
+    }
+}
+
+fn check_dir(name: &str, path: &str) -> Check {
+    let dir = Path::new(path);
+    if !dir.exists() {
+        return Check::Ok(format!("{} directory {} will be created", name, path));
+    }
+    if !dir.is_dir() {
+        return Check::Fail(
+            format!("{} directory {} is not a directory", name, path),
+            format!("Remove or move {}", path),
+        );
+    }
+
+    let probe = dir.join(".plmc-doctor");
+    if let Err(e) = std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        return Check::Fail(
+            format!("{} directory {} is not writable: {}", name, path, e),
+            format!("Fix the permissions of {}", path),
+        );
+    }
+
+    match DirLock::is_locked(path) {
+        Ok(true) => Check::Warn(
+            format!("{} directory {} is locked", name, path),
+            "Another launcher is using it, launches will wait for it".to_string(),
+        ),
+        Ok(false) => Check::Ok(format!("{} directory {} is usable", name, path)),
+        Err(e) => Check::Warn(
+            format!("Could not check the lock of {}: {}", path, e),
+            format!("Remove {} if no launcher is running", LOCK_FILE_NAME),
+        ),
+    }
+}
+
+fn check_instance(path: &str) -> Check {
+    if !Path::new(path).join(INSTANCE_INFO_FILE).is_file() {
+        return Check::Ok(format!("No instance info in {}", path));
+    }
+
+    match InstanceInfo::load(path) {
+        Ok(info) => Check::Ok(format!(
+            "Instance {} ({}) is valid",
+            info.name, info.version
+        )),
+        Err(e) => Check::Warn(
+            format!("Instance info in {} is invalid: {}", path, e),
+            format!(
+                "Remove {}, it is recreated on the next launch",
+                INSTANCE_INFO_FILE
+            ),
+        ),
+    }
+}
+
+/// Whether the JSON file at `path` parses as `T`.
+fn parses<T: DeserializeOwned>(path: &Path) -> bool {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<T>(&data).ok())
+        .is_some()
+}
+
+fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+}
+
+fn is_json(path: &Path) -> bool {
+    path.is_file() && path.extension() == Some(OsStr::new("json"))
+}
+
+/// Check that the cached meta index, package indexes and manifests parse.
+fn check_meta_cache(meta_dir: &str) -> Check {
+    let dir = Path::new(meta_dir);
+    let mut invalid = Vec::new();
+    let index = dir.join("index.json");
+    if index.exists() && !parses::<MetaIndex>(&index) {
+        invalid.push(index);
+    }
+    for package in entries(dir).filter(|p| p.is_dir()) {
+        for file in entries(&package).filter(|p| is_json(p)) {
+            let valid = if file.ends_with("index.json") {
+                parses::<PackageIndex>(&file)
+            } else {
+                parses::<Manifest>(&file)
+            };
+            if !valid {
+                invalid.push(file);
+            }
+        }
+    }
+
+    if invalid.is_empty() {
+        return Check::Ok(format!("Meta data in {} is valid", meta_dir));
+    }
+    Check::Warn(
+        format!("Invalid meta data in {}: {}", meta_dir, paths(&invalid)),
+        "Remove them, they are downloaded again on the next launch".to_string(),
+    )
+}
+
+/// Check that the asset indexes parse and objects are stored under their hash.
+fn check_asset_store(assets_dir: &str) -> Check {
+    let dir = Path::new(assets_dir);
+    let mut invalid: Vec<_> = entries(&dir.join("indexes"))
+        .filter(|p| is_json(p) && !parses::<AssetIndex>(p))
+        .collect();
+    for prefix in entries(&dir.join("objects")) {
+        let prefix_name = prefix
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !prefix.is_dir() {
+            invalid.push(prefix);
+            continue;
+        }
+        for object in entries(&prefix) {
+            let name = object
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let valid = name.len() == 40
+                && name.starts_with(&prefix_name)
+                && name.bytes().all(|b| b.is_ascii_hexdigit());
+            if !valid && !name.ends_with(".part") {
+                invalid.push(object);
+            }
+        }
+    }
+
+    if invalid.is_empty() {
+        return Check::Ok(format!("Assets in {} are valid", assets_dir));
+    }
+    Check::Warn(
+        format!("Invalid assets in {}: {}", assets_dir, paths(&invalid)),
+        "Remove them, they are downloaded again on the next launch".to_string(),
+    )
+}
+
+/// Check for temporary files of downloads which were interrupted, see
+/// [`polymc::PartialFile`].
+fn check_partial_files(dirs: &[&str]) -> Check {
+    fn find(dir: &Path, found: &mut Vec<PathBuf>) {
+        for path in entries(dir) {
+            if path.is_dir() {
+                find(&path, found);
+            } else if path.extension() == Some(OsStr::new("part")) {
+                found.push(path);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    for dir in dirs {
+        find(Path::new(dir), &mut found);
+    }
+    if found.is_empty() {
+        return Check::Ok("No interrupted downloads".to_string());
+    }
+    Check::Warn(
+        format!("Files of interrupted downloads: {}", paths(&found)),
+        "Remove them if no launcher is running".to_string(),
+    )
+}
+
+/// List of `paths`, shortened if there are many.
+fn paths(paths: &[PathBuf]) -> String {
+    let mut list: Vec<_> = paths
+        .iter()
+        .take(5)
+        .map(|p| p.display().to_string())
+        .collect();
+    if paths.len() > 5 {
+        list.push(format!("and {} more", paths.len() - 5));
+    }
+    list.join(", ")
+}
+
+/// Check the java executable and the runtimes used for versions needing another Java.
+fn check_java(java: &str) -> Vec<Check> {
+    let runtimes = polymc::runtime::discover(&[crate::run::get_dir("runtimes")]);
+    let mut checks: Vec<_> = runtimes
+        .iter()
+        .map(|runtime| {
+            Check::Ok(format!(
+                "Java {} runtime: {}",
+                runtime.major,
+                runtime.java.path().display()
+            ))
+        })
+        .collect();
+
+    checks.push(match Java::new(java).version() {
+        Ok(version) => Check::Ok(format!(
+            "Java: {}",
+            version.lines().next().unwrap_or_default()
+        )),
+        // Versions requiring a Java version use the runtimes
+        Err(e) if !runtimes.is_empty() => Check::Warn(
+            format!("Could not run {}: {}", java, e),
+            "Versions without a required Java version need --java".to_string(),
+        ),
+        Err(e) => Check::Fail(
+            format!("Could not run {}: {}", java, e),
+            "Install Java or pass its path with --java".to_string(),
+        ),
+    });
+    checks
+}
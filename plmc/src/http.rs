@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use hyper::client::HttpConnector;
+use hyper::header::{CONTENT_TYPE, RETRY_AFTER, USER_AGENT};
+use hyper::http::request;
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use polymc::host_policy::{HostPattern, HostPolicy};
+use polymc::local_source::{is_remote, LocalSource};
+#[cfg(feature = "tls-rustls")]
+use polymc::meta::manifest::Sha256Sum;
+use polymc::meta::DownloadRequest;
+use polymc::{is_throttled, parse_retry_after, Backoff};
+#[cfg(feature = "tls-rustls")]
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+#[cfg(feature = "tls-rustls")]
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use serde::Serialize;
+use tracing::*;
+
+#[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+compile_error!("Either the tls-rustls or the tls-native feature has to be enabled");
+
+#[cfg(feature = "tls-rustls")]
+type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+#[cfg(all(feature = "tls-native", not(feature = "tls-rustls")))]
+type HttpsConnector = hyper_tls::HttpsConnector<HttpConnector>;
+
+/// HTTP client used for all requests of the cli.
+#[derive(Clone)]
+pub(crate) struct HttpClient {
+    client: Client<HttpsConnector>,
+    user_agent: String,
+    policy: Arc<HostPolicy>,
+    /// Url of the meta server and mirrors serving the same files.
+    base_url: String,
+    mirrors: Arc<Vec<String>>,
+    /// Hosts contacted so far, each is logged once.
+    contacted: Arc<Mutex<HashSet<String>>>,
+    /// Waits for servers rate limiting GET requests.
+    backoff: Backoff,
+    /// Directory the files are read from if the base url is local.
+    local: Option<Arc<LocalSource>>,
+}
+
+impl HttpClient {
+    /// Send a GET request identifying as the launcher.
+    ///
+    /// Requests the server rate limits are retried as it asks for, failing with
+    /// [`polymc::Error::Throttled`] once it keeps doing so. Fails with
+    /// [`polymc::Error::BlockedUrl`] if the host policy does not allow the host.
+    pub(crate) async fn get(&self, uri: Uri) -> Result<Response<Body>> {
+        let mut attempt = 0;
+        loop {
+            let response = self.send(Request::get(uri.clone()), Body::empty()).await?;
+            let retry_after = response.headers().get(RETRY_AFTER);
+            let throttled = is_throttled(response.status().as_u16(), retry_after.is_some());
+            if !throttled {
+                return Ok(response);
+            }
+
+            let retry_after = retry_after
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, SystemTime::now()));
+            let wait = match self.backoff.delay(attempt, retry_after) {
+                Some(wait) => wait,
+                None => return Err(self.backoff.give_up(&uri.to_string(), retry_after).into()),
+            };
+            warn!(
+                "{} is rate limiting, retrying in {}s",
+                uri.host().unwrap_or_default(),
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    /// GET `url`, which is read from the local meta source if it is a file.
+    ///
+    /// Missing files are answered with `404 Not Found` like by a server. Fails with
+    /// [`polymc::Error::OutsideLocalSource`] for files outside of the source, or any file if the
+    /// base url is remote.
+    pub(crate) async fn fetch(&self, url: &str) -> Result<Response<Body>> {
+        if is_remote(url) {
+            return self.get(url.parse()?).await;
+        }
+        let path = match &self.local {
+            Some(local) => local.resolve(url)?,
+            None => return Err(polymc::Error::OutsideLocalSource(url.to_string()).into()),
+        };
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Response::new(Body::from(data))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())?),
+            Err(e) => Err(e).with_context(|| format!("Reading {}", path.display())),
+        }
+    }
+
+    /// Take libraries and assets of the stores at `libraries_dir` and `assets_dir` from the
+    /// local meta source, if the base url is local.
+    pub(crate) fn set_local_stores(&mut self, libraries_dir: &str, assets_dir: &str) {
+        if let Some(local) = &mut self.local {
+            Arc::make_mut(local).set_stores(libraries_dir, assets_dir);
+        }
+    }
+
+    /// Url of the file of `request` in the local meta source, see [`LocalSource::file_url`].
+    pub(crate) fn local_url(&self, request: &DownloadRequest) -> Option<String> {
+        self.local.as_ref()?.file_url(request)
+    }
+
+    /// Send a POST request with `body` as JSON, see [`HttpClient::get`].
+    pub(crate) async fn post_json<T: Serialize>(
+        &self,
+        uri: Uri,
+        body: &T,
+    ) -> Result<Response<Body>> {
+        let request = Request::post(uri).header(CONTENT_TYPE, "application/json");
+        self.send(request, Body::from(serde_json::to_vec(body)?))
+            .await
+    }
+
+    /// Send the request built by `request`, identifying as the launcher.
+    pub(crate) async fn send(
+        &self,
+        request: request::Builder,
+        body: Body,
+    ) -> Result<Response<Body>> {
+        let uri = request.uri_ref().context("Request without url")?;
+        self.policy.check_url(&uri.to_string())?;
+        if let Some(host) = uri.host() {
+            if self.contacted.lock().unwrap().insert(host.to_string()) {
+                info!("contacting {}", host);
+            }
+        }
+
+        let request = request.header(USER_AGENT, &self.user_agent).body(body)?;
+
+        Ok(self.client.request(request).await?)
+    }
+
+    /// Urls to download `url` from, `url` itself followed by the same file on each mirror if it
+    /// is hosted by the meta server.
+    pub(crate) fn sources(&self, url: &str) -> Vec<String> {
+        let mut ret = vec![url.to_string()];
+        if let Some(path) = url.strip_prefix(self.base_url.trim_end_matches('/')) {
+            ret.extend(
+                self.mirrors
+                    .iter()
+                    .map(|mirror| format!("{}{}", mirror.trim_end_matches('/'), path)),
+            );
+        }
+        ret
+    }
+}
+
+/// Add the arguments to configure the HTTP client.
+pub(crate) fn args(app: App<'static>) -> App<'static> {
+    app.arg(
+        Arg::new("ca_cert")
+            .long("ca-cert")
+            .env("PLMC_CA_CERT")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("PEM file with additional root certificates to trust"),
+    )
+    .arg(
+        Arg::new("pin_cert")
+            .long("pin-cert")
+            .env("PLMC_PIN_CERT")
+            .takes_value(true)
+            .help("Sha256 of the certificate the meta server has to use"),
+    )
+    .arg(
+        Arg::new("allow_host")
+            .long("allow-host")
+            .env("PLMC_ALLOW_HOSTS")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .use_value_delimiter(true)
+            .help("Only download from these hosts, `*.example.com` includes subdomains"),
+    )
+    .arg(
+        Arg::new("deny_host")
+            .long("deny-host")
+            .env("PLMC_DENY_HOSTS")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .use_value_delimiter(true)
+            .help("Never download from these hosts"),
+    )
+    .arg(
+        Arg::new("mirror")
+            .long("mirror")
+            .env("PLMC_MIRRORS")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .use_value_delimiter(true)
+            .help("Mirror of the meta server, used if it fails or returns an invalid response"),
+    )
+}
+
+/// Parse the host patterns given for `name`.
+fn host_patterns(sub_matches: &ArgMatches, name: &str) -> Result<Vec<HostPattern>> {
+    sub_matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(|p| Ok(p.parse()?))
+        .collect()
+}
+
+/// Build the HTTP client, trusting the native roots and the certificates given on the command line.
+pub(crate) fn client(sub_matches: &ArgMatches, base_url: &str) -> Result<HttpClient> {
+    Ok(HttpClient {
+        client: Client::builder().build(connector(sub_matches, base_url)?),
+        user_agent: polymc::user_agent(Some(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))),
+        policy: Arc::new(HostPolicy {
+            allow: host_patterns(sub_matches, "allow_host")?,
+            deny: host_patterns(sub_matches, "deny_host")?,
+        }),
+        base_url: base_url.to_string(),
+        mirrors: Arc::new(
+            sub_matches
+                .values_of("mirror")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect(),
+        ),
+        contacted: Arc::default(),
+        backoff: Backoff::default(),
+        local: LocalSource::from_base_url(base_url).map(Arc::new),
+    })
+}
+
+#[cfg(feature = "tls-rustls")]
+fn connector(sub_matches: &ArgMatches, base_url: &str) -> Result<HttpsConnector> {
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs().context("Loading native certificates")? {
+        if let Err(e) = roots.add(&Certificate(cert.0)) {
+            debug!("Skipping invalid native certificate: {}", e);
+        }
+    }
+
+    if let Some(paths) = sub_matches.values_of("ca_cert") {
+        for path in paths {
+            let file = File::open(path).with_context(|| format!("Opening {}", path))?;
+            let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+                .with_context(|| format!("Reading certificates from {}", path))?;
+            let (added, _) = roots.add_parsable_certificates(&certs);
+            if added == 0 {
+                bail!("No valid certificates found in {}", path);
+            }
+            debug!("Trusting {} certificates from {}", added, path);
+        }
+    }
+
+    let config = ClientConfig::builder().with_safe_defaults();
+    let config = match sub_matches.value_of("pin_cert") {
+        Some(pin) => {
+            let host = base_url
+                .parse::<hyper::Uri>()?
+                .host()
+                .context("Base url has no host")?
+                .to_string();
+            let verifier = PinnedVerifier {
+                host,
+                sha256: pin
+                    .parse::<Sha256Sum>()
+                    .context("Invalid certificate hash")?
+                    .as_ref()
+                    .to_vec(),
+                inner: WebPkiVerifier::new(roots, None),
+            };
+            config
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth()
+        }
+        None => config.with_root_certificates(roots).with_no_client_auth(),
+    };
+
+    Ok(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(config)
+        .https_or_http()
+        .enable_http1()
+        .build())
+}
+
+#[cfg(all(feature = "tls-native", not(feature = "tls-rustls")))]
+fn connector(sub_matches: &ArgMatches, _base_url: &str) -> Result<HttpsConnector> {
+    use std::io::Read;
+
+    if sub_matches.is_present("pin_cert") {
+        bail!("Certificate pinning is only supported with the tls-rustls feature");
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(paths) = sub_matches.values_of("ca_cert") {
+        for path in paths {
+            let mut pem = String::new();
+            BufReader::new(File::open(path).with_context(|| format!("Opening {}", path))?)
+                .read_to_string(&mut pem)
+                .with_context(|| format!("Reading certificates from {}", path))?;
+
+            // native-tls only parses the first certificate of a PEM file
+            let mut added = 0;
+            for block in pem.split_inclusive("-----END CERTIFICATE-----") {
+                if !block.contains("-----BEGIN CERTIFICATE-----") {
+                    continue;
+                }
+                let cert = native_tls::Certificate::from_pem(block.trim().as_bytes())
+                    .with_context(|| format!("Reading certificates from {}", path))?;
+                builder.add_root_certificate(cert);
+                added += 1;
+            }
+            if added == 0 {
+                bail!("No valid certificates found in {}", path);
+            }
+            debug!("Trusting {} certificates from {}", added, path);
+        }
+    }
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    Ok(HttpsConnector::from((
+        http,
+        builder.build().context("Creating TLS connector")?.into(),
+    )))
+}
+
+/// Verifier accepting only the pinned certificate for the meta server, other hosts are verified
+/// against the trusted roots.
+#[cfg(feature = "tls-rustls")]
+struct PinnedVerifier {
+    host: String,
+    sha256: Vec<u8>,
+    inner: WebPkiVerifier,
+}
+
+#[cfg(feature = "tls-rustls")]
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match server_name {
+            ServerName::DnsName(name) if name.as_ref() == self.host => {
+                let digest = ring::digest::digest(&ring::digest::SHA256, &end_entity.0);
+                if digest.as_ref() == self.sha256 {
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(rustls::Error::General(format!(
+                        "certificate of {} does not match the pinned hash",
+                        self.host
+                    )))
+                }
+            }
+            _ => self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            ),
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use crate::http::HttpClient;
+use crate::meta::index::{cancel_on_ctrl_c, download_file, download_meta};
+use crate::modrinth::Modrinth;
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use indicatif::HumanDuration;
+use polymc::gpu::GpuPreference;
+use polymc::instance::{
+    clone_instance_dir, list_instances, sort_instances, AssetVerification, CloneOptions,
+    InstanceEntry, InstanceInfo, InstanceSort, InstanceTemplate, TemplateMod, INSTANCE_INFO_FILE,
+};
+use polymc::log_forward::{LogForwarding, LogTarget, DEFAULT_LOG_BUFFER};
+use polymc::meta::manifest::LibraryDownload;
+use polymc::meta::{DownloadRequest, MetaManager};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::*;
+
+pub(crate) fn app() -> App<'static> {
+    let templates_url = Arg::new("templates_url")
+        .long("templates-url")
+        .env("PLMC_TEMPLATES_URL")
+        .takes_value(true)
+        .help("Url of a JSON list of templates, added to the builtin ones");
+
+    let create = App::new("create")
+        .about("Create an instance from a template")
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .short('t')
+                .takes_value(true)
+                .required(true)
+                .help("Name of the template, see `plmc instance templates`"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .takes_value(true)
+                .help("Name of the instance, the name of the template if not given"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .takes_value(true)
+                .help("Directory of the instance, in the instances directory if not given"),
+        )
+        .arg(templates_url.clone())
+        .arg(
+            Arg::new("meta_url")
+                .long("base-url")
+                .env("PLMC_BASE_URL")
+                .help("Base url of the meta server to use, the fastest known one if not given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("meta_dir")
+                .long("meta-dir")
+                .takes_value(true)
+                .env("PLMC_META_DIR"),
+        )
+        .arg(crate::modrinth::arg());
+
+    let clone = App::new("clone")
+        .about("Copy an instance, sharing its mods by hardlinks where possible")
+        .arg(
+            Arg::new("from")
+                .required(true)
+                .help("Directory of the instance to clone"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .takes_value(true)
+                .required(true)
+                .help("Name of the new instance"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .takes_value(true)
+                .help("Directory of the new instance, next to the cloned one if not given"),
+        )
+        .arg(Arg::new("mods").long("mods").help("Copy the mods"))
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("Copy the mod configs and game options"),
+        )
+        .arg(Arg::new("saves").long("saves").help("Copy the worlds"))
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .help("Copy the whole game directory except logs"),
+        )
+        .arg(
+            Arg::new("no_hardlinks")
+                .long("no-hardlinks")
+                .help("Copy jars and zips instead of hardlinking them"),
+        );
+
+    let list = App::new("list")
+        .about("List the instances")
+        .arg(
+            Arg::new("root")
+                .long("root")
+                .takes_value(true)
+                .help("Directory containing the instances, the instances directory if not given"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(["name", "last-played"])
+                .default_value("name"),
+        )
+        .arg(
+            Arg::new("long")
+                .long("long")
+                .short('l')
+                .help("Show the version, account and exit code of the last launch"),
+        );
+
+    let set = App::new("set")
+        .alias("edit")
+        .about("Change the settings of an instance")
+        .arg(
+            Arg::new("dir")
+                .required(true)
+                .help("Directory of the instance"),
+        )
+        .arg(
+            Arg::new("asset_verification")
+                .long("asset-verification")
+                .takes_value(true)
+                .possible_values(["always", "on-first-launch", "weekly", "never"])
+                .help("When to verify the hashes of the assets before starting the game"),
+        )
+        .arg(
+            Arg::new("java_agent")
+                .long("java-agent")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("PATH[=OPTIONS]")
+                .help("Java agents to attach, relative paths are in the instance directory"),
+        )
+        .arg(
+            Arg::new("no_java_agents")
+                .long("no-java-agents")
+                .conflicts_with("java_agent")
+                .help("Remove all Java agents"),
+        )
+        .arg(
+            Arg::new("launcher_log_keep")
+                .long("launcher-log-keep")
+                .takes_value(true)
+                .validator(|v| v.parse::<usize>())
+                .help("Number of game output logs kept in logs/launcher, 0 to not capture it"),
+        )
+        .arg(
+            Arg::new("launcher_log_max_size")
+                .long("launcher-log-max-size")
+                .takes_value(true)
+                .value_name("MIB")
+                .validator(|v| v.parse::<u64>())
+                .help("Size after which the game output continues in a new log"),
+        )
+        .arg(
+            Arg::new("limit_per_launch")
+                .long("limit-per-launch")
+                .takes_value(true)
+                .value_name("MINUTES")
+                .validator(|v| v.parse::<u64>())
+                .help("Minutes a launch may last, 0 for no limit"),
+        )
+        .arg(
+            Arg::new("limit_per_day")
+                .long("limit-per-day")
+                .takes_value(true)
+                .value_name("MINUTES")
+                .validator(|v| v.parse::<u64>())
+                .help("Minutes the instance may be played per day, 0 for no limit"),
+        )
+        .arg(
+            Arg::new("limit_grace")
+                .long("limit-grace")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .validator(|v| v.parse::<u64>())
+                .help("Time the game gets to exit once the play time is up before it is killed"),
+        )
+        .arg(
+            Arg::new("limit_chat_warnings")
+                .long("limit-chat-warnings")
+                .takes_value(true)
+                .possible_values(["on", "off"])
+                .help("Send the warnings as say command to the game, for servers"),
+        )
+        .arg(
+            Arg::new("gpu")
+                .long("gpu")
+                .takes_value(true)
+                .possible_values(["integrated", "discrete", "default"])
+                .help(
+                    "GPU to start the game on, default lets the system choose. On Windows the \
+                     choice is stored for the java executable and applies to everything it runs",
+                ),
+        )
+        .arg(
+            Arg::new("log_syslog")
+                .long("log-syslog")
+                .takes_value(true)
+                .value_name("HOST[:PORT]|local")
+                .help("Forward the game output to a syslog server over UDP or the local syslog"),
+        )
+        .arg(
+            Arg::new("log_http")
+                .long("log-http")
+                .takes_value(true)
+                .value_name("URL")
+                .conflicts_with("log_syslog")
+                .help("Forward the game output as JSON to an HTTP endpoint"),
+        )
+        .arg(
+            Arg::new("log_buffer")
+                .long("log-buffer")
+                .takes_value(true)
+                .value_name("EVENTS")
+                .validator(|v| v.parse::<usize>())
+                .help("Log events kept while the log target is unreachable"),
+        )
+        .arg(
+            Arg::new("no_log_forwarding")
+                .long("no-log-forwarding")
+                .conflicts_with_all(&["log_syslog", "log_http", "log_buffer"])
+                .help("Stop forwarding the game output"),
+        )
+        .arg(
+            Arg::new("main_class")
+                .long("main-class")
+                .takes_value(true)
+                .help("Main class started instead of the one of the components, empty to reset it"),
+        )
+        .arg(override_args(
+            "prepend_jvm_arg",
+            "prepend-jvm-arg",
+            "Arguments passed to the JVM before all others",
+        ))
+        .arg(override_args(
+            "append_jvm_arg",
+            "append-jvm-arg",
+            "Arguments passed to the JVM after all others",
+        ))
+        .arg(override_args(
+            "prepend_game_arg",
+            "prepend-game-arg",
+            "Arguments passed to the game before all others",
+        ))
+        .arg(override_args(
+            "append_game_arg",
+            "append-game-arg",
+            "Arguments passed to the game after all others",
+        ))
+        .arg(
+            Arg::new("clear_overrides")
+                .long("clear-overrides")
+                .help("Remove the main class and arguments set with the options above first"),
+        );
+
+    App::new("instance")
+        .about("Manage instances")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(list)
+        .subcommand(set)
+        .subcommand(crate::http::args(create))
+        .subcommand(clone)
+        .subcommand(crate::http::args(
+            App::new("templates")
+                .about("List the templates instances can be created from")
+                .arg(templates_url),
+        ))
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    match sub_matches.subcommand() {
+        Some(("create", sub_matches)) => run_create(sub_matches).await,
+        Some(("clone", sub_matches)) => run_clone(sub_matches),
+        Some(("list", sub_matches)) => run_list(sub_matches),
+        Some(("set", sub_matches)) => run_set(sub_matches),
+        Some(("templates", sub_matches)) => run_templates(sub_matches).await,
+        _ => bail!("Unknown command"),
+    }
+}
+
+fn run_list(sub_matches: &ArgMatches) -> Result<i32> {
+    let root = sub_matches
+        .value_of("root")
+        .map(ToString::to_string)
+        .unwrap_or_else(|| crate::run::get_dir("instances"));
+    let mut instances = match list_instances(&root) {
+        Ok(instances) => instances,
+        Err(polymc::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("Listing instances in {}", root)),
+    };
+    let sort = match sub_matches.value_of("sort") {
+        Some("last-played") => InstanceSort::LastPlayed,
+        _ => InstanceSort::Name,
+    };
+    sort_instances(&mut instances, sort);
+
+    for instance in &instances {
+        if sub_matches.is_present("long") {
+            print_long(instance);
+        } else {
+            println!("{}\t{}", instance.info.name, instance.path.display());
+        }
+    }
+
+    Ok(0)
+}
+
+fn print_long(instance: &InstanceEntry) {
+    let info = &instance.info;
+    let last_played = match info.last_played_time() {
+        Some(time) => {
+            let ago = SystemTime::now().duration_since(time).unwrap_or_default();
+            format!("{} ago", HumanDuration(ago))
+        }
+        None => "never".to_string(),
+    };
+    let exit_code = match (info.last_played, info.last_exit_code) {
+        (None, _) => "-".to_string(),
+        (Some(_), Some(code)) => code.to_string(),
+        (Some(_), None) => "none".to_string(),
+    };
+
+    println!("{}", info.name);
+    println!("  path:         {}", instance.path.display());
+    println!("  version:      {}", info.version);
+    println!("  last played:  {}", last_played);
+    if let Some(version) = &info.last_version {
+        println!("  last version: {}", version);
+    }
+    if let Some(account) = &info.last_account {
+        println!("  last account: {}", account);
+    }
+    println!("  exit code:    {}", exit_code);
+    println!(
+        "  playtime:     {}",
+        HumanDuration(Duration::from_secs(info.playtime))
+    );
+    if let Some(remaining) = info.remaining_play_time(SystemTime::now()) {
+        println!("  time left:    {}", HumanDuration(remaining));
+    }
+}
+
+/// Option replacing a list of the launch overrides, given once per argument.
+fn override_args(name: &'static str, long: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(long)
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .allow_hyphen_values(true)
+        .value_name("ARG")
+        .help(help)
+}
+
+fn run_set(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = sub_matches.value_of("dir").unwrap();
+    let mut info =
+        InstanceInfo::load(dir).with_context(|| format!("No instance found in {}", dir))?;
+    if let Some(policy) = sub_matches.value_of("asset_verification") {
+        info.asset_verification = match policy {
+            "on-first-launch" => AssetVerification::OnFirstLaunch,
+            "weekly" => AssetVerification::Weekly,
+            "never" => AssetVerification::Never,
+            _ => AssetVerification::Always,
+        };
+    }
+    if let Some(agents) = sub_matches.values_of("java_agent") {
+        info.java_agents = agents
+            .map(str::parse)
+            .collect::<polymc::Result<_>>()
+            .context("Invalid Java agent")?;
+    } else if sub_matches.is_present("no_java_agents") {
+        info.java_agents.clear();
+    }
+    if let Some(keep) = sub_matches.value_of("launcher_log_keep") {
+        info.launcher_log.keep = keep.parse()?;
+    }
+    if let Some(size) = sub_matches.value_of("launcher_log_max_size") {
+        info.launcher_log.max_size = size.parse::<u64>()? * 1024 * 1024;
+    }
+    let limit = &mut info.session_limit;
+    for (name, minutes) in [
+        ("limit_per_launch", &mut limit.per_launch),
+        ("limit_per_day", &mut limit.per_day),
+    ] {
+        if let Some(value) = sub_matches.value_of(name) {
+            *minutes = Some(value.parse()?).filter(|m| *m != 0);
+        }
+    }
+    if let Some(grace) = sub_matches.value_of("limit_grace") {
+        limit.grace = grace.parse()?;
+    }
+    if let Some(chat) = sub_matches.value_of("limit_chat_warnings") {
+        limit.chat_warnings = chat == "on";
+    }
+    if let Some(gpu) = sub_matches.value_of("gpu") {
+        info.gpu = match gpu {
+            "integrated" => Some(GpuPreference::Integrated),
+            "discrete" => Some(GpuPreference::Discrete),
+            _ => None,
+        };
+    }
+    let target = if let Some(address) = sub_matches.value_of("log_syslog") {
+        Some(LogTarget::Syslog {
+            address: Some(address.to_string()).filter(|a| a != "local"),
+        })
+    } else {
+        sub_matches.value_of("log_http").map(|url| LogTarget::Http {
+            url: url.to_string(),
+        })
+    };
+    if let Some(target) = target {
+        // Keep the buffer size when changing the target
+        let buffer = info
+            .log_forwarding
+            .as_ref()
+            .map_or(DEFAULT_LOG_BUFFER, |f| f.buffer);
+        info.log_forwarding = Some(LogForwarding { target, buffer });
+    } else if sub_matches.is_present("no_log_forwarding") {
+        info.log_forwarding = None;
+    }
+    if let Some(buffer) = sub_matches.value_of("log_buffer") {
+        match &mut info.log_forwarding {
+            Some(forwarding) => forwarding.buffer = buffer.parse()?,
+            None => {
+                bail!("--log-buffer needs a log target, set one with --log-syslog or --log-http")
+            }
+        }
+    }
+    if sub_matches.is_present("clear_overrides") {
+        info.overrides = Default::default();
+    }
+    let overrides = &mut info.overrides;
+    if let Some(main_class) = sub_matches.value_of("main_class") {
+        overrides.main_class = Some(main_class.to_string()).filter(|c| !c.is_empty());
+    }
+    for (name, args) in [
+        ("prepend_jvm_arg", &mut overrides.jvm_args_prepend),
+        ("append_jvm_arg", &mut overrides.jvm_args_append),
+        ("prepend_game_arg", &mut overrides.game_args_prepend),
+        ("append_game_arg", &mut overrides.game_args_append),
+    ] {
+        if let Some(values) = sub_matches.values_of(name) {
+            *args = values.map(ToString::to_string).collect();
+        }
+    }
+    info.save(dir)?;
+
+    Ok(0)
+}
+
+fn run_clone(sub_matches: &ArgMatches) -> Result<i32> {
+    let from = Path::new(sub_matches.value_of("from").unwrap());
+    let name = sub_matches.value_of("name").unwrap();
+    let to = match sub_matches.value_of("dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => from
+            .parent()
+            .context("Instance directory has no parent")?
+            .join(name),
+    };
+
+    let mut options = if sub_matches.is_present("all") {
+        CloneOptions::all()
+    } else {
+        CloneOptions {
+            mods: sub_matches.is_present("mods"),
+            config: sub_matches.is_present("config"),
+            saves: sub_matches.is_present("saves"),
+            ..Default::default()
+        }
+    };
+    options.hardlink = !sub_matches.is_present("no_hardlinks");
+
+    clone_instance_dir(from, &to, name, None, &options)
+        .with_context(|| format!("Cloning {}", from.display()))?;
+    println!("Cloned {} to {}", from.display(), to.display());
+
+    Ok(0)
+}
+
+async fn run_templates(sub_matches: &ArgMatches) -> Result<i32> {
+    let url = sub_matches.value_of("templates_url").unwrap_or_default();
+    let client = crate::http::client(sub_matches, url)?;
+    for template in load_templates(&client, sub_matches).await? {
+        println!("{}\t{}", template.name, template.description);
+    }
+
+    Ok(0)
+}
+
+/// The builtin templates, replaced by the ones of the same name from `--templates-url`.
+async fn load_templates(
+    client: &HttpClient,
+    sub_matches: &ArgMatches,
+) -> Result<Vec<InstanceTemplate>> {
+    let mut templates = InstanceTemplate::builtin();
+    let url = match sub_matches.value_of("templates_url") {
+        Some(url) => url,
+        None => return Ok(templates),
+    };
+
+    let res = client.get(url.parse()?).await?;
+    if !res.status().is_success() {
+        bail!("Failed to download templates: {} ({})", url, res.status());
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    let fetched: Vec<InstanceTemplate> =
+        serde_json::from_slice(&body).with_context(|| format!("Invalid templates from {}", url))?;
+    for template in fetched {
+        templates.retain(|t| t.name != template.name);
+        templates.push(template);
+    }
+
+    Ok(templates)
+}
+
+async fn run_create(sub_matches: &ArgMatches) -> Result<i32> {
+    let meta_url = &crate::meta::servers::base_url(sub_matches, "meta_url").await?;
+    let meta_dir = sub_matches
+        .value_of("meta_dir")
+        .map(ToString::to_string)
+        .unwrap_or_else(|| crate::run::get_store_dir("meta"));
+    let client = crate::http::client(sub_matches, meta_url)?;
+
+    let templates = load_templates(&client, sub_matches).await?;
+    let template = InstanceTemplate::find(&templates, sub_matches.value_of("template").unwrap())?;
+    let name = sub_matches.value_of("name").unwrap_or(&template.name);
+    let dir = match sub_matches.value_of("dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&crate::run::get_dir("instances")).join(name),
+    };
+    if dir.join(INSTANCE_INFO_FILE).exists() {
+        bail!("An instance already exists in {}", dir.display());
+    }
+
+    // Resolve the versions of the components from their package indices
+    let mut manager = MetaManager::new("", "", meta_url);
+    let cancel = manager.cancellation_token();
+    cancel_on_ctrl_c(cancel.clone());
+    let lock = crate::run::lock_dir(&meta_dir).await?;
+    let (file, f_type) =
+        download_meta(&client, &manager.index_request(), &meta_dir, &cancel).await?;
+    manager.load_reader(
+        &mut file.context("Failed to download the meta index")?,
+        f_type,
+    )?;
+    for component in &template.components {
+        let package = manager.index.as_ref().unwrap().get_uid(&component.uid)?;
+        let request = DownloadRequest::new_package_index(meta_url, package);
+        let (file, f_type) = download_meta(&client, &request, &meta_dir, &cancel).await?;
+        if let Some(mut file) = file {
+            manager.load_reader(&mut file, f_type)?;
+        }
+    }
+    drop(lock);
+    let components = template.resolve(manager.index.as_ref().unwrap())?;
+
+    let info = InstanceInfo {
+        name: name.to_string(),
+        version: components
+            .iter()
+            .find(|c| c.uid == "net.minecraft")
+            .or_else(|| components.first())
+            .map(|c| c.version.clone())
+            .unwrap_or_default(),
+        components,
+        ..Default::default()
+    };
+    for component in &info.components {
+        println!("{} {}", component.uid, component.version);
+    }
+
+    let mods_dir = dir.join("mods");
+    std::fs::create_dir_all(&mods_dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let projects: Vec<String> = template
+        .mods
+        .iter()
+        .filter_map(|m| match m {
+            TemplateMod::Modrinth { modrinth } => Some(modrinth.to_string()),
+            TemplateMod::Url { .. } => None,
+        })
+        .collect();
+    let modrinth = Modrinth::new(client.clone(), sub_matches);
+    crate::mods::install(
+        &client,
+        &modrinth,
+        &dir,
+        &projects,
+        info.component("net.minecraft"),
+        info.mod_loader(),
+        &cancel,
+    )
+    .await?;
+    for template_mod in &template.mods {
+        if let (TemplateMod::Url { url, sha1, .. }, Some(filename)) =
+            (template_mod, template_mod.file_name()?)
+        {
+            println!("Installing {}", filename);
+            let request = DownloadRequest::new_library(
+                LibraryDownload {
+                    sha1: sha1.clone(),
+                    size: -1,
+                    url: url.to_string(),
+                },
+                mods_dir.join(&filename),
+            );
+            download_file(&client, &request, &cancel).await?;
+        }
+    }
+
+    // Saved last, so an interrupted creation can be started again
+    info.save(&dir)?;
+    info!("created {} from {}", name, template.name);
+    println!("Created {} in {}", name, dir.display());
+    println!(
+        "Start it with: plmc run --mc-dir {} -v {}",
+        dir.display(),
+        info.version
+    );
+
+    Ok(0)
+}
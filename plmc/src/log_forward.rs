@@ -0,0 +1,310 @@
+use crate::http::HttpClient;
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+use hyper::Uri;
+use polymc::log_forward::{Log4jParser, LogBuffer, LogEvent, LogForwarding, LogTarget};
+use polymc::logger::LogLevel;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::UdpSocket;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::*;
+
+/// Port of syslog servers if the address has none.
+const SYSLOG_PORT: u16 = 514;
+/// Socket of the syslog daemon of the machine.
+#[cfg(unix)]
+const LOCAL_SYSLOG: &str = "/dev/log";
+/// Events sent per HTTP request.
+const HTTP_BATCH: usize = 500;
+/// Longest wait between attempts to reach a target which is down.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How long the events left at exit may take to be sent.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Output without line break is forwarded once it gets this long.
+const MAX_LINE: usize = 16 * 1024;
+
+struct Shared {
+    buffer: Mutex<LogBuffer>,
+    /// Wakes the sender when events were added or the forwarder is closed.
+    notify: Notify,
+    closed: AtomicBool,
+}
+
+/// Forwards the log events of an instance to its [`LogTarget`] in the background.
+///
+/// Adding events never waits for the target, they are buffered until it catches up.
+pub(crate) struct LogForwarder {
+    instance: String,
+    /// XML events of the logging configuration on the standard output.
+    log4j: Mutex<Log4jParser>,
+    shared: Arc<Shared>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LogForwarder {
+    /// Start forwarding the events of `instance` as configured in `forwarding`.
+    pub(crate) fn spawn(
+        sub_matches: &ArgMatches,
+        instance: &str,
+        forwarding: &LogForwarding,
+    ) -> Result<Self> {
+        let client = match &forwarding.target {
+            LogTarget::Http { url } => {
+                let uri = url
+                    .parse()
+                    .with_context(|| format!("Invalid url {}", url))?;
+                Some((crate::http::client(sub_matches, url)?, uri))
+            }
+            LogTarget::Syslog { .. } => None,
+        };
+        let shared = Arc::new(Shared {
+            buffer: Mutex::new(LogBuffer::new(forwarding.buffer)),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+        });
+        let task = tokio::spawn(send_loop(
+            instance.to_string(),
+            forwarding.target.clone(),
+            client,
+            shared.clone(),
+        ));
+
+        Ok(Self {
+            instance: instance.to_string(),
+            log4j: Mutex::new(Log4jParser::new()),
+            shared,
+            task: Mutex::new(Some(task)),
+        })
+    }
+
+    /// Queue an event to be sent.
+    pub(crate) fn push(&self, event: LogEvent) {
+        self.shared.buffer.lock().unwrap().push(event);
+        self.shared.notify.notify_one();
+    }
+
+    /// Queue an event of the launcher.
+    pub(crate) fn launcher(&self, level: LogLevel, message: &str) {
+        self.push(LogEvent::launcher(
+            &self.instance,
+            level,
+            message,
+            SystemTime::now(),
+        ));
+    }
+
+    /// Queue a line of output of the game.
+    pub(crate) fn game_line(&self, line: &[u8], is_stderr: bool) {
+        let line = String::from_utf8_lossy(line);
+        let now = SystemTime::now();
+        if is_stderr {
+            self.push(LogEvent::game(&self.instance, &line, true, now));
+        } else if let Some(event) = self.log4j.lock().unwrap().line(&self.instance, &line, now) {
+            self.push(event);
+        }
+    }
+
+    /// Queue the complete lines of output in `pending` followed by `data`, keeping the rest in
+    /// `pending` until the line ends.
+    pub(crate) fn game_output(&self, pending: &mut Vec<u8>, data: &[u8], is_stderr: bool) {
+        pending.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(end) = pending[start..].iter().position(|b| *b == b'\n') {
+            self.game_line(&pending[start..start + end], is_stderr);
+            start += end + 1;
+        }
+        pending.drain(..start);
+        if pending.len() >= MAX_LINE {
+            self.game_line(pending, is_stderr);
+            pending.clear();
+        }
+    }
+
+    /// Send the events left, giving up after [`FLUSH_TIMEOUT`] if the target is unreachable.
+    pub(crate) async fn finish(&self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.notify.notify_one();
+        let task = self.task.lock().unwrap().take();
+        if let Some(mut task) = task {
+            if tokio::time::timeout(FLUSH_TIMEOUT, &mut task)
+                .await
+                .is_err()
+            {
+                task.abort();
+                let left = self.shared.buffer.lock().unwrap().len();
+                warn!(
+                    "{} log events of {} were not forwarded",
+                    left, self.instance
+                );
+            }
+        }
+    }
+}
+
+/// Connection to the log target, opened when the first events are sent.
+enum Sink {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Local(tokio::net::UnixDatagram),
+    Http(Box<HttpClient>, Uri),
+}
+
+impl Sink {
+    async fn open(target: &LogTarget, client: &Option<(HttpClient, Uri)>) -> Result<Self> {
+        match (target, client) {
+            (LogTarget::Http { .. }, Some((client, uri))) => {
+                Ok(Self::Http(Box::new(client.clone()), uri.clone()))
+            }
+            (LogTarget::Http { url }, None) => bail!("No client for {}", url),
+            (
+                LogTarget::Syslog {
+                    address: Some(address),
+                },
+                _,
+            ) => {
+                let has_port = matches!(
+                    address.rsplit_once(':'),
+                    Some((_, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit())
+                );
+                let address = if has_port {
+                    address.clone()
+                } else {
+                    format!("{}:{}", address, SYSLOG_PORT)
+                };
+                let remote = tokio::net::lookup_host(&address)
+                    .await?
+                    .next()
+                    .with_context(|| format!("{} has no address", address))?;
+                let local = if remote.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(local).await?;
+                socket.connect(remote).await?;
+                Ok(Self::Udp(socket))
+            }
+            #[cfg(unix)]
+            (LogTarget::Syslog { address: None }, _) => {
+                let socket = tokio::net::UnixDatagram::unbound()?;
+                socket
+                    .connect(LOCAL_SYSLOG)
+                    .with_context(|| format!("Connecting to {}", LOCAL_SYSLOG))?;
+                Ok(Self::Local(socket))
+            }
+            #[cfg(not(unix))]
+            (LogTarget::Syslog { address: None }, _) => {
+                bail!("There is no local syslog, set the address of a syslog server")
+            }
+        }
+    }
+
+    async fn send(&self, events: &[LogEvent], hostname: &str) -> Result<()> {
+        match self {
+            Self::Udp(socket) => {
+                for event in events {
+                    socket.send(event.to_syslog(hostname).as_bytes()).await?;
+                }
+            }
+            #[cfg(unix)]
+            Self::Local(socket) => {
+                for event in events {
+                    socket.send(event.to_syslog(hostname).as_bytes()).await?;
+                }
+            }
+            Self::Http(client, uri) => {
+                let response = client.post_json(uri.clone(), &events).await?;
+                if !response.status().is_success() {
+                    bail!("{} answered {}", uri, response.status());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Send the buffered events until the forwarder is closed and the buffer is empty.
+async fn send_loop(
+    instance: String,
+    target: LogTarget,
+    client: Option<(HttpClient, Uri)>,
+    shared: Arc<Shared>,
+) {
+    let hostname = hostname();
+    let batch_size = match target {
+        LogTarget::Http { .. } => HTTP_BATCH,
+        LogTarget::Syslog { .. } => 1,
+    };
+    let mut sink: Option<Sink> = None;
+    let mut failures = 0;
+    loop {
+        let batch = {
+            let mut buffer = shared.buffer.lock().unwrap();
+            let dropped = buffer.take_dropped();
+            let mut batch = Vec::new();
+            if dropped > 0 {
+                batch.push(LogEvent::launcher(
+                    &instance,
+                    LogLevel::Warn,
+                    &format!(
+                        "{} log events were dropped, the log target fell behind",
+                        dropped
+                    ),
+                    SystemTime::now(),
+                ));
+            }
+            batch.extend(buffer.take(batch_size));
+            batch
+        };
+        if batch.is_empty() {
+            if shared.closed.load(Ordering::SeqCst) {
+                break;
+            }
+            shared.notify.notified().await;
+            continue;
+        }
+
+        let result = match &sink {
+            Some(sink) => sink.send(&batch, &hostname).await,
+            None => match Sink::open(&target, &client).await {
+                Ok(opened) => {
+                    let result = opened.send(&batch, &hostname).await;
+                    sink = Some(opened);
+                    result
+                }
+                Err(e) => Err(e),
+            },
+        };
+        match result {
+            Ok(()) => {
+                if failures > 0 {
+                    info!("Forwarding the log of {} again", instance);
+                }
+                failures = 0;
+            }
+            Err(e) => {
+                if failures == 0 {
+                    warn!("Failed to forward the log of {}: {:#}", instance, e);
+                }
+                shared.buffer.lock().unwrap().requeue(batch);
+                // Reconnect, e.g. the syslog daemon may have been restarted
+                sink = None;
+                let delay = Duration::from_millis(500 << failures.min(6));
+                tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+                failures += 1;
+            }
+        }
+    }
+}
+
+/// Name of this machine sent to syslog, empty if unknown.
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
@@ -1,8 +1,25 @@
+mod config;
+mod curseforge;
+mod debug_bundle;
+mod doctor;
+mod http;
+mod instance;
+mod log_forward;
 mod meta;
+mod modrinth;
+mod mods;
+mod peer;
+mod resources_proxy;
 mod run;
 mod run_raw;
+mod scratch;
+mod self_update;
+mod state;
+mod store;
 
-use clap::{App, ColorChoice};
+use clap::{App, Arg, ColorChoice};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
@@ -11,29 +28,98 @@ async fn main() {
 }
 
 async fn main_ret() -> i32 {
-    pretty_env_logger::init();
-
     let app = App::new("plmc")
         .about("libpolymc cli interface")
         .color(ColorChoice::Auto)
+        .arg(
+            Arg::new("debug")
+                .long("debug")
+                .global(true)
+                .help("Show debug output with the time spent in each phase"),
+        )
         .subcommand(run_raw::app())
         .subcommand(run::app())
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-        .subcommand(meta::app());
+        .subcommand(meta::app())
+        .subcommand(debug_bundle::app())
+        .subcommand(doctor::app())
+        .subcommand(store::app())
+        .subcommand(instance::app())
+        .subcommand(mods::app())
+        .subcommand(state::export_app())
+        .subcommand(state::import_app())
+        .subcommand(self_update::app());
 
     let matches = app.get_matches();
+    init_tracing(matches.is_present("debug"));
 
     let ret = match matches.subcommand() {
-        Some(("run-raw", sub_matches)) => run_raw::run(sub_matches),
+        Some(("run-raw", sub_matches)) => run_raw::run(sub_matches).await,
         Some(("run", sub_matches)) => run::run(sub_matches).await,
         Some(("meta", sub_matches)) => meta::run(sub_matches).await,
+        Some(("debug-bundle", sub_matches)) => debug_bundle::run(sub_matches).await,
+        Some(("doctor", sub_matches)) => doctor::run(sub_matches).await,
+        Some(("store", sub_matches)) => store::run(sub_matches).await,
+        Some(("instance", sub_matches)) => instance::run(sub_matches).await,
+        Some(("mods", sub_matches)) => mods::run(sub_matches).await,
+        Some(("export-all", sub_matches)) => state::run_export(sub_matches),
+        Some(("import-all", sub_matches)) => state::run_import(sub_matches),
+        Some(("self-update", sub_matches)) => self_update::run(sub_matches).await,
         _ => unreachable!(),
     };
 
     if let Err(e) = ret {
         eprintln!("Error executing:\n{:?}", e);
+        if let Some(hint) = e.downcast_ref::<polymc::Error>().and_then(hint) {
+            eprintln!("{}", hint);
+        }
         1
     } else {
         ret.unwrap()
     }
 }
+
+/// What the user can do about errors caused by the arguments or the meta server.
+fn hint(e: &polymc::Error) -> Option<String> {
+    match e {
+        polymc::Error::MetaFormatUnsupported { version, max, .. } if version > max => {
+            Some("The meta server is newer than plmc, update plmc to use it".to_string())
+        }
+        polymc::Error::MetaFormatUnsupported { .. } => Some(
+            "The meta server is older than plmc, point --base-url to a current one".to_string(),
+        ),
+        polymc::Error::UnknownUid(_) => Some(
+            "Find the uid of the package with `plmc meta search <name> --base-url <url>`"
+                .to_string(),
+        ),
+        polymc::Error::UnknownVersion { uid, .. } => Some(format!(
+            "The meta server does not have this version of {}, check the version or point --base-url to another meta server",
+            uid
+        )),
+        polymc::Error::PendingDownloads => Some(
+            "The meta data of the instance is not downloaded yet, run it once with `plmc run`"
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Log to stderr, filtered by `RUST_LOG`.
+///
+/// With `--debug` the debug output of plmc and the library is shown, including the duration of
+/// the resolve, download, verify and launch phases.
+fn init_tracing(debug: bool) {
+    let default = if debug {
+        "plmc=debug,polymc=debug"
+    } else {
+        "error"
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default));
+    let span_events = if debug { FmtSpan::CLOSE } else { FmtSpan::NONE };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(span_events)
+        .with_writer(std::io::stderr)
+        .init();
+}
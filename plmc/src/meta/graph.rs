@@ -0,0 +1,198 @@
+use super::index::{cancel_on_ctrl_c, download_meta};
+use crate::scratch::ScratchDir;
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+use polymc::meta::manifest::Requirement;
+use polymc::meta::{DownloadRequest, MetaManager, ResolutionPlan, Wants};
+use std::collections::HashSet;
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("graph")
+        .about("Print the requirement graph of a component (* marks repeated components)")
+        .arg(
+            Arg::new("uid")
+                .long("uid")
+                .default_value("net.minecraft")
+                .help("The component to start from"),
+        )
+        .arg(
+            Arg::new("version")
+                .long("version")
+                .short('v')
+                .required(true)
+                .takes_value(true)
+                .help("Version of the component"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .possible_values(["tree", "dot"])
+                .default_value("tree")
+                .help("Print an ASCII tree or a Graphviz graph"),
+        )
+        .arg(
+            Arg::new("base_url")
+                .long("base-url")
+                .required(true)
+                .takes_value(true)
+                .env("PLMC_BASE_URL"),
+        )
+        .arg(
+            Arg::new("meta_index_sha256")
+                .long("meta-index-sha256")
+                .env("PLMC_META_INDEX_SHA256")
+                .takes_value(true)
+                .help("Pinned sha256 the meta index has to match"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail on meta files with fields unknown to the launcher"),
+        )
+        .arg(
+            Arg::new("meta_dir")
+                .long("meta-dir")
+                .takes_value(true)
+                .env("PLMC_META_DIR"),
+        )
+        .arg(crate::scratch::arg());
+    crate::http::args(app)
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let meta_dir = ScratchDir::new(sub_matches, "meta_dir")?;
+    let meta_dir = meta_dir.display();
+
+    let base_url = sub_matches.value_of("base_url").unwrap();
+    let client = crate::http::client(sub_matches, base_url)?;
+
+    let mut meta_manager = MetaManager::new("", "", base_url);
+    if let Some(hash) = sub_matches.value_of("meta_index_sha256") {
+        meta_manager.set_index_sha256(Some(hash.parse().context("Invalid meta index hash")?));
+    }
+    meta_manager.set_strict(sub_matches.is_present("strict"));
+    let cancel = meta_manager.cancellation_token();
+    cancel_on_ctrl_c(cancel.clone());
+
+    let wants = Wants::new(
+        sub_matches.value_of("uid").unwrap(),
+        sub_matches.value_of("version").unwrap(),
+    );
+
+    // Asset indexes are not needed for the graph
+    let plan = loop {
+        let plan = meta_manager.plan(wants.clone())?;
+        let pending: Vec<&DownloadRequest> = plan
+            .pending
+            .iter()
+            .filter(|r| !matches!(r, DownloadRequest::AssetIndex { .. }))
+            .collect();
+        if pending.is_empty() {
+            break plan;
+        }
+
+        for request in pending {
+            let (file, f_type) = match download_meta(&client, request, &meta_dir, &cancel).await {
+                Err(e)
+                    if !cancel.is_cancelled()
+                        && meta_manager.skip_optional(request, &format!("{:#}", e)) =>
+                {
+                    continue
+                }
+                ret => ret?,
+            };
+            let mut file =
+                file.with_context(|| format!("Failed to download {}", request.get_url()))?;
+            meta_manager.load_reader(&mut file, f_type)?;
+        }
+    };
+
+    for missing in &plan.missing {
+        eprintln!(
+            "Missing optional component {} {}: {}",
+            missing.uid, missing.version, missing.reason
+        );
+    }
+
+    match sub_matches.value_of("format") {
+        Some("dot") => print_dot(&plan),
+        _ => print_tree(&plan, &wants.uid),
+    }
+
+    Ok(0)
+}
+
+fn version_of<'a>(plan: &'a ResolutionPlan, uid: &str) -> &'a str {
+    plan.components
+        .iter()
+        .find(|(c, _)| c == uid)
+        .map(|(_, version)| version.as_str())
+        .unwrap_or("?")
+}
+
+/// Describe the versions a requirement asks for.
+fn annotation(req: &Requirement) -> String {
+    let version = match (&req.equals, &req.range) {
+        (Some(equals), _) => format!("equals {}", equals),
+        (None, Some(range)) => format!("{}, suggests {}", range, req.suggests),
+        (None, None) => format!("suggests {}", req.suggests),
+    };
+    if req.optional {
+        format!("{}, optional", version)
+    } else {
+        version
+    }
+}
+
+fn print_tree(plan: &ResolutionPlan, uid: &str) {
+    println!("{} {}", uid, version_of(plan, uid));
+    let mut seen = HashSet::new();
+    seen.insert(uid.to_string());
+    print_children(plan, uid, "", &mut seen);
+}
+
+fn print_children(plan: &ResolutionPlan, uid: &str, prefix: &str, seen: &mut HashSet<String>) {
+    let children: Vec<&Requirement> = plan
+        .requirements
+        .iter()
+        .filter(|(from, _)| from == uid)
+        .map(|(_, req)| req)
+        .collect();
+
+    for (i, req) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let expanded = seen.insert(req.uid.clone());
+        println!(
+            "{}{} {} {} ({}){}",
+            prefix,
+            if last { "└──" } else { "├──" },
+            req.uid,
+            version_of(plan, &req.uid),
+            annotation(req),
+            if expanded { "" } else { " *" }
+        );
+
+        if expanded {
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            print_children(plan, &req.uid, &prefix, seen);
+        }
+    }
+}
+
+fn print_dot(plan: &ResolutionPlan) {
+    let node = |uid: &str| format!("\"{} {}\"", uid, version_of(plan, uid));
+
+    println!("digraph requirements {{");
+    for (uid, _) in &plan.components {
+        println!("    {};", node(uid));
+    }
+    for (from, req) in &plan.requirements {
+        println!(
+            "    {} -> {} [label=\"{}\"];",
+            node(from),
+            node(&req.uid),
+            annotation(req)
+        );
+    }
+    println!("}}");
+}
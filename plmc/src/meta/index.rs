@@ -1,21 +1,25 @@
-use anyhow::{bail, Context, Result};
+use crate::http::HttpClient;
+use crate::scratch::ScratchDir;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{App, Arg, ArgMatches};
 use hyper::body::HttpBody;
-use hyper::client::connect::Connect;
-use hyper::Client;
-use log::*;
-use mktemp::Temp;
-use polymc::meta::{DownloadRequest, FileType, MetaIndex, MetaManager, Wants};
+use hyper::header::CONTENT_TYPE;
+use polymc::cancel::CancellationToken;
+use polymc::meta::{
+    DownloadRequest, DownloadStats, FileType, MetaIndex, MetaManager, PackageIndex, Wants,
+};
+use polymc::{parse_sha1, FileDownload, PartialFile};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use tracing::*;
 
 pub(crate) fn app() -> App<'static> {
     App::new("index")
         .about("Parse a meta index definition")
         .arg(Arg::new("file").long("file").short('i').takes_value(true))
         .setting(clap::AppSettings::ArgRequiredElseHelp)
-        .subcommand(
+        .subcommand(crate::http::args(
             App::new("search")
                 .about("Search in meta index")
                 .arg(
@@ -25,6 +29,18 @@ pub(crate) fn app() -> App<'static> {
                         .takes_value(true)
                         .env("PLMC_BASE_URL"),
                 )
+                .arg(
+                    Arg::new("meta_index_sha256")
+                        .long("meta-index-sha256")
+                        .env("PLMC_META_INDEX_SHA256")
+                        .takes_value(true)
+                        .help("Pinned sha256 the meta index has to match"),
+                )
+                .arg(
+                    Arg::new("strict")
+                        .long("strict")
+                        .help("Fail on meta files with fields unknown to the launcher"),
+                )
                 .arg(
                     Arg::new("lib_dir")
                         .long("lib-dir")
@@ -42,8 +58,9 @@ pub(crate) fn app() -> App<'static> {
                         .long("meta-dir")
                         .takes_value(true)
                         .env("PLMC_META_DIR"),
-                ),
-        )
+                )
+                .arg(crate::scratch::arg()),
+        ))
 }
 
 pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
@@ -69,38 +86,23 @@ fn run_index(sub_matches: &ArgMatches) -> Result<i32> {
 }
 
 async fn run_search(sub_matches: &ArgMatches) -> Result<i32> {
-    let tmp_lib = Temp::new_dir()?;
-    let tmp_meta = Temp::new_dir()?;
-    let tmp_assets = Temp::new_dir()?;
-    let lib_dir = if let Some(dir) = sub_matches.value_of("lib_dir") {
-        dir.to_string()
-    } else {
-        tmp_lib.display().to_string()
-    };
-
-    let meta_dir = if let Some(dir) = sub_matches.value_of("meta_dir") {
-        dir.to_string()
-    } else {
-        tmp_meta.display().to_string()
-    };
-
-    let assets_dir = if let Some(dir) = sub_matches.value_of("assets_dir") {
-        dir.to_string()
-    } else {
-        tmp_assets.display().to_string()
-    };
+    let lib_dir = ScratchDir::new(sub_matches, "lib_dir")?;
+    let meta_dir = ScratchDir::new(sub_matches, "meta_dir")?;
+    let assets_dir = ScratchDir::new(sub_matches, "assets_dir")?;
+    let (lib_dir, meta_dir, assets_dir) =
+        (lib_dir.display(), meta_dir.display(), assets_dir.display());
 
     let base_url = sub_matches.value_of("base_url").unwrap().to_string();
 
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
-
-    let mut client = Client::builder().build(https);
+    let client = crate::http::client(sub_matches, &base_url)?;
 
     let mut meta_manager = MetaManager::new(&lib_dir, &assets_dir, &base_url);
+    if let Some(hash) = sub_matches.value_of("meta_index_sha256") {
+        meta_manager.set_index_sha256(Some(hash.parse().context("Invalid meta index hash")?));
+    }
+    meta_manager.set_strict(sub_matches.is_present("strict"));
+    let cancel = meta_manager.cancellation_token();
+    cancel_on_ctrl_c(cancel.clone());
     let wants = Wants::new("net.minecraft", "1.18.1"); // TODO: non hardcoded values
 
     meta_manager.search(wants)?;
@@ -114,14 +116,11 @@ async fn run_search(sub_matches: &ArgMatches) -> Result<i32> {
         for r in &search.requests {
             info!("requested: {:?}", r);
             if r.is_file() {
-                download_file(&mut client, r).await?;
+                download_file(&client, r, &cancel).await?;
             } else {
-                let (file, f_type) = download_meta(&mut client, r, &meta_dir).await?;
-                if file.is_some() {
-                    if matches!(f_type, FileType::AssetIndex) {
-                    } else {
-                        meta_manager.load_reader(&mut file.unwrap(), f_type)?;
-                    }
+                let (file, f_type) = download_meta(&client, r, &meta_dir, &cancel).await?;
+                if let Some(mut file) = file {
+                    meta_manager.load_reader(&mut file, f_type)?;
                 }
             }
         }
@@ -130,125 +129,314 @@ async fn run_search(sub_matches: &ArgMatches) -> Result<i32> {
     Ok(0)
 }
 
-pub async fn download_file<C: Connect + Clone + Send + Sync + 'static>(
-    client: &mut Client<C>,
+/// Cancel the token once the user presses Ctrl-C.
+pub fn cancel_on_ctrl_c(cancel: CancellationToken) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Cancelling...");
+            cancel.cancel();
+        }
+    });
+}
+
+/// Download the library or asset of `request` to its path.
+///
+/// Returns the downloaded bytes, `None` if the file was in the cache already. Files in the local
+/// meta source are taken from there, see [`HttpClient::local_url`].
+pub async fn download_file(
+    client: &HttpClient,
     request: &DownloadRequest,
-) -> Result<()> {
+    cancel: &CancellationToken,
+) -> Result<Option<u64>> {
+    if let Some(url) = client.local_url(request) {
+        match download_file_from(client, request, &url, cancel).await {
+            Err(e) if !cancel.is_cancelled() => debug!("{} not in the local source: {:#}", url, e),
+            result => return result,
+        }
+    }
+    download_file_from(client, request, request.get_url(), cancel).await
+}
+
+/// Download the file of `request` from `url` instead of its own url, see [`download_file`].
+pub async fn download_file_from(
+    client: &HttpClient,
+    request: &DownloadRequest,
+    url: &str,
+    cancel: &CancellationToken,
+) -> Result<Option<u64>> {
     let filename = request.get_path().unwrap();
 
-    if verify_hash(&filename, request).is_ok() {
-        return Ok(());
+    if verify_hash(filename, request).is_ok() {
+        return Ok(None);
     }
 
-    std::fs::create_dir_all(
-        Path::new(filename)
-            .parent()
-            .context("Filename has no parent")?,
-    )?;
-
-    let url = request.get_url().parse()?;
+    cancel.check()?;
+    let fetched_hash = if !request.has_hash() && request.is_library() {
+        fetch_sha1(client, url).await?
+    } else {
+        None
+    };
 
-    let mut res = client.get(url).await?;
+    let mut res = client.fetch(url).await?;
 
     if !res.status().is_success() {
-        bail!(
-            "Failed to download file: {} ({})",
-            request.get_url(),
-            res.status()
-        );
+        bail!("Failed to download file: {} ({})", url, res.status());
     }
 
-    let mut file = OpenOptions::new()
-        .write(true)
-        .read(true)
-        .create(true)
-        .append(false)
-        .open(&filename)?;
-
-    let mut digest = ring::digest::Context::new(request.get_hash_algo().unwrap());
-
+    let mut download = FileDownload::create(request, fetched_hash)?;
     while let Some(chunk) = res.body_mut().data().await {
-        let chunk = chunk?;
-        digest.update(&chunk);
-        file.write_all(&chunk)?;
+        cancel.check()?;
+        download
+            .write(&chunk?)
+            .with_context(|| format!("Failed to download {}", url))?;
     }
+    let size = download
+        .finish()
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    Ok(Some(size))
+}
 
-    let digest = digest.finish();
-    if digest.as_ref() != request.get_hash() {
-        bail!("Failed to download file, got invalid hash");
+/// Fetch the hash maven repositories publish next to an artifact as `<artifact>.sha1`.
+///
+/// Returns `None` if the repository does not have one.
+async fn fetch_sha1(client: &HttpClient, url: &str) -> Result<Option<Vec<u8>>> {
+    let res = client.fetch(&format!("{}.sha1", url)).await?;
+    if !res.status().is_success() {
+        debug!("no hash for {} ({})", url, res.status());
+        return Ok(None);
     }
 
-    Ok(())
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    let hash = parse_sha1(&body).with_context(|| format!("Invalid hash for {}", url))?;
+
+    Ok(Some(hash.as_ref().to_vec()))
 }
 
-pub async fn download_meta<C: Connect + Clone + Send + Sync + 'static>(
-    client: &mut Client<C>,
+pub async fn download_meta(
+    client: &HttpClient,
     request: &DownloadRequest,
     meta_dir: &str,
+    cancel: &CancellationToken,
+) -> Result<(Option<File>, FileType)> {
+    let mut stats = DownloadStats::default();
+    download_meta_counted(client, request, meta_dir, cancel, &mut stats).await
+}
+
+/// Download meta data like [`download_meta`], counting it as cached or downloaded in `stats`.
+pub async fn download_meta_counted(
+    client: &HttpClient,
+    request: &DownloadRequest,
+    meta_dir: &str,
+    cancel: &CancellationToken,
+    stats: &mut DownloadStats,
 ) -> Result<(Option<File>, FileType)> {
-    // TODO: implement digest based on has_hash
     let filename = match request {
-        DownloadRequest::MetaIndex { .. } => format!("{}/index.json", meta_dir),
-        DownloadRequest::Index { uid, .. } => format!("{}/{}/index.json", meta_dir, uid),
-        DownloadRequest::Manifest { uid, version, .. } => {
-            format!("{}/{}/{}.json", meta_dir, uid, version)
-        }
         DownloadRequest::AssetIndex { path, .. } => path.to_string(),
-        _ => bail!("Could not find location to store meta data in"),
+        _ => request
+            .meta_path_at(meta_dir)
+            .context("Could not find location to store meta data in")?
+            .display()
+            .to_string(),
     };
 
-    if let Ok(file) = verify_hash(&filename, request) {
+    if let Ok(mut file) = verify_hash(&filename, request) {
+        invalidate_dependents(request, &mut file, meta_dir)?;
+        stats.record_cached(request);
         return Ok((Some(file), request.request_type()));
     } else {
         info!("Cache mismatch for {}", request.get_url());
     }
 
+    // Don't leave a corrupted or tampered file in the cache, even if the download fails
+    if request.has_hash() && Path::new(&filename).exists() {
+        warn!("Discarding invalid cached {}", filename);
+        std::fs::remove_file(&filename)?;
+    }
+
     std::fs::create_dir_all(
         Path::new(&filename)
             .parent()
             .context("Filename has no parent")?,
     )?;
 
-    let url = request.get_url().parse()?;
-
-    let mut res = client.get(url).await?;
-    if !res.status().is_success() {
-        bail!("Failed to download file: {}", res.status());
+    // Fall back to the mirrors if the meta server fails
+    let mut sources = client.sources(request.get_url());
+    if let Some(url) = client.local_url(request) {
+        sources.insert(0, url);
     }
+    let mut result = Err(anyhow!("Nothing to download {} from", request.get_url()));
+    for (i, url) in sources.iter().enumerate() {
+        result = fetch_meta(client, url, request, cancel).await;
+        match &result {
+            Err(e)
+                if i + 1 < sources.len()
+                    && !matches!(e.downcast_ref(), Some(polymc::Error::Cancelled)) =>
+            {
+                warn!("{:#}, trying {}", e, sources[i + 1]);
+            }
+            _ => break,
+        }
+    }
+    let data = result?;
 
-    let mut file = OpenOptions::new()
-        .write(true)
-        .read(true)
-        .create(true)
-        .append(false)
-        .open(&filename)?;
+    let mut file = PartialFile::create(&filename)?;
+    file.write_all(&data)?;
 
-    let mut digest = if request.has_hash() {
-        Some(ring::digest::Context::new(request.get_hash_algo().unwrap()))
-    } else {
-        None
+    let mut file = file.persist()?;
+    file.seek(SeekFrom::Start(0))?;
+    invalidate_dependents(request, &mut file, meta_dir)?;
+    stats.record_download(request, data.len() as u64);
+
+    Ok((Some(file), request.request_type()))
+}
+
+/// Open the cached meta file of `request` without downloading it, like [`download_meta`] does
+/// for a verified cache entry.
+///
+/// Fails if the file is not cached or does not match the hash of the request. Meta data
+/// without a hash, like the meta index, is used as it is.
+pub(crate) fn cached_meta(request: &DownloadRequest, meta_dir: &str) -> Result<(File, FileType)> {
+    let filename = match request {
+        DownloadRequest::AssetIndex { path, .. } => path.to_string(),
+        _ => request
+            .meta_path_at(meta_dir)
+            .context("Could not find location to store meta data in")?
+            .display()
+            .to_string(),
     };
 
+    let file = if request.has_hash() {
+        verify_hash(&filename, request)
+    } else {
+        OpenOptions::new()
+            .read(true)
+            .open(&filename)
+            .map_err(Into::into)
+    }
+    .with_context(|| format!("{} is not cached", request.get_url()))?;
+    Ok((file, request.request_type()))
+}
+
+/// Download the meta file of `request` from `url`.
+///
+/// Fails if the server returns something else than JSON, like the HTML page of a captive portal,
+/// or if the file does not match the hash of the request.
+async fn fetch_meta(
+    client: &HttpClient,
+    url: &str,
+    request: &DownloadRequest,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>> {
+    cancel.check()?;
+    let mut res = client.fetch(url).await?;
+    if !res.status().is_success() {
+        bail!("Failed to download {}: {}", url, res.status());
+    }
+    let html = matches!(
+        res.headers().get(CONTENT_TYPE).map(|t| t.to_str()),
+        Some(Ok(t)) if t.starts_with("text/html")
+    );
+
+    let mut data = Vec::new();
     while let Some(chunk) = res.body_mut().data().await {
-        let chunk = chunk?;
-        if let Some(digest) = digest.as_mut() {
-            digest.update(&chunk);
-        }
-        file.write_all(&chunk)?;
+        cancel.check()?;
+        data.extend_from_slice(&chunk?);
+    }
+
+    // Meta files are JSON objects
+    let first = data.iter().find(|b| !b.is_ascii_whitespace());
+    if html || first != Some(&b'{') {
+        let start = String::from_utf8_lossy(&data[..data.len().min(64)]).into_owned();
+        bail!(
+            "{} did not return meta data, it starts with {:?}",
+            url,
+            start
+        );
     }
 
-    // TODO: check hash
-    /*if let Some(digest) = digest {
-        let digest = digest.finish();
+    if request.has_hash() {
+        let digest = ring::digest::digest(request.get_hash_algo().unwrap(), &data);
         if digest.as_ref() != request.get_hash() {
-            warn!("Hash mismatch after downloading file");
-            return Ok((None, request.request_type()));
+            bail!(
+                "Failed to download {}, the hash does not match the index",
+                url
+            );
         }
-    }*/
+    }
+
+    Ok(data)
+}
 
+/// Remove the cached files of `meta_dir` which the index in `file` lists with a different hash.
+///
+/// For the meta index these are the package indices, for a package index the manifests of the
+/// package. A discarded package index in turn discards the stale manifests of its package once
+/// the new one is downloaded. Files the index does not list are left alone. The position of
+/// `file` is reset to the start.
+fn invalidate_dependents(request: &DownloadRequest, file: &mut File, meta_dir: &str) -> Result<()> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
     file.seek(SeekFrom::Start(0))?;
 
-    Ok((Some(file), request.request_type()))
+    // Invalid indices are reported when they are loaded
+    match request {
+        DownloadRequest::MetaIndex { .. } => {
+            if let Ok(index) = serde_json::from_slice::<MetaIndex>(&data) {
+                for package in &index.packages {
+                    let path = [package.uid.as_str(), "index.json"];
+                    remove_stale(meta_dir, &path, package.sha256.as_ref());
+                }
+            }
+        }
+        DownloadRequest::Index { uid, .. } => {
+            if let Ok(index) = serde_json::from_slice::<PackageIndex>(&data) {
+                for version in &index.versions {
+                    let name = format!("{}.json", version.version);
+                    let path = [uid.as_str(), name.as_str()];
+                    remove_stale(meta_dir, &path, version.sha256.as_ref());
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Remove the cached file at `parts` below `meta_dir` if it exists and does not have the sha256
+/// `expected`.
+///
+/// The parts come from the index, so ones which could lead out of `meta_dir` are skipped.
+/// Failures are only logged, the file is checked again when it is loaded.
+fn remove_stale(meta_dir: &str, parts: &[&str], expected: &[u8]) {
+    let mut path = PathBuf::from(meta_dir);
+    for part in parts {
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) if !part.contains(':') => path.push(part),
+            _ => {
+                warn!("Not discarding {:?}, it is outside of {}", parts, meta_dir);
+                return;
+            }
+        }
+    }
+
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("Failed to check {}: {}", path.display(), e);
+            return;
+        }
+    };
+    if ring::digest::digest(&ring::digest::SHA256, &data).as_ref() == expected {
+        return;
+    }
+    info!("Discarding stale cached {}", path.display());
+    if let Err(e) = std::fs::remove_file(&path) {
+        warn!("Failed to remove {}: {}", path.display(), e);
+    }
 }
 
 fn verify_hash(filename: &str, request: &DownloadRequest) -> Result<File> {
@@ -256,20 +444,16 @@ fn verify_hash(filename: &str, request: &DownloadRequest) -> Result<File> {
         bail!("Request has no hash");
     }
 
-    let mut file = OpenOptions::new().read(true).open(&filename)?;
+    let mut file = OpenOptions::new().read(true).open(filename)?;
 
-    let mut digest = ring::digest::Context::new(request.get_hash_algo().unwrap());
-
-    loop {
-        let mut buf = [0u8; 8192];
-        let read = file.read(&mut buf)?;
-        digest.update(&buf[..read]);
-        if read < buf.len() {
-            break;
+    if let Some(expected) = request.get_size() {
+        let actual = file.metadata()?.len();
+        if actual != expected {
+            bail!("Invalid size: expected {} bytes, got {}", expected, actual);
         }
     }
 
-    let digest = digest.finish();
+    let digest = polymc::hash_reader(request.get_hash_algo().unwrap(), &mut file)?;
 
     if digest.as_ref() == request.get_hash() {
         debug!("found {} in cache", request.get_url());
@@ -1,8 +1,8 @@
 use anyhow::{Context, Result};
 use clap::{App, Arg, ArgMatches};
-use log::trace;
 use polymc::meta::manifest::{Manifest, OS};
 use std::fs::OpenOptions;
+use tracing::trace;
 
 pub(crate) fn app() -> App<'static> {
     App::new("manifest")
@@ -54,6 +54,14 @@ fn run_lib(sub_matches: &ArgMatches, meta: Manifest) -> Result<i32> {
     let os = OS::new(os);
 
     if sub_matches.is_present("verify") {
+        let unsupported = meta.unsupported_libraries(&os);
+        if !unsupported.is_empty() {
+            println!("Libraries not supported on {}:", os.name);
+            for (lib, reason) in unsupported {
+                println!("{}: {}", lib.name, reason);
+            }
+        }
+
         let verify = meta.verify_at(dir, &os)?;
         if !verify.is_empty() {
             println!("Failed to verify libraries:");
@@ -1,5 +1,10 @@
+mod graph;
 pub mod index;
 mod manifest;
+mod resolve;
+mod search;
+pub(crate) mod servers;
+mod snapshot;
 
 use anyhow::{bail, Result};
 use clap::{App, ArgMatches};
@@ -10,12 +15,20 @@ pub(crate) fn app() -> App<'static> {
         .subcommand(manifest::app())
         .setting(clap::AppSettings::ArgRequiredElseHelp)
         .subcommand(index::app())
+        .subcommand(search::app())
+        .subcommand(graph::app())
+        .subcommand(resolve::app())
+        .subcommand(snapshot::app())
 }
 
 pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
     match sub_matches.subcommand() {
         Some(("manifest", sub_matches)) => manifest::run(sub_matches),
         Some(("index", sub_matches)) => index::run(sub_matches).await,
+        Some(("search", sub_matches)) => search::run(sub_matches).await,
+        Some(("graph", sub_matches)) => graph::run(sub_matches).await,
+        Some(("resolve", sub_matches)) => resolve::run(sub_matches).await,
+        Some(("snapshot", sub_matches)) => snapshot::run(sub_matches).await,
         _ => bail!("no command given"),
     }
 }
@@ -0,0 +1,190 @@
+use super::index::{cancel_on_ctrl_c, download_meta};
+use crate::scratch::ScratchDir;
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use polymc::meta::{DownloadRequest, MetaManager, VersionPolicy, VersionRange, Wants};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Components to resolve together, read from the TOML file given to `meta resolve`, e.g.
+///
+/// ```toml
+/// [[components]]
+/// uid = "net.minecraft"
+/// version = "1.18.2"
+///
+/// [[components]]
+/// uid = "net.fabricmc.fabric-loader"
+/// range = ">=0.14"
+/// ```
+///
+/// The lock file written by `meta resolve` has the same format, with every component pinned.
+#[derive(Debug, Serialize, Deserialize)]
+struct ComponentList {
+    components: Vec<WantedComponent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WantedComponent {
+    uid: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    version: String,
+    /// Versions to choose from if `version` is not set, the newest matching one is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range: Option<VersionRange>,
+}
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("resolve")
+        .about("Resolve several components together and print the versions as a lock file")
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .short('f')
+                .required(true)
+                .takes_value(true)
+                .help("TOML file listing the components as [[components]] tables with a uid and a version or range, JSON if it ends in .json"),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .short('o')
+                .takes_value(true)
+                .help("Write the lock file here instead of printing it"),
+        )
+        .arg(
+            Arg::new("version_conflicts")
+                .long("version-conflicts")
+                .env("PLMC_VERSION_CONFLICTS")
+                .possible_values(["fail", "first"])
+                .default_value("fail")
+                .help("Fail on components wanted in different versions, or use the first one"),
+        )
+        .arg(
+            Arg::new("base_url")
+                .long("base-url")
+                .required(true)
+                .takes_value(true)
+                .env("PLMC_BASE_URL"),
+        )
+        .arg(
+            Arg::new("meta_index_sha256")
+                .long("meta-index-sha256")
+                .env("PLMC_META_INDEX_SHA256")
+                .takes_value(true)
+                .help("Pinned sha256 the meta index has to match"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail on meta files with fields unknown to the launcher"),
+        )
+        .arg(
+            Arg::new("meta_dir")
+                .long("meta-dir")
+                .takes_value(true)
+                .env("PLMC_META_DIR"),
+        )
+        .arg(crate::scratch::arg());
+    crate::http::args(app)
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let path = sub_matches.value_of("file").unwrap();
+    let data = std::fs::read_to_string(path).with_context(|| format!("Opening {}", path))?;
+    let json = Path::new(path).extension() == Some("json".as_ref());
+    let list: ComponentList = if json {
+        serde_json::from_str(&data).with_context(|| format!("Reading {}", path))?
+    } else {
+        toml::from_str(&data).with_context(|| format!("Reading {}", path))?
+    };
+    let mut wants = Vec::new();
+    for component in list.components {
+        if component.version.is_empty() && component.range.is_none() {
+            bail!("{} has neither a version nor a range", component.uid);
+        }
+        let mut what = Wants::new(&component.uid, &component.version);
+        what.range = component.range;
+        wants.push(what);
+    }
+    if wants.is_empty() {
+        bail!("{} lists no components", path);
+    }
+
+    let meta_dir = ScratchDir::new(sub_matches, "meta_dir")?;
+    let meta_dir = meta_dir.display();
+
+    let base_url = sub_matches.value_of("base_url").unwrap();
+    let client = crate::http::client(sub_matches, base_url)?;
+
+    let mut meta_manager = MetaManager::new("", "", base_url);
+    if let Some(hash) = sub_matches.value_of("meta_index_sha256") {
+        meta_manager.set_index_sha256(Some(hash.parse().context("Invalid meta index hash")?));
+    }
+    meta_manager.set_strict(sub_matches.is_present("strict"));
+    meta_manager.set_version_policy(match sub_matches.value_of("version_conflicts") {
+        Some("first") => VersionPolicy::FirstRequested,
+        _ => VersionPolicy::Fail,
+    });
+    let cancel = meta_manager.cancellation_token();
+    cancel_on_ctrl_c(cancel.clone());
+
+    // Asset indexes are not needed for the versions
+    let plan = loop {
+        let plan = meta_manager.plan_all(&wants)?;
+        let pending: Vec<&DownloadRequest> = plan
+            .pending
+            .iter()
+            .filter(|r| !matches!(r, DownloadRequest::AssetIndex { .. }))
+            .collect();
+        if pending.is_empty() {
+            break plan;
+        }
+
+        for request in pending {
+            let (file, f_type) = match download_meta(&client, request, &meta_dir, &cancel).await {
+                Err(e)
+                    if !cancel.is_cancelled()
+                        && meta_manager.skip_optional(request, &format!("{:#}", e)) =>
+                {
+                    continue
+                }
+                ret => ret?,
+            };
+            let mut file =
+                file.with_context(|| format!("Failed to download {}", request.get_url()))?;
+            meta_manager.load_reader(&mut file, f_type)?;
+        }
+    };
+
+    for missing in &plan.missing {
+        eprintln!(
+            "Missing optional component {} {}: {}",
+            missing.uid, missing.version, missing.reason
+        );
+    }
+
+    let lock = ComponentList {
+        components: plan
+            .components
+            .into_iter()
+            .map(|(uid, version)| WantedComponent {
+                uid,
+                version,
+                range: None,
+            })
+            .collect(),
+    };
+    // In the format of the components file, to be fed back in
+    let lock = if json {
+        serde_json::to_string_pretty(&lock)? + "\n"
+    } else {
+        toml::to_string(&lock)?
+    };
+    match sub_matches.value_of("out") {
+        Some(out) => std::fs::write(out, lock).with_context(|| format!("Writing {}", out))?,
+        None => print!("{}", lock),
+    }
+
+    Ok(0)
+}
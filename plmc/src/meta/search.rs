@@ -0,0 +1,76 @@
+use super::index::{cancel_on_ctrl_c, download_meta};
+use crate::scratch::ScratchDir;
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+use polymc::meta::MetaManager;
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("search")
+        .about("Search packages in the meta index by name or uid")
+        .arg(
+            Arg::new("query")
+                .required(true)
+                .help("Part of the name or uid to search for"),
+        )
+        .arg(
+            Arg::new("base_url")
+                .long("base-url")
+                .required(true)
+                .takes_value(true)
+                .env("PLMC_BASE_URL"),
+        )
+        .arg(
+            Arg::new("meta_index_sha256")
+                .long("meta-index-sha256")
+                .env("PLMC_META_INDEX_SHA256")
+                .takes_value(true)
+                .help("Pinned sha256 the meta index has to match"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail on meta files with fields unknown to the launcher"),
+        )
+        .arg(
+            Arg::new("meta_dir")
+                .long("meta-dir")
+                .takes_value(true)
+                .env("PLMC_META_DIR"),
+        )
+        .arg(crate::scratch::arg());
+    crate::http::args(app)
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let meta_dir = ScratchDir::new(sub_matches, "meta_dir")?;
+    let meta_dir = meta_dir.display();
+
+    let base_url = sub_matches.value_of("base_url").unwrap();
+    let client = crate::http::client(sub_matches, base_url)?;
+
+    let mut meta_manager = MetaManager::new("", "", base_url);
+    if let Some(hash) = sub_matches.value_of("meta_index_sha256") {
+        meta_manager.set_index_sha256(Some(hash.parse().context("Invalid meta index hash")?));
+    }
+    meta_manager.set_strict(sub_matches.is_present("strict"));
+    let cancel = meta_manager.cancellation_token();
+    cancel_on_ctrl_c(cancel.clone());
+
+    let request = meta_manager.index_request();
+    let (file, f_type) = download_meta(&client, &request, &meta_dir, &cancel).await?;
+    let mut file = file.context("Failed to download the meta index")?;
+    meta_manager.load_reader(&mut file, f_type)?;
+
+    let query = sub_matches.value_of("query").unwrap();
+    let packages = meta_manager.find_packages(query)?;
+    if packages.is_empty() {
+        eprintln!("No packages found for '{}'", query);
+        return Ok(1);
+    }
+
+    for package in packages {
+        println!("{}\t{}", package.uid, package.name);
+    }
+
+    Ok(0)
+}
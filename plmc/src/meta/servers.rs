@@ -0,0 +1,84 @@
+use crate::config::GlobalConfig;
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+use polymc::meta::{fastest_server, ServerProbe};
+use std::time::{Duration, Instant};
+use tracing::*;
+
+/// How long a meta server may take to answer before it is considered down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Base url of the meta server given as `arg`, or the fastest of the configured servers.
+pub(crate) async fn base_url(sub_matches: &ArgMatches, arg: &str) -> Result<String> {
+    if let Some(url) = sub_matches.value_of(arg) {
+        return Ok(url.to_string());
+    }
+
+    let servers = GlobalConfig::load()?.meta_servers();
+    let probes = probe_all(sub_matches, &servers).await;
+    match fastest_server(&probes) {
+        Some(probe) => {
+            info!(
+                "Using the meta server {}, it answered in {}ms",
+                probe.url,
+                probe.latency.unwrap_or_default().as_millis()
+            );
+            Ok(probe.url.clone())
+        }
+        None => bail!(
+            "None of the meta servers is reachable ({}), pass one with --base-url",
+            servers.join(", ")
+        ),
+    }
+}
+
+/// Base url of the meta server given as `arg`, or the first of the configured servers without
+/// probing them, for working from the cache.
+pub(crate) fn configured_url(sub_matches: &ArgMatches, arg: &str) -> Result<String> {
+    if let Some(url) = sub_matches.value_of(arg) {
+        return Ok(url.to_string());
+    }
+
+    match GlobalConfig::load()?.meta_servers().into_iter().next() {
+        Some(url) => Ok(url),
+        None => bail!("No meta server is configured, pass one with --base-url"),
+    }
+}
+
+/// Request the index of every server at once.
+async fn probe_all(sub_matches: &ArgMatches, servers: &[String]) -> Vec<ServerProbe> {
+    let mut tasks = Vec::new();
+    for url in servers {
+        let client = crate::http::client(sub_matches, url);
+        let url = url.clone();
+        tasks.push(tokio::spawn(async move {
+            let latency = match client {
+                Ok(client) => probe(&client, &url).await,
+                Err(e) => Err(e),
+            };
+            match latency {
+                Ok(latency) => ServerProbe::new(url, Some(latency)),
+                Err(e) => {
+                    debug!("meta server {} is down: {:#}", url, e);
+                    ServerProbe::new(url, None)
+                }
+            }
+        }));
+    }
+
+    let mut probes = Vec::new();
+    for task in tasks {
+        probes.extend(task.await.ok());
+    }
+    probes
+}
+
+async fn probe(client: &crate::http::HttpClient, url: &str) -> Result<Duration> {
+    let started = Instant::now();
+    let uri = format!("{}/index.json", url.trim_end_matches('/')).parse()?;
+    let response = tokio::time::timeout(PROBE_TIMEOUT, client.get(uri)).await??;
+    if !response.status().is_success() {
+        bail!("{}", response.status());
+    }
+    Ok(started.elapsed())
+}
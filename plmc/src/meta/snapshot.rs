@@ -0,0 +1,115 @@
+use super::index::{cancel_on_ctrl_c, download_meta};
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+use indicatif::{ProgressBar, ProgressStyle};
+use polymc::cancel::CancellationToken;
+use polymc::meta::format::check_format_version;
+use polymc::meta::{DownloadRequest, FileType, MetaIndex, MetaSnapshot, PackageIndex};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("snapshot")
+        .about("Save the meta data of packages for reproducible builds, see `run --meta-snapshot`")
+        .arg(
+            Arg::new("uid")
+                .required(true)
+                .multiple_occurrences(true)
+                .help("Packages to save, the packages they require are saved too"),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .short('o')
+                .required(true)
+                .takes_value(true)
+                .help("Directory to save the snapshot in"),
+        )
+        .arg(
+            Arg::new("base_url")
+                .long("base-url")
+                .required(true)
+                .takes_value(true)
+                .env("PLMC_BASE_URL"),
+        )
+        .arg(
+            Arg::new("meta_index_sha256")
+                .long("meta-index-sha256")
+                .env("PLMC_META_INDEX_SHA256")
+                .takes_value(true)
+                .help("Pinned sha256 the meta index has to match"),
+        );
+    crate::http::args(app)
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let out = sub_matches.value_of("out").unwrap();
+    let base_url = sub_matches.value_of("base_url").unwrap();
+    let client = crate::http::client(sub_matches, base_url)?;
+    let cancel = CancellationToken::new();
+    cancel_on_ctrl_c(cancel.clone());
+    std::fs::create_dir_all(out).with_context(|| format!("Creating {}", out))?;
+
+    let hash = match sub_matches.value_of("meta_index_sha256") {
+        Some(hash) => Some(hash.parse().context("Invalid meta index hash")?),
+        None => None,
+    };
+    let request = DownloadRequest::new_meta_index(format!("{}/index.json", base_url), hash);
+    let (file, _) = download_meta(&client, &request, out, &cancel).await?;
+    let index_data = read_meta(file, FileType::MetaIndex)?;
+    let index = MetaIndex::from_data(&index_data)?;
+
+    let style = ProgressStyle::default_bar()
+        .progress_chars("=> ")
+        .template("{prefix:.bold} [{bar}] {pos}/{len} {msg}");
+    let mut uids: Vec<String> = Vec::new();
+    let mut queue: VecDeque<String> = sub_matches
+        .values_of("uid")
+        .unwrap()
+        .map(ToString::to_string)
+        .collect();
+    let mut manifests = 0;
+    while let Some(uid) = queue.pop_front() {
+        if uids.contains(&uid) {
+            continue;
+        }
+        let package = index.get_uid(&uid)?;
+        let request = DownloadRequest::new_package_index(base_url, package);
+        let (file, _) = download_meta(&client, &request, out, &cancel).await?;
+        let package_index = PackageIndex::from_data(&read_meta(file, FileType::Index)?)?;
+
+        let pb = ProgressBar::new(package_index.versions.len() as u64);
+        pb.set_style(style.clone());
+        pb.set_prefix(uid.clone());
+        for version in &package_index.versions {
+            pb.set_message(version.version.clone());
+            queue.extend(version.requires.iter().map(|r| r.uid.clone()));
+            let request = DownloadRequest::new_package_manifest(base_url, &uid, version);
+            download_meta(&client, &request, out, &cancel).await?;
+            pb.inc(1);
+        }
+        pb.finish_with_message("done");
+        manifests += package_index.versions.len();
+        uids.push(uid);
+    }
+
+    MetaSnapshot::new(base_url, &index_data, uids.clone()).save(out)?;
+    println!(
+        "Saved {} manifests of {} in {}",
+        manifests,
+        uids.join(", "),
+        out
+    );
+
+    Ok(0)
+}
+
+/// Read a downloaded meta file, failing early if the meta server uses an unsupported format.
+fn read_meta(file: Option<File>, file_type: FileType) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.context("Failed to download meta data")?
+        .read_to_end(&mut data)?;
+    check_format_version(&data, file_type)?;
+    Ok(data)
+}
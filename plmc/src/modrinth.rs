@@ -0,0 +1,222 @@
+use crate::http::HttpClient;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgMatches};
+use hyper::{Body, Response};
+use polymc::instance::check_file_name;
+use polymc::meta::manifest::{LibraryDownload, Sha1Sum};
+use polymc::meta::DownloadRequest;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) const DEFAULT_API_URL: &str = "https://api.modrinth.com/v2";
+
+/// Argument for the url of the Modrinth API.
+pub(crate) fn arg() -> Arg<'static> {
+    Arg::new("modrinth_url")
+        .long("modrinth-url")
+        .env("PLMC_MODRINTH_URL")
+        .takes_value(true)
+        .default_value(DEFAULT_API_URL)
+        .help("Url of the Modrinth API mods are installed from")
+}
+
+/// Version of a project on Modrinth.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Version {
+    pub id: String,
+    pub project_id: String,
+    pub version_number: String,
+    pub files: Vec<VersionFile>,
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+    #[serde(default)]
+    pub loaders: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Dependency {
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// Exact version required, any version compatible with the instance if not set.
+    #[serde(default)]
+    pub version_id: Option<String>,
+    /// `required`, `optional`, `incompatible` or `embedded`.
+    pub dependency_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct VersionFile {
+    pub url: String,
+    pub filename: String,
+    #[serde(default)]
+    pub primary: bool,
+    pub size: i64,
+    pub hashes: FileHashes,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FileHashes {
+    pub sha1: Sha1Sum,
+}
+
+impl Version {
+    /// The file to install, the one marked as primary or the first one.
+    pub fn primary_file(&self) -> Option<&VersionFile> {
+        self.files
+            .iter()
+            .find(|f| f.primary)
+            .or_else(|| self.files.first())
+    }
+
+    /// Why the version does not fit the instance, `None` if it supports the game version and
+    /// mod loader.
+    pub fn incompatibility(
+        &self,
+        game_version: Option<&str>,
+        loader: Option<&str>,
+    ) -> Option<String> {
+        match (game_version, loader) {
+            (Some(version), _) if !self.game_versions.iter().any(|v| v == version) => {
+                Some(format!("does not support Minecraft {}", version))
+            }
+            (_, Some(loader)) if !self.loaders.iter().any(|l| l == loader) => {
+                Some(format!("does not support {}", loader))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl VersionFile {
+    /// Request to download the file into `dir`.
+    ///
+    /// Fails if the API returned a file name which is not a plain file name.
+    pub fn request(&self, dir: &Path) -> Result<DownloadRequest> {
+        check_file_name(&self.filename)?;
+        Ok(DownloadRequest::new_library(
+            LibraryDownload {
+                sha1: Some(self.hashes.sha1.clone()),
+                size: self.size,
+                url: self.url.clone(),
+            },
+            dir.join(&self.filename),
+        ))
+    }
+}
+
+pub(crate) struct Modrinth {
+    client: HttpClient,
+    api_url: String,
+}
+
+impl Modrinth {
+    pub fn new(client: HttpClient, sub_matches: &ArgMatches) -> Self {
+        Self {
+            client,
+            api_url: sub_matches
+                .value_of("modrinth_url")
+                .unwrap_or(DEFAULT_API_URL)
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// Versions of `project` for the game version and mod loader, the newest first.
+    pub async fn versions(
+        &self,
+        project: &str,
+        game_version: Option<&str>,
+        loader: Option<&str>,
+    ) -> Result<Vec<Version>> {
+        let mut query = Vec::new();
+        if let Some(loader) = loader {
+            query.push(format!("loaders=%5B%22{}%22%5D", loader));
+        }
+        if let Some(version) = game_version {
+            query.push(format!("game_versions=%5B%22{}%22%5D", version));
+        }
+        let url = format!(
+            "{}/project/{}/version?{}",
+            self.api_url,
+            project,
+            query.join("&")
+        );
+        self.get_json(&url)
+            .await
+            .with_context(|| format!("Looking up the versions of {}", project))
+    }
+
+    /// Newest version of `project` for the game version and mod loader.
+    pub async fn latest_version(
+        &self,
+        project: &str,
+        game_version: Option<&str>,
+        loader: Option<&str>,
+    ) -> Result<Version> {
+        match self
+            .versions(project, game_version, loader)
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(version) => Ok(version),
+            None => bail!(
+                "{} has no version for Minecraft {} with {}",
+                project,
+                game_version.unwrap_or("(any)"),
+                loader.unwrap_or("no mod loader")
+            ),
+        }
+    }
+
+    pub async fn version(&self, id: &str) -> Result<Version> {
+        self.get_json(&format!("{}/version/{}", self.api_url, id))
+            .await
+            .with_context(|| format!("Looking up version {}", id))
+    }
+
+    /// Versions the files with the sha1 hashes `hashes` belong to, by hash.
+    ///
+    /// Files unknown to Modrinth are left out.
+    pub async fn version_files(&self, hashes: &[String]) -> Result<HashMap<String, Version>> {
+        let url = format!("{}/version_files", self.api_url);
+        let body = json!({"hashes": hashes, "algorithm": "sha1"});
+        json(&url, self.client.post_json(url.parse()?, &body).await?).await
+    }
+
+    /// Newest versions of the projects of the files with the sha1 hashes `hashes` for the game
+    /// version and mod loader, by hash.
+    pub async fn latest_versions(
+        &self,
+        hashes: &[String],
+        game_version: Option<&str>,
+        loader: Option<&str>,
+    ) -> Result<HashMap<String, Version>> {
+        let url = format!("{}/version_files/update", self.api_url);
+        let body = json!({
+            "hashes": hashes,
+            "algorithm": "sha1",
+            "loaders": loader.into_iter().collect::<Vec<_>>(),
+            "game_versions": game_version.into_iter().collect::<Vec<_>>(),
+        });
+        json(&url, self.client.post_json(url.parse()?, &body).await?).await
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        json(url, self.client.get(url.parse()?).await?).await
+    }
+}
+
+/// Parse the JSON response `res` to a request to `url`.
+pub(crate) async fn json<T: DeserializeOwned>(url: &str, res: Response<Body>) -> Result<T> {
+    if !res.status().is_success() {
+        bail!("Request to {} failed ({})", url, res.status());
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    serde_json::from_slice(&body).with_context(|| format!("Invalid response from {}", url))
+}
@@ -0,0 +1,478 @@
+use crate::curseforge::CurseForge;
+use crate::http::HttpClient;
+use crate::meta::index::{cancel_on_ctrl_c, download_file};
+use crate::modrinth::{Modrinth, Version};
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use console::style;
+use polymc::cancel::CancellationToken;
+use polymc::instance::{
+    apply_updates, check_file_name, list_mods, InstalledMod, InstanceInfo, LockedMod, ModLock,
+    ModUpdate, MODS_DIR,
+};
+use polymc::meta::DownloadRequest;
+use std::path::{Path, PathBuf};
+use tracing::*;
+
+/// Directory in the mods directory updates are downloaded to.
+const STAGING_DIR: &str = ".staging";
+
+pub(crate) fn app() -> App<'static> {
+    let args = |app: App<'static>| {
+        crate::http::args(
+            app.arg(
+                Arg::new("dir")
+                    .long("dir")
+                    .short('d')
+                    .env("PLMC_MC_DIR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Directory of the instance"),
+            )
+            .arg(
+                Arg::new("game_version")
+                    .long("game-version")
+                    .takes_value(true)
+                    .help("Minecraft version, taken from the instance if not given"),
+            )
+            .arg(
+                Arg::new("loader")
+                    .long("loader")
+                    .takes_value(true)
+                    .help("Mod loader, e.g. `fabric`, taken from the instance if not given"),
+            )
+            .arg(crate::modrinth::arg())
+            .args(crate::curseforge::args()),
+        )
+    };
+
+    App::new("mods")
+        .about("Manage the mods of an instance")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(args(
+            App::new("add")
+                .about("Install mods from Modrinth with the mods they require")
+                .arg(
+                    Arg::new("projects")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .required(true)
+                        .help("Slugs or ids of the Modrinth projects"),
+                ),
+        ))
+        .subcommand(args(
+            App::new("outdated").about("List mods with newer versions for the instance"),
+        ))
+        .subcommand(args(
+            App::new("update")
+                .about("Update mods, restoring the previous versions if it fails")
+                .arg(
+                    Arg::new("mods")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .help("Start of the file names of the mods to update, all if not given"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .help("Don't ask before updating"),
+                ),
+        ))
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    match sub_matches.subcommand() {
+        Some(("add", sub_matches)) => run_add(sub_matches).await,
+        Some(("outdated", sub_matches)) => run_outdated(sub_matches).await,
+        Some(("update", sub_matches)) => run_update(sub_matches).await,
+        _ => bail!("Unknown command"),
+    }
+}
+
+/// Newer version of an installed mod.
+struct Outdated<'a> {
+    installed: &'a InstalledMod,
+    current: Option<String>,
+    latest: String,
+    source: &'static str,
+    /// `None` if the file may not be downloaded by launchers.
+    request: Option<DownloadRequest>,
+    /// Entry of the new version in the [`ModLock`], for mods from Modrinth.
+    locked: Option<LockedMod>,
+}
+
+impl Outdated<'_> {
+    fn print(&self) {
+        println!(
+            "{}: {} -> {} ({}){}",
+            self.installed.file_name(),
+            self.current.as_deref().unwrap_or("unknown"),
+            self.latest,
+            self.source,
+            if self.request.is_some() {
+                ""
+            } else {
+                ", has to be downloaded manually"
+            }
+        );
+    }
+}
+
+async fn run_add(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = Path::new(sub_matches.value_of("dir").unwrap());
+    let info = InstanceInfo::load(dir).ok();
+    let (game_version, loader) = target(sub_matches, &info);
+    let projects: Vec<String> = sub_matches
+        .values_of("projects")
+        .unwrap()
+        .map(ToString::to_string)
+        .collect();
+
+    let client = crate::http::client(sub_matches, "")?;
+    let modrinth = Modrinth::new(client.clone(), sub_matches);
+    let cancel = CancellationToken::new();
+    cancel_on_ctrl_c(cancel.clone());
+    install(
+        &client,
+        &modrinth,
+        dir,
+        &projects,
+        game_version,
+        loader,
+        &cancel,
+    )
+    .await?;
+
+    Ok(0)
+}
+
+/// Print a warning for the user.
+pub(crate) fn warn_user(message: &str) {
+    eprintln!("{}", style(format!("Warning: {}", message)).yellow());
+}
+
+/// Install the Modrinth `projects` with the mods they require into the game directory `dir`.
+///
+/// Dependencies already in the [`ModLock`] of the instance are kept. Versions not made for the
+/// game version or mod loader and mods declared incompatible with installed ones are installed
+/// with a warning.
+pub(crate) async fn install(
+    client: &HttpClient,
+    modrinth: &Modrinth,
+    dir: &Path,
+    projects: &[String],
+    game_version: Option<&str>,
+    loader: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let mut lock = ModLock::load(dir)?;
+    let installed = |lock: &ModLock, resolved: &[(Version, bool)], project: &str| {
+        lock.get(project).is_some() || resolved.iter().any(|(v, _)| v.project_id == project)
+    };
+
+    // Projects by slug or id, or exact versions of dependencies
+    let mut queue: Vec<(Option<String>, Option<String>, bool)> = projects
+        .iter()
+        .rev()
+        .map(|p| (Some(p.to_string()), None, false))
+        .collect();
+    let mut resolved: Vec<(Version, bool)> = Vec::new();
+    while let Some((project, version_id, dependency)) = queue.pop() {
+        cancel.check()?;
+        if let Some(project) = &project {
+            if resolved.iter().any(|(v, _)| &v.project_id == project)
+                || (dependency && lock.get(project).is_some())
+            {
+                continue;
+            }
+        }
+
+        let version = match (&project, &version_id) {
+            (_, Some(id)) => modrinth.version(id).await?,
+            (Some(project), None) => {
+                modrinth
+                    .latest_version(project, game_version, loader)
+                    .await?
+            }
+            (None, None) => continue,
+        };
+        // Projects given by slug are only known by id now
+        if resolved
+            .iter()
+            .any(|(v, _)| v.project_id == version.project_id)
+            || (dependency && lock.get(&version.project_id).is_some())
+        {
+            continue;
+        }
+        let name = project.as_deref().unwrap_or(&version.project_id);
+        if let Some(problem) = version.incompatibility(game_version, loader) {
+            warn_user(&format!("{} {} {}", name, version.version_number, problem));
+        }
+
+        for dep in &version.dependencies {
+            match dep.dependency_type.as_str() {
+                "required" => {
+                    debug!(
+                        "{} requires {:?} {:?}",
+                        name, dep.project_id, dep.version_id
+                    );
+                    queue.push((dep.project_id.clone(), dep.version_id.clone(), true));
+                }
+                "incompatible" => match &dep.project_id {
+                    Some(other) if installed(&lock, &resolved, other) => {
+                        warn_user(&format!("{} is incompatible with {}", name, other));
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        resolved.push((version, dependency));
+    }
+
+    let mods_dir = dir.join(MODS_DIR);
+    for (version, dependency) in resolved {
+        let file = version.primary_file().with_context(|| {
+            format!(
+                "{} {} has no files",
+                version.project_id, version.version_number
+            )
+        })?;
+        if let Some(old) = lock.get(&version.project_id) {
+            if old.version_id == version.id {
+                println!("{} is already installed", file.filename);
+                continue;
+            }
+        }
+
+        println!(
+            "Installing {}{}",
+            file.filename,
+            if dependency { " (dependency)" } else { "" }
+        );
+        download_file(client, &file.request(&mods_dir)?, cancel).await?;
+        if let Some(old) = lock.get(&version.project_id) {
+            if old.file_name != file.filename {
+                // The lock file may have been edited
+                match check_file_name(&old.file_name) {
+                    Ok(()) => {
+                        let _ = std::fs::remove_file(mods_dir.join(&old.file_name));
+                    }
+                    Err(e) => warn!("Not removing the old version of {}: {}", file.filename, e),
+                }
+            }
+        }
+        lock.insert(LockedMod {
+            project_id: version.project_id.clone(),
+            version_id: version.id.clone(),
+            file_name: file.filename.clone(),
+            sha1: file.hashes.sha1.clone(),
+            dependency,
+        });
+        lock.save(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Game version and mod loader to look for mods for, from the arguments or the instance.
+fn target<'a>(
+    sub_matches: &'a ArgMatches,
+    info: &'a Option<InstanceInfo>,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let game_version = sub_matches.value_of("game_version").or_else(|| {
+        let info = info.as_ref()?;
+        Some(info.component("net.minecraft").unwrap_or(&info.version))
+    });
+    let loader = sub_matches
+        .value_of("loader")
+        .or_else(|| info.as_ref()?.mod_loader());
+    (game_version, loader)
+}
+
+async fn run_outdated(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = Path::new(sub_matches.value_of("dir").unwrap());
+    let mods = list_mods(dir)?;
+    let client = crate::http::client(sub_matches, "")?;
+    let outdated = find_outdated(sub_matches, &client, dir, &mods).await?;
+
+    if outdated.is_empty() {
+        println!("All {} mods are up to date", mods.len());
+    }
+    for update in &outdated {
+        update.print();
+    }
+
+    Ok(0)
+}
+
+async fn run_update(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = Path::new(sub_matches.value_of("dir").unwrap());
+    let selected: Vec<&str> = sub_matches
+        .values_of("mods")
+        .into_iter()
+        .flatten()
+        .collect();
+    let mods: Vec<_> = list_mods(dir)?
+        .into_iter()
+        .filter(|m| selected.is_empty() || selected.iter().any(|s| m.file_name().starts_with(s)))
+        .collect();
+    if mods.is_empty() {
+        bail!("No mods to update in {}", dir.join(MODS_DIR).display());
+    }
+
+    let client = crate::http::client(sub_matches, "")?;
+    let outdated = find_outdated(sub_matches, &client, dir, &mods).await?;
+    if outdated.is_empty() {
+        println!("All {} mods are up to date", mods.len());
+        return Ok(0);
+    }
+    for update in &outdated {
+        update.print();
+    }
+    if !sub_matches.is_present("yes") && !crate::run::confirm("Update these mods? [Y/n]")? {
+        return Ok(1);
+    }
+
+    let mods_dir = dir.join(MODS_DIR);
+    let staging = mods_dir.join(STAGING_DIR);
+    let cancel = CancellationToken::new();
+    cancel_on_ctrl_c(cancel.clone());
+
+    // Download everything first, so failed downloads don't leave a mix of versions
+    let result = async {
+        let mut updates = Vec::new();
+        for update in outdated.iter().filter(|u| u.request.is_some()) {
+            let request = update.request.as_ref().unwrap();
+            download_file(&client, request, &cancel).await?;
+            let staged = PathBuf::from(request.get_path().unwrap());
+            updates.push(ModUpdate {
+                old: update.installed.path.clone(),
+                file_name: staged
+                    .file_name()
+                    .context("Download without file name")?
+                    .to_string_lossy()
+                    .to_string(),
+                staged,
+            });
+        }
+        apply_updates(&mods_dir, &updates)?;
+        Ok::<_, anyhow::Error>(updates.len())
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&staging);
+
+    let updated = result.context("Updating mods, no mods were changed")?;
+
+    let mut lock = ModLock::load(dir)?;
+    for update in outdated.iter().filter(|u| u.request.is_some()) {
+        if let Some(locked) = &update.locked {
+            lock.insert(locked.clone());
+        }
+    }
+    lock.save(dir)?;
+    println!("Updated {} mods", updated);
+    Ok(0)
+}
+
+/// Look up newer versions of `mods` on Modrinth and on CurseForge, if an API key is set.
+///
+/// Mods are matched by their hashes. The requests download the new files into the staging
+/// directory.
+async fn find_outdated<'a>(
+    sub_matches: &ArgMatches,
+    client: &HttpClient,
+    dir: &Path,
+    mods: &'a [InstalledMod],
+) -> Result<Vec<Outdated<'a>>> {
+    let info = InstanceInfo::load(dir).ok();
+    let (game_version, loader) = target(sub_matches, &info);
+    let lock = ModLock::load(dir)?;
+    debug!("looking for mods for {:?} with {:?}", game_version, loader);
+    let staging = dir.join(MODS_DIR).join(STAGING_DIR);
+
+    let mut ret = Vec::new();
+    let modrinth = Modrinth::new(client.clone(), sub_matches);
+    let hashes: Vec<String> = mods.iter().map(|m| m.sha1.to_string()).collect();
+    let current = modrinth.version_files(&hashes).await?;
+    let latest = modrinth
+        .latest_versions(&hashes, game_version, loader)
+        .await?;
+    for (installed, hash) in mods.iter().zip(&hashes) {
+        let version = match latest.get(hash) {
+            Some(version) => version,
+            None => continue,
+        };
+        if version
+            .files
+            .iter()
+            .any(|f| f.hashes.sha1 == installed.sha1)
+        {
+            continue;
+        }
+        let file = match version.primary_file() {
+            Some(file) => file,
+            None => continue,
+        };
+        ret.push(Outdated {
+            installed,
+            current: current.get(hash).map(|v| v.version_number.clone()),
+            latest: version.version_number.clone(),
+            source: "Modrinth",
+            request: Some(file.request(&staging)?),
+            locked: Some(LockedMod {
+                project_id: version.project_id.clone(),
+                version_id: version.id.clone(),
+                file_name: file.filename.clone(),
+                sha1: file.hashes.sha1.clone(),
+                dependency: matches!(lock.by_sha1(&installed.sha1), Some(m) if m.dependency),
+            }),
+        });
+    }
+
+    let curseforge = match CurseForge::new(client.clone(), sub_matches) {
+        Some(curseforge) => curseforge,
+        None => return Ok(ret),
+    };
+    let unknown: Vec<&InstalledMod> = mods
+        .iter()
+        .zip(&hashes)
+        .filter(|(_, hash)| !current.contains_key(*hash))
+        .map(|(m, _)| m)
+        .collect();
+    if unknown.is_empty() {
+        return Ok(ret);
+    }
+    let fingerprints: Vec<u32> = unknown.iter().map(|m| m.fingerprint).collect();
+    let files = curseforge.match_fingerprints(&fingerprints).await?;
+    for installed in unknown {
+        let file = match files.get(&installed.fingerprint) {
+            Some(file) => file,
+            None => {
+                debug!(
+                    "{} is not known to any mod repository",
+                    installed.file_name()
+                );
+                continue;
+            }
+        };
+        let newest = match curseforge
+            .latest_file(file.mod_id, game_version, loader)
+            .await?
+        {
+            Some(newest) if newest.id != file.id => newest,
+            _ => continue,
+        };
+        ret.push(Outdated {
+            installed,
+            current: Some(file.display_name.clone()),
+            latest: newest.display_name.clone(),
+            source: "CurseForge",
+            request: newest.request(&staging)?,
+            locked: None,
+        });
+    }
+
+    Ok(ret)
+}
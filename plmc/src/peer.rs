@@ -0,0 +1,278 @@
+use crate::http::HttpClient;
+use crate::meta::index::{download_file, download_file_from};
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches};
+#[cfg(feature = "peer-cache")]
+use hyper::header::CONTENT_LENGTH;
+#[cfg(feature = "peer-cache")]
+use hyper::service::{make_service_fn, service_fn};
+#[cfg(feature = "peer-cache")]
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use polymc::cancel::CancellationToken;
+use polymc::meta::DownloadRequest;
+#[cfg(feature = "peer-cache")]
+use std::convert::Infallible;
+#[cfg(feature = "peer-cache")]
+use std::net::{Ipv4Addr, SocketAddr};
+#[cfg(feature = "peer-cache")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "peer-cache")]
+use std::sync::Arc;
+#[cfg(feature = "peer-cache")]
+use std::time::Duration;
+#[cfg(feature = "peer-cache")]
+use tokio::net::UdpSocket;
+use tracing::*;
+
+/// Libraries and assets fetched from other launchers on the local network before the origin.
+///
+/// Without the peer-cache feature there are never any peers, so everything comes from the
+/// origin.
+#[derive(Clone)]
+pub(crate) struct PeerCache {
+    /// Urls of the peers, each serving its stores with `plmc store serve`.
+    peers: Vec<String>,
+    /// Whether to look for peers on the local network as well.
+    discover: bool,
+    libraries_dir: PathBuf,
+    assets_dir: PathBuf,
+}
+
+/// Add the arguments to fetch from peers.
+pub(crate) fn args(app: App<'static>) -> App<'static> {
+    if cfg!(feature = "peer-cache") {
+        app.arg(
+            Arg::new("peer")
+                .long("peer")
+                .env("PLMC_PEERS")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("Url of a `plmc store serve` to fetch libraries and assets from first"),
+        )
+        .arg(
+            Arg::new("discover_peers")
+                .long("discover-peers")
+                .help("Also fetch from the `plmc store serve --lan` found on the local network"),
+        )
+    } else {
+        app
+    }
+}
+
+impl PeerCache {
+    /// Peers given on the command line, sharing the stores at `libraries_dir` and `assets_dir`.
+    pub(crate) fn new(sub_matches: &ArgMatches, libraries_dir: &str, assets_dir: &str) -> Self {
+        let (peers, discover) = if cfg!(feature = "peer-cache") {
+            let peers = sub_matches
+                .values_of("peer")
+                .into_iter()
+                .flatten()
+                .map(|peer| peer.trim_end_matches('/').to_string())
+                .collect();
+            (peers, sub_matches.is_present("discover_peers"))
+        } else {
+            (Vec::new(), false)
+        };
+        Self {
+            peers,
+            discover,
+            libraries_dir: libraries_dir.into(),
+            assets_dir: assets_dir.into(),
+        }
+    }
+
+    /// Add the peers answering a discovery broadcast, if asked to look for them.
+    pub(crate) async fn discover(&mut self) {
+        if self.discover {
+            #[cfg(feature = "peer-cache")]
+            match discover_peers(Duration::from_millis(500)).await {
+                Ok(found) => {
+                    for peer in found {
+                        if !self.peers.contains(&peer) {
+                            info!("found peer {}", peer);
+                            self.peers.push(peer);
+                        }
+                    }
+                }
+                Err(e) => warn!("failed to look for peers: {:#}", e),
+            }
+        }
+    }
+
+    /// Download the file of `request` from the first peer having it, from its own url otherwise.
+    ///
+    /// Only files with a known hash are fetched from peers, see [`polymc::peer_path`].
+    pub(crate) async fn download_file(
+        &self,
+        client: &HttpClient,
+        request: &DownloadRequest,
+        cancel: &CancellationToken,
+    ) -> Result<Option<u64>> {
+        let path = match polymc::peer_path(request, &self.libraries_dir, &self.assets_dir) {
+            Some(path) if !self.peers.is_empty() => path,
+            _ => return download_file(client, request, cancel).await,
+        };
+        for peer in &self.peers {
+            let url = format!("{}/{}", peer, path);
+            match download_file_from(client, request, &url, cancel).await {
+                Ok(downloaded) => return Ok(downloaded),
+                Err(_) if cancel.is_cancelled() => break,
+                Err(e) => debug!("{} not available from {}: {:#}", path, peer, e),
+            }
+        }
+
+        download_file(client, request, cancel).await
+    }
+}
+
+#[cfg(feature = "peer-cache")]
+pub(crate) fn serve_app() -> App<'static> {
+    App::new("serve")
+        .about("Share the libraries and assets with other launchers passed --peer")
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .takes_value(true)
+                .default_value("127.0.0.1:25580")
+                .help("Address to listen on, only this machine can connect by default"),
+        )
+        .arg(
+            Arg::new("lan")
+                .long("lan")
+                .help("Listen on all interfaces and answer peer discovery on the local network"),
+        )
+        .arg(
+            Arg::new("lib_dir")
+                .long("lib-dir")
+                .takes_value(true)
+                .env("PLMC_LIB_DIR"),
+        )
+        .arg(
+            Arg::new("assets_dir")
+                .long("assets-dir")
+                .env("PLMC_ASSETS_DIR")
+                .takes_value(true),
+        )
+}
+
+/// Serve the library and asset stores read-only until interrupted.
+#[cfg(feature = "peer-cache")]
+pub(crate) async fn run_serve(sub_matches: &ArgMatches) -> Result<i32> {
+    let mut addr: SocketAddr = sub_matches.value_of("bind").unwrap().parse()?;
+    let lan = sub_matches.is_present("lan");
+    if lan {
+        addr.set_ip(Ipv4Addr::UNSPECIFIED.into());
+    }
+    let dirs = Arc::new((
+        PathBuf::from(
+            sub_matches
+                .value_of("lib_dir")
+                .map(ToString::to_string)
+                .unwrap_or_else(|| crate::run::get_store_dir("lib")),
+        ),
+        PathBuf::from(
+            sub_matches
+                .value_of("assets_dir")
+                .map(ToString::to_string)
+                .unwrap_or_else(|| crate::run::get_store_dir("assets")),
+        ),
+    ));
+
+    let make_service = make_service_fn(move |_| {
+        let dirs = dirs.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let dirs = dirs.clone();
+                async move { Ok::<_, Infallible>(respond(&dirs.0, &dirs.1, &req).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    println!("Serving the stores on http://{}", server.local_addr());
+    if lan {
+        let port = server.local_addr().port();
+        tokio::spawn(async move {
+            if let Err(e) = answer_discovery(port).await {
+                warn!("not answering peer discovery: {:#}", e);
+            }
+        });
+    }
+    server
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    Ok(0)
+}
+
+/// Tell the launchers looking for peers that the stores are served on `port`.
+#[cfg(feature = "peer-cache")]
+async fn answer_discovery(port: u16) -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, polymc::DISCOVERY_PORT)).await?;
+    let reply = polymc::discovery_reply(port);
+    let mut buf = [0u8; 64];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if &buf[..len] == polymc::DISCOVERY_QUERY {
+            debug!("answering peer discovery of {}", from);
+            socket.send_to(&reply, from).await?;
+        }
+    }
+}
+
+/// Urls of the peers answering a discovery broadcast within `wait`.
+#[cfg(feature = "peer-cache")]
+async fn discover_peers(wait: Duration) -> Result<Vec<String>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(
+            polymc::DISCOVERY_QUERY,
+            (Ipv4Addr::BROADCAST, polymc::DISCOVERY_PORT),
+        )
+        .await?;
+
+    let mut ret = Vec::new();
+    let mut buf = [0u8; 64];
+    let deadline = tokio::time::Instant::now() + wait;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received?;
+        if let Some(port) = polymc::parse_discovery_reply(&buf[..len]) {
+            ret.push(format!("http://{}", SocketAddr::new(from.ip(), port)));
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(feature = "peer-cache")]
+async fn respond(libraries_dir: &Path, assets_dir: &Path, req: &Request<Body>) -> Response<Body> {
+    let path = match polymc::store_path(req.uri().path(), libraries_dir, assets_dir) {
+        Some(path) if req.method() == Method::GET => path,
+        _ => return not_found(),
+    };
+    match tokio::fs::read(&path).await {
+        Ok(data) => {
+            debug!("serving {}", path.display());
+            Response::builder()
+                .header(CONTENT_LENGTH, data.len())
+                .body(Body::from(data))
+                .unwrap()
+        }
+        Err(e) => {
+            trace!("not serving {}: {}", path.display(), e);
+            not_found()
+        }
+    }
+}
+
+#[cfg(feature = "peer-cache")]
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use hyper::client::HttpConnector;
+use hyper::header::{CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, HOST, PROXY_AUTHORIZATION};
+use hyper::http::uri::{Authority, Scheme, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response, Server, StatusCode};
+use polymc::resources_proxy::{ProxyResponse, ResourceProxy};
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::sync::Arc;
+use tracing::*;
+
+/// Serve the resources of an old version through a local HTTP proxy in the background.
+///
+/// Returns the port to pass to [`ResourceProxy::jvm_args`].
+pub(crate) fn spawn(proxy: ResourceProxy) -> Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    listener.set_nonblocking(true)?;
+    let server = Server::from_tcp(listener)?;
+
+    let proxy = Arc::new(proxy);
+    let client = Client::new();
+    let make_service = make_service_fn(move |_| {
+        let proxy = proxy.clone();
+        let client = client.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let proxy = proxy.clone();
+                let client = client.clone();
+                async move { Ok::<_, Infallible>(respond(&proxy, &client, req).await) }
+            }))
+        }
+    });
+
+    tokio::spawn(async move {
+        if let Err(e) = server.serve(make_service).await {
+            warn!("resources proxy failed: {}", e);
+        }
+    });
+    debug!("serving legacy resources on port {}", port);
+
+    Ok(port)
+}
+
+async fn respond(
+    proxy: &ResourceProxy,
+    client: &Client<HttpConnector>,
+    req: Request<Body>,
+) -> Response<Body> {
+    let host = request_host(&req);
+    trace!("resources proxy: {} {}", host, req.uri().path());
+
+    match proxy.respond(&host, req.uri().path()) {
+        ProxyResponse::File { path, .. } => match tokio::fs::read(&path).await {
+            Ok(data) => Response::builder()
+                .header(CONTENT_LENGTH, data.len())
+                .body(Body::from(data)),
+            Err(e) => {
+                warn!("failed to serve {}: {}", path.display(), e);
+                not_found()
+            }
+        },
+        ProxyResponse::Listing { content_type, body } => Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body)),
+        ProxyResponse::NotFound => not_found(),
+        ProxyResponse::Forward => return forward(client, req).await,
+    }
+    .unwrap()
+}
+
+/// Host the request is for, without the port.
+fn request_host(req: &Request<Body>) -> String {
+    // Proxied requests have an absolute uri, fall back to the host header
+    if let Some(host) = req.uri().host() {
+        return host.to_string();
+    }
+    req.headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+        .map(|authority| authority.host().to_string())
+        .unwrap_or_default()
+}
+
+/// Pass a request the proxy does not answer itself, like skins or logins, on to its host.
+async fn forward(client: &Client<HttpConnector>, mut req: Request<Body>) -> Response<Body> {
+    if req.uri().authority().is_none() {
+        let authority = req
+            .headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok());
+        let mut parts = req.uri().clone().into_parts();
+        parts.scheme = Some(Scheme::HTTP);
+        parts.authority = authority;
+        match Uri::from_parts(parts) {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(e) => {
+                debug!("resources proxy: not forwarding {}: {}", req.uri(), e);
+                return status(StatusCode::BAD_REQUEST);
+            }
+        }
+    }
+    let headers = req.headers_mut();
+    headers.remove(CONNECTION);
+    headers.remove(PROXY_AUTHORIZATION);
+    headers.remove("proxy-connection");
+
+    let uri = req.uri().clone();
+    match client.request(req).await {
+        Ok(res) => res,
+        Err(e) => {
+            warn!("resources proxy: failed to forward {}: {}", uri, e);
+            status(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
+
+fn not_found() -> hyper::http::Result<Response<Body>> {
+    Ok(status(StatusCode::NOT_FOUND))
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -1,37 +1,118 @@
-use anyhow::{anyhow, Context, Result};
+use crate::config::GlobalConfig;
+use crate::log_forward::LogForwarder;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{App, Arg, ArgMatches};
 use console::style;
 use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
-use log::*;
-use mktemp::Temp;
-use polymc::auth::Auth;
-use polymc::instance::Instance;
+use polymc::auth::{sanitize_username, validate_username, Auth};
+use polymc::i18n::Phase;
+use polymc::instance::{
+    Instance, InstanceInfo, LauncherBrand, LauncherLog, MainJar, NativesExtractor, RestartPolicy,
+    Sandbox, SandboxWrapper,
+};
 use polymc::java_wrapper::Java;
+use polymc::lock::DirLock;
+use polymc::logger::LogLevel;
+use polymc::meta::lwjgl::LwjglOverride;
+use polymc::meta::manifest::{Unsupported, CLASS_PATH_SEPARATOR, OS};
 use polymc::meta::FileType::AssetIndex;
-use polymc::meta::{DownloadRequest, MetaManager, Wants};
+use polymc::meta::{
+    DownloadRequest, DownloadStats, MetaManager, VersionPolicy, Wants, META_INDEX_STATS_KEY,
+};
+use polymc::resources_proxy::ResourceProxy;
+use polymc::watchdog::StartupWatchdog;
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::time::{Duration, Instant};
-use tokio::io::{stderr, stdout};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{stderr, stdout, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::task::JoinHandle;
+use tracing::*;
 
-fn get_dir(sub: &str) -> String {
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// How long to wait for other launchers to release the shared directories.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Bytes of the output of the game kept to analyze crashes.
+const OUTPUT_TAIL_LEN: usize = 64 * 1024;
+
+pub(crate) fn get_dir(sub: &str) -> String {
     let mut dir = dirs::data_dir().unwrap();
     dir.push("plmc");
     dir.push(sub);
     dir.display().to_string()
 }
 
+/// Default directory of the `lib`, `assets` or `meta` store, in the store directory of the
+/// [`GlobalConfig`] if one was set with `plmc store move`, or the directory of another launcher
+/// adopted with `plmc store adopt`.
+pub(crate) fn get_store_dir(sub: &str) -> String {
+    match GlobalConfig::load() {
+        Ok(config) => match (config.adopted(sub), &config.store) {
+            (Some(adopted), _) => adopted.display().to_string(),
+            (None, Some(store)) => store.join(sub).display().to_string(),
+            (None, None) => get_dir(sub),
+        },
+        Err(e) => {
+            warn!("Ignoring config: {:#}", e);
+            get_dir(sub)
+        }
+    }
+}
+
 pub(crate) fn app() -> App<'static> {
-    App::new("run")
-        .about("Run the game")
+    args(App::new("run").about("Run the game"))
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help("Print the command line to start the game instead of starting it"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("Print how many files were cached or downloaded and the time spent on it"),
+        )
+        .arg(
+            Arg::new("restart_on_crash")
+                .long("restart-on-crash")
+                .help("Restart the game if it exits with an error"),
+        )
+        .arg(
+            Arg::new("startup_timeout")
+                .long("startup-timeout")
+                .env("PLMC_STARTUP_TIMEOUT")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .validator(|v| v.parse::<u64>())
+                .help("Report the launch as stalled if the game window does not open in time"),
+        )
+        .arg(
+            Arg::new("kill_stalled")
+                .long("kill-stalled")
+                .requires("startup_timeout")
+                .help("Kill the game if its launch stalled"),
+        )
+        .arg(
+            Arg::new("no_resources_proxy")
+                .long("no-resources-proxy")
+                .help("Don't serve the sounds of versions before 1.6 through a local proxy"),
+        )
+}
+
+/// Add the arguments needed to set up an instance.
+pub(crate) fn args(app: App<'static>) -> App<'static> {
+    let app = app
         .arg(
             Arg::new("java")
                 .long("java")
                 .short('j')
                 .env("PLMC_JAVA")
                 .takes_value(true)
-                .help("Path to the java executable")
-                .required(true),
+                .help(
+                    "Path to the java executable, picked from the installed runtimes if not given",
+                ),
         )
         .arg(
             Arg::new("mc_version")
@@ -53,9 +134,27 @@ pub(crate) fn app() -> App<'static> {
             Arg::new("meta_url")
                 .long("base-url")
                 .env("PLMC_BASE_URL")
-                .help("Base url of the meta server to use")
+                .help("Base url of the meta server or a directory with the meta data, libraries and assets, the fastest known server if not given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("meta_index_sha256")
+                .long("meta-index-sha256")
+                .env("PLMC_META_INDEX_SHA256")
                 .takes_value(true)
-                .required(true),
+                .help("Pinned sha256 the meta index has to match"),
+        )
+        .arg(
+            Arg::new("meta_snapshot")
+                .long("meta-snapshot")
+                .env("PLMC_META_SNAPSHOT")
+                .takes_value(true)
+                .help("Resolve only from a snapshot saved with `meta snapshot`"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail on meta files with fields unknown to the launcher"),
         )
         .arg(
             Arg::new("lib_dir")
@@ -83,12 +182,46 @@ pub(crate) fn app() -> App<'static> {
                 .env("PLMC_ASSETS_DIR")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("assets_url")
+                .long("assets-url")
+                .env("PLMC_ASSETS_URL")
+                .takes_value(true)
+                .help("Base url to download assets from, e.g. a mirror"),
+        )
+        .arg(
+            Arg::new("lwjgl_version")
+                .long("lwjgl-version")
+                .env("PLMC_LWJGL_VERSION")
+                .takes_value(true)
+                .help("LWJGL version to use instead of the one required by the game"),
+        )
+        .arg(
+            Arg::new("lwjgl_natives_arch")
+                .long("lwjgl-natives-arch")
+                .env("PLMC_LWJGL_NATIVES_ARCH")
+                .takes_value(true)
+                .help("Architecture to substitute the LWJGL natives for, e.g. arm64, detected by default"),
+        )
+        .arg(
+            Arg::new("lwjgl_natives_url")
+                .long("lwjgl-natives-url")
+                .env("PLMC_LWJGL_NATIVES_URL")
+                .takes_value(true)
+                .help("Maven repository to download substituted LWJGL natives from"),
+        )
         .arg(
             Arg::new("natives_dir")
                 .long("natives-dir")
                 .env("PLMC_NATIVE_DIR")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("extract_natives")
+                .long("extract-natives")
+                .env("PLMC_EXTRACT_NATIVES")
+                .help("Extract the natives right after their download instead of at the start"),
+        )
         .arg(
             Arg::new("username")
                 .long("username")
@@ -98,6 +231,140 @@ pub(crate) fn app() -> App<'static> {
                 .help("The username to use for authentication")
                 .default_value("Player"),
         )
+        .arg(
+            Arg::new("sanitize_username")
+                .long("sanitize-username")
+                .help("Replace characters the game does not allow in the username"),
+        )
+        .arg(
+            Arg::new("title")
+                .long("title")
+                .env("PLMC_TITLE")
+                .takes_value(true)
+                .help("Name of the game in the dock (macOS only)"),
+        )
+        .arg(
+            Arg::new("icon")
+                .long("icon")
+                .env("PLMC_ICON")
+                .takes_value(true)
+                .help("Image to use as window icon (macOS only)"),
+        )
+        .arg(
+            Arg::new("auto_memory")
+                .long("auto-memory")
+                .env("PLMC_AUTO_MEMORY")
+                .help("Size the Java heap according to the memory of the system"),
+        )
+        .arg(
+            Arg::new("no_assets")
+                .long("no-assets")
+                .env("PLMC_NO_ASSETS")
+                .help("Skip assets and start the game with minimal arguments, e.g. for automated tests"),
+        )
+        .arg(
+            Arg::new("launch_early")
+                .long("launch-early")
+                .env("PLMC_LAUNCH_EARLY")
+                .help("Start the game once only assets are missing and download them while it runs"),
+        )
+        .arg(
+            Arg::new("log_config")
+                .long("log-config")
+                .env("PLMC_LOG_CONFIG")
+                .help("Pass the logging configuration, the game logs XML events instead of plain text"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .env("PLMC_HEADLESS")
+                .help("Start Java in headless mode"),
+        )
+        .arg(
+            Arg::new("launcher_brand")
+                .long("launcher-brand")
+                .env("PLMC_LAUNCHER_BRAND")
+                .takes_value(true)
+                .help("Launcher name the game reports in crash reports and statistics"),
+        )
+        .arg(
+            Arg::new("launcher_brand_version")
+                .long("launcher-brand-version")
+                .env("PLMC_LAUNCHER_BRAND_VERSION")
+                .takes_value(true)
+                .help("Launcher version the game reports in crash reports and statistics"),
+        )
+        .arg(
+            Arg::new("no_launcher_brand")
+                .long("no-launcher-brand")
+                .env("PLMC_NO_LAUNCHER_BRAND")
+                .conflicts_with_all(&["launcher_brand", "launcher_brand_version"])
+                .help("Don't tell the game which launcher started it"),
+        )
+        .arg(
+            Arg::new("skip_verify")
+                .long("skip-verify")
+                .env("PLMC_SKIP_VERIFY")
+                .help("Only check that files exist instead of verifying their hashes"),
+        )
+        .arg(
+            Arg::new("version_conflicts")
+                .long("version-conflicts")
+                .env("PLMC_VERSION_CONFLICTS")
+                .takes_value(true)
+                .possible_values(["fail", "first"])
+                .default_value("fail")
+                .help("Fail if a component is wanted in different versions, or use the first one"),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .conflicts_with("skip_verify")
+                .help("Verify all assets, even if the instance's asset verification policy says not to"),
+        )
+        .arg(
+            Arg::new("sandbox")
+                .long("sandbox")
+                .env("PLMC_SANDBOX")
+                .takes_value(true)
+                .possible_values(["bwrap", "firejail"])
+                .help("Run the game through a sandbox which may only write to the game directory"),
+        )
+        .arg(
+            Arg::new("clean_env")
+                .long("clean-env")
+                .help("Only pass the environment variables needed to show the game"),
+        )
+        .arg(
+            Arg::new("main_jar")
+                .long("main-jar")
+                .env("PLMC_MAIN_JAR")
+                .takes_value(true)
+                .conflicts_with("main_jar_library")
+                .help("Path to a custom client jar to use instead of the one of the version"),
+        )
+        .arg(
+            Arg::new("main_jar_library")
+                .long("main-jar-library")
+                .takes_value(true)
+                .help(
+                    "Maven coordinate of a library in the library directory to use as client jar",
+                ),
+        )
+        .arg(
+            Arg::new("main_jar_sha1")
+                .long("main-jar-sha1")
+                .takes_value(true)
+                .help("Sha1 the custom client jar has to match"),
+        )
+        .arg(
+            Arg::new("java_agent")
+                .long("java-agent")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("PATH[=OPTIONS]")
+                .help("Attach a Java agent, in addition to the ones set for the instance"),
+        )
         .arg(
             Arg::new("java_extra_args")
                 .long("java-args")
@@ -112,52 +379,287 @@ pub(crate) fn app() -> App<'static> {
                 .takes_value(false)
                 .default_value("false"),
         )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("Download without asking for confirmation"),
+        )
         .arg(
             Arg::new("extra_args")
                 .long("extra-args")
                 .takes_value(true)
                 .help("Extra flags to pass to Minecraft")
                 .multiple_values(true),
-        )
+        );
+
+    crate::peer::args(crate::http::args(app))
+}
+
+/// Lock a shared directory, waiting for other launchers to finish with it.
+pub(crate) async fn lock_dir(dir: &str) -> Result<DirLock> {
+    match DirLock::try_lock(dir) {
+        Err(polymc::Error::Locked(_)) => {
+            println!("Waiting for another launcher using {}...", dir);
+            Ok(DirLock::lock_timeout_async(dir, LOCK_TIMEOUT).await?)
+        }
+        ret => Ok(ret?),
+    }
+}
+
+/// Directory of the instance to run.
+fn game_dir(sub_matches: &ArgMatches) -> String {
+    sub_matches
+        .value_of("mc_dir")
+        .map(ToString::to_string)
+        .unwrap_or_else(|| get_dir("game"))
+}
+
+/// Ask the user a yes/no question on stdin, defaulting to yes.
+pub(crate) fn confirm(question: &str) -> Result<bool> {
+    print!("{} ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(
+        answer.trim().to_lowercase().as_str(),
+        "" | "y" | "yes"
+    ))
 }
 
 pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
-    let meta_url = sub_matches.value_of("meta_url").unwrap();
+    // Correlate everything logged for this instance
+    let span = info_span!(
+        "instance",
+        uid = sub_matches.value_of("uid").unwrap(),
+        version = sub_matches.value_of("mc_version").unwrap_or_default()
+    );
+    // Preparing rewrites the instance info and extracts the natives, so the instance must not be
+    // running while it happens
+    let _instance_lock =
+        DirLock::try_lock(&game_dir(sub_matches)).context("The instance is already running")?;
+    let (mut instance, java, auth, stats) =
+        match prepare(sub_matches, false).instrument(span.clone()).await? {
+            Some(prepared) => prepared,
+            None => return Ok(1),
+        };
+    let _span = span.enter();
+
+    if sub_matches.is_present("restart_on_crash") {
+        instance.set_restart_policy(Some(RestartPolicy::default()));
+    }
+
+    if sub_matches.is_present("dry_run") {
+        print_command(&java.build_command(&instance, &auth)?, &auth);
+        if sub_matches.is_present("stats") {
+            print_stats(&stats);
+        }
+        return Ok(0);
+    }
+
+    let proxied = instance
+        .asset_index()
+        .filter(|index| index.map_to_resources)
+        .filter(|_| !instance.config.minimal_args && !sub_matches.is_present("no_resources_proxy"));
+    if let Some(index) = proxied {
+        let proxy = ResourceProxy::new(index, &instance.get_assets_path());
+        let port = crate::resources_proxy::spawn(proxy)?;
+        instance.java_opts.extend(ResourceProxy::jvm_args(port));
+    }
+
+    let mut child = java.start(&instance, auth.clone())?;
+    let watchdog = sub_matches.value_of("startup_timeout").map(|timeout| {
+        let mut watchdog = StartupWatchdog::new(Duration::from_secs(timeout.parse().unwrap()));
+        watchdog.kill = sub_matches.is_present("kill_stalled");
+        Arc::new(Mutex::new(watchdog))
+    });
+    let info = InstanceInfo::load(&instance.minecraft_path).ok();
+    let policy = info
+        .as_ref()
+        .map(|info| info.launcher_log.clone())
+        .unwrap_or_default();
+    let log = match LauncherLog::create(&instance.minecraft_path, &policy, SystemTime::now()) {
+        Ok(log) => log.map(|log| Arc::new(Mutex::new(log))),
+        Err(e) => {
+            warn!("Failed to create the launcher log: {}", e);
+            None
+        }
+    };
+    let forwarder = info.as_ref().and_then(|info| {
+        let forwarding = info.log_forwarding.as_ref()?;
+        match LogForwarder::spawn(sub_matches, &info.name, forwarding) {
+            Ok(forwarder) => Some(Arc::new(forwarder)),
+            Err(e) => {
+                warn!("Failed to forward the log: {:#}", e);
+                None
+            }
+        }
+    });
+    if let Some(forwarder) = &forwarder {
+        forwarder.launcher(
+            LogLevel::Info,
+            &format!("Starting Minecraft {}", instance.get_version_id()),
+        );
+    }
+    let tail = OutputTail {
+        watchdog: watchdog.clone(),
+        log,
+        forwarder: forwarder.clone(),
+        ..Default::default()
+    };
+    forward_output(&mut child.process, &tail);
+
+    if let Some(watchdog) = &watchdog {
+        match child.wait_for_startup(watchdog).await {
+            Err(e @ polymc::Error::LaunchStalled { .. })
+                if !sub_matches.is_present("kill_stalled") =>
+            {
+                eprintln!(
+                    "{}",
+                    style(format!("{}, waiting for it to exit", e)).yellow()
+                );
+            }
+            result => result?,
+        }
+    }
+
+    let exit = if sub_matches.is_present("restart_on_crash") {
+        child
+            .supervise(&java, &auth, |process| forward_output(process, &tail))
+            .await?
+    } else {
+        child.wait_async().await?
+    };
+
+    let report = if !exit.success() {
+        let report = child.exit_report(exit, &tail.finish().await).to_string();
+        eprint!("{}", style(&report).yellow());
+        Some(report)
+    } else {
+        None
+    };
+    if let Some(forwarder) = &forwarder {
+        // The output after the exit comes before the exit in the log
+        tail.finish().await;
+        match &report {
+            Some(report) => forwarder.launcher(LogLevel::Error, report.trim_end()),
+            None => forwarder.launcher(LogLevel::Info, "Game exited with code 0"),
+        }
+        forwarder.finish().await;
+    }
+    if sub_matches.is_present("stats") {
+        print_stats(&stats);
+    }
+
+    Ok(exit.code().unwrap_or(1))
+}
+
+/// Download everything needed and set up the instance described by the arguments of [`args`].
+///
+/// Returns `None` if the user declined the download. `offline` only uses the meta data in the
+/// cache and downloads nothing, leaving the instance and the stores as they are.
+pub(crate) async fn prepare(
+    sub_matches: &ArgMatches,
+    offline: bool,
+) -> Result<Option<(Instance, Java, Auth, DownloadStats)>> {
+    let meta_url = if offline {
+        crate::meta::servers::configured_url(sub_matches, "meta_url")?
+    } else {
+        crate::meta::servers::base_url(sub_matches, "meta_url").await?
+    };
     let meta_dir = sub_matches
         .value_of("meta_dir")
         .map(ToString::to_string)
-        .unwrap_or_else(|| get_dir("meta"));
+        .unwrap_or_else(|| get_store_dir("meta"));
 
     let lib_dir = sub_matches
         .value_of("lib_dir")
         .map(ToString::to_string)
-        .unwrap_or_else(|| get_dir("lib"));
+        .unwrap_or_else(|| get_store_dir("lib"));
 
-    let mc_dir = sub_matches
-        .value_of("mc_dir")
-        .map(ToString::to_string)
-        .unwrap_or_else(|| get_dir("game"));
+    let mc_dir = game_dir(sub_matches);
     let username = sub_matches.value_of("username").unwrap();
+    let username = if sub_matches.is_present("sanitize_username") {
+        let sanitized = sanitize_username(username);
+        if sanitized != username {
+            warn!("Using the username {} instead of {:?}", sanitized, username);
+        }
+        sanitized
+    } else {
+        validate_username(username)?;
+        username.to_string()
+    };
 
     let assets_dir = sub_matches
         .value_of("assets_dir")
         .map(ToString::to_string)
-        .unwrap_or_else(|| get_dir("assets"));
+        .unwrap_or_else(|| get_store_dir("assets"));
 
-    let version = sub_matches.value_of("mc_version").unwrap();
+    let info = InstanceInfo::load_existing(&mc_dir)?;
+    let version = match (sub_matches.value_of("mc_version"), &info) {
+        (Some(version), _) => version.to_string(),
+        // `plmc run-raw` starts the instance in its own version
+        (None, Some(info)) => info.version.clone(),
+        (None, None) => bail!("No instance in {}, pass the version with --version", mc_dir),
+    };
     let uid = sub_matches.value_of("uid").unwrap();
-    let wants = Wants::new(uid, version);
+    let wants = Wants::new(uid, &version);
 
     let mut manager = MetaManager::new(&lib_dir, &assets_dir, &meta_url);
-    manager.search(wants);
+    if let Some(hash) = sub_matches.value_of("meta_index_sha256") {
+        manager.set_index_sha256(Some(hash.parse().context("Invalid meta index hash")?));
+    }
+    manager
+        .set_snapshot(sub_matches.value_of("meta_snapshot"))
+        .context("Loading the meta snapshot")?;
+    manager.set_strict(sub_matches.is_present("strict"));
+    if let Some(url) = sub_matches.value_of("assets_url") {
+        manager.set_assets_url(url);
+    }
+    let mut lwjgl = LwjglOverride::for_host();
+    lwjgl.version = sub_matches
+        .value_of("lwjgl_version")
+        .map(ToString::to_string);
+    if let Some(arch) = sub_matches.value_of("lwjgl_natives_arch") {
+        lwjgl.natives_arch = Some(arch.to_string());
+    }
+    if let Some(url) = sub_matches.value_of("lwjgl_natives_url") {
+        lwjgl.natives_url = url.to_string();
+    }
+    manager.set_lwjgl_override(Some(lwjgl));
+    manager.set_skip_assets(sub_matches.is_present("no_assets"));
+    manager.set_skip_verify(sub_matches.is_present("skip_verify"));
+    let cancel = manager.cancellation_token();
+    crate::meta::index::cancel_on_ctrl_c(cancel.clone());
+    manager.set_version_policy(match sub_matches.value_of("version_conflicts") {
+        Some("first") => VersionPolicy::FirstRequested,
+        _ => VersionPolicy::Fail,
+    });
+    manager.search(wants)?;
+    // Components pinned when the instance was created from a template
+    if let Some(info) = &info {
+        for component in info.components.iter().filter(|c| c.uid != uid) {
+            manager.search(Wants::new(&component.uid, &component.version))?;
+        }
+    }
 
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
+    let now = SystemTime::now();
+    let verify_assets = sub_matches.is_present("verify")
+        || info
+            .as_ref()
+            .map(|info| info.should_verify_assets(now))
+            .unwrap_or(true);
+    manager.set_skip_asset_verify(!verify_assets);
+    manager.set_previous_asset_index(info.as_ref().and_then(|info| info.asset_index.clone()));
 
-    let mut client = hyper::Client::builder().build(https);
+    let mut client = crate::http::client(sub_matches, &meta_url)?;
+    client.set_local_stores(&lib_dir, &assets_dir);
+    let mut peers = crate::peer::PeerCache::new(sub_matches, &lib_dir, &assets_dir);
+    if !offline {
+        peers.discover().await;
+    }
 
     // Let's use indicatif to show the progress!
     let mut rng = rand::thread_rng();
@@ -166,53 +668,212 @@ pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
         .tick_chars("/-\\|")
         .progress_chars("=> ")
         .template("{prefix:.bold.dim} {spinner} [{bar}] {msg}");
-    println!("Downloading Assets...");
+    if !offline {
+        println!("Downloading Assets...");
+    }
+
+    let mut store_locks = if offline {
+        Vec::new()
+    } else {
+        vec![
+            lock_dir(&meta_dir).await?,
+            lock_dir(&lib_dir).await?,
+            lock_dir(&assets_dir).await?,
+        ]
+    };
 
+    let mut confirmed = sub_matches.is_present("yes");
+    // Assets are still downloading, so they are not verified yet
+    let mut streaming = false;
+
+    let extract_natives = sub_matches.is_present("extract_natives");
+    let mut natives = None;
     let search = loop {
         let search = manager.continue_search()?;
         if search.is_ready() {
             break search;
         }
+
+        // Resolve all meta data first, so the files to download are known before asking the user
+        let has_meta = search.requests.iter().any(|r| !r.is_file());
+        if offline {
+            // Missing files are left pending, the instance is only inspected
+            if !has_meta {
+                break search;
+            }
+            for r in search.requests.iter().filter(|r| !r.is_file()) {
+                match crate::meta::index::cached_meta(r, &meta_dir) {
+                    Ok((mut file, f_type)) => manager.load_reader(&mut file, f_type)?,
+                    Err(e) if manager.skip_optional(r, &format!("{:#}", e)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            continue;
+        }
+        if !has_meta && !confirmed {
+            let size = search.total_download_size();
+            if !confirm(&format!(
+                "About {:.0} MiB to download, continue? [Y/n]",
+                size as f64 / MIB
+            ))? {
+                return Ok(None);
+            }
+            confirmed = true;
+        }
+
+        // The assets keep downloading while the game runs, it loads them once they are needed
+        if search.is_launchable() && sub_matches.is_present("launch_early") {
+            info!(
+                "Downloading {} assets in the background",
+                search.requests.len()
+            );
+            let (client, peers, cancel) = (client.clone(), peers.clone(), cancel.clone());
+            let requests = search.requests.clone();
+            let assets_lock = store_locks.pop();
+            tokio::spawn(async move {
+                let _assets_lock = assets_lock;
+                for r in &requests {
+                    if let Err(e) = peers.download_file(&client, r, &cancel).await {
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                        warn!("Failed to download {}: {:#}", r.get_url(), e);
+                    }
+                }
+            });
+            streaming = true;
+            break search;
+        }
+
+        let requests: Vec<&DownloadRequest> = search
+            .requests
+            .iter()
+            .filter(|r| !has_meta || !r.is_file())
+            .collect();
         // get the total amount of files to download
         // total is search.requests's length, but we have to return the variable because rust
-        let mut total = search.requests.len();
+        let total = requests.len();
         let pb = ProgressBar::new(total as u64);
         pb.set_style(spinner_style.clone());
         pb.set_message("Loading...");
-        // draw the progress bar
-        for r in &search.requests {
-            info!("requested: {:?}", r);
-            if r.is_file() {
-                // print download progress
-                // set the progress bar to the current file
-                pb.set_message(format!(
-                    "[{}/{}] Downloading {}",
-                    pb.position(),
-                    total,
-                    r.get_url()
-                ));
-                //println!("Downloading {}", r.get_url());
-                crate::meta::index::download_file(&mut client, r).await?;
-                pb.inc(1);
-            } else {
-                // print download progress
-                pb.set_message(format!("Loading Metadata from {}", r.get_url()));
-                let (file, f_type) =
-                    crate::meta::index::download_meta(&mut client, r, &meta_dir).await?;
-                if let Some(mut file) = file {
-                    if let DownloadRequest::AssetIndex { version, uid, .. } = &r {
-                        manager.load_asset_index_reader(uid, &version, &mut file)?;
-                    } else {
-                        manager.load_reader(&mut file, f_type)?;
+        let download = debug_span!("download", files = requests.len());
+        async {
+            // draw the progress bar
+            for r in requests {
+                info!("requested: {:?}", r);
+                if r.is_file() {
+                    // print download progress
+                    // set the progress bar to the current file
+                    pb.set_message(format!(
+                        "[{}/{}] Downloading {}",
+                        pb.position(),
+                        total,
+                        r.get_url()
+                    ));
+                    //println!("Downloading {}", r.get_url());
+                    let start = Instant::now();
+                    let stats = manager.stats_mut();
+                    match peers.download_file(&client, r, &cancel).await? {
+                        Some(bytes) => stats.record_download(r, bytes),
+                        None => stats.record_cached(r),
                     }
+                    stats.add_time(Phase::Download, start.elapsed());
+                    if extract_natives && r.natives().is_some() {
+                        if natives.is_none() {
+                            let dir = match sub_matches.value_of("natives_dir") {
+                                Some(dir) => dir.to_string(),
+                                None => Instance::default_natives_path(&mc_dir, &version),
+                            };
+                            natives = Some(NativesExtractor::new_async(dir).await?);
+                        }
+                        if let Some(natives) = &mut natives {
+                            natives.extract(r)?;
+                        }
+                    }
+                    pb.inc(1);
+                } else {
+                    // print download progress
+                    pb.set_message(format!("Loading Metadata from {}", r.get_url()));
+                    let start = Instant::now();
+                    let stats = manager.stats_mut();
+                    let result = crate::meta::index::download_meta_counted(
+                        &client, r, &meta_dir, &cancel, stats,
+                    )
+                    .await;
+                    stats.add_time(Phase::Resolve, start.elapsed());
+                    match result {
+                        Ok((Some(mut file), f_type)) => manager.load_reader(&mut file, f_type)?,
+                        Ok((None, _)) => {}
+                        Err(e)
+                            if !cancel.is_cancelled()
+                                && manager.skip_optional(r, &format!("{:#}", e)) => {}
+                        Err(e) => return Err(e),
+                    }
+                    pb.inc(1);
                 }
-                pb.inc(1);
             }
+            Ok::<_, anyhow::Error>(())
         }
+        .instrument(download)
+        .await?;
         pb.finish();
     };
-    let mut instance = Instance::new(uid, &version, &mc_dir, search);
+    drop(store_locks);
+    for (uid, diff) in &manager.stats().asset_diffs {
+        println!(
+            "Assets of {} changed: {} of {} objects reused ({:.1} MiB), {} new ({:.1} MiB)",
+            uid,
+            diff.kept,
+            diff.kept + diff.added,
+            diff.kept_bytes as f64 / MIB,
+            diff.added,
+            diff.added_bytes as f64 / MIB
+        );
+    }
+    if verify_assets
+        && !offline
+        && !streaming
+        && !sub_matches.is_present("skip_verify")
+        && !sub_matches.is_present("no_assets")
+    {
+        let mut info = info.clone().unwrap_or_else(|| InstanceInfo {
+            name: uid.to_string(),
+            version: version.to_string(),
+            ..Default::default()
+        });
+        info.record_assets_verified(now);
+        std::fs::create_dir_all(&mc_dir)?;
+        if let Err(e) = info.save(&mc_dir) {
+            warn!("Failed to save the asset verification: {}", e);
+        }
+    }
+    for missing in manager.missing() {
+        crate::mods::warn_user(&format!(
+            "Starting without the optional component {} {}: {}",
+            missing.uid, missing.version, missing.reason
+        ));
+    }
+
+    // Keep the name of instances created by `plmc instance create`
+    let name = match &info {
+        Some(info) if !info.name.is_empty() => info.name.as_str(),
+        _ => uid,
+    };
+    let stats = manager.stats().clone();
+    let verifies_assets = manager.verifies_assets();
+    let mut instance = Instance::new(name, &version, &mc_dir, search);
     instance.set_libraries_path(&lib_dir);
+    let os = OS::get();
+    for manifest in instance.sorted_manifests() {
+        for (lib, reason) in manifest.unsupported_libraries(&os) {
+            if reason != Unsupported::ExcludedByRules {
+                crate::mods::warn_user(&format!(
+                    "{} of {} is not supported on {}: {}",
+                    lib.name, manifest.uid, os.name, reason
+                ));
+            }
+        }
+    }
     let mut extras = Vec::new();
 
     if let Some(extra_args) = sub_matches.values_of("extra_args") {
@@ -232,39 +893,240 @@ pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
     if let Some(dir) = sub_matches.value_of("natives_dir") {
         instance.set_natives_path(dir);
     }
+    if let Some(natives) = natives {
+        natives.finish(&instance)?;
+    }
 
-    instance.set_assets_path(&assets_dir);
+    let wrapper = match sub_matches.value_of("sandbox") {
+        Some("bwrap") => Some(SandboxWrapper::Bubblewrap),
+        Some("firejail") => Some(SandboxWrapper::Firejail),
+        _ => None,
+    };
+    if wrapper.is_some() || sub_matches.is_present("clean_env") {
+        instance.set_sandbox(Some(Sandbox {
+            clean_env: sub_matches.is_present("clean_env"),
+            wrapper,
+            ..Default::default()
+        }));
+    }
 
-    let java = sub_matches.value_of("java").unwrap();
-    let java = Java::new(java);
-
-    let mut child = java.start(&instance, Auth::new_offline(username))?;
-
-    let mut c_stdout = child
-        .process
-        .stdout
-        .take()
-        .context("Failed to get stdout")?;
-    let mut c_stderr = child
-        .process
-        .stderr
-        .take()
-        .context("Failed to get stderr")?;
-
-    tokio::spawn(async move {
-        let mut c_stdout = tokio::process::ChildStdout::from_std(c_stdout).unwrap();
-        loop {
-            tokio::io::copy(&mut c_stdout, &mut stdout()).await.unwrap();
+    instance.config.title = sub_matches.value_of("title").map(ToString::to_string);
+    instance.config.icon = sub_matches.value_of("icon").map(ToString::to_string);
+    instance.config.auto_memory = sub_matches.is_present("auto_memory");
+    instance.config.minimal_args = sub_matches.is_present("no_assets");
+    instance.config.headless = sub_matches.is_present("headless");
+    instance.config.log_config = sub_matches.is_present("log_config");
+    instance.config.skip_verify = sub_matches.is_present("skip_verify");
+    instance.config.launcher_brand = if sub_matches.is_present("no_launcher_brand") {
+        None
+    } else {
+        let mut brand = LauncherBrand::default();
+        if let Some(name) = sub_matches.value_of("launcher_brand") {
+            brand.name = name.to_string();
         }
-    });
-    tokio::spawn(async move {
-        let mut c_stderr = tokio::process::ChildStderr::from_std(c_stderr).unwrap();
-        loop {
-            tokio::io::copy(&mut c_stderr, &mut stderr()).await.unwrap();
+        if let Some(version) = sub_matches.value_of("launcher_brand_version") {
+            brand.version = version.to_string();
         }
-    });
+        Some(brand)
+    };
+
+    let sha1 = sub_matches
+        .value_of("main_jar_sha1")
+        .map(str::parse)
+        .transpose()
+        .context("Invalid main jar hash")?;
+    if let Some(path) = sub_matches.value_of("main_jar") {
+        instance.set_main_jar(Some(MainJar::Path {
+            path: path.into(),
+            sha1,
+        }));
+    } else if let Some(name) = sub_matches.value_of("main_jar_library") {
+        instance.set_main_jar(Some(MainJar::Library {
+            name: name.parse().context("Invalid main jar library")?,
+            sha1,
+        }));
+    }
+    // A dry run leaves the instance as it is
+    if !offline && !sub_matches.is_present("dry_run") {
+        instance.pin_main_jar().context("Pinning the client jar")?;
+        instance
+            .record_asset_index(verifies_assets)
+            .context("Recording the asset index")?;
+    }
+
+    let mut agents = info
+        .as_ref()
+        .map(|info| info.java_agents.clone())
+        .unwrap_or_default();
+    for agent in sub_matches.values_of("java_agent").into_iter().flatten() {
+        agents.push(agent.parse().context("Invalid Java agent")?);
+    }
+    instance.set_java_agents(agents);
+    if let Some(info) = &info {
+        instance.set_overrides(info.overrides.clone());
+        instance.config.gpu = info.gpu;
+    }
+
+    instance.set_assets_path(&assets_dir);
+
+    let java = match (sub_matches.value_of("java"), instance.required_java_major()) {
+        (Some(java), major) => {
+            let java = Java::new(java);
+            if let (Some(major), Ok(actual)) = (major, java.major_version()) {
+                if actual < major {
+                    warn!("{} needs Java {}, got Java {}", uid, major, actual);
+                }
+            }
+            java
+        }
+        (None, Some(major)) => polymc::runtime::find(&[get_dir("runtimes")], major)?,
+        (None, None) => Java::new("java"),
+    };
+
+    let auth = Auth::new_offline(&username);
+
+    Ok(Some((instance, java, auth, stats)))
+}
+
+/// Print the command line used to start the game.
+fn print_command(command: &std::process::Command, auth: &Auth) {
+    println!("java: {}", command.get_program().to_string_lossy());
+
+    let mut args = Java::redacted_args(command, auth).into_iter();
+    println!("jvm args:");
+    for arg in args.by_ref().take_while(|arg| arg != "-cp") {
+        println!("  {}", arg);
+    }
+
+    println!("classpath:");
+    for entry in args.next().unwrap_or_default().split(CLASS_PATH_SEPARATOR) {
+        println!("  {}", entry);
+    }
+
+    println!("main class: {}", args.next().unwrap_or_default());
+
+    println!("game args:");
+    for arg in args {
+        println!("  {}", arg);
+    }
+
+    let mut env = command.get_envs().peekable();
+    if env.peek().is_some() {
+        println!("env:");
+    }
+    for (name, value) in env {
+        let value = value.unwrap_or_default().to_string_lossy();
+        println!("  {}={}", name.to_string_lossy(), value);
+    }
+}
+
+/// Print how much of the game was cached or downloaded and how long it took.
+fn print_stats(stats: &DownloadStats) {
+    println!("download statistics:");
+    for (uid, component) in &stats.components {
+        let uid = match uid.as_str() {
+            "" => "unknown",
+            META_INDEX_STATS_KEY => "meta index",
+            uid => uid,
+        };
+        println!(
+            "  {}: {} cached, {} downloaded ({:.1} MiB)",
+            uid,
+            component.cached,
+            component.downloaded,
+            component.bytes as f64 / MIB
+        );
+    }
+    let total = stats.total();
+    println!(
+        "  total: {} cached, {} downloaded ({:.1} MiB)",
+        total.cached,
+        total.downloaded,
+        total.bytes as f64 / MIB
+    );
+    for phase in [Phase::Resolve, Phase::Verify, Phase::Download] {
+        println!("  {}: {:.2}s", phase, stats.time(phase).as_secs_f64());
+    }
+}
+
+/// Forward stdout and stderr of the game to our own, keeping the end in `tail`.
+fn forward_output(process: &mut std::process::Child, tail: &OutputTail) {
+    if let Some(c_stdout) = process.stdout.take() {
+        let c_stdout = tokio::process::ChildStdout::from_std(c_stdout).unwrap();
+        tail.spawn_tee(c_stdout, stdout(), false);
+    }
+    if let Some(c_stderr) = process.stderr.take() {
+        let c_stderr = tokio::process::ChildStderr::from_std(c_stderr).unwrap();
+        tail.spawn_tee(c_stderr, stderr(), true);
+    }
+}
+
+/// End of the output of the game, checked for known causes when it crashes.
+#[derive(Clone, Default)]
+struct OutputTail {
+    data: Arc<Mutex<Vec<u8>>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    /// Watchdog shown the output while the game starts.
+    watchdog: Option<Arc<Mutex<StartupWatchdog>>>,
+    /// Log in the instance directory the output is captured in.
+    log: Option<Arc<Mutex<LauncherLog>>>,
+    /// Log collector the output is forwarded to.
+    forwarder: Option<Arc<LogForwarder>>,
+}
 
-    let exit = child.process.wait()?;
+impl OutputTail {
+    /// Copy `from` to `to` in the background, keeping the data.
+    fn spawn_tee<R, W>(&self, mut from: R, mut to: W, is_stderr: bool)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let data = self.data.clone();
+        let watchdog = self.watchdog.clone();
+        let log = self.log.clone();
+        let forwarder = self.forwarder.clone();
+        let task = tokio::spawn(async move {
+            let mut buf = [0u8; 8192];
+            // Start of a line not ended yet, forwarded once it is complete
+            let mut line = Vec::new();
+            loop {
+                let read = match from.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                let _ = to.write_all(&buf[..read]).await;
+                if let Some(log) = &log {
+                    let _ = log.lock().unwrap().write(&buf[..read]);
+                }
+                if let Some(forwarder) = &forwarder {
+                    forwarder.game_output(&mut line, &buf[..read], is_stderr);
+                }
+                if let Some(watchdog) = &watchdog {
+                    let text = String::from_utf8_lossy(&buf[..read]);
+                    watchdog.lock().unwrap().observe(&text, is_stderr);
+                }
 
-    Ok(exit.code().context("Failed to get exit code")?)
+                let mut data = data.lock().unwrap();
+                data.extend_from_slice(&buf[..read]);
+                if data.len() > OUTPUT_TAIL_LEN {
+                    let excess = data.len() - OUTPUT_TAIL_LEN;
+                    data.drain(..excess);
+                }
+            }
+            if let (Some(forwarder), false) = (&forwarder, line.is_empty()) {
+                forwarder.game_line(&line, is_stderr);
+            }
+        });
+        self.tasks.lock().unwrap().push(task);
+    }
+
+    /// Wait until the output of the exited game is read and return it.
+    async fn finish(&self) -> String {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in tasks {
+            // Processes started by the game may keep the pipes open
+            let _ = tokio::time::timeout(Duration::from_secs(1), task).await;
+        }
+        String::from_utf8_lossy(&self.data.lock().unwrap()).into_owned()
+    }
 }
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use clap::{Arg, ArgMatches};
+use rand::Rng;
+use std::path::PathBuf;
+use tracing::*;
+
+/// Argument keeping the scratch directories of a command for debugging.
+pub(crate) fn arg() -> Arg<'static> {
+    Arg::new("keep_temp")
+        .long("keep-temp")
+        .env("PLMC_KEEP_TEMP")
+        .help("Keep the temporary directories of downloaded files instead of removing them")
+}
+
+/// Directory holding the files of a single command.
+///
+/// Either given by the user, or a new temporary directory which is removed when dropped. Declare
+/// it before everything writing into it, so it outlives cancelled downloads. Commands using it
+/// need the argument of [`arg`].
+pub(crate) struct ScratchDir {
+    path: PathBuf,
+    /// Whether plmc created the directory, the user's own directories are left alone.
+    temporary: bool,
+    keep: bool,
+}
+
+impl ScratchDir {
+    /// The directory in the argument `name` if given, otherwise a temporary directory named
+    /// after `name`, kept with [`arg`].
+    pub fn new(sub_matches: &ArgMatches, name: &str) -> Result<Self> {
+        if let Some(dir) = sub_matches.value_of(name) {
+            return Ok(Self {
+                path: PathBuf::from(dir),
+                temporary: false,
+                keep: true,
+            });
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "plmc-{}-{}-{:08x}",
+            name,
+            std::process::id(),
+            rand::thread_rng().gen::<u32>()
+        ));
+        std::fs::create_dir_all(&path).with_context(|| format!("Creating {}", path.display()))?;
+        Ok(Self {
+            path,
+            temporary: true,
+            keep: sub_matches.is_present("keep_temp"),
+        })
+    }
+
+    pub fn display(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if !self.temporary {
+            return;
+        }
+        if self.keep {
+            eprintln!("Kept temporary files in {}", self.path.display());
+            return;
+        }
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
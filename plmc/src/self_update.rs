@@ -0,0 +1,155 @@
+use crate::http::HttpClient;
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use hyper::header::LOCATION;
+use polymc::{Release, DEFAULT_RELEASES_URL};
+use tracing::*;
+
+/// Redirects followed to a download, GitHub serves assets from another host.
+const MAX_REDIRECTS: usize = 5;
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("self-update")
+        .about("Update plmc to the latest release")
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .env("PLMC_UPDATE_URL")
+                .takes_value(true)
+                .default_value(DEFAULT_RELEASES_URL)
+                .help("Release to update to, in the format of the GitHub releases API"),
+        )
+        .arg(
+            Arg::new("public_key")
+                .long("public-key")
+                .env("PLMC_UPDATE_PUBLIC_KEY")
+                .takes_value(true)
+                .help("Hex ed25519 key the release has to be signed with [default: the key of the official releases]"),
+        )
+        .arg(
+            Arg::new("allow_unsigned")
+                .long("allow-unsigned")
+                .help("Install releases without a signature, only verifying their checksum"),
+        )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Only check whether an update is available"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("Don't ask before updating"),
+        );
+    crate::http::args(app)
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    let url = sub_matches.value_of("url").unwrap();
+    let allow_unsigned = sub_matches.is_present("allow_unsigned");
+    let public_key = match sub_matches
+        .value_of("public_key")
+        .or(polymc::RELEASE_PUBLIC_KEY)
+    {
+        Some(key) => Some(hex::decode(key).context("Invalid public key")?),
+        None if allow_unsigned => None,
+        None => bail!(
+            "This build of plmc has no key to verify updates with, pass --public-key or --allow-unsigned"
+        ),
+    };
+    let client = crate::http::client(sub_matches, url)?;
+
+    let release: Release = serde_json::from_slice(&download(&client, url).await?)
+        .with_context(|| format!("Invalid response from {}", url))?;
+    let current = env!("CARGO_PKG_VERSION");
+    if !release.is_newer_than(current) {
+        println!("plmc {} is up to date", current);
+        return Ok(0);
+    }
+    if sub_matches.is_present("check") {
+        println!(
+            "plmc {} is available, {} is installed",
+            release.version(),
+            current
+        );
+        return Ok(0);
+    }
+
+    let target = polymc::current_target();
+    let asset = release
+        .asset_for("plmc", &target)
+        .with_context(|| format!("Release {} has no binary for {}", release.tag_name, target))?;
+    let checksum = release.checksum_for(asset).with_context(|| {
+        format!(
+            "Release {} has no checksum for {}",
+            release.tag_name, asset.name
+        )
+    })?;
+    let signature = match (&public_key, release.signature_for(asset)) {
+        (Some(_), Some(signature)) => Some(signature),
+        (Some(_), None) if !allow_unsigned => bail!(
+            "Release {} has no signature for {}, pass --allow-unsigned to install it anyway",
+            release.tag_name,
+            asset.name
+        ),
+        _ => None,
+    };
+
+    if !sub_matches.is_present("yes")
+        && !crate::run::confirm(&format!(
+            "Update plmc from {} to {}? [Y/n]",
+            current,
+            release.version()
+        ))?
+    {
+        return Ok(1);
+    }
+
+    let checksum =
+        polymc::parse_checksum(&download(&client, &checksum.browser_download_url).await?)?;
+    println!("Downloading {}", asset.browser_download_url);
+    let data = download(&client, &asset.browser_download_url).await?;
+    match (&public_key, signature) {
+        (Some(public_key), Some(signature)) => {
+            let signature = download(&client, &signature.browser_download_url).await?;
+            polymc::verify_release(&data, &checksum, public_key, &signature)?;
+        }
+        _ => {
+            warn!(
+                "{} is not signed, only its checksum was verified",
+                asset.name
+            );
+            polymc::verify_checksum(&data, &checksum)?;
+        }
+    }
+
+    let exe = std::env::current_exe().context("Finding the plmc binary")?;
+    polymc::replace_binary(&exe, &data).with_context(|| format!("Replacing {}", exe.display()))?;
+    println!("Updated plmc to {}", release.version());
+
+    Ok(0)
+}
+
+/// Download `url`, following redirects.
+async fn download(client: &HttpClient, url: &str) -> Result<Vec<u8>> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let res = client.get(url.parse()?).await?;
+        if res.status().is_redirection() {
+            url = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .with_context(|| format!("Redirect from {} without location", url))?
+                .to_string();
+            continue;
+        }
+        if !res.status().is_success() {
+            bail!("Failed to download {}: {}", url, res.status());
+        }
+        return Ok(hyper::body::to_bytes(res.into_body()).await?.to_vec());
+    }
+
+    bail!("Too many redirects downloading {}", url)
+}
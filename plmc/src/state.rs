@@ -0,0 +1,107 @@
+use crate::config::{GlobalConfig, ACCOUNTS_FILE};
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+use polymc::state::{export_state, import_state, StateDir};
+use std::fs::{File, OpenOptions};
+use tracing::*;
+
+pub(crate) fn export_app() -> App<'static> {
+    App::new("export-all")
+        .about("Save the config, accounts and instances in one archive, without the stores")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .takes_value(true)
+                .default_value("plmc-state.zip")
+                .help("Zip file to write the state to"),
+        )
+        .arg(
+            Arg::new("with_accounts").long("with-accounts").help(
+                "Include the accounts and their tokens, anyone with the archive can use them",
+            ),
+        )
+}
+
+pub(crate) fn import_app() -> App<'static> {
+    App::new("import-all")
+        .about("Restore the state saved with export-all")
+        .arg(
+            Arg::new("archive")
+                .required(true)
+                .takes_value(true)
+                .help("Zip file written by export-all"),
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .help("Replace files which exist already"),
+        )
+}
+
+/// Directories of the state, the stores are downloaded again where it is imported.
+fn state_dirs(accounts: bool) -> Vec<StateDir> {
+    let mut config = StateDir::new("config", GlobalConfig::dir());
+    if !accounts {
+        config.exclude.push(ACCOUNTS_FILE.into());
+    }
+    vec![
+        config,
+        StateDir::instances("instances", crate::run::get_dir("instances"), 1),
+        StateDir::instances("game", crate::run::get_dir("game"), 0),
+    ]
+}
+
+pub(crate) fn run_export(sub_matches: &ArgMatches) -> Result<i32> {
+    let output = sub_matches.value_of("output").unwrap();
+    let accounts = sub_matches.is_present("with_accounts");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Only readable by the user, it may hold tokens and server addresses
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(output)
+        .with_context(|| format!("Creating {}", output))?;
+    let count = export_state(&state_dirs(accounts), file)?;
+    println!("Saved {} files in {}", count, output);
+    if accounts && GlobalConfig::dir().join(ACCOUNTS_FILE).is_file() {
+        println!("The archive contains the tokens of your accounts, keep it private");
+    } else if GlobalConfig::dir().join(ACCOUNTS_FILE).is_file() {
+        println!("Left out the accounts, pass --with-accounts to include them");
+    }
+
+    Ok(0)
+}
+
+pub(crate) fn run_import(sub_matches: &ArgMatches) -> Result<i32> {
+    let archive = sub_matches.value_of("archive").unwrap();
+    let file = File::open(archive).with_context(|| format!("Opening {}", archive))?;
+    let count = import_state(file, &state_dirs(true), sub_matches.is_present("overwrite"))
+        .with_context(|| format!("Importing {}", archive))?;
+    println!("Restored {} files from {}", count, archive);
+
+    // Stores configured on the old machine may not exist here
+    match GlobalConfig::load() {
+        Ok(config) => {
+            let stores = config
+                .store
+                .iter()
+                .chain(config.adopted("lib"))
+                .chain(config.adopted("assets"));
+            for store in stores.filter(|store| !store.is_dir()) {
+                crate::mods::warn_user(&format!(
+                    "The stores in {} do not exist here, choose others with `plmc store move` \
+                     or `plmc store adopt`",
+                    store.display()
+                ));
+            }
+        }
+        Err(e) => warn!("Failed to load the imported config: {:#}", e),
+    }
+
+    Ok(0)
+}
@@ -0,0 +1,293 @@
+use crate::config::GlobalConfig;
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use polymc::adopt::ForeignStores;
+use polymc::lock::{DirLock, LOCK_FILE_NAME};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::*;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+pub(crate) fn app() -> App<'static> {
+    let app = App::new("store")
+        .about("Manage the directories holding libraries, assets and meta data")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            App::new("move")
+                .about("Move the stores to another directory and use them from there")
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Directory to move the stores into"),
+                )
+                .arg(
+                    Arg::new("lib_dir")
+                        .long("lib-dir")
+                        .takes_value(true)
+                        .env("PLMC_LIB_DIR"),
+                )
+                .arg(
+                    Arg::new("meta_dir")
+                        .long("meta-dir")
+                        .takes_value(true)
+                        .env("PLMC_META_DIR"),
+                )
+                .arg(
+                    Arg::new("assets_dir")
+                        .long("assets-dir")
+                        .env("PLMC_ASSETS_DIR")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("adopt")
+                .about(
+                    "Reuse the libraries and assets of MultiMC, its forks or the vanilla launcher",
+                )
+                .arg(
+                    Arg::new("dir")
+                        .required(true)
+                        .takes_value(true)
+                        .help("Data directory of the launcher, e.g. ~/.minecraft"),
+                )
+                .arg(
+                    Arg::new("hardlink")
+                        .long("hardlink")
+                        .conflicts_with("in_place")
+                        .help("Link the files into the stores instead of copying them, copying where links are not supported"),
+                )
+                .arg(Arg::new("in_place").long("in-place").help(
+                    "Use the stores of the launcher in place instead of copying their files",
+                ))
+                .arg(
+                    Arg::new("lib_dir")
+                        .long("lib-dir")
+                        .takes_value(true)
+                        .env("PLMC_LIB_DIR"),
+                )
+                .arg(
+                    Arg::new("assets_dir")
+                        .long("assets-dir")
+                        .env("PLMC_ASSETS_DIR")
+                        .takes_value(true),
+                ),
+        );
+    #[cfg(feature = "peer-cache")]
+    let app = app.subcommand(crate::peer::serve_app());
+    app
+}
+
+pub(crate) async fn run(sub_matches: &ArgMatches) -> Result<i32> {
+    match sub_matches.subcommand() {
+        Some(("move", sub_matches)) => run_move(sub_matches),
+        Some(("adopt", sub_matches)) => run_adopt(sub_matches).await,
+        #[cfg(feature = "peer-cache")]
+        Some(("serve", sub_matches)) => crate::peer::run_serve(sub_matches).await,
+        _ => bail!("Unknown command"),
+    }
+}
+
+fn run_move(sub_matches: &ArgMatches) -> Result<i32> {
+    let to = PathBuf::from(sub_matches.value_of("to").unwrap());
+    std::fs::create_dir_all(&to).with_context(|| format!("Creating {}", to.display()))?;
+    let to = to.canonicalize()?;
+
+    let stores = [
+        ("lib", sub_matches.value_of("lib_dir")),
+        ("assets", sub_matches.value_of("assets_dir")),
+        ("meta", sub_matches.value_of("meta_dir")),
+    ];
+    // Check everything before moving anything
+    let mut config = GlobalConfig::load()?;
+    let mut moves = Vec::new();
+    for (name, dir) in stores {
+        if let (Some(adopted), None) = (config.adopted(name), dir) {
+            println!(
+                "Not moving {}, it is shared with another launcher",
+                adopted.display()
+            );
+            continue;
+        }
+        let from = PathBuf::from(
+            dir.map(ToString::to_string)
+                .unwrap_or_else(|| crate::run::get_store_dir(name)),
+        );
+        let dest = to.join(name);
+        if !from.is_dir() {
+            debug!("{} does not exist, nothing to move", from.display());
+            continue;
+        }
+        if from.canonicalize()? == dest {
+            continue;
+        }
+        if dest.exists() {
+            bail!("{} already exists", dest.display());
+        }
+        moves.push((from, dest));
+    }
+
+    for (from, dest) in &moves {
+        let lock = DirLock::try_lock(from)
+            .with_context(|| format!("{} is in use by another launcher", from.display()))?;
+        println!("Moving {} to {}", from.display(), dest.display());
+        move_dir(from, dest, lock)?;
+    }
+
+    config.store = Some(to.clone());
+    config.save()?;
+    println!("Using the stores in {}", to.display());
+
+    Ok(0)
+}
+
+async fn run_adopt(sub_matches: &ArgMatches) -> Result<i32> {
+    let dir = sub_matches.value_of("dir").unwrap();
+    let stores = ForeignStores::detect(dir)?;
+    println!("Scanning the stores of the {} in {}", stores.kind, dir);
+    let scan = stores.scan()?;
+    println!(
+        "{} libraries ({:.0} MiB) and {} assets ({:.0} MiB) can be reused",
+        scan.libraries,
+        scan.library_bytes as f64 / MIB,
+        scan.assets,
+        scan.asset_bytes as f64 / MIB
+    );
+    for path in &scan.corrupt {
+        debug!("corrupt: {}", path.display());
+    }
+
+    if !sub_matches.is_present("in_place") {
+        if !scan.corrupt.is_empty() {
+            crate::mods::warn_user(&format!(
+                "{} files have the wrong hash, they are not copied and downloaded again",
+                scan.corrupt.len()
+            ));
+        }
+        let store_dir = |name: &str, arg: &str| {
+            sub_matches
+                .value_of(arg)
+                .map(ToString::to_string)
+                .unwrap_or_else(|| crate::run::get_store_dir(name))
+        };
+        let (lib_dir, assets_dir) = (
+            store_dir("lib", "lib_dir"),
+            store_dir("assets", "assets_dir"),
+        );
+        std::fs::create_dir_all(&lib_dir)?;
+        std::fs::create_dir_all(&assets_dir)?;
+        let _locks = (
+            crate::run::lock_dir(&lib_dir).await?,
+            crate::run::lock_dir(&assets_dir).await?,
+        );
+        let hardlink = sub_matches.is_present("hardlink");
+        let added = stores.adopt_into(&scan, &lib_dir, &assets_dir, hardlink)?;
+        println!("Added {} files to the stores", added);
+        return Ok(0);
+    }
+
+    // Corrupt files would be replaced in the stores of the other launcher once they are used
+    if !scan.corrupt.is_empty() {
+        bail!(
+            "{} files in {} have the wrong hash, copy them without --in-place to leave the \
+             stores of the {} untouched",
+            scan.corrupt.len(),
+            dir,
+            stores.kind
+        );
+    }
+
+    // The other files are verified when they are used
+    let mut config = GlobalConfig::load()?;
+    if stores.libraries_dir.is_dir() {
+        config.adopted_lib = Some(stores.libraries_dir.canonicalize()?);
+    }
+    if stores.assets_dir.is_dir() {
+        config.adopted_assets = Some(stores.assets_dir.canonicalize()?);
+    }
+    config.save()?;
+    println!("Using the libraries and assets in {}", dir);
+
+    Ok(0)
+}
+
+/// Move the locked directory `from` to `to`.
+///
+/// Directories on other file systems are copied, the copy is only moved in place once the hashes
+/// of all files match and `from` is only removed after that.
+fn move_dir(from: &Path, to: &Path, lock: DirLock) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        drop(lock);
+        let _ = std::fs::remove_file(to.join(LOCK_FILE_NAME));
+        return Ok(());
+    }
+
+    let partial = to.with_extension("partial");
+    let _ = std::fs::remove_dir_all(&partial);
+    let result = (|| {
+        let hashes = hash_files(from)?;
+        copy_dir(from, &partial)?;
+        let copied = hash_files(&partial)?;
+        for (path, hash) in &hashes {
+            if copied.get(path) != Some(hash) {
+                bail!("{} differs after copying", from.join(path).display());
+            }
+        }
+        info!("verified {} files", hashes.len());
+        std::fs::rename(&partial, to)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e.context(format!("Copying {}", from.display())));
+    }
+
+    drop(lock);
+    std::fs::remove_dir_all(from).with_context(|| format!("Removing {}", from.display()))?;
+    Ok(())
+}
+
+/// Copy the files of `from` into `to`, except the lock file.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name() == LOCK_FILE_NAME {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(&path, to.join(entry.file_name()))
+                .with_context(|| format!("Copying {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sha256 of all files in `dir` by their path relative to it, except the lock file.
+fn hash_files(dir: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut ret = BTreeMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_name() == LOCK_FILE_NAME {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let digest = polymc::hash_file(&ring::digest::SHA256, &path)?;
+            let relative = path.strip_prefix(dir)?.to_path_buf();
+            ret.insert(relative, digest.as_ref().to_vec());
+        }
+    }
+    Ok(ret)
+}
@@ -0,0 +1,2003 @@
+//! End-to-end tests of resolution, download and verification against a local meta server.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde_json::json;
+
+const LIBRARY: &[u8] = b"library jar";
+const CLIENT: &[u8] = b"client jar";
+const ASSET: &[u8] = b"asset";
+const MAVEN_LIBRARY: &[u8] = b"maven jar";
+const MAVEN_PATH: &str = "org/example/maven-lib/2.0/maven-lib-2.0.jar";
+const MOD: &[u8] = b"mod jar";
+const OLD_MOD: &[u8] = b"old mod jar";
+const ADDON: &[u8] = b"addon jar";
+const LOG_CONFIG: &[u8] = b"<Configuration/>";
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha1(data: &[u8]) -> String {
+    hex(ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, data).as_ref())
+}
+
+fn sha256(data: &[u8]) -> String {
+    hex(ring::digest::digest(&ring::digest::SHA256, data).as_ref())
+}
+
+/// Files served by the fixture server, by path.
+fn fixtures(url: &str) -> HashMap<String, Vec<u8>> {
+    let mut files = HashMap::new();
+
+    let asset_hash = sha1(ASSET);
+    let asset_index = json!({
+        "objects": {"icon.png": {"hash": asset_hash, "size": ASSET.len()}}
+    })
+    .to_string();
+
+    let artifact = |path: &str, data: &[u8]| {
+        json!({"artifact": {
+            "sha1": sha1(data),
+            "size": data.len(),
+            "url": format!("{}/{}", url, path),
+        }})
+    };
+    let manifest = json!({
+        "formatVersion": 1,
+        "name": "Minecraft",
+        "uid": "net.minecraft",
+        "version": "1.0",
+        "order": -2,
+        "releaseTime": "2022-01-01T00:00:00+00:00",
+        "type": "release",
+        "mainClass": "net.minecraft.client.main.Main",
+        "minecraftArguments": "--username ${auth_player_name} --assetIndex ${assets_index_name}",
+        "libraries": [{
+            "name": "org.example:lib:1.0",
+            "downloads": artifact("libraries/lib-1.0.jar", LIBRARY),
+        }, {
+            "name": "org.example:maven-lib:2.0",
+            "url": format!("{}/maven/", url),
+        }],
+        "mainJar": {
+            "name": "com.mojang:minecraft:1.0:client",
+            "downloads": artifact("libraries/client.jar", CLIENT),
+        },
+        "assetIndex": {
+            "id": "1.0",
+            "sha1": sha1(asset_index.as_bytes()),
+            "size": asset_index.len(),
+            "totalSize": ASSET.len(),
+            "url": format!("{}/asset-index.json", url),
+        },
+        "logging": {"client": {
+            "argument": "-Dlog4j.configurationFile=${path}",
+            "file": {
+                "id": "client-1.0.xml",
+                "sha1": sha1(LOG_CONFIG),
+                "size": LOG_CONFIG.len(),
+                "url": format!("{}/client-1.0.xml", url),
+            },
+            "type": "log4j2-xml",
+        }},
+    })
+    .to_string();
+    let package_index = json!({
+        "formatVersion": 1,
+        "name": "Minecraft",
+        "uid": "net.minecraft",
+        "versions": [{
+            "releaseTime": "2022-01-01T00:00:00+00:00",
+            "sha256": sha256(manifest.as_bytes()),
+            "type": "release",
+            "version": "1.0",
+        }],
+    })
+    .to_string();
+    let index = json!({
+        "formatVersion": 1,
+        "packages": [{
+            "name": "Minecraft",
+            "uid": "net.minecraft",
+            "sha256": sha256(package_index.as_bytes()),
+        }],
+    })
+    .to_string();
+
+    files.insert("/index.json".to_string(), index.into_bytes());
+    files.insert(
+        "/net.minecraft/index.json".to_string(),
+        package_index.into_bytes(),
+    );
+    files.insert("/net.minecraft/1.0.json".to_string(), manifest.into_bytes());
+    files.insert("/asset-index.json".to_string(), asset_index.into_bytes());
+    files.insert("/client-1.0.xml".to_string(), LOG_CONFIG.to_vec());
+    files.insert("/libraries/lib-1.0.jar".to_string(), LIBRARY.to_vec());
+    files.insert("/libraries/client.jar".to_string(), CLIENT.to_vec());
+    files.insert(format!("/maven/{}", MAVEN_PATH), MAVEN_LIBRARY.to_vec());
+    files.insert(
+        format!("/maven/{}.sha1", MAVEN_PATH),
+        sha1(MAVEN_LIBRARY).into_bytes(),
+    );
+    files.insert(
+        format!("/assets/{}/{}", &asset_hash[..2], asset_hash),
+        ASSET.to_vec(),
+    );
+
+    let templates = json!([{
+        "name": "modded",
+        "components": [{"uid": "net.minecraft"}],
+        "mods": [
+            {"modrinth": "example"},
+            {"url": format!("{}/mods/extra.jar", url), "sha1": sha1(MOD)},
+            {
+                "url": format!("{}/mods/addon-1.0.jar?download", url),
+                "sha1": sha1(ADDON),
+                "fileName": "addon.jar",
+            },
+        ],
+    }]);
+    files.insert(
+        "/templates.json".to_string(),
+        templates.to_string().into_bytes(),
+    );
+
+    let version = |project: &str, number: &str, data: &[u8], dependencies| {
+        json!({
+            "id": format!("{}-{}", project, number),
+            "project_id": project,
+            "version_number": number,
+            "game_versions": ["1.0"],
+            "loaders": ["fabric"],
+            "dependencies": dependencies,
+            "files": [{
+                "url": format!("{}/mods/{}-{}.jar", url, project, number),
+                "filename": format!("{}-{}.jar", project, number),
+                "primary": true,
+                "size": data.len(),
+                "hashes": {"sha1": sha1(data)},
+            }],
+        })
+    };
+    let versions = [
+        ("example", version("example", "2.0", MOD, json!([]))),
+        (
+            "addon",
+            version(
+                "addon",
+                "1.0",
+                ADDON,
+                json!([{"project_id": "example", "dependency_type": "required"}]),
+            ),
+        ),
+        (
+            "conflict",
+            version(
+                "conflict",
+                "1.0",
+                ADDON,
+                json!([{"project_id": "example", "dependency_type": "incompatible"}]),
+            ),
+        ),
+    ];
+    let mut escape = version("escape", "1.0", MOD, json!([]));
+    escape["files"][0]["filename"] = json!("../escape.jar");
+    for (project, version) in versions.into_iter().chain([("escape", escape)]) {
+        files.insert(
+            format!("/modrinth/project/{}/version", project),
+            json!([version]).to_string().into_bytes(),
+        );
+    }
+    files.insert(
+        "/modrinth/version_files".to_string(),
+        json!({ sha1(OLD_MOD): version("example", "1.0", OLD_MOD, json!([])) })
+            .to_string()
+            .into_bytes(),
+    );
+    files.insert(
+        "/modrinth/version_files/update".to_string(),
+        json!({ sha1(OLD_MOD): version("example", "2.0", MOD, json!([])) })
+            .to_string()
+            .into_bytes(),
+    );
+    files.insert("/mods/addon-1.0.jar".to_string(), ADDON.to_vec());
+    files.insert("/mods/conflict-1.0.jar".to_string(), ADDON.to_vec());
+    files.insert("/mods/example-2.0.jar".to_string(), MOD.to_vec());
+    files.insert("/mods/extra.jar".to_string(), MOD.to_vec());
+
+    files
+}
+
+/// Local meta server, recording the paths requested from it.
+struct FixtureServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    /// Paths answered with `429 Too Many Requests`, with the number of times left.
+    throttled: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl FixtureServer {
+    fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    /// Start the server with fixtures changed by `modify`.
+    fn start_with(modify: impl FnOnce(&mut HashMap<String, Vec<u8>>)) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mut files = fixtures(&url);
+        modify(&mut files);
+        let files = Arc::new(files);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let throttled = Arc::new(Mutex::new(HashMap::new()));
+
+        let log = requests.clone();
+        let throttle = throttled.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let make_service = make_service_fn(move |_| {
+                    let files = files.clone();
+                    let log = log.clone();
+                    let throttle = throttle.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                            let path = req.uri().path().to_string();
+                            log.lock().unwrap().push(path.clone());
+                            let throttled = match throttle.lock().unwrap().get_mut(&path) {
+                                Some(left) if *left > 0 => {
+                                    *left -= 1;
+                                    true
+                                }
+                                _ => false,
+                            };
+                            let response = match files.get(&path) {
+                                _ if throttled => Response::builder()
+                                    .status(StatusCode::TOO_MANY_REQUESTS)
+                                    .header("Retry-After", "0")
+                                    .body(Body::empty())
+                                    .unwrap(),
+                                Some(data) => Response::new(Body::from(data.clone())),
+                                None => Response::builder()
+                                    .status(StatusCode::NOT_FOUND)
+                                    .body(Body::empty())
+                                    .unwrap(),
+                            };
+                            async move { Ok::<_, Infallible>(response) }
+                        }))
+                    }
+                });
+                listener.set_nonblocking(true).unwrap();
+                Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(make_service)
+                    .await
+                    .unwrap();
+            });
+        });
+
+        Self {
+            url,
+            requests,
+            throttled,
+        }
+    }
+
+    fn take_requests(&self) -> Vec<String> {
+        std::mem::take(&mut *self.requests.lock().unwrap())
+    }
+
+    /// Answer the next `times` requests of `path` with `429 Too Many Requests`.
+    fn throttle(&self, path: &str, times: u32) {
+        self.throttled
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), times);
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()));
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("plmc-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Prepare the instance with `plmc run --dry-run`, which prints the command instead of
+/// starting the game.
+fn dry_run(server: &FixtureServer, dir: &Path) -> Output {
+    dry_run_command(server, dir).output().unwrap()
+}
+
+fn dry_run_command(server: &FixtureServer, dir: &Path) -> Command {
+    dry_run_command_from(&server.url, dir)
+}
+
+/// Command of [`dry_run`] with the meta server at `base_url`.
+fn dry_run_command_from(base_url: &str, dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_plmc"));
+    command
+        .args(["run", "--dry-run", "--yes", "-v", "1.0"])
+        .arg("--java")
+        .arg("java")
+        .arg("--base-url")
+        .arg(base_url)
+        .arg("--assets-url")
+        .arg(format!("{}/assets", base_url))
+        .arg("--meta-dir")
+        .arg(dir.join("meta"))
+        .arg("--lib-dir")
+        .arg(dir.join("lib"))
+        .arg("--assets-dir")
+        .arg(dir.join("assets"))
+        .arg("--mc-dir")
+        .arg(dir.join("game"))
+        .env_remove("PLMC_META_INDEX_SHA256");
+    command
+}
+
+/// Command starting the game like [`dry_run_command`], with a shell script running `script`
+/// standing in for java.
+#[cfg(unix)]
+fn run_command(server: &FixtureServer, dir: &Path, script: &str) -> Command {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::create_dir_all(dir).unwrap();
+    let java = dir.join("java");
+    std::fs::write(&java, format!("#!/bin/sh\n{}", script)).unwrap();
+    std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let dry_run = dry_run_command(server, dir);
+    let mut command = Command::new(dry_run.get_program());
+    command
+        .args(
+            dry_run
+                .get_args()
+                .filter(|arg| !["--dry-run", "--java", "java"].contains(&arg.to_str().unwrap())),
+        )
+        .arg("--java")
+        .arg(java)
+        .env_remove("PLMC_META_INDEX_SHA256");
+    command
+}
+
+#[test]
+fn download_and_verify() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("pipeline");
+
+    let output = dry_run_command(&server, &dir)
+        .arg("--log-config")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let lib = dir.join("lib");
+    let library = lib.join("org/example/lib/1.0/lib-1.0.jar");
+    let client = lib.join("com/mojang/minecraft/1.0/minecraft-1.0-client.jar");
+    let asset_hash = sha1(ASSET);
+    let asset = dir
+        .join("assets/objects")
+        .join(&asset_hash[..2])
+        .join(&asset_hash);
+    assert_eq!(std::fs::read(&library).unwrap(), LIBRARY);
+    assert_eq!(std::fs::read(&client).unwrap(), CLIENT);
+    assert_eq!(std::fs::read(lib.join(MAVEN_PATH)).unwrap(), MAVEN_LIBRARY);
+    assert_eq!(std::fs::read(&asset).unwrap(), ASSET);
+    assert!(dir.join("assets/indexes/1.0.json").is_file());
+    let log_config = dir.join("assets/log_configs/client-1.0.xml");
+    assert_eq!(std::fs::read(&log_config).unwrap(), LOG_CONFIG);
+
+    assert!(stdout.contains(&format!("  {}\n", library.display())));
+    assert!(stdout.contains(&format!("  {}\n", client.display())));
+    assert!(stdout.contains("main class: net.minecraft.client.main.Main"));
+    assert!(stdout.contains(&format!(
+        "  -Dlog4j.configurationFile={}\n",
+        log_config.display()
+    )));
+    let requested = server.take_requests();
+    assert!(requested.contains(&"/libraries/lib-1.0.jar".to_string()));
+
+    // Verified files are not downloaded again
+    let output = dry_run(&server, &dir);
+    assert!(output.status.success());
+    let requested = server.take_requests();
+    assert!(
+        requested
+            .iter()
+            .all(|p| !p.starts_with("/libraries/") && !p.starts_with("/assets/")),
+        "{:?}",
+        requested
+    );
+
+    // A corrupted library is replaced
+    std::fs::write(&library, b"corrupted!!").unwrap();
+    let output = dry_run(&server, &dir);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&library).unwrap(), LIBRARY);
+    let requested = server.take_requests();
+    assert!(requested.contains(&"/libraries/lib-1.0.jar".to_string()));
+    assert!(!requested.contains(&"/libraries/client.jar".to_string()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn download_stats() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("download-stats");
+
+    let output = dry_run_command(&server, &dir)
+        .arg("--stats")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("download statistics:\n"));
+    assert!(stdout.contains("  net.minecraft: 0 cached, "));
+
+    // Everything is verified from the cache on the second run
+    let output = dry_run_command(&server, &dir)
+        .arg("--stats")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("  net.minecraft: 8 cached, 0 downloaded"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rate_limited() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("rate-limited");
+
+    // Requests are retried as told by the server
+    server.throttle("/index.json", 2);
+    server.throttle("/libraries/lib-1.0.jar", 1);
+    let output = dry_run(&server, &dir);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requested = server.take_requests();
+    let count = |path: &str| requested.iter().filter(|p| *p == path).count();
+    assert_eq!(count("/index.json"), 3);
+    assert_eq!(count("/libraries/lib-1.0.jar"), 2);
+
+    // Until it keeps throttling
+    server.throttle("/index.json", 100);
+    let output = dry_run(&server, &dir);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("keeps rate limiting"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "peer-cache")]
+#[test]
+fn peer_cache() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let server = FixtureServer::start();
+    let seed = temp_dir("peer-seed");
+    let dir = temp_dir("peer");
+    assert!(dry_run(&server, &seed).status.success());
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut peer = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args(["store", "serve", "--bind"])
+        .arg(format!("127.0.0.1:{}", port))
+        .arg("--lib-dir")
+        .arg(seed.join("lib"))
+        .arg("--assets-dir")
+        .arg(seed.join("assets"))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(peer.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    assert!(line.contains("Serving the stores"), "{}", line);
+
+    // Files with a hash come from the peer, the rest from the origin
+    server.take_requests();
+    let output = dry_run_command(&server, &dir)
+        .arg("--peer")
+        .arg(format!("http://127.0.0.1:{}", port))
+        .output()
+        .unwrap();
+    let _ = peer.kill();
+    let _ = peer.wait();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requested = server.take_requests();
+    assert!(!requested
+        .iter()
+        .any(|p| p.starts_with("/libraries/") || p.starts_with("/assets/")));
+    assert!(requested.iter().any(|p| p.starts_with("/maven/")));
+    assert_eq!(
+        std::fs::read(dir.join("lib/com/mojang/minecraft/1.0/minecraft-1.0-client.jar")).unwrap(),
+        CLIENT
+    );
+
+    let _ = std::fs::remove_dir_all(&seed);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn meta_server_selection() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("meta-servers");
+    let config = dir.join("config/plmc");
+    std::fs::create_dir_all(&config).unwrap();
+    // Nothing listens on port 1
+    std::fs::write(
+        config.join("config.json"),
+        json!({"metaServers": ["http://127.0.0.1:1", server.url]}).to_string(),
+    )
+    .unwrap();
+
+    // The dry run without --base-url
+    let command = dry_run_command(&server, &dir);
+    let args: Vec<_> = command.get_args().collect();
+    let base_url = args.iter().position(|arg| *arg == "--base-url").unwrap();
+    let run = || {
+        Command::new(command.get_program())
+            .args(&args[..base_url])
+            .args(&args[base_url + 2..])
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env_remove("PLMC_BASE_URL")
+            .env_remove("PLMC_META_INDEX_SHA256")
+            .output()
+            .unwrap()
+    };
+
+    let output = run();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(server
+        .take_requests()
+        .contains(&"/libraries/client.jar".to_string()));
+
+    std::fs::write(
+        config.join("config.json"),
+        json!({"metaServers": ["http://127.0.0.1:1"]}).to_string(),
+    )
+    .unwrap();
+    let output = run();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass one with --base-url"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn blocked_host() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("blocked");
+
+    let output = dry_run_command(&server, &dir)
+        .args(["--allow-host", "*.example.com"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not allowed"));
+    assert!(server.take_requests().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn meta_cache_poisoning() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("poisoning");
+    let manifest = dir.join("meta/net.minecraft/1.0.json");
+
+    assert!(dry_run(&server, &dir).status.success());
+    let original = std::fs::read(&manifest).unwrap();
+
+    // A tampered cache entry is replaced by the file from the server
+    std::fs::write(&manifest, br#"{"uid": "net.minecraft", "libraries": []}"#).unwrap();
+    let output = dry_run(&server, &dir);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&manifest).unwrap(), original);
+
+    // A tampered file from the server is rejected and not cached
+    let tampered = FixtureServer::start_with(|files| {
+        let manifest = files.get_mut("/net.minecraft/1.0.json").unwrap();
+        *manifest = String::from_utf8_lossy(manifest)
+            .replace("lib-1.0.jar", "evil-1.0.jar")
+            .into_bytes();
+    });
+    let tampered_dir = temp_dir("tampered");
+    let output = dry_run(&tampered, &tampered_dir);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("hash does not match"));
+    assert!(!tampered_dir.join("meta/net.minecraft/1.0.json").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&tampered_dir);
+}
+
+#[test]
+fn stale_meta_invalidation() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("stale");
+    let meta = dir.join("meta");
+
+    assert!(dry_run(&server, &dir).status.success());
+    server.take_requests();
+
+    // Files the index does not list are left alone
+    std::fs::create_dir_all(meta.join("org.removed")).unwrap();
+    std::fs::write(meta.join("org.removed/index.json"), b"{}").unwrap();
+    std::fs::write(meta.join("net.minecraft/0.9.json"), b"{}").unwrap();
+    assert!(dry_run(&server, &dir).status.success());
+    assert!(meta.join("org.removed/index.json").exists());
+    assert!(meta.join("net.minecraft/0.9.json").exists());
+    assert!(!server
+        .take_requests()
+        .contains(&"/net.minecraft/1.0.json".to_string()));
+
+    // A package index with a new hash is discarded, and its manifests are checked again
+    std::fs::write(meta.join("net.minecraft/index.json"), b"{}").unwrap();
+    assert!(dry_run(&server, &dir).status.success());
+    let requested = server.take_requests();
+    assert!(requested.contains(&"/net.minecraft/index.json".to_string()));
+    assert!(!requested.contains(&"/net.minecraft/1.0.json".to_string()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn html_error_page() {
+    let portal = FixtureServer::start_with(|files| {
+        for (path, data) in files.iter_mut() {
+            if path.ends_with(".json") {
+                *data = b"<html><body>Please log in</body></html>".to_vec();
+            }
+        }
+    });
+    let dir = temp_dir("portal");
+
+    let output = dry_run(&portal, &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("{}/index.json", portal.url)),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("<html><body>Please log in"), "{}", stderr);
+
+    // The mirror is used instead
+    let mirror = FixtureServer::start();
+    let output = dry_run_command(&portal, &dir)
+        .arg("--mirror")
+        .arg(&mirror.url)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(mirror.take_requests().contains(&"/index.json".to_string()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn no_assets() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("no-assets");
+
+    let output = dry_run_command(&server, &dir)
+        .args(["--no-assets", "--headless", "--skip-verify"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("  -Djava.awt.headless=true\n"));
+    assert!(!stdout.contains("--assetIndex"));
+    assert!(!stdout.contains("-Dminecraft.launcher.brand"));
+    assert!(dir.join("lib/org/example/lib/1.0/lib-1.0.jar").is_file());
+    let requested = server.take_requests();
+    assert!(
+        requested
+            .iter()
+            .all(|p| p != "/asset-index.json" && !p.starts_with("/assets/")),
+        "{:?}",
+        requested
+    );
+
+    // Existing files are not hashed, so a corrupted library is kept
+    std::fs::write(
+        dir.join("lib/org/example/lib/1.0/lib-1.0.jar"),
+        b"corrupted",
+    )
+    .unwrap();
+    let output = dry_run_command(&server, &dir)
+        .args(["--no-assets", "--skip-verify"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!server
+        .take_requests()
+        .contains(&"/libraries/lib-1.0.jar".to_string()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn java_agent() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("java-agent");
+
+    let output = dry_run_command(&server, &dir)
+        .args(["--no-assets", "--java-agent", "agents/agent.jar=debug"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    let agent = dir.join("game/agents/agent.jar");
+    assert!(stdout.contains(&format!("  -javaagent:{}=debug\n", agent.display())));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn launcher_brand() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("launcher-brand");
+
+    let output = dry_run_command(&server, &dir)
+        .args([
+            "--launcher-brand",
+            "custom",
+            "--launcher-brand-version",
+            "2.0",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("  -Dminecraft.launcher.brand=custom\n"));
+    assert!(stdout.contains("  -Dminecraft.launcher.version=2.0\n"));
+
+    let output = dry_run_command(&server, &dir)
+        .arg("--no-launcher-brand")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(!stdout.contains("-Dminecraft.launcher"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn launch_overrides() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("launch-overrides");
+    let game = dir.join("game");
+    std::fs::create_dir_all(&game).unwrap();
+    std::fs::write(
+        game.join("instance.json"),
+        json!({"name": "overrides", "version": "1.0"}).to_string(),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args(["instance", "edit"])
+        .arg(&game)
+        .args(["--main-class", "com.example.Wrapper"])
+        .args(["--append-jvm-arg", "-Xmx4G"])
+        .args(["--prepend-game-arg", "--demo"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = dry_run_command(&server, &dir)
+        .arg("--no-assets")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("  -Xmx4G\nclasspath:\n"));
+    assert!(stdout.contains("main class: com.example.Wrapper\ngame args:\n  --demo\n"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn gpu_preference() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("gpu");
+    let game = dir.join("game");
+    std::fs::create_dir_all(&game).unwrap();
+    std::fs::write(
+        game.join("instance.json"),
+        json!({"name": "gpu", "version": "1.0"}).to_string(),
+    )
+    .unwrap();
+    let set_gpu = |gpu: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .args(["instance", "set"])
+            .arg(&game)
+            .args(["--gpu", gpu])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+
+    set_gpu("discrete");
+    let output = dry_run_command(&server, &dir)
+        .arg("--no-assets")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("env:\n  DRI_PRIME=1\n"), "{}", stdout);
+
+    set_gpu("default");
+    let output = dry_run_command(&server, &dir)
+        .arg("--no-assets")
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("DRI_PRIME"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn meta_resolve() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("meta-resolve");
+    std::fs::create_dir_all(&dir).unwrap();
+    let resolve = |wants: &str| {
+        let wants_path = dir.join("wants.toml");
+        std::fs::write(&wants_path, wants).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .args(["meta", "resolve", "--base-url", &server.url])
+            .arg("--file")
+            .arg(&wants_path)
+            .arg("--meta-dir")
+            .arg(dir.join("meta"))
+            .env_remove("PLMC_META_INDEX_SHA256")
+            .env_remove("PLMC_VERSION_CONFLICTS")
+            .output()
+            .unwrap()
+    };
+
+    let output = resolve(
+        r#"
+        [[components]]
+        uid = "net.minecraft"
+        range = ">=1.0"
+        "#,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // The meta directory is the user's own, it is not a kept temporary directory
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Kept temporary files"));
+    let lock = String::from_utf8_lossy(&output.stdout);
+    let lock: toml::Value = toml::from_str(&lock).unwrap();
+    let expected: toml::Value = toml::from_str(
+        r#"
+        [[components]]
+        uid = "net.minecraft"
+        version = "1.0"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(lock, expected);
+
+    // The lock file resolves to itself
+    let output = resolve(&toml::to_string(&lock).unwrap());
+    assert!(output.status.success());
+    assert_eq!(
+        toml::from_str::<toml::Value>(&String::from_utf8_lossy(&output.stdout)).unwrap(),
+        lock
+    );
+
+    let output = resolve(
+        r#"
+        [[components]]
+        uid = "net.minecraft"
+        version = "1.0"
+
+        [[components]]
+        uid = "net.minecraft"
+        version = "2.0"
+        "#,
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("net.minecraft"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn meta_snapshot() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("meta-snapshot");
+    let snapshot = dir.join("snapshot");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args([
+            "meta",
+            "snapshot",
+            "net.minecraft",
+            "--base-url",
+            &server.url,
+        ])
+        .arg("--out")
+        .arg(&snapshot)
+        .env_remove("PLMC_META_INDEX_SHA256")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(snapshot.join("snapshot.json").is_file());
+    assert!(snapshot.join("net.minecraft/1.0.json").is_file());
+    server.take_requests();
+
+    // Meta data is only read from the snapshot
+    let output = dry_run_command(&server, &dir)
+        .arg("--no-assets")
+        .arg("--meta-snapshot")
+        .arg(&snapshot)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requested = server.take_requests();
+    assert!(
+        requested
+            .iter()
+            .all(|p| p.starts_with("/libraries/") || p.starts_with("/maven/")),
+        "{:?}",
+        requested
+    );
+
+    // A modified snapshot is refused
+    std::fs::write(snapshot.join("net.minecraft/1.0.json"), b"{}").unwrap();
+    let output = dry_run_command(&server, &dir)
+        .arg("--meta-snapshot")
+        .arg(&snapshot)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid meta snapshot"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn local_source() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("local-source");
+    let usb = dir.join("usb");
+
+    // A snapshot of the meta data with the stores of an installed instance
+    assert!(dry_run(&server, &dir.join("seed")).status.success());
+    let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args([
+            "meta",
+            "snapshot",
+            "net.minecraft",
+            "--base-url",
+            &server.url,
+        ])
+        .arg("--out")
+        .arg(&usb)
+        .env_remove("PLMC_META_INDEX_SHA256")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    copy_dir(&dir.join("seed/lib"), &usb.join("libraries"));
+    copy_dir(&dir.join("seed/assets"), &usb.join("assets"));
+    server.take_requests();
+
+    // Everything is installed from the directory
+    let target = dir.join("offline");
+    let base_url = format!("file://{}", usb.display());
+    let output = dry_run_command_from(&base_url, &target).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requested = server.take_requests();
+    assert!(requested.is_empty(), "{:?}", requested);
+
+    // Files with the wrong hash are taken from the origin instead
+    let client = target.join("lib/com/mojang/minecraft/1.0/minecraft-1.0-client.jar");
+    std::fs::write(
+        usb.join("libraries/com/mojang/minecraft/1.0/minecraft-1.0-client.jar"),
+        b"tampered",
+    )
+    .unwrap();
+    std::fs::remove_file(&client).unwrap();
+    let output = dry_run_command_from(usb.to_str().unwrap(), &target)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(server.take_requests(), ["/libraries/client.jar"]);
+    assert_eq!(std::fs::read(&client).unwrap(), CLIENT);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn store_move() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("store");
+    let plmc = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_plmc"));
+        command
+            .args(args)
+            .env("XDG_DATA_HOME", dir.join("data"))
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env_remove("PLMC_LIB_DIR")
+            .env_remove("PLMC_META_DIR")
+            .env_remove("PLMC_ASSETS_DIR")
+            .env_remove("PLMC_META_INDEX_SHA256");
+        command
+    };
+    let run = || {
+        plmc(&["run", "--dry-run", "--yes", "-v", "1.0", "--java", "java"])
+            .arg("--base-url")
+            .arg(&server.url)
+            .arg("--assets-url")
+            .arg(format!("{}/assets", server.url))
+            .arg("--mc-dir")
+            .arg(dir.join("game"))
+            .output()
+            .unwrap()
+    };
+
+    assert!(run().status.success());
+    let library = "org/example/lib/1.0/lib-1.0.jar";
+    assert!(dir.join("data/plmc/lib").join(library).is_file());
+    server.take_requests();
+
+    let moved = dir.join("moved");
+    let output = plmc(&["store", "move", "--to"])
+        .arg(&moved)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!dir.join("data/plmc/lib").exists());
+    assert_eq!(
+        std::fs::read(moved.join("lib").join(library)).unwrap(),
+        LIBRARY
+    );
+    assert!(moved.join("meta/index.json").is_file());
+    assert!(moved.join("assets/indexes/1.0.json").is_file());
+
+    // The moved stores are used without downloading again
+    let output = run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&moved.join("lib").join(library).display().to_string()));
+    let requested = server.take_requests();
+    assert!(
+        requested.iter().all(|p| !p.starts_with("/libraries/")),
+        "{:?}",
+        requested
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn adopt_stores() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("adopt");
+    let minecraft = dir.join(".minecraft");
+    let asset_hash = sha1(ASSET);
+    for (file, data) in [
+        ("launcher_profiles.json", &b"{}"[..]),
+        ("libraries/org/example/lib/1.0/lib-1.0.jar", LIBRARY),
+        (
+            "libraries/com/mojang/minecraft/1.0/minecraft-1.0-client.jar",
+            b"corrupt",
+        ),
+        (
+            &format!("assets/objects/{}/{}", &asset_hash[..2], asset_hash),
+            ASSET,
+        ),
+    ] {
+        let path = minecraft.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+    let plmc = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_plmc"));
+        command
+            .args(args)
+            .env("XDG_DATA_HOME", dir.join("data"))
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env_remove("PLMC_LIB_DIR")
+            .env_remove("PLMC_ASSETS_DIR")
+            .env_remove("PLMC_META_INDEX_SHA256");
+        command
+    };
+
+    // The files are copied into the stores by default
+    let output = plmc(&["store", "adopt"]).arg(&minecraft).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("vanilla launcher"), "{}", stdout);
+    assert!(stdout.contains("2 libraries"), "{}", stdout);
+    assert!(stdout.contains("1 assets"), "{}", stdout);
+    assert_eq!(
+        std::fs::read(dir.join("data/plmc/lib/org/example/lib/1.0/lib-1.0.jar")).unwrap(),
+        LIBRARY
+    );
+    assert!(!dir.join("config/plmc/config.json").exists());
+
+    // Stores with files known to be corrupt are not used in place
+    let sha1_path = minecraft.join("libraries/org/example/lib/1.0/lib-1.0.jar.sha1");
+    std::fs::write(&sha1_path, sha1(b"other")).unwrap();
+    let output = plmc(&["store", "adopt", "--in-place"])
+        .arg(&minecraft)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wrong hash"));
+    std::fs::remove_file(&sha1_path).unwrap();
+
+    let output = plmc(&["store", "adopt", "--in-place"])
+        .arg(&minecraft)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Only the corrupt client jar is downloaded
+    let output = plmc(&["run", "--dry-run", "--yes", "-v", "1.0", "--java", "java"])
+        .arg("--base-url")
+        .arg(&server.url)
+        .arg("--assets-url")
+        .arg(format!("{}/assets", server.url))
+        .arg("--mc-dir")
+        .arg(dir.join("game"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requested = server.take_requests();
+    assert!(requested.contains(&"/libraries/client.jar".to_string()));
+    assert!(!requested.contains(&"/libraries/lib-1.0.jar".to_string()));
+    assert!(
+        !requested.iter().any(|p| p.starts_with("/assets/")),
+        "{:?}",
+        requested
+    );
+    assert_eq!(
+        std::fs::read(
+            minecraft.join("libraries/com/mojang/minecraft/1.0/minecraft-1.0-client.jar")
+        )
+        .unwrap(),
+        CLIENT
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn export_import_state() {
+    let dir = temp_dir("state");
+    let plmc = |home: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .args(args)
+            .current_dir(&dir)
+            .env("XDG_DATA_HOME", dir.join(home).join("data"))
+            .env("XDG_CONFIG_HOME", dir.join(home).join("config"))
+            .output()
+            .unwrap()
+    };
+    let old = dir.join("old");
+    for (file, data) in [
+        ("config/plmc/config.json", "{}"),
+        ("config/plmc/accounts.json", "[]"),
+        ("data/plmc/instances/a/instance.json", r#"{"name": "a"}"#),
+        ("data/plmc/instances/a/mods.lock.json", "{}"),
+        ("data/plmc/instances/a/logs/latest.log", "log"),
+        ("data/plmc/lib/org/example/lib.jar", "jar"),
+    ] {
+        let path = old.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    let output = plmc("old", &["export-all", "-o", "state.zip"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.join("state.zip"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let output = plmc("new", &["import-all", "state.zip"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let new = dir.join("new");
+    assert!(new.join("config/plmc/config.json").is_file());
+    assert!(new.join("data/plmc/instances/a/mods.lock.json").is_file());
+    // Accounts were left out, as well as logs and the stores
+    assert!(!new.join("config/plmc/accounts.json").exists());
+    assert!(!new.join("data/plmc/instances/a/logs").exists());
+    assert!(!new.join("data/plmc/lib").exists());
+
+    // Importing again does not overwrite anything
+    let output = plmc("new", &["import-all", "state.zip"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    // Accounts are only exported when asked to
+    let output = plmc(
+        "old",
+        &["export-all", "--with-accounts", "-o", "accounts.zip"],
+    );
+    assert!(output.status.success());
+    let output = plmc("accounts", &["import-all", "accounts.zip"]);
+    assert!(output.status.success());
+    assert!(dir.join("accounts/config/plmc/accounts.json").is_file());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn instance_template() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("template");
+    let create = |template: &str| {
+        Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .args(["instance", "create", "--template", template, "--dir"])
+            .arg(dir.join(template))
+            .arg("--base-url")
+            .arg(&server.url)
+            .arg("--meta-dir")
+            .arg(dir.join("meta"))
+            .arg("--templates-url")
+            .arg(format!("{}/templates.json", server.url))
+            .arg("--modrinth-url")
+            .arg(format!("{}/modrinth", server.url))
+            .output()
+            .unwrap()
+    };
+
+    let output = create("modded");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let info: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("modded/instance.json")).unwrap()).unwrap();
+    assert_eq!(info["version"], "1.0");
+    assert_eq!(
+        info["components"],
+        json!([{"uid": "net.minecraft", "version": "1.0"}])
+    );
+    assert_eq!(
+        std::fs::read(dir.join("modded/mods/example-2.0.jar")).unwrap(),
+        MOD
+    );
+    assert!(dir.join("modded/mods/extra.jar").is_file());
+    assert_eq!(
+        std::fs::read(dir.join("modded/mods/addon.jar")).unwrap(),
+        ADDON
+    );
+
+    // Builtin templates resolve the latest release
+    let output = create("vanilla-latest");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("net.minecraft 1.0"));
+
+    // Existing instances are not overwritten
+    assert!(!create("modded").status.success());
+    assert!(!create("missing").status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn update_mods() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("mods");
+    std::fs::create_dir_all(dir.join("mods")).unwrap();
+    std::fs::write(dir.join("mods/example-1.0.jar"), OLD_MOD).unwrap();
+    std::fs::write(dir.join("mods/unknown.jar"), b"unknown").unwrap();
+    let mods = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .arg("mods")
+            .args(args)
+            .arg("--dir")
+            .arg(&dir)
+            .args(["--game-version", "1.0", "--loader", "fabric"])
+            .arg("--modrinth-url")
+            .arg(format!("{}/modrinth", server.url))
+            .env_remove("PLMC_CURSEFORGE_API_KEY")
+            .output()
+            .unwrap()
+    };
+
+    let output = mods(&["outdated"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("example-1.0.jar: 1.0 -> 2.0 (Modrinth)"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("unknown.jar"));
+
+    let output = mods(&["update", "--yes", "example"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!dir.join("mods/example-1.0.jar").exists());
+    assert_eq!(
+        std::fs::read(dir.join("mods/example-2.0.jar")).unwrap(),
+        MOD
+    );
+    assert!(dir.join("mods/unknown.jar").is_file());
+    assert!(!dir.join("mods/.staging").exists());
+
+    let output = mods(&["outdated"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("All 2 mods are up to date"));
+    let lock: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("mods.lock.json")).unwrap()).unwrap();
+    assert_eq!(lock["mods"][0]["versionId"], "example-2.0");
+
+    // Required mods are installed along, already installed ones are kept
+    let output = mods(&["add", "addon"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(dir.join("mods/addon-1.0.jar").is_file());
+    let lock: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("mods.lock.json")).unwrap()).unwrap();
+    assert_eq!(lock["mods"].as_array().unwrap().len(), 2);
+    assert_eq!(lock["mods"][1]["projectId"], "addon");
+    assert_eq!(lock["mods"][1]["dependency"], false);
+
+    let output = mods(&["add", "conflict"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("conflict is incompatible with example")
+    );
+
+    // Files are only written to the mods directory
+    let output = mods(&["add", "escape"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid file name \"../escape.jar\""));
+    assert!(!dir.join("escape.jar").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn clone_instance() {
+    let dir = temp_dir("clone");
+    let from = dir.join("original");
+    std::fs::create_dir_all(from.join("mods")).unwrap();
+    std::fs::create_dir_all(from.join("saves/world")).unwrap();
+    std::fs::write(from.join("mods/example-2.0.jar"), MOD).unwrap();
+    std::fs::write(from.join("saves/world/level.dat"), b"world").unwrap();
+    std::fs::write(
+        from.join("instance.json"),
+        json!({"name": "original", "version": "1.0", "playtime": 60}).to_string(),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args(["instance", "clone", "--name", "copy", "--mods"])
+        .arg(&from)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let to = dir.join("copy");
+    assert_eq!(std::fs::read(to.join("mods/example-2.0.jar")).unwrap(), MOD);
+    assert!(!to.join("saves").exists());
+    let info: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(to.join("instance.json")).unwrap()).unwrap();
+    assert_eq!(info["name"], "copy");
+    assert_eq!(info["playtime"], 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn list_instances_long() {
+    let dir = temp_dir("list");
+    for (name, info) in [
+        (
+            "played",
+            json!({
+                "name": "played",
+                "version": "1.0",
+                "lastPlayed": 1000,
+                "lastExitCode": 1,
+                "lastAccount": "steve",
+                "lastVersion": "0.9",
+                "playtime": 3600,
+            }),
+        ),
+        ("new", json!({"name": "new", "version": "1.0"})),
+    ] {
+        std::fs::create_dir_all(dir.join(name)).unwrap();
+        std::fs::write(dir.join(name).join("instance.json"), info.to_string()).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args([
+            "instance",
+            "list",
+            "--long",
+            "--sort",
+            "last-played",
+            "--root",
+        ])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("played\n"), "{}", stdout);
+    assert!(stdout.contains("last version: 0.9"), "{}", stdout);
+    assert!(stdout.contains("last account: steve"), "{}", stdout);
+    assert!(stdout.contains("exit code:    1"), "{}", stdout);
+    assert!(stdout.contains("new\n"), "{}", stdout);
+    assert!(stdout.contains("last played:  never"), "{}", stdout);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn session_limit() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("session-limit");
+    let game = dir.join("game");
+    std::fs::create_dir_all(&game).unwrap();
+    let today = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / (24 * 60 * 60);
+    std::fs::write(
+        game.join("instance.json"),
+        json!({
+            "name": "limited",
+            "version": "1.0",
+            "playtimeDay": today,
+            "playtimeToday": 3600,
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let plmc = |args: &[&str], path: &Path| {
+        let output = Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .args(args)
+            .arg(path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    plmc(&["instance", "set", "--limit-per-day", "90"], &game);
+    let stdout = plmc(&["instance", "list", "--long", "--root"], &dir);
+    assert!(stdout.contains("time left:    30 minutes"), "{}", stdout);
+
+    // The day's time is used up, the game is not started
+    plmc(&["instance", "set", "--limit-per-day", "60"], &game);
+    let mut command = dry_run_command(&server, &dir);
+    let args: Vec<_> = command
+        .get_args()
+        .filter(|arg| *arg != "--dry-run")
+        .map(ToOwned::to_owned)
+        .collect();
+    command = Command::new(command.get_program());
+    let output = command
+        .args(args)
+        .env_remove("PLMC_META_INDEX_SHA256")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no play time left today"), "{}", stderr);
+
+    plmc(&["instance", "set", "--limit-per-day", "0"], &game);
+    let info = std::fs::read_to_string(game.join("instance.json")).unwrap();
+    assert!(!info.contains("sessionLimit"), "{}", info);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn log_forwarding() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("log-forwarding");
+    let game = dir.join("game");
+    std::fs::create_dir_all(&game).unwrap();
+    std::fs::write(
+        game.join("instance.json"),
+        json!({"name": "headless", "version": "1.0"}).to_string(),
+    )
+    .unwrap();
+    let syslog = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    syslog
+        .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+        .unwrap();
+
+    let set = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args(["instance", "set", "--log-syslog"])
+        .arg(syslog.local_addr().unwrap().to_string())
+        .arg(&game)
+        .output()
+        .unwrap();
+    assert!(set.status.success());
+    let info = std::fs::read_to_string(game.join("instance.json")).unwrap();
+    assert!(info.contains(r#""type": "syslog""#), "{}", info);
+
+    // Stands in for java, the output of a server without a trailing line break
+    let output = run_command(
+        &server,
+        &dir,
+        "echo '[12:00:00] [Server thread/INFO]: Done (1.0s)!'\n\
+         printf '[12:00:01] [Server thread/WARN]: Stopping' >&2\n",
+    )
+    .output()
+    .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut messages = Vec::new();
+    let mut buf = [0; 4096];
+    while messages.len() < 4 {
+        let read = syslog.recv(&mut buf).unwrap();
+        messages.push(String::from_utf8_lossy(&buf[..read]).into_owned());
+    }
+    assert!(messages[0].starts_with("<14>1 "), "{:?}", messages);
+    assert!(messages[0].contains(" headless - launcher - Starting Minecraft 1.0"));
+    let stdout = messages.iter().find(|m| m.contains(" stdout ")).unwrap();
+    assert!(stdout.starts_with("<14>1 ") && stdout.ends_with("Done (1.0s)!"));
+    let stderr = messages.iter().find(|m| m.contains(" stderr ")).unwrap();
+    assert!(stderr.starts_with("<12>1 ") && stderr.ends_with("Stopping"));
+    assert!(messages[3].ends_with("Game exited with code 0"));
+
+    let set = Command::new(env!("CARGO_BIN_EXE_plmc"))
+        .args(["instance", "set", "--no-log-forwarding"])
+        .arg(&game)
+        .output()
+        .unwrap();
+    assert!(set.status.success());
+    let info = std::fs::read_to_string(game.join("instance.json")).unwrap();
+    assert!(!info.contains("logForwarding"), "{}", info);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn main_jar_pin() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("main-jar-pin");
+
+    let output = run_command(&server, &dir, "exit 0\n").output().unwrap();
+    assert!(output.status.success());
+    let info = std::fs::read_to_string(dir.join("game/instance.json")).unwrap();
+    assert!(info.contains(&sha1(CLIENT)), "{}", info);
+
+    // A corrupted client jar is found even without verifying the libraries
+    let client = dir.join("lib/com/mojang/minecraft/1.0/minecraft-1.0-client.jar");
+    std::fs::write(&client, b"client JAR").unwrap();
+    let output = run_command(&server, &dir, "exit 0\n")
+        .arg("--skip-verify")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("does not match"), "{}", stderr);
+
+    // Verifying downloads it again
+    let output = run_command(&server, &dir, "exit 0\n").output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(std::fs::read(&client).unwrap(), CLIENT);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn asset_index_diff() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("asset-diff");
+    let info_path = dir.join("game/instance.json");
+    let read_info = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(&info_path).unwrap()).unwrap()
+    };
+
+    // A dry run leaves the instance as it is
+    assert!(dry_run(&server, &dir).status.success());
+    assert!(read_info().get("assetIndex").is_none());
+    assert!(read_info().get("mainJarPin").is_none());
+
+    let output = run_command(&server, &dir, "exit 0\n").output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut info = read_info();
+    assert_eq!(info["assetIndex"]["id"], "1.0");
+    assert_eq!(info["assetIndex"]["verified"], true);
+
+    // As if the instance was resolved with the same assets under another id before
+    std::fs::copy(
+        dir.join("assets/indexes/1.0.json"),
+        dir.join("assets/indexes/0.9.json"),
+    )
+    .unwrap();
+    info["assetIndex"]["id"] = json!("0.9");
+    std::fs::write(&info_path, info.to_string()).unwrap();
+
+    let output = run_command(&server, &dir, "exit 0\n").output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(
+        stdout.contains("Assets of net.minecraft changed: 1 of 1 objects reused"),
+        "{}",
+        stdout
+    );
+    assert_eq!(read_info()["assetIndex"]["id"], "1.0");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn unknown_package() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("unknown-package");
+
+    let output = dry_run_command(&server, &dir)
+        .args(["--uid", "org.unknown"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Unknown package: org.unknown"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("plmc meta search"), "{}", stderr);
+
+    let dry_run = dry_run_command(&server, &dir);
+    let output = Command::new(dry_run.get_program())
+        .args(dry_run.get_args().map(|arg| match arg.to_str().unwrap() {
+            "1.0" => "9.9".as_ref(),
+            _ => arg,
+        }))
+        .env_remove("PLMC_META_INDEX_SHA256")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Unknown version 9.9 of net.minecraft"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("does not have this version"), "{}", stderr);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn debug_bundle() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("debug-bundle");
+    let bundle = dir.join("bundle.zip");
+    let debug_bundle = || {
+        let dry_run = dry_run_command(&server, &dir);
+        let mut command = Command::new(dry_run.get_program());
+        command
+            .arg("debug-bundle")
+            .args(
+                dry_run
+                    .get_args()
+                    .filter(|arg| !["run", "--dry-run"].contains(&arg.to_str().unwrap())),
+            )
+            .arg("--output")
+            .arg(&bundle)
+            .env_remove("PLMC_META_INDEX_SHA256");
+        command.output().unwrap()
+    };
+
+    // Nothing is downloaded to collect the bundle
+    let output = debug_bundle();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not cached"), "{}", stderr);
+    assert!(server.take_requests().is_empty());
+
+    assert!(dry_run(&server, &dir).status.success());
+    server.take_requests();
+    std::fs::remove_file(dir.join("lib/org/example/lib/1.0/lib-1.0.jar")).unwrap();
+    let output = debug_bundle();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(std::fs::read(&bundle).unwrap().starts_with(b"PK"));
+    assert!(server.take_requests().is_empty());
+    assert!(!dir.join("lib/org/example/lib/1.0/lib-1.0.jar").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn maven_library_hash() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("maven-hash");
+
+    assert!(dry_run(&server, &dir).status.success());
+    let jar = dir.join("lib").join(MAVEN_PATH);
+    let mut sidecar = jar.clone().into_os_string();
+    sidecar.push(".sha1");
+    assert_eq!(
+        std::fs::read_to_string(&sidecar).unwrap(),
+        sha1(MAVEN_LIBRARY)
+    );
+    server.take_requests();
+
+    // The cached jar is checked against the hash of the repository, not only its size
+    std::fs::write(&jar, b"maven JAR").unwrap();
+    assert!(dry_run(&server, &dir).status.success());
+    assert!(server
+        .take_requests()
+        .contains(&format!("/maven/{}", MAVEN_PATH)));
+    assert_eq!(std::fs::read(&jar).unwrap(), MAVEN_LIBRARY);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = FixtureServer::start();
+    let dir = temp_dir("doctor");
+    assert!(dry_run(&server, &dir).status.success());
+    let java = dir.join("java");
+    std::fs::write(&java, "#!/bin/sh\necho 'openjdk version \"17.0.2\"' >&2").unwrap();
+    std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let config = dir.join("config/plmc/config.json");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    let doctor = |java: &Path, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_plmc"))
+            .arg("doctor")
+            .args(args)
+            .arg("--java")
+            .arg(java)
+            .arg("--assets-url")
+            .arg(format!("{}/assets", server.url))
+            .arg("--meta-dir")
+            .arg(dir.join("meta"))
+            .arg("--lib-dir")
+            .arg(dir.join("lib"))
+            .arg("--assets-dir")
+            .arg(dir.join("assets"))
+            .arg("--mc-dir")
+            .arg(dir.join("game"))
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env("XDG_DATA_HOME", dir.join("data"))
+            .env_remove("PLMC_BASE_URL")
+            .output()
+            .unwrap()
+    };
+
+    // Nothing listens on port 1, one reachable server is enough
+    std::fs::write(
+        &config,
+        json!({"metaServers": ["http://127.0.0.1:1", server.url], "metaServer": ""}).to_string(),
+    )
+    .unwrap();
+    let output = doctor(&java, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains(&format!("ok Meta server {} is reachable", server.url)));
+    assert!(stdout.contains("warn Could not connect to http://127.0.0.1:1"));
+    assert!(stdout.contains("ok Assets server"));
+    assert!(stdout.contains("unknown keys: metaServer"), "{}", stdout);
+    assert!(stdout.contains("ok Java: openjdk version \"17.0.2\""));
+    assert!(stdout.contains("ok Meta data"));
+    assert!(stdout.contains("ok Assets in"));
+
+    // Broken caches are only warnings, they are downloaded again
+    std::fs::write(dir.join("meta/net.minecraft/1.0.json"), "{").unwrap();
+    std::fs::write(dir.join("assets/objects/stray"), "").unwrap();
+    std::fs::write(dir.join("lib/client.jar.1.part"), "").unwrap();
+    let output = doctor(&java, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("warn Invalid meta data") && stdout.contains("1.0.json"));
+    assert!(stdout.contains("warn Invalid assets") && stdout.contains("stray"));
+    assert!(stdout.contains("warn Files of interrupted downloads"));
+
+    // Critical problems fail
+    std::fs::write(&config, json!({"metaServers": 1}).to_string()).unwrap();
+    let output = doctor(&dir.join("missing-java"), &["--base-url", &server.url]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("fail Config"));
+    assert!(stdout.contains("Fix or remove"));
+    assert!(stdout.contains("Could not run"));
+
+    std::fs::write(
+        &config,
+        json!({"metaServers": ["http://127.0.0.1:1"]}).to_string(),
+    )
+    .unwrap();
+    let output = doctor(&java, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("fail Could not connect"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn run_raw() {
+    let server = FixtureServer::start();
+    let dir = temp_dir("run-raw");
+    let run_raw = || {
+        let dry_run = dry_run_command(&server, &dir);
+        let args: Vec<_> = dry_run.get_args().collect();
+        let version = args.iter().position(|arg| *arg == "-v").unwrap();
+        Command::new(dry_run.get_program())
+            .arg("run-raw")
+            .args(&args[1..version])
+            .args(&args[version + 2..])
+            .env_remove("PLMC_META_INDEX_SHA256")
+            .output()
+            .unwrap()
+    };
+
+    let output = run_raw();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No valid instance"));
+
+    // The version is taken from the instance
+    assert!(dry_run(&server, &dir).status.success());
+    let output = run_raw();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--assetIndex\n  1.0\n"), "{}", stdout);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,269 @@
+//! Reusing the libraries and assets other launchers downloaded already.
+//!
+//! The vanilla launcher, MultiMC and its forks keep libraries in the maven layout and assets in
+//! `objects` by their hash, like the stores of the library. [`ForeignStores::scan`] checks which
+//! of their files can be used, frontends then point their stores at them or copy them with
+//! [`ForeignStores::adopt_into`].
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use tracing::*;
+
+use crate::hash::{sha1_file, sha1_sidecar};
+use crate::meta::manifest::LOG_CONFIGS_DIR;
+use crate::{Error, Result};
+
+/// Parts of the assets directory which are shared, the rest is specific to the launcher.
+const ASSET_DIRS: &[&str] = &["objects", "indexes", LOG_CONFIGS_DIR];
+
+/// Launcher the stores belong to, detected from its files.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LauncherKind {
+    /// The `.minecraft` directory of the vanilla launcher.
+    Vanilla,
+    MultiMc,
+    PolyMc,
+    PrismLauncher,
+    /// A directory with `libraries` and `assets` of an unknown launcher.
+    Unknown,
+}
+
+impl Display for LauncherKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Vanilla => "vanilla launcher",
+            Self::MultiMc => "MultiMC",
+            Self::PolyMc => "PolyMC",
+            Self::PrismLauncher => "Prism Launcher",
+            Self::Unknown => "unknown launcher",
+        })
+    }
+}
+
+/// Files of another launcher which can be reused, found by [`ForeignStores::scan`].
+#[derive(Debug, Clone, Default)]
+pub struct StoreScan {
+    /// Libraries, checked against the `.sha1` file next to them if there is one. The others are
+    /// verified against the meta data once they are used.
+    pub libraries: usize,
+    pub library_bytes: u64,
+    /// Asset objects whose content matches the hash they are named after.
+    pub assets: usize,
+    pub asset_bytes: u64,
+    /// Asset indexes and logging configurations, verified once they are used.
+    pub asset_metadata: usize,
+    /// Files with the wrong hash, which are never adopted.
+    pub corrupt: Vec<PathBuf>,
+    /// All reusable files.
+    files: Vec<PathBuf>,
+}
+
+/// Library and assets directory of another launcher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignStores {
+    pub kind: LauncherKind,
+    pub libraries_dir: PathBuf,
+    pub assets_dir: PathBuf,
+}
+
+impl ForeignStores {
+    /// Find the stores in the data directory `dir` of another launcher.
+    ///
+    /// Fails with [`Error::NoForeignStores`] if it has neither libraries nor asset objects.
+    pub fn detect<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let kind = if dir.join("launcher_profiles.json").is_file() {
+            LauncherKind::Vanilla
+        } else if dir.join("prismlauncher.cfg").is_file() {
+            LauncherKind::PrismLauncher
+        } else if dir.join("polymc.cfg").is_file() {
+            LauncherKind::PolyMc
+        } else if dir.join("multimc.cfg").is_file() {
+            LauncherKind::MultiMc
+        } else {
+            LauncherKind::Unknown
+        };
+        let ret = Self {
+            kind,
+            libraries_dir: dir.join("libraries"),
+            assets_dir: dir.join("assets"),
+        };
+        if !ret.libraries_dir.is_dir() && !ret.assets_dir.join("objects").is_dir() {
+            return Err(Error::NoForeignStores(dir.display().to_string()));
+        }
+
+        Ok(ret)
+    }
+
+    /// Check the files of the stores, hashing the asset objects and libraries with a `.sha1` file.
+    pub fn scan(&self) -> Result<StoreScan> {
+        let mut scan = StoreScan::default();
+        for path in files(&self.libraries_dir)? {
+            if path.extension() == Some("sha1".as_ref()) {
+                continue;
+            }
+            let expected = std::fs::read_to_string(sha1_sidecar(&path)).ok();
+            match expected
+                .as_deref()
+                .and_then(|e| e.split_whitespace().next())
+            {
+                Some(expected) if sha1_file(&path)?.to_string() != expected.to_lowercase() => {
+                    scan.corrupt.push(path);
+                }
+                _ => {
+                    scan.libraries += 1;
+                    scan.library_bytes += path.metadata()?.len();
+                    scan.files.push(path);
+                }
+            }
+        }
+
+        for dir in ASSET_DIRS {
+            for path in files(&self.assets_dir.join(dir))? {
+                if *dir != "objects" {
+                    scan.asset_metadata += 1;
+                    scan.files.push(path);
+                    continue;
+                }
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if name.len() != 40 || !name.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    trace!("skipping {}, it is no asset object", path.display());
+                    continue;
+                }
+                if sha1_file(&path)?.to_string() == name.to_lowercase() {
+                    scan.assets += 1;
+                    scan.asset_bytes += path.metadata()?.len();
+                    scan.files.push(path);
+                } else {
+                    scan.corrupt.push(path);
+                }
+            }
+        }
+
+        Ok(scan)
+    }
+
+    /// Copy the reusable files of `scan` into the stores at `libraries_dir` and `assets_dir`,
+    /// keeping the files there already.
+    ///
+    /// With `hardlink` the files are linked instead, falling back to a copy on file systems
+    /// without hardlinks. Returns the number of files added.
+    pub fn adopt_into<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        scan: &StoreScan,
+        libraries_dir: P,
+        assets_dir: Q,
+        hardlink: bool,
+    ) -> Result<usize> {
+        let mut added = 0;
+        for path in &scan.files {
+            let to = match path.strip_prefix(&self.libraries_dir) {
+                Ok(relative) => libraries_dir.as_ref().join(relative),
+                Err(_) => match path.strip_prefix(&self.assets_dir) {
+                    Ok(relative) => assets_dir.as_ref().join(relative),
+                    Err(_) => continue,
+                },
+            };
+            if to.exists() {
+                continue;
+            }
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if hardlink {
+                match std::fs::hard_link(path, &to) {
+                    Ok(()) => {
+                        added += 1;
+                        continue;
+                    }
+                    Err(e) => trace!("copying {}, hardlink failed: {}", path.display(), e),
+                }
+            }
+            std::fs::copy(path, &to)?;
+            added += 1;
+        }
+
+        Ok(added)
+    }
+}
+
+/// All files in `dir` and its subdirectories, none if it does not exist.
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut ret = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ret),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            ret.extend(files(&entry.path())?);
+        } else {
+            ret.push(entry.path());
+        }
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn scan_and_adopt() {
+        let dir = TempDir::new("adopt");
+        let minecraft = dir.join(".minecraft");
+        let asset = b"asset";
+        let asset_hash = hex::encode(ring::digest::digest(
+            &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+            asset,
+        ));
+        let bad_hash = "0000000000000000000000000000000000000000";
+        for (file, data) in [
+            ("launcher_profiles.json", &b"{}"[..]),
+            ("libraries/org/example/lib/1.0/lib-1.0.jar", b"jar"),
+            ("libraries/org/example/bad/1.0/bad-1.0.jar", b"jar"),
+            (
+                "libraries/org/example/bad/1.0/bad-1.0.jar.sha1",
+                bad_hash.as_bytes(),
+            ),
+            ("assets/indexes/1.18.json", b"{}"),
+            (
+                &format!("assets/objects/{}/{}", &asset_hash[..2], asset_hash),
+                asset,
+            ),
+            (&format!("assets/objects/00/{}", bad_hash), asset),
+            ("assets/skins/skin.png", b"skin"),
+        ] {
+            let path = minecraft.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+
+        let stores = ForeignStores::detect(&minecraft).unwrap();
+        assert_eq!(stores.kind, LauncherKind::Vanilla);
+        let scan = stores.scan().unwrap();
+        assert_eq!(scan.libraries, 1);
+        assert_eq!(scan.assets, 1);
+        assert_eq!(scan.asset_bytes, asset.len() as u64);
+        assert_eq!(scan.asset_metadata, 1);
+        assert_eq!(scan.corrupt.len(), 2);
+
+        let (lib, assets) = (dir.join("lib"), dir.join("assets"));
+        assert_eq!(stores.adopt_into(&scan, &lib, &assets, true).unwrap(), 3);
+        assert!(lib.join("org/example/lib/1.0/lib-1.0.jar").is_file());
+        assert!(!lib.join("org/example/bad").exists());
+        assert!(!assets.join("skins").exists());
+        assert_eq!(stores.adopt_into(&scan, &lib, &assets, true).unwrap(), 0);
+
+        assert!(matches!(
+            ForeignStores::detect(dir.join("missing")),
+            Err(Error::NoForeignStores(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::{Debug, Formatter};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use super::{Auth, AuthProfile, REDACTED};
+use crate::Result;
+
+/// Tokens are refreshed this long before they expire, so they stay valid while starting the game.
+pub const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Tokens and expiry of an account, kept to avoid logging in on every launch.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenCache {
+    /// Expiry of the access token in seconds since the unix epoch.
+    pub expires_at: Option<u64>,
+    pub refresh_token: Option<String>,
+    pub xsts_token: Option<String>,
+    pub user_hash: Option<String>,
+}
+
+impl Debug for TokenCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCache")
+            .field("expires_at", &self.expires_at)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| REDACTED),
+            )
+            .field("xsts_token", &self.xsts_token.as_ref().map(|_| REDACTED))
+            .field("user_hash", &self.user_hash)
+            .finish()
+    }
+}
+
+impl TokenCache {
+    /// Set the expiry from the `expires_in` seconds of a token response.
+    pub fn set_expires_in(&mut self, expires_in: u64) {
+        self.expires_at = Some(unix_time(SystemTime::now()) + expires_in);
+    }
+
+    /// True if the access token expires within [`REFRESH_MARGIN`] of `now`.
+    ///
+    /// Tokens without a known expiry always need a refresh.
+    pub fn needs_refresh(&self, now: SystemTime) -> bool {
+        match self.expires_at {
+            Some(expires_at) => unix_time(now + REFRESH_MARGIN) >= expires_at,
+            None => true,
+        }
+    }
+}
+
+impl AuthProfile {
+    /// Refresh the tokens with `refresh` if they are about to expire.
+    ///
+    /// The decision is made locally from the cached expiry, so no request has to be sent if the
+    /// token is still valid. Offline accounts are never refreshed. Returns true if `refresh` was
+    /// called.
+    pub fn refresh_if_needed<F>(&mut self, refresh: F) -> Result<bool>
+    where
+        F: FnOnce(&AuthProfile) -> Result<(Auth, TokenCache)>,
+    {
+        if matches!(self.auth, Auth::Offline { .. }) || !self.cache.needs_refresh(SystemTime::now())
+        {
+            return Ok(false);
+        }
+
+        debug!("refreshing tokens of {}", self.name);
+        let (auth, cache) = refresh(self)?;
+        self.auth = auth;
+        self.cache = cache;
+        Ok(true)
+    }
+
+    /// Load profiles saved by [`AuthProfile::save_all`].
+    pub fn load_all<P: AsRef<Path>>(path: P) -> Result<Vec<AuthProfile>> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Save profiles with their tokens, only readable by the current user.
+    pub fn save_all<P: AsRef<Path>>(profiles: &[AuthProfile], path: P) -> Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(path)?;
+        serde_json::to_writer_pretty(&mut file, profiles)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::GameProfile;
+
+    #[test]
+    fn refresh_if_needed() {
+        let mut profile = AuthProfile {
+            name: "Steve".to_string(),
+            uuid: Some("0123".to_string()),
+            auth: Auth::new_msft(
+                "old",
+                GameProfile {
+                    id: "0123".to_string(),
+                    name: "Steve".to_string(),
+                },
+            ),
+            cache: TokenCache::default(),
+        };
+        profile.cache.set_expires_in(3600);
+
+        let refreshed = profile
+            .refresh_if_needed(|_| panic!("token is still valid"))
+            .unwrap();
+        assert!(!refreshed);
+
+        profile.cache.set_expires_in(60);
+        let refreshed = profile
+            .refresh_if_needed(|profile| {
+                let mut auth = profile.auth.clone();
+                if let Auth::MSFT { token, .. } = &mut auth {
+                    *token = "new".to_string();
+                }
+                let mut cache = TokenCache::default();
+                cache.set_expires_in(3600);
+                Ok((auth, cache))
+            })
+            .unwrap();
+        assert!(refreshed);
+        assert_eq!(profile.auth.get_token(), Some("new"));
+        assert!(!profile.cache.needs_refresh(SystemTime::now()));
+    }
+}
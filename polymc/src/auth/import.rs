@@ -0,0 +1,255 @@
+//! Import accounts from other launchers.
+//!
+//! Only the access tokens are imported, refreshing them is up to the launcher that owns them.
+//! Entries without a usable token or profile are skipped.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::*;
+
+use super::{Auth, AuthProfile, GameProfile};
+use crate::Result;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VanillaAccounts {
+    #[serde(default)]
+    accounts: HashMap<String, VanillaAccount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VanillaAccount {
+    access_token: Option<String>,
+    #[serde(rename = "type")]
+    account_type: Option<String>,
+    minecraft_profile: Option<Profile>,
+}
+
+#[derive(Deserialize)]
+struct MultiMcAccounts {
+    #[serde(default)]
+    accounts: Vec<MultiMcAccount>,
+}
+
+#[derive(Deserialize)]
+struct MultiMcAccount {
+    #[serde(rename = "type")]
+    account_type: String,
+    ygg: Option<MultiMcToken>,
+    profile: Option<Profile>,
+}
+
+#[derive(Deserialize)]
+struct MultiMcToken {
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    id: Option<String>,
+    name: String,
+}
+
+/// Import the accounts of the vanilla launcher from `launcher_accounts.json`.
+pub fn import_vanilla_accounts<P: AsRef<Path>>(path: P) -> Result<Vec<AuthProfile>> {
+    parse_vanilla_accounts(&std::fs::read(path)?)
+}
+
+/// Parse the content of the vanilla launcher's `launcher_accounts.json`.
+pub fn parse_vanilla_accounts(data: &[u8]) -> Result<Vec<AuthProfile>> {
+    let accounts: VanillaAccounts = serde_json::from_slice(data)?;
+
+    let mut ret: Vec<AuthProfile> = accounts
+        .accounts
+        .into_iter()
+        .filter_map(|(id, account)| {
+            let (profile, token) = match (account.minecraft_profile, account.access_token) {
+                (Some(profile), Some(token)) => (profile, token),
+                _ => {
+                    debug!("Skipping vanilla account {} without profile", id);
+                    return None;
+                }
+            };
+
+            let auth = match account.account_type.as_deref() {
+                Some("Xbox") => Auth::MSFT {
+                    token,
+                    profile: GameProfile {
+                        id: profile.id.clone().unwrap_or_default(),
+                        name: profile.name.clone(),
+                    },
+                },
+                // The username of the account is the email address used to log in
+                _ => Auth::Mojang {
+                    username: profile.name.clone(),
+                    token,
+                },
+            };
+
+            Some(AuthProfile {
+                name: profile.name,
+                uuid: profile.id,
+                auth,
+                cache: Default::default(),
+            })
+        })
+        .collect();
+
+    // accounts are stored in a map, keep the order stable
+    ret.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(ret)
+}
+
+/// Import the accounts of MultiMC or one of its forks from `accounts.json`.
+pub fn import_multimc_accounts<P: AsRef<Path>>(path: P) -> Result<Vec<AuthProfile>> {
+    parse_multimc_accounts(&std::fs::read(path)?)
+}
+
+/// Parse the content of MultiMC's `accounts.json`.
+pub fn parse_multimc_accounts(data: &[u8]) -> Result<Vec<AuthProfile>> {
+    let accounts: MultiMcAccounts = serde_json::from_slice(data)?;
+
+    Ok(accounts
+        .accounts
+        .into_iter()
+        .filter_map(|account| {
+            let profile = account.profile?;
+            let token = account.ygg.and_then(|ygg| ygg.token);
+
+            let auth = match (account.account_type.as_str(), token) {
+                ("Offline", _) => Auth::new_offline(&profile.name),
+                ("MSA", Some(token)) => Auth::MSFT {
+                    token,
+                    profile: GameProfile {
+                        id: profile.id.clone().unwrap_or_default(),
+                        name: profile.name.clone(),
+                    },
+                },
+                ("Mojang", Some(token)) => Auth::Mojang {
+                    username: profile.name.clone(),
+                    token,
+                },
+                (account_type, _) => {
+                    debug!(
+                        "Skipping {} account {} without token",
+                        account_type, profile.name
+                    );
+                    return None;
+                }
+            };
+
+            Some(AuthProfile {
+                name: profile.name,
+                uuid: profile.id,
+                auth,
+                cache: Default::default(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vanilla_accounts() {
+        // As written by the vanilla launcher, with an account owning no game
+        let data = br#"{
+            "accounts": {
+                "9a8a1e1a5b3c4d2e8f7a6b5c4d3e2f1a": {
+                    "accessToken": "xbox-token",
+                    "accessTokenExpiresAt": "2022-03-01T12:00:00.000000Z",
+                    "avatar": "",
+                    "eligibleForMigration": false,
+                    "hasMultipleProfiles": false,
+                    "legacy": false,
+                    "localId": "9a8a1e1a5b3c4d2e8f7a6b5c4d3e2f1a",
+                    "minecraftProfile": {
+                        "id": "069a79f444e94726a5befca90e38aaf5",
+                        "name": "Steve"
+                    },
+                    "persistent": true,
+                    "remoteId": "00034001a2b3c4d5",
+                    "type": "Xbox",
+                    "userProperites": [],
+                    "username": "steve@example.com"
+                },
+                "1f2e3d4c5b6a79881f2e3d4c5b6a7988": {
+                    "accessToken": "mojang-token",
+                    "accessTokenExpiresAt": "2022-03-01T12:00:00.000000Z",
+                    "avatar": "",
+                    "eligibleForMigration": true,
+                    "hasMultipleProfiles": false,
+                    "legacy": false,
+                    "localId": "1f2e3d4c5b6a79881f2e3d4c5b6a7988",
+                    "minecraftProfile": {
+                        "id": "853c80ef3c3749fdaa49938b674adae6",
+                        "name": "Alex"
+                    },
+                    "persistent": true,
+                    "remoteId": "5e4d3c2b1a09f8e7d6c5b4a39281706f",
+                    "type": "Mojang",
+                    "userProperites": [],
+                    "username": "alex@example.com"
+                },
+                "0a1b2c3d4e5f60718293a4b5c6d7e8f9": {
+                    "accessToken": "demo-token",
+                    "avatar": "",
+                    "localId": "0a1b2c3d4e5f60718293a4b5c6d7e8f9",
+                    "persistent": true,
+                    "remoteId": "00034001f6e5d4c3",
+                    "type": "Xbox",
+                    "userProperites": [],
+                    "username": "demo@example.com"
+                }
+            },
+            "activeAccountLocalId": "9a8a1e1a5b3c4d2e8f7a6b5c4d3e2f1a",
+            "mojangClientToken": "c0ffee00c0ffee00c0ffee00c0ffee00"
+        }"#;
+
+        let profiles = parse_vanilla_accounts(data).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "Alex");
+        assert!(matches!(profiles[0].auth, Auth::Mojang { .. }));
+        assert_eq!(profiles[0].auth.get_username(), "Alex");
+        assert_eq!(profiles[0].auth.get_token(), Some("mojang-token"));
+        assert_eq!(profiles[1].name, "Steve");
+        assert_eq!(
+            profiles[1].uuid.as_deref(),
+            Some("069a79f444e94726a5befca90e38aaf5")
+        );
+        assert_eq!(
+            profiles[1].auth.get_uuid(),
+            Some("069a79f444e94726a5befca90e38aaf5")
+        );
+        assert_eq!(profiles[1].auth.get_username(), "Steve");
+        assert_eq!(profiles[1].auth.get_token(), Some("xbox-token"));
+    }
+
+    #[test]
+    fn multimc_accounts() {
+        let data = br#"{
+            "formatVersion": 3,
+            "accounts": [
+                {
+                    "type": "MSA",
+                    "ygg": { "token": "msa-token" },
+                    "profile": { "id": "0123", "name": "Steve" }
+                },
+                { "type": "Offline", "profile": { "name": "Alex" } },
+                { "type": "MSA", "ygg": {} }
+            ]
+        }"#;
+
+        let profiles = parse_multimc_accounts(data).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "Steve");
+        assert_eq!(profiles[0].uuid.as_deref(), Some("0123"));
+        assert_eq!(profiles[0].auth.get_token(), Some("msa-token"));
+        assert_eq!(profiles[1].auth.get_username(), "Alex");
+    }
+}
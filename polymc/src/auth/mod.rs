@@ -0,0 +1,348 @@
+// use HTTP for logging in?
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::{Debug, Formatter};
+
+mod cache;
+mod import;
+mod profile;
+mod username;
+mod xbox;
+
+pub use cache::*;
+pub use import::*;
+pub use profile::*;
+pub use username::*;
+pub use xbox::*;
+
+/// Placeholder printed instead of secrets in debug output.
+const REDACTED: &str = "<redacted>";
+
+pub enum LoginRequest {
+    Mojang {
+        username: String,
+        password: String,
+    },
+    Msft {
+        client_id: String,
+        redirect_uri: String,
+        state: String,
+    },
+    MsftToken {
+        client_id: String,
+        client_secret: String,
+        code: String,
+        redirect_uri: String,
+    },
+    MsftRefresh {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+        redirect_uri: String,
+    },
+    /// Authenticate with Xbox Live using a Microsoft access token.
+    XboxUser {
+        access_token: String,
+    },
+    /// Authorize the Xbox Live token for Minecraft, see [`parse_xbox_response`].
+    Xsts {
+        xbl_token: String,
+    },
+}
+
+impl Debug for LoginRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoginRequest::Mojang { username, .. } => f
+                .debug_struct("Mojang")
+                .field("username", username)
+                .field("password", &REDACTED)
+                .finish(),
+            LoginRequest::Msft {
+                client_id,
+                redirect_uri,
+                state,
+            } => f
+                .debug_struct("Msft")
+                .field("client_id", client_id)
+                .field("redirect_uri", redirect_uri)
+                .field("state", state)
+                .finish(),
+            LoginRequest::MsftToken {
+                client_id,
+                redirect_uri,
+                ..
+            } => f
+                .debug_struct("MsftToken")
+                .field("client_id", client_id)
+                .field("client_secret", &REDACTED)
+                .field("code", &REDACTED)
+                .field("redirect_uri", redirect_uri)
+                .finish(),
+            LoginRequest::MsftRefresh {
+                client_id,
+                redirect_uri,
+                ..
+            } => f
+                .debug_struct("MsftRefresh")
+                .field("client_id", client_id)
+                .field("client_secret", &REDACTED)
+                .field("refresh_token", &REDACTED)
+                .field("redirect_uri", redirect_uri)
+                .finish(),
+            LoginRequest::XboxUser { .. } => f
+                .debug_struct("XboxUser")
+                .field("access_token", &REDACTED)
+                .finish(),
+            LoginRequest::Xsts { .. } => f
+                .debug_struct("Xsts")
+                .field("xbl_token", &REDACTED)
+                .finish(),
+        }
+    }
+}
+
+impl LoginRequest {
+    // login requests come in 2 types: mojang and msft
+    // msft uses HTTP options to input data because OAuth
+    // mojang sends a POST request with JSON data
+
+    pub fn new_login(&self) -> String {
+        match self {
+            LoginRequest::Mojang { username, password } => {
+                let data = json!({
+                    "agent": {
+                        "name": "Minecraft",
+                        "version": 1
+                    },
+                    "username": username,
+                    "password": password,
+                });
+                data.to_string()
+            }
+            LoginRequest::Msft {
+                client_id,
+                redirect_uri,
+                state,
+            } => {
+                let mut opts = Vec::<String>::new();
+                opts.push(format!("client_id={}", client_id));
+                opts.push("response_type=code".to_string());
+                opts.push(format!("redirect_uri={}", redirect_uri));
+                opts.push("scope=XboxLive.signin%20offline_access".to_string());
+                opts.push(format!("state={}", state));
+                opts.join("&")
+            }
+            LoginRequest::MsftToken {
+                client_id,
+                client_secret,
+                code,
+                redirect_uri,
+            } => {
+                let mut opts = Vec::<String>::new();
+                opts.push(format!("client_id={}", client_id));
+                opts.push(format!("client_secret={}", client_secret));
+                opts.push(format!("code={}", code));
+                opts.push(format!("redirect_uri={}", redirect_uri));
+                opts.push("grant_type=authorization_code".to_string());
+                opts.join("&")
+            }
+            LoginRequest::MsftRefresh {
+                client_id,
+                client_secret,
+                refresh_token,
+                redirect_uri,
+            } => {
+                let mut opts = Vec::<String>::new();
+                opts.push(format!("client_id={}", client_id));
+                opts.push(format!("client_secret={}", client_secret));
+                opts.push(format!("refresh_token={}", refresh_token));
+                opts.push("grant_type=refresh_token".to_string());
+                opts.push(format!("redirect_uri={}", redirect_uri));
+                opts.join("&")
+            }
+            LoginRequest::XboxUser { access_token } => {
+                let data = json!({
+                    "Properties": {
+                        "AuthMethod": "RPS",
+                        "SiteName": "user.auth.xboxlive.com",
+                        "RpsTicket": format!("d={}", access_token),
+                    },
+                    "RelyingParty": "http://auth.xboxlive.com",
+                    "TokenType": "JWT",
+                });
+                data.to_string()
+            }
+            LoginRequest::Xsts { xbl_token } => {
+                let data = json!({
+                    "Properties": {
+                        "SandboxId": "RETAIL",
+                        "UserTokens": [xbl_token],
+                    },
+                    "RelyingParty": "rp://api.minecraftservices.com/",
+                    "TokenType": "JWT",
+                });
+                data.to_string()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Auth {
+    Offline { username: String },
+    Mojang { username: String, token: String },
+    MSFT { token: String, profile: GameProfile },
+}
+
+impl Debug for Auth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Offline { username } => f
+                .debug_struct("Offline")
+                .field("username", username)
+                .finish(),
+            Auth::Mojang { username, .. } => f
+                .debug_struct("Mojang")
+                .field("username", username)
+                .field("token", &REDACTED)
+                .finish(),
+            Auth::MSFT { profile, .. } => f
+                .debug_struct("MSFT")
+                .field("token", &REDACTED)
+                .field("profile", profile)
+                .finish(),
+        }
+    }
+}
+
+impl Auth {
+    /// Create a new offline user.
+    pub fn new_offline(name: &str) -> Self {
+        Auth::Offline {
+            username: name.to_owned(),
+        }
+    }
+
+    /// Create a Microsoft account user playing with the selected profile.
+    ///
+    /// Accounts can have multiple profiles, see [`parse_game_profiles`].
+    pub fn new_msft(token: &str, profile: GameProfile) -> Self {
+        Auth::MSFT {
+            token: token.to_owned(),
+            profile,
+        }
+    }
+
+    /// Get The username from the current auth scheme.
+    pub fn get_username(&self) -> &str {
+        match self {
+            Auth::Offline { ref username, .. } => username,
+            Auth::Mojang { ref username, .. } => username,
+            Auth::MSFT { profile, .. } => &profile.name,
+        }
+    }
+
+    /// Get the UUID of the selected profile, if known.
+    pub fn get_uuid(&self) -> Option<&str> {
+        match self {
+            Auth::MSFT { profile, .. } => Some(&profile.id),
+            _ => None,
+        }
+    }
+
+    /// UUID to start the game with, the one of the selected profile or the [`offline_uuid`] of
+    /// offline users, so they keep their inventory on servers in offline mode.
+    pub fn game_uuid(&self) -> Option<String> {
+        match self {
+            Auth::Offline { username } => Some(offline_uuid(username)),
+            _ => self.get_uuid().map(ToString::to_string),
+        }
+    }
+
+    /// Select another profile of a Microsoft account.
+    pub fn select_profile(&mut self, selected: GameProfile) {
+        if let Auth::MSFT { profile, .. } = self {
+            *profile = selected;
+        }
+    }
+
+    /// Check the username of offline accounts, see [`validate_username`].
+    ///
+    /// Names of online accounts come from their profile and are not checked.
+    pub fn validate(&self) -> crate::Result<()> {
+        match self {
+            Auth::Offline { username } => validate_username(username),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_token(&self) -> Option<&str> {
+        match self {
+            Auth::Offline { .. } => None,
+            Auth::Mojang { token, .. } => Some(token),
+            Auth::MSFT { token, .. } => Some(token),
+        }
+    }
+}
+
+/// Saved account usable to launch the game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthProfile {
+    /// Name of the Minecraft profile.
+    pub name: String,
+    /// UUID of the Minecraft profile, if known.
+    pub uuid: Option<String>,
+    pub auth: Auth,
+    #[serde(default)]
+    pub cache: TokenCache,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_username() {
+        let offline = Auth::new_offline("offline");
+        assert_eq!(offline.get_username(), "offline");
+        assert_eq!(offline.get_uuid(), None);
+        assert_eq!(offline.game_uuid(), Some(offline_uuid("offline")));
+
+        let mojang = Auth::Mojang {
+            username: "mojang".to_string(),
+            token: "".to_string(),
+        };
+        assert_eq!(mojang.get_username(), "mojang");
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let mojang = Auth::Mojang {
+            username: "mojang".to_string(),
+            token: "secret-token".to_string(),
+        };
+        let msft = Auth::new_msft(
+            "secret-token",
+            GameProfile {
+                id: "0123".to_string(),
+                name: "msft".to_string(),
+            },
+        );
+        for auth in [mojang, msft] {
+            assert!(!format!("{:?}", auth).contains("secret-token"));
+            assert!(!format!("{:#?}", auth).contains("secret-token"));
+        }
+
+        let refresh = LoginRequest::MsftRefresh {
+            client_id: "id".to_string(),
+            client_secret: "secret-client".to_string(),
+            refresh_token: "secret-refresh".to_string(),
+            redirect_uri: "uri".to_string(),
+        };
+        let output = format!("{:?}", refresh);
+        assert!(!output.contains("secret-client"));
+        assert!(!output.contains("secret-refresh"));
+    }
+}
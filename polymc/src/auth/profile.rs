@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Minecraft profile of an account, as returned by the Minecraft services API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameProfile {
+    /// UUID of the profile, without dashes.
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProfileResponse {
+    Profile(GameProfile),
+    Profiles(Vec<GameProfile>),
+    Error {
+        #[serde(default, rename = "errorType")]
+        error_type: Option<String>,
+        #[serde(default)]
+        error: Option<String>,
+    },
+}
+
+/// Parse the response of the profile endpoint of the Minecraft services API.
+///
+/// Returns all profiles of the account for the frontend to choose from. Fails with
+/// [`Error::NoGameProfile`] if the account owns the game (e.g. via Game Pass) but no profile
+/// has been created yet.
+pub fn parse_game_profiles(data: &[u8]) -> Result<Vec<GameProfile>> {
+    let profiles = match serde_json::from_slice(data)? {
+        ProfileResponse::Profile(profile) => vec![profile],
+        ProfileResponse::Profiles(profiles) => profiles,
+        ProfileResponse::Error { error_type, error } => {
+            let error = error_type.or(error).unwrap_or_default();
+            return Err(if error == "NOT_FOUND" {
+                Error::NoGameProfile
+            } else {
+                Error::ProfileRequest(error)
+            });
+        }
+    };
+
+    if profiles.is_empty() {
+        Err(Error::NoGameProfile)
+    } else {
+        Ok(profiles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_profiles() {
+        let profiles =
+            parse_game_profiles(br#"{"id": "0123", "name": "Steve", "skins": [], "capes": []}"#)
+                .unwrap();
+        assert_eq!(
+            profiles,
+            vec![GameProfile {
+                id: "0123".to_string(),
+                name: "Steve".to_string()
+            }]
+        );
+
+        let not_found = br#"{"path": "/minecraft/profile", "errorType": "NOT_FOUND",
+            "error": "NOT_FOUND", "errorMessage": "..."}"#;
+        assert!(matches!(
+            parse_game_profiles(not_found),
+            Err(Error::NoGameProfile)
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(feature = "ctypes")]
+use std::ffi::CStr;
+#[cfg(feature = "ctypes")]
+use std::os::raw::{c_char, c_int};
+
+use crate::{Error, Result};
+
+/// Shortest username the game accepts.
+pub const USERNAME_MIN_LEN: usize = 3;
+/// Longest username the game accepts.
+pub const USERNAME_MAX_LEN: usize = 16;
+
+/// Check that `name` is a username the game accepts, 3 to 16 ASCII letters, digits or
+/// underscores.
+///
+/// Other names start the game but break in confusing ways, e.g. when joining servers.
+pub fn validate_username(name: &str) -> Result<()> {
+    let len = name.chars().count();
+    if (USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&len) && name.chars().all(is_username_char) {
+        Ok(())
+    } else {
+        Err(Error::InvalidUsername(name.to_string()))
+    }
+}
+
+/// Turn `name` into a valid username by replacing invalid characters with underscores,
+/// cutting it to 16 characters and padding it to 3.
+pub fn sanitize_username(name: &str) -> String {
+    let mut ret: String = name
+        .chars()
+        .map(|c| if is_username_char(c) { c } else { '_' })
+        .take(USERNAME_MAX_LEN)
+        .collect();
+    while ret.len() < USERNAME_MIN_LEN {
+        ret.push('_');
+    }
+
+    ret
+}
+
+/// UUID the game and servers in offline mode give the player `name`, the version 3 UUID of
+/// `OfflinePlayer:<name>` as hex like the ids of online profiles.
+pub fn offline_uuid(name: &str) -> String {
+    let mut uuid = md5::compute(format!("OfflinePlayer:{}", name)).0;
+    uuid[6] = uuid[6] & 0x0f | 0x30;
+    uuid[8] = uuid[8] & 0x3f | 0x80;
+    hex::encode(uuid)
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Check that *name* is a valid username, see [`validate_username`].
+///
+/// Returns 0 if it is valid.
+///
+/// # Safety
+/// name has to be a valid CStr.
+#[cfg(feature = "ctypes")]
+#[doc(hidden)]
+#[export_name = "auth_validate_username"]
+pub unsafe extern "C" fn validate_username_c(name: *const c_char) -> c_int {
+    if name.is_null() {
+        return -libc::EINVAL;
+    }
+    let result = unsafe { CStr::from_ptr(name) }
+        .to_str()
+        .map_err(Error::from)
+        .and_then(validate_username);
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => -e.as_c_error(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn usernames() {
+        for valid in ["Steve", "abc", "a_b_c_d_e_f_g_h_", "Player123"] {
+            validate_username(valid).unwrap();
+            assert_eq!(sanitize_username(valid), valid);
+        }
+        for invalid in ["", "ab", "a_b_c_d_e_f_g_h_i", "Steve Jobs", "Jürgen", "a-b"] {
+            assert!(matches!(
+                validate_username(invalid),
+                Err(Error::InvalidUsername(_))
+            ));
+            validate_username(&sanitize_username(invalid)).unwrap();
+        }
+        assert_eq!(sanitize_username("Steve Jobs"), "Steve_Jobs");
+        assert_eq!(sanitize_username("ab"), "ab_");
+        assert_eq!(sanitize_username("Jürgen"), "J_rgen");
+    }
+
+    #[test]
+    fn offline_uuids() {
+        assert_eq!(offline_uuid("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+        assert_ne!(offline_uuid("Steve"), offline_uuid("steve"));
+    }
+}
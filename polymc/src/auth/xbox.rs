@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// Reasons Xbox Live refuses to authorize an account, from the `XErr` code of XSTS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XboxError {
+    /// The Microsoft account has no Xbox account yet.
+    NoAccount,
+    /// Xbox Live is not available in the country of the account.
+    CountryNotAvailable,
+    /// The account needs adult verification (South Korea).
+    AdultVerification,
+    /// Child accounts have to be added to a family by an adult.
+    ChildAccount,
+    Unknown(u64),
+}
+
+impl XboxError {
+    pub fn from_code(code: u64) -> Self {
+        match code {
+            2148916233 => Self::NoAccount,
+            2148916235 => Self::CountryNotAvailable,
+            2148916236 | 2148916237 => Self::AdultVerification,
+            2148916238 => Self::ChildAccount,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl Display for XboxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAccount => f.write_str(
+                "The account has no Xbox account, sign in on xbox.com once to create one",
+            ),
+            Self::CountryNotAvailable => {
+                f.write_str("Xbox Live is not available in the country of the account")
+            }
+            Self::AdultVerification => {
+                f.write_str("The account needs adult verification on xbox.com")
+            }
+            Self::ChildAccount => {
+                f.write_str("Child accounts have to be added to a family by an adult first")
+            }
+            Self::Unknown(code) => write!(f, "Xbox Live refused the account (XErr {})", code),
+        }
+    }
+}
+
+/// Token issued by Xbox user authentication or XSTS.
+#[derive(Debug, Clone)]
+pub struct XboxToken {
+    pub token: String,
+    /// User hash needed together with the token to log into Minecraft.
+    pub user_hash: String,
+    /// Expiry of the token as RFC 3339 timestamp.
+    pub not_after: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum XboxResponse {
+    Token {
+        #[serde(rename = "Token")]
+        token: String,
+        #[serde(rename = "NotAfter", default)]
+        not_after: Option<String>,
+        #[serde(rename = "DisplayClaims")]
+        display_claims: DisplayClaims,
+    },
+    Error {
+        #[serde(rename = "XErr")]
+        xerr: u64,
+    },
+}
+
+#[derive(Deserialize)]
+struct DisplayClaims {
+    xui: Vec<UserClaim>,
+}
+
+#[derive(Deserialize)]
+struct UserClaim {
+    uhs: String,
+}
+
+/// Parse the response of Xbox user authentication or XSTS authorization.
+///
+/// Refusals are returned as [`Error::XboxAuth`] with the reason.
+pub fn parse_xbox_response(data: &[u8]) -> Result<XboxToken> {
+    match serde_json::from_slice(data)? {
+        XboxResponse::Token {
+            token,
+            not_after,
+            display_claims,
+        } => Ok(XboxToken {
+            token,
+            not_after,
+            user_hash: display_claims
+                .xui
+                .into_iter()
+                .next()
+                .map(|claim| claim.uhs)
+                .ok_or(Error::XboxAuth(XboxError::NoAccount))?,
+        }),
+        XboxResponse::Error { xerr } => Err(Error::XboxAuth(XboxError::from_code(xerr))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn xsts_response() {
+        let token = parse_xbox_response(
+            br#"{"IssueInstant": "2022-01-01T00:00:00Z", "NotAfter": "2022-01-02T00:00:00Z",
+                "Token": "token", "DisplayClaims": {"xui": [{"uhs": "hash"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(token.token, "token");
+        assert_eq!(token.user_hash, "hash");
+
+        let child = br#"{"Identity": "0", "XErr": 2148916238, "Message": "",
+            "Redirect": "https://start.ui.xboxlive.com/AddChildToFamily"}"#;
+        assert!(matches!(
+            parse_xbox_response(child),
+            Err(Error::XboxAuth(XboxError::ChildAccount))
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::{Error, Result};
+
+/// Token to cancel long running operations like searches, verification and downloads.
+///
+/// Clones share the same state, so a frontend can keep a clone and cancel from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all operations using this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return [`Error::Cancelled`] if the token was cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "cancellation_token_new"]
+    pub extern "C" fn new_c() -> *mut Self {
+        Box::into_raw(Box::new(Self::new()))
+    }
+
+    /// Cancel the token, this is safe to call from any thread.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "cancellation_token_cancel"]
+    pub extern "C" fn cancel_c(&self) {
+        self.cancel()
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "cancellation_token_is_cancelled"]
+    pub extern "C" fn is_cancelled_c(&self) -> bool {
+        self.is_cancelled()
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "cancellation_token_free"]
+    pub unsafe extern "C" fn free(v: *mut Self) {
+        let _ = unsafe { Box::from_raw(v) };
+    }
+}
@@ -0,0 +1,371 @@
+//! Analysis of why the game exited, so users get a hint instead of a window which just closes.
+//!
+//! The exit status, the newest crash report and the logs of the game are checked for the usual
+//! causes of crashes, like running it with the wrong Java version.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::SystemTime;
+
+use serde::Serialize;
+use tracing::*;
+
+use crate::i18n::Locale;
+use crate::instance::Instance;
+
+/// Log output indicating a known cause, matched case sensitive.
+const PATTERNS: &[(&str, CrashCause)] = &[
+    (
+        "java.lang.UnsupportedClassVersionError",
+        CrashCause::JavaTooOld,
+    ),
+    (
+        "compiled by a more recent version of the Java Runtime",
+        CrashCause::JavaTooOld,
+    ),
+    ("Unsupported major.minor version", CrashCause::JavaTooOld),
+    // LaunchWrapper and old Forge versions expect Java 8
+    (
+        "AppClassLoader cannot be cast to class java.net.URLClassLoader",
+        CrashCause::JavaTooNew,
+    ),
+    ("java.lang.UnsatisfiedLinkError", CrashCause::MissingNatives),
+    ("in java.library.path", CrashCause::MissingNatives),
+    ("Failed to locate library:", CrashCause::MissingNatives),
+    ("Pixel format not accelerated", CrashCause::OpenGl),
+    (
+        "The driver does not appear to support OpenGL",
+        CrashCause::OpenGl,
+    ),
+    ("GLFW error 65542", CrashCause::OpenGl),
+    ("GLFW error 65543", CrashCause::OpenGl),
+    (
+        "No OpenGL context found in the current thread",
+        CrashCause::OpenGl,
+    ),
+    ("java.lang.OutOfMemoryError", CrashCause::OutOfMemory),
+    (
+        "A fatal error has been detected by the Java Runtime Environment",
+        CrashCause::NativeCrash,
+    ),
+];
+
+/// Known cause of a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrashCause {
+    /// The game or a mod needs a newer Java version.
+    JavaTooOld,
+    /// The game or mod loader only runs on an older Java version.
+    JavaTooNew,
+    /// Native libraries are missing or built for another platform.
+    MissingNatives,
+    /// The graphics driver does not support the required OpenGL version.
+    OpenGl,
+    /// The maximum heap size is too small.
+    OutOfMemory,
+    /// The Java runtime itself crashed, e.g. in the graphics driver.
+    NativeCrash,
+}
+
+impl CrashCause {
+    /// Causes indicated by the output or logs of the game, in the order of [`PATTERNS`].
+    pub fn detect(text: &str) -> Vec<Self> {
+        let mut ret = Vec::new();
+        for (pattern, cause) in PATTERNS {
+            if !ret.contains(cause) && text.contains(pattern) {
+                ret.push(*cause);
+            }
+        }
+        ret
+    }
+
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            Self::JavaTooOld => "crash-java-too-old",
+            Self::JavaTooNew => "crash-java-too-new",
+            Self::MissingNatives => "crash-missing-natives",
+            Self::OpenGl => "crash-opengl",
+            Self::OutOfMemory => "crash-out-of-memory",
+            Self::NativeCrash => "crash-native",
+        }
+    }
+
+    pub fn localize(&self, locale: &Locale) -> String {
+        locale
+            .format(self.message_id(), &[])
+            .unwrap_or_else(|| self.to_string())
+    }
+}
+
+impl Display for CrashCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::JavaTooOld => "The game or a mod needs a newer version of Java",
+            Self::JavaTooNew => "This version of the game or mod loader needs Java 8",
+            Self::MissingNatives => {
+                "Native libraries are missing or were built for another platform"
+            }
+            Self::OpenGl => {
+                "The graphics driver does not support OpenGL, try updating or installing it"
+            }
+            Self::OutOfMemory => "The game ran out of memory, try a larger maximum heap size",
+            Self::NativeCrash => "The Java runtime crashed, often caused by the graphics driver",
+        })
+    }
+}
+
+/// Summary of a crash report written by the game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrashReport {
+    pub path: PathBuf,
+    /// What the game was doing, e.g. `Initializing game`.
+    pub description: Option<String>,
+    /// First line of the exception which crashed the game.
+    pub exception: Option<String>,
+}
+
+impl CrashReport {
+    /// Summarize the crash report at `path` with the content `text`.
+    pub fn parse(path: &Path, text: &str) -> Self {
+        let mut lines = text.lines();
+        let description = lines
+            .by_ref()
+            .find_map(|l| l.strip_prefix("Description:"))
+            .map(|d| d.trim().to_string());
+        // The exception follows the description after an empty line
+        let exception = lines
+            .find(|l| !l.trim().is_empty())
+            .map(|l| l.trim().to_string());
+
+        Self {
+            path: path.to_path_buf(),
+            description,
+            exception,
+        }
+    }
+}
+
+/// Analysis of an exit of the game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExitReport {
+    /// Exit code, `None` if the game was killed by a signal.
+    pub code: Option<i32>,
+    /// Signal which killed the game (Unix only).
+    pub signal: Option<i32>,
+    /// Crash report written by the game since it was started.
+    pub crash_report: Option<CrashReport>,
+    /// Causes detected from the output and logs, see [`CrashCause::detect`].
+    pub causes: Vec<CrashCause>,
+}
+
+impl ExitReport {
+    /// Analyze the exit of the game of `instance` started at `started`.
+    ///
+    /// `output` is the output of the game captured by the frontend, if any. Errors of the Java
+    /// runtime, e.g. about the class version, are only printed there and not in the logs.
+    pub fn analyze(
+        instance: &Instance,
+        status: ExitStatus,
+        started: SystemTime,
+        output: &str,
+    ) -> Self {
+        #[cfg(target_family = "unix")]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(target_family = "unix"))]
+        let signal = None;
+
+        Self::analyze_dir(
+            Path::new(&instance.minecraft_path),
+            status.code(),
+            signal,
+            started,
+            output,
+        )
+    }
+
+    fn analyze_dir(
+        game_dir: &Path,
+        code: Option<i32>,
+        signal: Option<i32>,
+        started: SystemTime,
+        output: &str,
+    ) -> Self {
+        let mut text = output.to_string();
+
+        let crash_report = newest_since(&game_dir.join("crash-reports"), "crash-", started)
+            .and_then(|path| {
+                let report = std::fs::read_to_string(&path).ok()?;
+                text.push_str(&report);
+                Some(CrashReport::parse(&path, &report))
+            });
+
+        let latest_log = game_dir.join("logs").join("latest.log");
+        if modified_since(&latest_log, started) {
+            if let Ok(log) = std::fs::read_to_string(&latest_log) {
+                text.push_str(&log);
+            }
+        }
+
+        // Written by the Java runtime when it crashes
+        if let Some(path) = newest_since(game_dir, "hs_err_pid", started) {
+            debug!("Java runtime crashed, see {}", path.display());
+            if let Ok(log) = std::fs::read_to_string(&path) {
+                text.push_str(&log);
+            }
+        }
+
+        Self {
+            code,
+            signal,
+            crash_report,
+            causes: CrashCause::detect(&text),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Usual meaning of the exit code or signal.
+    pub fn meaning(&self) -> &'static str {
+        match (self.code, self.signal) {
+            (Some(0), _) => "The game exited normally",
+            (Some(-1 | 255), _) => "The game stopped after a crash",
+            (Some(137), _) | (_, Some(9)) => {
+                "The game was killed, possibly because the system ran out of memory"
+            }
+            (Some(134), _) | (_, Some(6)) => "The Java runtime aborted after a fatal error",
+            (Some(139), _) | (_, Some(11)) => {
+                "The game crashed in native code, often in the graphics driver"
+            }
+            (Some(143), _) | (_, Some(15)) => "The game was terminated",
+            (Some(130), _) | (_, Some(2)) => "The game was interrupted",
+            _ => "The game exited with an error",
+        }
+    }
+}
+
+impl Display for ExitReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "Game exited with code {}", code)?,
+            (None, Some(signal)) => write!(f, "Game was killed by signal {}", signal)?,
+            (None, None) => write!(f, "Game exited")?,
+        }
+        writeln!(f, ": {}", self.meaning())?;
+
+        if let Some(report) = &self.crash_report {
+            writeln!(f, "Crash report: {}", report.path.display())?;
+            if let Some(description) = &report.description {
+                writeln!(f, "  {}", description)?;
+            }
+            if let Some(exception) = &report.exception {
+                writeln!(f, "  {}", exception)?;
+            }
+        }
+
+        if !self.causes.is_empty() {
+            writeln!(f, "Possible causes:")?;
+            for cause in &self.causes {
+                writeln!(f, "  - {}", cause)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn modified_since(path: &Path, since: SystemTime) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|modified| modified >= since)
+        .unwrap_or(false)
+}
+
+/// Newest file in `dir` starting with `prefix` modified since `since`.
+fn newest_since(dir: &Path, prefix: &str, since: SystemTime) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            (metadata.is_file() && modified >= since).then(|| (modified, e.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::time::Duration;
+
+    #[test]
+    fn exit_report() {
+        let dir = TempDir::new("crash");
+        std::fs::create_dir_all(dir.join("crash-reports")).unwrap();
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        std::fs::write(
+            dir.join("crash-reports/crash-2022-01-01_00.00.00-client.txt"),
+            "---- Minecraft Crash Report ----\n// Oops.\n\n\
+             Time: 2022-01-01 00:00\nDescription: Initializing game\n\n\
+             java.lang.OutOfMemoryError: Java heap space\n\tat net.minecraft.Main\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("logs/latest.log"),
+            "[Render thread/ERROR]: GLFW error 65542: WGL: The driver does not appear to \
+             support OpenGL\n",
+        )
+        .unwrap();
+        let started = SystemTime::now() - Duration::from_secs(60);
+
+        let report = ExitReport::analyze_dir(
+            &dir,
+            Some(-1),
+            None,
+            started,
+            "Exception in thread \"main\" java.lang.UnsupportedClassVersionError",
+        );
+        assert_eq!(
+            report.causes,
+            [
+                CrashCause::JavaTooOld,
+                CrashCause::OpenGl,
+                CrashCause::OutOfMemory
+            ]
+        );
+        let crash_report = report.crash_report.as_ref().unwrap();
+        assert_eq!(
+            crash_report.description.as_deref(),
+            Some("Initializing game")
+        );
+        assert_eq!(
+            crash_report.exception.as_deref(),
+            Some("java.lang.OutOfMemoryError: Java heap space")
+        );
+        assert_eq!(report.meaning(), "The game stopped after a crash");
+        assert!(report
+            .to_string()
+            .contains("  - The game ran out of memory"));
+
+        // Files from earlier sessions are ignored
+        let report = ExitReport::analyze_dir(
+            &dir,
+            None,
+            Some(9),
+            SystemTime::now() + Duration::from_secs(60),
+            "",
+        );
+        assert!(report.crash_report.is_none());
+        assert!(report.causes.is_empty());
+        assert!(report
+            .to_string()
+            .starts_with("Game was killed by signal 9"));
+    }
+}
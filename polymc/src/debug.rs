@@ -0,0 +1,152 @@
+//! Debug bundles for bug reports.
+//!
+//! A bundle is a zip with everything needed to reproduce a launch problem: the launch command,
+//! the components of the instance, system and Java information, the latest log and crash
+//! reports. The access token is redacted from all of them.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use tracing::*;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::auth::Auth;
+use crate::instance::Instance;
+use crate::java_wrapper::Java;
+use crate::meta::manifest::OS;
+use crate::Result;
+
+/// Write a debug bundle of `instance` as zip into `writer`.
+pub fn write_debug_bundle<W: Write + Seek>(
+    instance: &Instance,
+    java: &Java,
+    auth: &Auth,
+    writer: W,
+) -> Result<()> {
+    let mut zip = ZipWriter::new(writer);
+    let options = FileOptions::default();
+
+    zip.start_file("command.txt", options)?;
+    match java.build_command(instance, auth) {
+        Ok(command) => writeln!(
+            zip,
+            "{} {}",
+            command.get_program().to_string_lossy(),
+            Java::redacted_args(&command, auth).join(" ")
+        )?,
+        Err(e) => writeln!(zip, "failed to build command: {}", e)?,
+    }
+
+    zip.start_file("components.txt", options)?;
+    for manifest in instance.sorted_manifests() {
+        writeln!(zip, "{} {}", manifest.uid, manifest.version)?;
+    }
+
+    zip.start_file("system.txt", options)?;
+    writeln!(
+        zip,
+        "{} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(zip, "os: {} ({})", OS::get().name, std::env::consts::ARCH)?;
+    match java.version() {
+        Ok(version) => writeln!(zip, "java:\n{}", version)?,
+        Err(e) => writeln!(zip, "java: failed to get version: {}", e)?,
+    }
+
+    let game_dir = Path::new(&instance.minecraft_path);
+    add_file(
+        &mut zip,
+        &game_dir.join("logs").join("latest.log"),
+        "logs/latest.log",
+        auth,
+    )?;
+
+    if let Ok(reports) = std::fs::read_dir(game_dir.join("crash-reports")) {
+        for report in reports {
+            let report = report?;
+            let name = format!("crash-reports/{}", report.file_name().to_string_lossy());
+            add_file(&mut zip, &report.path(), &name, auth)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Add a text file to the bundle with the access token redacted, skipping missing files.
+fn add_file<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    path: &Path,
+    name: &str,
+    auth: &Auth,
+) -> Result<()> {
+    let mut data = Vec::new();
+    match OpenOptions::new().read(true).open(path) {
+        Ok(mut file) => file.read_to_end(&mut data)?,
+        Err(e) => {
+            debug!("not adding {} to debug bundle: {}", path.display(), e);
+            return Ok(());
+        }
+    };
+
+    let mut data = String::from_utf8_lossy(&data).into_owned();
+    if let Some(token) = auth.get_token().filter(|t| !t.is_empty()) {
+        data = data.replace(token, "<redacted>");
+    }
+
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(data.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::SearchResult;
+    use crate::test_util::TempDir;
+    use std::io::Cursor;
+
+    #[test]
+    fn redact_token() {
+        let dir = TempDir::new("bundle");
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        std::fs::write(dir.join("logs/latest.log"), "token: secret-token").unwrap();
+
+        let manifest = r#"{"name": "Minecraft", "uid": "net.minecraft", "version": "1.18.1",
+            "order": 0, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": [],
+            "assetIndex": {
+                "id": "1.18", "sha1": "0000000000000000000000000000000000000000",
+                "size": 1, "totalSize": 1, "url": "https://example.com"
+            }
+        }"#;
+        let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+        result
+            .manifests
+            .insert("net.minecraft".to_string(), manifest.parse().unwrap());
+        let instance = Instance::new("test", "1.18.1", &dir.display().to_string(), result);
+        let auth = Auth::Mojang {
+            username: "Steve".to_string(),
+            token: "secret-token".to_string(),
+        };
+
+        let mut bundle = Cursor::new(Vec::new());
+        write_debug_bundle(&instance, &Java::new("true"), &auth, &mut bundle).unwrap();
+
+        let mut archive = zip::ZipArchive::new(bundle).unwrap();
+        let mut content = String::new();
+        for name in ["command.txt", "components.txt", "logs/latest.log"] {
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+        }
+        assert!(content.contains("net.minecraft 1.18.1"));
+        assert!(!content.contains("secret-token"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Writing downloads of libraries, assets and meta data to the stores.
+//!
+//! The frontend fetches the data and passes it on as it arrives. A [`FileDownload`] checks it
+//! against the size and hash of its [`DownloadRequest`] and only moves it to its path once it is
+//! complete and verified, so an interrupted or tampered download never ends up in the stores.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use ring::digest::Context;
+use tracing::*;
+
+use crate::meta::manifest::Sha1Sum;
+use crate::meta::DownloadRequest;
+use crate::{Error, Result};
+
+/// A file being downloaded.
+///
+/// Data is written to a temporary file next to the final path, which is only moved in place by
+/// [`PartialFile::persist`]. If the download fails the temporary file is removed on drop, so a
+/// crash never leaves a truncated file at the final path.
+#[derive(Debug)]
+pub struct PartialFile {
+    file: File,
+    tmp_path: PathBuf,
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartialFile {
+    /// Start writing the file at `path`, creating its directory.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(format!(".{}.part", std::process::id()));
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let file = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+
+        Ok(Self {
+            file,
+            tmp_path,
+            path,
+            persisted: false,
+        })
+    }
+
+    /// Move the file to its final path.
+    pub fn persist(mut self) -> Result<File> {
+        self.file.sync_all()?;
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        self.persisted = true;
+
+        Ok(self.file.try_clone()?)
+    }
+}
+
+impl Write for PartialFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+/// Download of the library or asset of a [`DownloadRequest`] to its path.
+pub struct FileDownload<'a> {
+    request: &'a DownloadRequest,
+    file: PartialFile,
+    digest: Option<Context>,
+    size: u64,
+    /// Hash fetched from the repository for requests without one, see [`parse_sha1`].
+    fetched_sha1: Option<Vec<u8>>,
+}
+
+impl<'a> FileDownload<'a> {
+    /// Start the download of `request`.
+    ///
+    /// `fetched_sha1` is the hash the repository published next to the file of a request
+    /// without a hash, it is verified against and kept in a `.sha1` file next to the file.
+    pub fn create(request: &'a DownloadRequest, fetched_sha1: Option<Vec<u8>>) -> Result<Self> {
+        let path = request.get_path().ok_or(Error::MetaNotFound)?;
+        Ok(Self {
+            request,
+            file: PartialFile::create(path)?,
+            digest: request.get_hash_algo().map(Context::new),
+            size: 0,
+            fetched_sha1,
+        })
+    }
+
+    /// Write the next part of the data.
+    ///
+    /// Fails with [`Error::LibraryInvalidSize`] as soon as there is more than expected.
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.size += data.len() as u64;
+        if let Some(expected) = self.request.get_size().filter(|e| self.size > *e) {
+            return Err(Error::LibraryInvalidSize {
+                expected,
+                actual: self.size,
+            });
+        }
+        if let Some(digest) = &mut self.digest {
+            digest.update(data);
+        }
+        self.file.write_all(data)?;
+        Ok(())
+    }
+
+    /// Verify the complete data and move it to the path of the request, returning its size.
+    pub fn finish(self) -> Result<u64> {
+        if let Some(expected) = self.request.get_size().filter(|e| self.size != *e) {
+            return Err(Error::LibraryInvalidSize {
+                expected,
+                actual: self.size,
+            });
+        }
+        let expected = if self.request.has_hash() {
+            Some(self.request.get_hash())
+        } else {
+            self.fetched_sha1.as_deref()
+        };
+        let digest = self.digest.map(Context::finish);
+        match (expected, digest) {
+            (Some(expected), Some(digest)) if digest.as_ref() != expected => {
+                return Err(Error::LibraryInvalidHash)
+            }
+            (Some(_), _) => {}
+            (None, _) => warn!(
+                "{} has no hash, it was not verified",
+                self.request.get_url()
+            ),
+        }
+
+        let path = self.file.path.clone();
+        self.file.persist()?;
+        // Verified against it when the library is used again
+        if let Some(sha1) = &self.fetched_sha1 {
+            crate::hash::write_sha1_sidecar(&path, sha1)?;
+        }
+
+        Ok(self.size)
+    }
+}
+
+/// Hash in a `.sha1` file published next to an artifact by maven repositories.
+pub fn parse_sha1(data: &[u8]) -> Option<Sha1Sum> {
+    // Some repositories append the file name
+    String::from_utf8_lossy(data)
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::manifest::LibraryDownload;
+    use crate::test_util::TempDir;
+
+    fn library(dir: &Path, sha1: Option<&str>, size: i64) -> DownloadRequest {
+        DownloadRequest::new_library(
+            LibraryDownload {
+                sha1: sha1.map(|h| h.parse().unwrap()),
+                size,
+                url: "https://libraries.example.com/lib.jar".to_string(),
+            },
+            dir.join("lib.jar"),
+        )
+    }
+
+    #[test]
+    fn file_download() {
+        let dir = TempDir::new("download");
+        let path = dir.join("lib.jar");
+        let wrong = "a7e7a1d1b3e6e1a7a1b05fa5e02fbb3fce8ad0d4";
+        let actual = hex::encode(ring::digest::digest(
+            &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+            b"jar",
+        ));
+
+        let request = library(&dir, Some(&actual), 3);
+        let mut download = FileDownload::create(&request, None).unwrap();
+        download.write(b"ja").unwrap();
+        download.write(b"r").unwrap();
+        assert_eq!(download.finish().unwrap(), 3);
+        assert_eq!(std::fs::read(&path).unwrap(), b"jar");
+
+        // Nothing is left behind by failed downloads
+        std::fs::remove_file(&path).unwrap();
+        let request = library(&dir, Some(wrong), 3);
+        let mut download = FileDownload::create(&request, None).unwrap();
+        download.write(b"jar").unwrap();
+        assert!(matches!(download.finish(), Err(Error::LibraryInvalidHash)));
+        let mut download = FileDownload::create(&request, None).unwrap();
+        assert!(matches!(
+            download.write(b"jars"),
+            Err(Error::LibraryInvalidSize { actual: 4, .. })
+        ));
+        drop(download);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // Hashes fetched from the repository are kept
+        let request = library(&dir, None, -1);
+        let fetched = parse_sha1(format!("{}  lib.jar\n", actual).as_bytes()).unwrap();
+        let mut download = FileDownload::create(&request, Some(fetched.as_ref().to_vec())).unwrap();
+        download.write(b"jar").unwrap();
+        download.finish().unwrap();
+        assert_eq!(crate::hash::read_sha1_sidecar(&path), Some(fetched));
+    }
+}
@@ -29,8 +29,162 @@ pub enum Error {
     #[error(display = "Library has invalid hash")]
     LibraryInvalidHash,
 
+    #[error(
+        display = "Library has invalid size: expected {} bytes, got {}",
+        expected,
+        actual
+    )]
+    LibraryInvalidSize { expected: u64, actual: u64 },
+
+    #[error(display = "Meta index does not match the pinned hash")]
+    MetaIndexInvalidHash,
+
+    #[error(display = "Unknown fields in meta data: {:?}", _0)]
+    MetaUnknownFields(Vec<String>),
+
+    #[error(
+        display = "Unsupported {} format version {}, this launcher reads versions {} to {}",
+        file_type,
+        version,
+        min,
+        max
+    )]
+    MetaFormatUnsupported {
+        file_type: String,
+        version: u64,
+        min: u64,
+        max: u64,
+    },
+
     #[error(display = "Meta data not found for requested search")]
     MetaNotFound,
+
+    #[error(display = "No asset index found, {} should provide one", _0)]
+    MissingAssetIndex(String),
+
+    #[error(display = "Meta data has to be downloaded first")]
+    PendingDownloads,
+
+    #[error(display = "Unknown package: {}", _0)]
+    UnknownUid(String),
+
+    #[error(display = "Unknown version {} of {}", version, uid)]
+    UnknownVersion { uid: String, version: String },
+
+    #[error(
+        display = "Component {} requested in versions {} and {}",
+        uid,
+        first,
+        second
+    )]
+    DuplicateUid {
+        uid: String,
+        first: String,
+        second: String,
+    },
+
+    #[error(display = "Invalid version range: {}", _0)]
+    InvalidVersionRange(String),
+
+    #[error(display = "Can't use {} {}, {}", uid, version, reason)]
+    RequirementConflict {
+        uid: String,
+        version: String,
+        reason: String,
+    },
+
+    #[error(
+        display = "The account owns the game but has no profile yet, create one on minecraft.net"
+    )]
+    NoGameProfile,
+
+    #[error(display = "Failed to get the game profile: {}", _0)]
+    ProfileRequest(String),
+
+    #[error(display = "{}", _0)]
+    XboxAuth(crate::auth::XboxError),
+
+    #[error(display = "Failed to run java: {}", _0)]
+    JavaFailed(String),
+
+    #[error(display = "No Java {} runtime found", _0)]
+    NoCompatibleJava(u32),
+
+    #[error(display = "Sandboxing with {} is not supported on this platform", _0)]
+    SandboxNotSupported(String),
+
+    #[error(display = "{} is locked by another process", _0)]
+    Locked(String),
+
+    #[error(display = "Game crashed {} times in a row, giving up", _0)]
+    CrashLoop(u32),
+
+    #[error(display = "Operation was cancelled")]
+    Cancelled,
+
+    #[error(display = "Invalid message in locale on line {}", _0)]
+    InvalidLocale(usize),
+
+    #[error(display = "Downloading from {} is not allowed", _0)]
+    BlockedUrl(String),
+
+    #[error(display = "Invalid host pattern {:?}", _0)]
+    InvalidHostPattern(String),
+
+    #[error(display = "Unknown instance template {}", _0)]
+    UnknownTemplate(String),
+
+    #[error(display = "Failed to download {}: {}", url, reason)]
+    Download { url: String, reason: String },
+
+    #[error(
+        display = "Invalid username {:?}, it has to be 3 to 16 letters, digits or underscores",
+        _0
+    )]
+    InvalidUsername(String),
+
+    #[error(display = "The game did not start within {}s", waited)]
+    LaunchStalled { waited: u64, stderr: String },
+
+    #[error(display = "Java agent {} not found", _0)]
+    JavaAgentMissing(String),
+
+    #[error(display = "Invalid meta snapshot: {}", _0)]
+    InvalidSnapshot(String),
+
+    #[error(display = "Refusing update, {}", _0)]
+    UpdateInvalid(String),
+
+    #[error(display = "Another logger is installed already")]
+    LoggerInstalled,
+
+    #[error(display = "{} keeps rate limiting the downloads, try again later", url)]
+    Throttled {
+        url: String,
+        /// Seconds the server asked to wait last.
+        retry_after: Option<u64>,
+    },
+
+    #[error(display = "{} is outside of the local meta source", _0)]
+    OutsideLocalSource(String),
+
+    #[error(display = "Invalid state archive: {}", _0)]
+    InvalidStateArchive(String),
+
+    #[error(display = "No libraries or assets found in {}", _0)]
+    NoForeignStores(String),
+
+    #[error(display = "{} has no play time left today", _0)]
+    SessionLimitReached(String),
+
+    #[error(
+        display = "The client jar {} does not match the one the instance was resolved with, verify the libraries to download it again",
+        _0
+    )]
+    MainJarCorrupted(String),
+
+    #[error(display = "Invalid file name {:?}", _0)]
+    InvalidFileName(String),
 }
 
 impl Error {
@@ -43,7 +197,44 @@ impl Error {
             Self::LibraryInvalidName => libc::EINVAL,
             Self::LibraryNotSupported => libc::ENOTSUP,
             Self::LibraryMissing => libc::ENOENT,
+            Self::LibraryInvalidSize { .. } => libc::EINVAL,
+            Self::MetaIndexInvalidHash => libc::EINVAL,
+            Self::MetaUnknownFields(_) => libc::EINVAL,
+            Self::MetaFormatUnsupported { .. } => libc::EPROTO,
             Self::MetaNotFound => libc::ENOENT,
+            Self::MissingAssetIndex(_) => libc::ENOENT,
+            Self::PendingDownloads => libc::EAGAIN,
+            Self::UnknownUid(_) => libc::ENOENT,
+            Self::UnknownVersion { .. } => libc::ENOENT,
+            Self::DuplicateUid { .. } => libc::EEXIST,
+            Self::InvalidVersionRange(_) => libc::EINVAL,
+            Self::RequirementConflict { .. } => libc::EEXIST,
+            Self::NoGameProfile => libc::ENOENT,
+            Self::ProfileRequest(_) => libc::EIO,
+            Self::XboxAuth(_) => libc::EACCES,
+            Self::JavaFailed(_) => libc::ENOEXEC,
+            Self::NoCompatibleJava(_) => libc::ENOENT,
+            Self::SandboxNotSupported(_) => libc::ENOTSUP,
+            Self::Locked(_) => libc::EWOULDBLOCK,
+            Self::Cancelled => libc::ECANCELED,
+            Self::InvalidLocale(_) => libc::EINVAL,
+            Self::BlockedUrl(_) => libc::EACCES,
+            Self::InvalidHostPattern(_) => libc::EINVAL,
+            Self::UnknownTemplate(_) => libc::ENOENT,
+            Self::Download { .. } => libc::EIO,
+            Self::InvalidUsername(_) => libc::EINVAL,
+            Self::LaunchStalled { .. } => libc::ETIMEDOUT,
+            Self::JavaAgentMissing(_) => libc::ENOENT,
+            Self::InvalidSnapshot(_) => libc::ENOENT,
+            Self::UpdateInvalid(_) => libc::EINVAL,
+            Self::LoggerInstalled => libc::EALREADY,
+            Self::Throttled { .. } => libc::EBUSY,
+            Self::OutsideLocalSource(_) => libc::EACCES,
+            Self::InvalidStateArchive(_) => libc::EINVAL,
+            Self::NoForeignStores(_) => libc::ENOENT,
+            Self::SessionLimitReached(_) => libc::EPERM,
+            Self::MainJarCorrupted(_) => libc::EINVAL,
+            Self::InvalidFileName(_) => libc::EINVAL,
             _ => libc::ENOTRECOVERABLE,
         }
     }
@@ -0,0 +1,153 @@
+//! Choosing the GPU the game renders on for systems with integrated and discrete graphics.
+//!
+//! On Linux the choice is passed in environment variables, `DRI_PRIME` for Mesa and the PRIME
+//! render offload variables for the NVIDIA driver. Windows keeps a preference per executable in
+//! the registry, which [`register_preference`] sets for the java executable, so there it also
+//! applies to other programs running on the same java. Other platforms choose the GPU themselves.
+
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+/// File which exists while the proprietary NVIDIA driver is loaded.
+const NVIDIA_DRIVER: &str = "/proc/driver/nvidia/version";
+
+/// GPU the game should render on.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpuPreference {
+    /// The power saving GPU, usually built into the CPU.
+    Integrated,
+    /// The high performance GPU.
+    Discrete,
+}
+
+impl GpuPreference {
+    /// Environment variables selecting the GPU on Linux.
+    ///
+    /// The NVIDIA offload variables are only set with `nvidia`, the proprietary driver, as they
+    /// break OpenGL without it.
+    pub fn linux_env(self, nvidia: bool) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Self::Integrated if nvidia => {
+                vec![("DRI_PRIME", "0"), ("__NV_PRIME_RENDER_OFFLOAD", "0")]
+            }
+            Self::Integrated => vec![("DRI_PRIME", "0")],
+            Self::Discrete if nvidia => vec![
+                ("DRI_PRIME", "1"),
+                ("__NV_PRIME_RENDER_OFFLOAD", "1"),
+                ("__GLX_VENDOR_LIBRARY_NAME", "nvidia"),
+                ("__VK_LAYER_NV_optimus", "NVIDIA_only"),
+            ],
+            Self::Discrete => vec![("DRI_PRIME", "1")],
+        }
+    }
+
+    /// Value of the Windows `UserGpuPreferences` registry entry.
+    pub fn windows_value(self) -> &'static str {
+        match self {
+            Self::Integrated => "GpuPreference=1;",
+            Self::Discrete => "GpuPreference=2;",
+        }
+    }
+}
+
+/// Environment variables selecting the GPU on this system, none where it is chosen otherwise.
+pub fn env(preference: GpuPreference) -> Vec<(&'static str, &'static str)> {
+    if cfg!(target_os = "linux") {
+        preference.linux_env(Path::new(NVIDIA_DRIVER).exists())
+    } else {
+        Vec::new()
+    }
+}
+
+/// Registry key holding the GPU preferences of the user per executable.
+#[cfg(windows)]
+const PREFERENCES_KEY: &str = r"HKCU\Software\Microsoft\DirectX\UserGpuPreferences";
+
+/// Store the preference for the executable `java` in the registry of the user, like the graphics
+/// settings of Windows do, returning the absolute path it was stored for.
+///
+/// Windows keys the preference by the path of the executable, so it applies to every program
+/// started with that java, not only to the game, until [`unregister_preference`] removes it.
+#[cfg(windows)]
+pub fn register_preference(java: &Path, preference: GpuPreference) -> crate::Result<PathBuf> {
+    let java = crate::runtime::which(java).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("{} was not found", java.display()),
+        )
+    })?;
+    reg(&[
+        OsStr::new("add"),
+        OsStr::new(PREFERENCES_KEY),
+        OsStr::new("/v"),
+        java.as_os_str(),
+        OsStr::new("/t"),
+        OsStr::new("REG_SZ"),
+        OsStr::new("/d"),
+        OsStr::new(preference.windows_value()),
+        OsStr::new("/f"),
+    ])?;
+    Ok(java)
+}
+
+/// Remove the preference [`register_preference`] stored for `java`, the absolute path it
+/// returned.
+#[cfg(windows)]
+pub fn unregister_preference(java: &Path) -> crate::Result<()> {
+    reg(&[
+        OsStr::new("delete"),
+        OsStr::new(PREFERENCES_KEY),
+        OsStr::new("/v"),
+        java.as_os_str(),
+        OsStr::new("/f"),
+    ])
+}
+
+/// Run `reg` with `args`, failing with its error message.
+#[cfg(windows)]
+fn reg(args: &[&OsStr]) -> crate::Result<()> {
+    let output = Command::new("reg").args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn linux_env() {
+        assert_eq!(
+            GpuPreference::Discrete.linux_env(false),
+            [("DRI_PRIME", "1")]
+        );
+        let nvidia = GpuPreference::Discrete.linux_env(true);
+        assert!(nvidia.contains(&("__NV_PRIME_RENDER_OFFLOAD", "1")));
+        assert!(nvidia.contains(&("__GLX_VENDOR_LIBRARY_NAME", "nvidia")));
+        assert!(!GpuPreference::Integrated
+            .linux_env(true)
+            .iter()
+            .any(|(name, _)| *name == "__GLX_VENDOR_LIBRARY_NAME"));
+
+        let preference: GpuPreference = serde_json::from_str(r#""discrete""#).unwrap();
+        assert_eq!(preference, GpuPreference::Discrete);
+        assert_eq!(preference.windows_value(), "GpuPreference=2;");
+    }
+}
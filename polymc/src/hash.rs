@@ -0,0 +1,97 @@
+//! Hashing files for verification, without reading them into memory at once.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use ring::digest::{Algorithm, Context, Digest, SHA1_FOR_LEGACY_USE_ONLY};
+
+use crate::meta::manifest::Sha1Sum;
+use crate::Result;
+
+/// Digest of everything `reader` returns.
+pub fn hash_reader<R: Read>(algorithm: &'static Algorithm, mut reader: R) -> Result<Digest> {
+    let mut digest = Context::new(algorithm);
+    let mut buf = [0u8; 8192];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        digest.update(&buf[..read]);
+    }
+    Ok(digest.finish())
+}
+
+/// Digest of the file at `path`.
+pub fn hash_file<P: AsRef<Path>>(algorithm: &'static Algorithm, path: P) -> Result<Digest> {
+    hash_reader(algorithm, File::open(path)?)
+}
+
+/// Sha1 of the file at `path`, as the meta data lists for libraries and assets.
+pub fn sha1_file<P: AsRef<Path>>(path: P) -> Result<Sha1Sum> {
+    let digest = hash_file(&SHA1_FOR_LEGACY_USE_ONLY, path)?;
+    hex::encode(digest).parse()
+}
+
+/// Path of the hash maven repositories publish next to the artifact at `path`.
+///
+/// Libraries without a hash in the meta data keep the one fetched from their repository there,
+/// to be verified against it later, see [`read_sha1_sidecar`].
+pub fn sha1_sidecar<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut ret = path.as_ref().to_path_buf().into_os_string();
+    ret.push(".sha1");
+    ret.into()
+}
+
+/// Hash in the `.sha1` file next to the artifact at `path`, `None` if there is none.
+pub fn read_sha1_sidecar<P: AsRef<Path>>(path: P) -> Option<Sha1Sum> {
+    crate::download::parse_sha1(&std::fs::read(sha1_sidecar(path)).ok()?)
+}
+
+/// Keep `sha1` in the `.sha1` file next to the artifact at `path`.
+pub fn write_sha1_sidecar<P: AsRef<Path>>(path: P, sha1: &[u8]) -> Result<()> {
+    Ok(std::fs::write(sha1_sidecar(path), hex::encode(sha1))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn hash() {
+        // Longer than the buffer, so it is hashed in several parts
+        let data = vec![7u8; 20_000];
+        let expected = ring::digest::digest(&SHA1_FOR_LEGACY_USE_ONLY, &data);
+        let digest = hash_reader(&SHA1_FOR_LEGACY_USE_ONLY, &data[..]).unwrap();
+        assert_eq!(digest.as_ref(), expected.as_ref());
+
+        let empty = hash_reader(&SHA1_FOR_LEGACY_USE_ONLY, &[][..]).unwrap();
+        assert_eq!(
+            hex::encode(empty),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+    }
+
+    #[test]
+    fn sidecar() {
+        let dir = TempDir::new("sidecar");
+        std::fs::create_dir_all(&dir).unwrap();
+        let jar = dir.join("lib-1.0.jar");
+        assert_eq!(sha1_sidecar(&jar), dir.join("lib-1.0.jar.sha1"));
+        assert!(read_sha1_sidecar(&jar).is_none());
+
+        let sha1 = [0xab; 20];
+        write_sha1_sidecar(&jar, &sha1).unwrap();
+        assert_eq!(read_sha1_sidecar(&jar).unwrap().as_ref(), &sha1);
+        std::fs::write(
+            sha1_sidecar(&jar),
+            format!("{}  lib-1.0.jar\n", hex::encode(sha1)),
+        )
+        .unwrap();
+        assert_eq!(read_sha1_sidecar(&jar).unwrap().as_ref(), &sha1);
+    }
+}
@@ -0,0 +1,147 @@
+//! Restrictions on the hosts the launcher downloads from.
+
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+/// Host pattern, either a host name (`example.com`), a domain with all its subdomains
+/// (`*.example.com`) or any host (`*`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    Any,
+    Exact(String),
+    Subdomains(String),
+}
+
+impl HostPattern {
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        match self {
+            Self::Any => true,
+            Self::Exact(name) => host == *name,
+            Self::Subdomains(domain) => host == *domain || host.ends_with(&format!(".{}", domain)),
+        }
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let pattern = s.trim().to_ascii_lowercase();
+        if pattern == "*" {
+            return Ok(Self::Any);
+        }
+
+        let (subdomains, name) = match pattern.strip_prefix("*.") {
+            Some(domain) => (true, domain),
+            None => (false, pattern.as_str()),
+        };
+        if name.is_empty() || name.contains(['*', '/', ':']) {
+            return Err(Error::InvalidHostPattern(s.to_string()));
+        }
+
+        if subdomains {
+            Ok(Self::Subdomains(name.to_string()))
+        } else {
+            Ok(Self::Exact(name.to_string()))
+        }
+    }
+}
+
+/// Allow and deny lists of hosts, checked before every download.
+///
+/// Denied hosts are always blocked, if any hosts are allowed all others are blocked as well.
+#[derive(Debug, Clone, Default)]
+pub struct HostPolicy {
+    pub allow: Vec<HostPattern>,
+    pub deny: Vec<HostPattern>,
+}
+
+impl HostPolicy {
+    pub fn is_allowed(&self, host: &str) -> bool {
+        if self.deny.iter().any(|p| p.matches(host)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|p| p.matches(host))
+    }
+
+    /// Fail with [`Error::BlockedUrl`] if the host of `url` may not be contacted.
+    pub fn check_url(&self, url: &str) -> Result<()> {
+        match host_of(url) {
+            Some(host) if self.is_allowed(host) => Ok(()),
+            _ => Err(Error::BlockedUrl(url.to_string())),
+        }
+    }
+}
+
+/// Host of an absolute url, without user info and port.
+pub(crate) fn host_of(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = if host.starts_with('[') {
+        // IPv6 literal
+        &host[..=host.find(']')?]
+    } else {
+        host.split(':').next()?
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_policy() {
+        assert_eq!(
+            host_of("https://user@Meta.Example.com:8443/v1/index.json"),
+            Some("Meta.Example.com")
+        );
+        assert_eq!(host_of("http://[::1]:80/index.json"), Some("[::1]"));
+        assert_eq!(host_of("index.json"), None);
+
+        let policy = HostPolicy {
+            allow: vec![
+                "*.example.com".parse().unwrap(),
+                "mirror.org".parse().unwrap(),
+            ],
+            deny: vec!["evil.example.com".parse().unwrap()],
+        };
+        policy.check_url("https://example.com/index.json").unwrap();
+        policy
+            .check_url("https://META.example.com/index.json")
+            .unwrap();
+        policy.check_url("https://mirror.org/index.json").unwrap();
+        for blocked in [
+            "https://evil.example.com/lib.jar",
+            "https://badexample.com/lib.jar",
+            "https://sub.mirror.org/lib.jar",
+            "file:///etc/passwd",
+        ] {
+            assert!(
+                matches!(policy.check_url(blocked), Err(Error::BlockedUrl(_))),
+                "{}",
+                blocked
+            );
+        }
+
+        let policy = HostPolicy {
+            allow: Vec::new(),
+            deny: vec!["*".parse().unwrap()],
+        };
+        assert!(!policy.is_allowed("example.com"));
+        assert!(HostPolicy::default().is_allowed("example.com"));
+        assert!(matches!(
+            "https://example.com".parse::<HostPattern>(),
+            Err(Error::InvalidHostPattern(_))
+        ));
+    }
+}
@@ -0,0 +1,334 @@
+//! Hooks for frontends to localize messages produced by the library.
+//!
+//! Messages are identified by an id (e.g. `error-unknown-uid`) and may reference arguments with
+//! `{ $name }` placeholders, like simple [Fluent](https://projectfluent.org) messages. Messages
+//! missing from a [`Locale`] fall back to English.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::{Error, Result};
+
+/// Translated messages of one language.
+#[derive(Debug, Clone, Default)]
+pub struct Locale {
+    tag: String,
+    messages: HashMap<String, String>,
+}
+
+impl Locale {
+    /// Create an empty locale, every message will fall back to English.
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            messages: HashMap::new(),
+        }
+    }
+
+    /// Parse messages in the form `id = message`, one per line.
+    ///
+    /// Lines starting with `#` are comments, indented lines continue the previous message.
+    pub fn parse(tag: &str, data: &str) -> Result<Self> {
+        let mut locale = Self::new(tag);
+        let mut last: Option<String> = None;
+
+        for (number, line) in data.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                let message = last
+                    .as_ref()
+                    .and_then(|id| locale.messages.get_mut(id))
+                    .ok_or(Error::InvalidLocale(number + 1))?;
+                if !message.is_empty() {
+                    message.push('\n');
+                }
+                message.push_str(line.trim());
+                continue;
+            }
+
+            let (id, message) = line
+                .split_once('=')
+                .ok_or(Error::InvalidLocale(number + 1))?;
+            let id = id.trim();
+            if id.is_empty() {
+                return Err(Error::InvalidLocale(number + 1));
+            }
+            locale.insert(id, message.trim());
+            last = Some(id.to_string());
+        }
+
+        Ok(locale)
+    }
+
+    /// Language tag of the locale, e.g. `de-DE`.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn insert(&mut self, id: &str, message: &str) {
+        self.messages.insert(id.to_string(), message.to_string());
+    }
+
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.messages.get(id).map(String::as_str)
+    }
+
+    /// Format the message `id` with the given arguments, if the locale has a translation for it.
+    pub fn format(&self, id: &str, args: &[(&str, String)]) -> Option<String> {
+        self.get(id).map(|message| substitute(message, args))
+    }
+}
+
+/// Replace the `{ $name }` placeholders in message, unknown placeholders are kept as is.
+fn substitute(message: &str, args: &[(&str, String)]) -> String {
+    let mut ret = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find('{') {
+        ret.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let name = rest[start + 1..end].trim();
+        match args
+            .iter()
+            .find(|(arg, _)| name.strip_prefix('$') == Some(*arg))
+        {
+            Some((_, value)) => ret.push_str(value),
+            None => ret.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    ret.push_str(rest);
+
+    ret
+}
+
+/// Phases reported while preparing and starting an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Resolve,
+    Download,
+    Verify,
+    Launch,
+}
+
+impl Phase {
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            Self::Resolve => "phase-resolve",
+            Self::Download => "phase-download",
+            Self::Verify => "phase-verify",
+            Self::Launch => "phase-launch",
+        }
+    }
+
+    pub fn localize(&self, locale: &Locale) -> String {
+        locale
+            .format(self.message_id(), &[])
+            .unwrap_or_else(|| self.to_string())
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Resolve => "Resolving components",
+            Self::Download => "Downloading",
+            Self::Verify => "Verifying files",
+            Self::Launch => "Launching",
+        })
+    }
+}
+
+impl Error {
+    /// Id and arguments of the message describing this error.
+    pub fn message(&self) -> (&'static str, Vec<(&'static str, String)>) {
+        match self {
+            Self::Io(e) => ("error-io", vec![("error", e.to_string())]),
+            Self::Json(e) => ("error-json", vec![("error", e.to_string())]),
+            Self::FromHex(e) => ("error-hex", vec![("error", e.to_string())]),
+            Self::FromUtf8(e) => ("error-utf8", vec![("error", e.to_string())]),
+            Self::FromZip(e) => ("error-zip", vec![("error", e.to_string())]),
+            Self::LibraryInvalidName => ("error-library-invalid-name", vec![]),
+            Self::LibraryNotSupported => ("error-library-not-supported", vec![]),
+            Self::LibraryMissing => ("error-library-missing", vec![]),
+            Self::LibraryInvalidHash => ("error-library-invalid-hash", vec![]),
+            Self::LibraryInvalidSize { expected, actual } => (
+                "error-library-invalid-size",
+                vec![
+                    ("expected", expected.to_string()),
+                    ("actual", actual.to_string()),
+                ],
+            ),
+            Self::MetaIndexInvalidHash => ("error-meta-index-invalid-hash", vec![]),
+            Self::MetaUnknownFields(fields) => (
+                "error-meta-unknown-fields",
+                vec![("fields", fields.join(", "))],
+            ),
+            Self::MetaFormatUnsupported {
+                file_type,
+                version,
+                min,
+                max,
+            } => (
+                "error-meta-format-unsupported",
+                vec![
+                    ("file", file_type.to_string()),
+                    ("version", version.to_string()),
+                    ("min", min.to_string()),
+                    ("max", max.to_string()),
+                ],
+            ),
+            Self::MetaNotFound => ("error-meta-not-found", vec![]),
+            Self::MissingAssetIndex(uid) => {
+                ("error-missing-asset-index", vec![("uid", uid.to_string())])
+            }
+            Self::PendingDownloads => ("error-pending-downloads", vec![]),
+            Self::UnknownUid(uid) => ("error-unknown-uid", vec![("uid", uid.to_string())]),
+            Self::UnknownVersion { uid, version } => (
+                "error-unknown-version",
+                vec![("uid", uid.to_string()), ("version", version.to_string())],
+            ),
+            Self::DuplicateUid { uid, first, second } => (
+                "error-duplicate-uid",
+                vec![
+                    ("uid", uid.to_string()),
+                    ("first", first.to_string()),
+                    ("second", second.to_string()),
+                ],
+            ),
+            Self::InvalidVersionRange(range) => (
+                "error-invalid-version-range",
+                vec![("range", range.to_string())],
+            ),
+            Self::RequirementConflict {
+                uid,
+                version,
+                reason,
+            } => (
+                "error-requirement-conflict",
+                vec![
+                    ("uid", uid.to_string()),
+                    ("version", version.to_string()),
+                    ("reason", reason.to_string()),
+                ],
+            ),
+            Self::NoGameProfile => ("error-no-game-profile", vec![]),
+            Self::ProfileRequest(e) => ("error-profile-request", vec![("error", e.to_string())]),
+            Self::XboxAuth(e) => ("error-xbox-auth", vec![("error", e.to_string())]),
+            Self::JavaFailed(e) => ("error-java-failed", vec![("error", e.to_string())]),
+            Self::NoCompatibleJava(major) => (
+                "error-no-compatible-java",
+                vec![("major", major.to_string())],
+            ),
+            Self::SandboxNotSupported(wrapper) => (
+                "error-sandbox-not-supported",
+                vec![("wrapper", wrapper.to_string())],
+            ),
+            Self::Locked(path) => ("error-locked", vec![("path", path.to_string())]),
+            Self::CrashLoop(count) => ("error-crash-loop", vec![("count", count.to_string())]),
+            Self::Cancelled => ("error-cancelled", vec![]),
+            Self::InvalidLocale(line) => ("error-invalid-locale", vec![("line", line.to_string())]),
+            Self::BlockedUrl(url) => ("error-blocked-url", vec![("url", url.to_string())]),
+            Self::InvalidHostPattern(pattern) => (
+                "error-invalid-host-pattern",
+                vec![("pattern", pattern.to_string())],
+            ),
+            Self::UnknownTemplate(name) => {
+                ("error-unknown-template", vec![("name", name.to_string())])
+            }
+            Self::Download { url, reason } => (
+                "error-download",
+                vec![("url", url.to_string()), ("reason", reason.to_string())],
+            ),
+            Self::InvalidUsername(name) => {
+                ("error-invalid-username", vec![("name", name.to_string())])
+            }
+            Self::LaunchStalled { waited, .. } => {
+                ("error-launch-stalled", vec![("waited", waited.to_string())])
+            }
+            Self::JavaAgentMissing(path) => {
+                ("error-java-agent-missing", vec![("path", path.to_string())])
+            }
+            Self::InvalidSnapshot(reason) => (
+                "error-invalid-snapshot",
+                vec![("reason", reason.to_string())],
+            ),
+            Self::UpdateInvalid(reason) => {
+                ("error-update-invalid", vec![("reason", reason.to_string())])
+            }
+            Self::LoggerInstalled => ("error-logger-installed", vec![]),
+            Self::Throttled { url, .. } => ("error-throttled", vec![("url", url.to_string())]),
+            Self::OutsideLocalSource(url) => {
+                ("error-outside-local-source", vec![("url", url.to_string())])
+            }
+            Self::InvalidStateArchive(reason) => (
+                "error-invalid-state-archive",
+                vec![("reason", reason.to_string())],
+            ),
+            Self::NoForeignStores(dir) => {
+                ("error-no-foreign-stores", vec![("dir", dir.to_string())])
+            }
+            Self::SessionLimitReached(name) => (
+                "error-session-limit-reached",
+                vec![("name", name.to_string())],
+            ),
+            Self::MainJarCorrupted(path) => {
+                ("error-main-jar-corrupted", vec![("path", path.to_string())])
+            }
+            Self::InvalidFileName(name) => {
+                ("error-invalid-file-name", vec![("name", name.to_string())])
+            }
+        }
+    }
+
+    /// Describe the error in the language of locale, falling back to English.
+    pub fn localize(&self, locale: &Locale) -> String {
+        let (id, args) = self.message();
+        locale.format(id, &args).unwrap_or_else(|| self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn localize_error() {
+        let locale = Locale::parse(
+            "de",
+            "# Fehlermeldungen\n\
+             error-unknown-version = Unbekannte Version { $version }\n  von {$uid}\n\
+             phase-launch = Starten\n",
+        )
+        .unwrap();
+
+        let e = Error::UnknownVersion {
+            uid: "net.minecraft".to_string(),
+            version: "1.18".to_string(),
+        };
+        assert_eq!(
+            e.localize(&locale),
+            "Unbekannte Version 1.18\nvon net.minecraft"
+        );
+        assert_eq!(Phase::Launch.localize(&locale), "Starten");
+
+        // English fallback
+        assert_eq!(
+            Error::Cancelled.localize(&locale),
+            "Operation was cancelled"
+        );
+        assert_eq!(Phase::Verify.localize(&locale), "Verifying files");
+
+        assert!(matches!(
+            Locale::parse("de", "kein text"),
+            Err(Error::InvalidLocale(1))
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Java agent attached to the game with `-javaagent`, e.g. authlib-injector or a profiler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaAgent {
+    /// Jar of the agent, relative paths are resolved against the game directory.
+    pub path: PathBuf,
+    /// Options passed to the agent after `=`.
+    #[serde(default)]
+    pub options: Option<String>,
+}
+
+impl JavaAgent {
+    /// Path of the jar for the game directory `game_dir`.
+    pub fn path_at<P: AsRef<Path>>(&self, game_dir: P) -> PathBuf {
+        game_dir.as_ref().join(&self.path)
+    }
+
+    /// JVM argument attaching the agent for the game directory `game_dir`.
+    pub fn arg_at<P: AsRef<Path>>(&self, game_dir: P) -> String {
+        let mut ret = format!("-javaagent:{}", self.path_at(game_dir).display());
+        if let Some(options) = &self.options {
+            ret.push('=');
+            ret.push_str(options);
+        }
+        ret
+    }
+
+    /// Fails with [`Error::JavaAgentMissing`] if the jar does not exist.
+    pub fn verify_at<P: AsRef<Path>>(&self, game_dir: P) -> Result<()> {
+        let path = self.path_at(game_dir);
+        if path.is_file() {
+            Ok(())
+        } else {
+            Err(Error::JavaAgentMissing(path.display().to_string()))
+        }
+    }
+}
+
+/// Parses `path[=options]`, like the value of `-javaagent`.
+impl std::str::FromStr for JavaAgent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (path, options) = match s.split_once('=') {
+            Some((path, options)) => (path, Some(options.to_string())),
+            None => (s, None),
+        };
+        if path.is_empty() {
+            return Err(Error::JavaAgentMissing(s.to_string()));
+        }
+
+        Ok(Self {
+            path: path.into(),
+            options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn java_agent() {
+        let dir = TempDir::new("agent");
+        std::fs::create_dir_all(dir.join("agents")).unwrap();
+
+        let agent: JavaAgent = "agents/authlib-injector.jar=https://example.com/api"
+            .parse()
+            .unwrap();
+        assert_eq!(agent.options.as_deref(), Some("https://example.com/api"));
+        assert_eq!(
+            agent.arg_at(&dir),
+            format!(
+                "-javaagent:{}=https://example.com/api",
+                dir.join("agents/authlib-injector.jar").display()
+            )
+        );
+        assert!(matches!(
+            agent.verify_at(&dir),
+            Err(Error::JavaAgentMissing(_))
+        ));
+        std::fs::write(dir.join("agents/authlib-injector.jar"), "").unwrap();
+        agent.verify_at(&dir).unwrap();
+
+        #[cfg(unix)]
+        {
+            let agent: JavaAgent = "/opt/profiler.jar".parse().unwrap();
+            assert_eq!(agent.arg_at(&dir), "-javaagent:/opt/profiler.jar");
+        }
+        assert!("=options".parse::<JavaAgent>().is_err());
+    }
+}
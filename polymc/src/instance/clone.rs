@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use tracing::*;
+
+use crate::lock::{DirLock, LOCK_FILE_NAME};
+use crate::Result;
+
+use super::{Instance, InstanceInfo, INSTANCE_INFO_FILE, MODS_DIR, MODS_LOCK_FILE};
+
+/// Files of the game directory which belong to a session and are never cloned.
+const SESSION_FILES: &[&str] = &["logs", "crash-reports", "natives", LOCK_FILE_NAME];
+
+/// Parts of the game directory copied by [`clone_instance_dir`]. The instance info is always
+/// copied.
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    /// The `mods` directory and the mod lock.
+    pub mods: bool,
+    /// The `config` directory of the mods and the game options.
+    pub config: bool,
+    /// The worlds in `saves`.
+    pub saves: bool,
+    /// Everything else, e.g. resource packs and screenshots, except logs and crash reports.
+    pub other: bool,
+    /// Hardlink jars and zips instead of copying them, falling back to a copy on file systems
+    /// without hardlinks. Other files are always copied, the game changes them in place.
+    pub hardlink: bool,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            mods: false,
+            config: false,
+            saves: false,
+            other: false,
+            hardlink: true,
+        }
+    }
+}
+
+impl CloneOptions {
+    /// Clone the whole game directory.
+    pub fn all() -> Self {
+        Self {
+            mods: true,
+            config: true,
+            saves: true,
+            other: true,
+            ..Default::default()
+        }
+    }
+
+    fn includes(&self, name: &str) -> bool {
+        match name {
+            INSTANCE_INFO_FILE => false,
+            MODS_DIR | MODS_LOCK_FILE => self.mods,
+            "config" | "options.txt" => self.config,
+            "saves" => self.saves,
+            name if is_session_file(name) => false,
+            _ => self.other,
+        }
+    }
+}
+
+/// Whether the file `name` of the game directory belongs to a session, like logs and crash
+/// reports, and is not worth keeping in copies of the instance.
+pub(crate) fn is_session_file(name: &str) -> bool {
+    SESSION_FILES.contains(&name) || name.starts_with("hs_err_pid")
+}
+
+impl Instance {
+    /// Clone the instance into a directory named `new_name` next to its game directory.
+    ///
+    /// Fails with [`crate::Error::Locked`] while the instance is running.
+    pub fn clone_to(&self, new_name: &str, options: &CloneOptions) -> Result<Instance> {
+        let from = Path::new(&self.minecraft_path);
+        let to = from
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(new_name);
+        let info = self.info()?;
+        clone_instance_dir(from, &to, new_name, Some(&info), options)?;
+
+        let mut ret = self.clone();
+        ret.name = new_name.to_string();
+        ret.minecraft_path = to.display().to_string();
+        Ok(ret)
+    }
+}
+
+/// Clone the instance in `from` into the new directory `to` as `name`.
+///
+/// `info` is used if the instance has no info file yet. The play statistics of the clone start
+/// at zero.
+pub fn clone_instance_dir<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    to: Q,
+    name: &str,
+    info: Option<&InstanceInfo>,
+    options: &CloneOptions,
+) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if to.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        )
+        .into());
+    }
+    let _lock = DirLock::try_lock(from)?;
+
+    let mut info = match InstanceInfo::load(from) {
+        Ok(info) => info,
+        Err(_) => info.cloned().unwrap_or_default(),
+    };
+    info.name = name.to_string();
+    info.last_played = None;
+    info.last_exit_code = None;
+    info.last_account = None;
+    info.last_version = None;
+    info.playtime = 0;
+    // The registry entry belongs to the original instance
+    info.gpu_registered = None;
+
+    std::fs::create_dir_all(to)?;
+    let result = (|| {
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if options.includes(&file_name.to_string_lossy()) {
+                copy_entry(&entry.path(), &to.join(&file_name), options.hardlink)?;
+            }
+        }
+        info.save(to)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(to);
+    }
+    result
+}
+
+fn copy_entry(from: &Path, to: &Path, hardlink: bool) -> Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &to.join(entry.file_name()), hardlink)?;
+        }
+        return Ok(());
+    }
+
+    let archive = matches!(
+        from.extension().and_then(|e| e.to_str()),
+        Some("jar" | "zip")
+    );
+    if hardlink && archive {
+        match std::fs::hard_link(from, to) {
+            Ok(()) => return Ok(()),
+            Err(e) => trace!("copying {}, hardlink failed: {}", from.display(), e),
+        }
+    }
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn clone_dir() {
+        let dir = TempDir::new("clone");
+        let from = dir.join("a");
+        for sub in ["mods", "config", "saves/world", "logs", "resourcepacks"] {
+            std::fs::create_dir_all(from.join(sub)).unwrap();
+        }
+        for file in [
+            "mods/a.jar",
+            "config/a.toml",
+            "options.txt",
+            "saves/world/level.dat",
+            "logs/latest.log",
+            "resourcepacks/pack.zip",
+        ] {
+            std::fs::write(from.join(file), file).unwrap();
+        }
+        InstanceInfo {
+            name: "a".to_string(),
+            version: "1.18.2".to_string(),
+            playtime: 60,
+            ..Default::default()
+        }
+        .save(&from)
+        .unwrap();
+
+        let options = CloneOptions {
+            mods: true,
+            saves: true,
+            ..Default::default()
+        };
+        let to = dir.join("b");
+        clone_instance_dir(&from, &to, "b", None, &options).unwrap();
+        let info = InstanceInfo::load(&to).unwrap();
+        assert_eq!(info.name, "b");
+        assert_eq!(info.version, "1.18.2");
+        assert_eq!(info.playtime, 0);
+        assert!(to.join("mods/a.jar").is_file());
+        assert!(to.join("saves/world/level.dat").is_file());
+        assert!(!to.join("config").exists());
+        assert!(!to.join("options.txt").exists());
+        assert!(!to.join("resourcepacks").exists());
+        assert!(!to.join("logs").exists());
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let nlink = |p: &str| std::fs::metadata(to.join(p)).unwrap().nlink();
+            assert_eq!(nlink("mods/a.jar"), 2);
+            assert_eq!(nlink("saves/world/level.dat"), 1);
+        }
+
+        // The target has to be new
+        assert!(clone_instance_dir(&from, &to, "b", None, &options).is_err());
+        let to = dir.join("c");
+        clone_instance_dir(&from, &to, "c", None, &CloneOptions::all()).unwrap();
+        assert!(to.join("resourcepacks/pack.zip").is_file());
+        assert!(to.join("options.txt").is_file());
+        assert!(!to.join("logs").exists());
+    }
+}
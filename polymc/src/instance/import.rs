@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use super::{Instance, InstanceInfo};
+use crate::Result;
+
+#[derive(Deserialize)]
+struct LauncherProfiles {
+    #[serde(default)]
+    profiles: HashMap<String, LauncherProfile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LauncherProfile {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    profile_type: Option<String>,
+    last_version_id: Option<String>,
+    game_dir: Option<String>,
+    java_dir: Option<String>,
+    java_args: Option<String>,
+    resolution: Option<Resolution>,
+}
+
+#[derive(Deserialize)]
+struct Resolution {
+    width: u32,
+    height: u32,
+}
+
+/// Profile of the vanilla launcher, to create an instance from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VanillaProfile {
+    pub name: String,
+    /// Version of the game, `latest-release` or `latest-snapshot` for the profiles following the
+    /// newest version.
+    pub version: String,
+    /// Game directory, the vanilla launcher defaults to `.minecraft` if not set.
+    pub game_dir: Option<String>,
+    /// Path to the java executable.
+    pub java: Option<String>,
+    pub java_args: Vec<String>,
+    pub resolution: Option<(u32, u32)>,
+}
+
+impl VanillaProfile {
+    /// Info to save in the new instance directory.
+    pub fn info(&self) -> InstanceInfo {
+        InstanceInfo {
+            name: self.name.to_string(),
+            version: self.version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply the JVM arguments and resolution of the profile to an instance created for it.
+    ///
+    /// `-Xms` and `-Xmx` are moved into the instance config.
+    pub fn apply(&self, instance: &mut Instance) {
+        for arg in &self.java_args {
+            if let Some(min) = arg.strip_prefix("-Xms") {
+                instance.config.min = Some(min.to_string());
+            } else if let Some(max) = arg.strip_prefix("-Xmx") {
+                instance.config.max = Some(max.to_string());
+            } else {
+                instance.java_opts.push(arg.to_string());
+            }
+        }
+
+        if let Some((width, height)) = self.resolution {
+            instance.config.width = width;
+            instance.config.height = height;
+        }
+    }
+}
+
+/// Import the profiles of the vanilla launcher from `launcher_profiles.json`.
+pub fn import_vanilla_profiles<P: AsRef<Path>>(path: P) -> Result<Vec<VanillaProfile>> {
+    parse_vanilla_profiles(&std::fs::read(path)?)
+}
+
+/// Parse the content of the vanilla launcher's `launcher_profiles.json`.
+pub fn parse_vanilla_profiles(data: &[u8]) -> Result<Vec<VanillaProfile>> {
+    let profiles: LauncherProfiles = serde_json::from_slice(data)?;
+
+    let mut ret: Vec<VanillaProfile> = profiles
+        .profiles
+        .into_values()
+        .map(|profile| {
+            let profile_type = profile.profile_type.unwrap_or_default();
+            let latest = match profile_type.as_str() {
+                "latest-snapshot" => "latest-snapshot",
+                _ => "latest-release",
+            };
+            let name = if profile.name.is_empty() {
+                latest.replace('-', " ")
+            } else {
+                profile.name
+            };
+
+            VanillaProfile {
+                name,
+                version: profile
+                    .last_version_id
+                    .unwrap_or_else(|| latest.to_string()),
+                game_dir: profile.game_dir,
+                java: profile.java_dir,
+                java_args: profile
+                    .java_args
+                    .map(|args| parse_java_args(&args))
+                    .unwrap_or_default(),
+                resolution: profile.resolution.map(|r| (r.width, r.height)),
+            }
+        })
+        .collect();
+
+    // profiles are stored in a map, keep the order stable
+    ret.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(ret)
+}
+
+/// Split the Java arguments of a profile like a shell, keeping quoted paths with spaces in one
+/// argument.
+fn parse_java_args(args: &str) -> Vec<String> {
+    shell_words::split(args).unwrap_or_else(|e| {
+        warn!("Failed to parse the Java arguments '{}': {}", args, e);
+        args.split_whitespace().map(str::to_string).collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::SearchResult;
+
+    #[test]
+    fn vanilla_profiles() {
+        let data = br#"{"version": 3, "profiles": {
+            "a": {"name": "", "type": "latest-release", "lastVersionId": "latest-release"},
+            "b": {
+                "name": "Modded", "type": "custom", "lastVersionId": "1.18.1",
+                "gameDir": "/games/modded", "javaArgs": "-Xmx4G  -XX:+UseG1GC -Dlog.dir=\"/games/my logs\"",
+                "resolution": {"width": 1280, "height": 720}
+            }
+        }}"#;
+
+        let profiles = parse_vanilla_profiles(data).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "Modded");
+        assert_eq!(profiles[1].version, "latest-release");
+
+        let modded = &profiles[0];
+        let mut instance = Instance::new(
+            &modded.name,
+            &modded.version,
+            modded.game_dir.as_deref().unwrap(),
+            SearchResult::new(Vec::new(), "net.minecraft"),
+        );
+        modded.apply(&mut instance);
+        assert_eq!(instance.config.max.as_deref(), Some("4G"));
+        assert_eq!(instance.config.width, 1280);
+        assert_eq!(
+            instance.java_opts,
+            vec!["-XX:+UseG1GC", "-Dlog.dir=/games/my logs"]
+        );
+        assert_eq!(parse_java_args("-Dunclosed=\"a b"), ["-Dunclosed=\"a", "b"]);
+    }
+}
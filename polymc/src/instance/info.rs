@@ -0,0 +1,501 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use crate::download::PartialFile;
+use crate::gpu::GpuPreference;
+use crate::log_forward::LogForwarding;
+use crate::meta::AssetIndexRef;
+use crate::Result;
+
+use super::{JavaAgent, LaunchOverrides, LauncherLogPolicy, MainJarPin, SessionLimit};
+
+/// Name of the file in the instance directory holding its [`InstanceInfo`].
+pub const INSTANCE_INFO_FILE: &str = "instance.json";
+
+/// Metadata of an instance shown in instance lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceInfo {
+    pub name: String,
+    pub version: String,
+    /// Group the instance is listed in, e.g. "Modded".
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Last start of the game in seconds since the unix epoch.
+    #[serde(default)]
+    pub last_played: Option<u64>,
+    /// Exit code of the last session, `None` while it runs or if the game was killed.
+    #[serde(default)]
+    pub last_exit_code: Option<i32>,
+    /// Name of the account the game was last started with.
+    #[serde(default)]
+    pub last_account: Option<String>,
+    /// Game version of the last start.
+    #[serde(default)]
+    pub last_version: Option<String>,
+    /// Total time played in seconds.
+    #[serde(default)]
+    pub playtime: u64,
+    /// Day of [`InstanceInfo::playtime_today`] in days since the unix epoch, in local time.
+    #[serde(default)]
+    pub playtime_day: u64,
+    /// Time played on `playtime_day` in seconds, sessions count for the day they ended on.
+    #[serde(default)]
+    pub playtime_today: u64,
+    /// Limits on the time the instance may be played.
+    #[serde(default, skip_serializing_if = "SessionLimit::is_empty")]
+    pub session_limit: SessionLimit,
+    /// Components the instance was created with, e.g. by an
+    /// [`InstanceTemplate`](super::InstanceTemplate).
+    #[serde(default)]
+    pub components: Vec<InstanceComponent>,
+    /// Client jar of the components when the instance was last resolved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_jar_pin: Option<MainJarPin>,
+    /// Asset index the instance was last resolved with, the next index is compared with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_index: Option<AssetIndexRef>,
+    /// When to verify the hashes of the assets before starting the game.
+    #[serde(default)]
+    pub asset_verification: AssetVerification,
+    /// Last verification of the assets in seconds since the unix epoch.
+    #[serde(default)]
+    pub assets_verified: Option<u64>,
+    /// Java agents attached to the game.
+    #[serde(default)]
+    pub java_agents: Vec<JavaAgent>,
+    /// Capture of the game output in the instance directory.
+    #[serde(default)]
+    pub launcher_log: LauncherLogPolicy,
+    /// Changes to the command taking precedence over the components.
+    #[serde(default, skip_serializing_if = "LaunchOverrides::is_empty")]
+    pub overrides: LaunchOverrides,
+    /// GPU to render on, the one chosen by the system if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<GpuPreference>,
+    /// Java executable the GPU preference was stored in the registry for on Windows, to remove
+    /// it again once the instance has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_registered: Option<PathBuf>,
+    /// Collector the output of the game and the launcher events are forwarded to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_forwarding: Option<LogForwarding>,
+}
+
+/// Policy for verifying the assets before a launch, checking their hashes is slow.
+///
+/// Assets which are missing are always downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssetVerification {
+    Always,
+    /// Until the assets were verified once.
+    OnFirstLaunch,
+    /// If the last verification is older than a week.
+    Weekly,
+    Never,
+}
+
+// `#[default]` on variants needs a newer Rust than the MSRV
+#[allow(clippy::derivable_impls)]
+impl Default for AssetVerification {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
+/// Package of the meta server in a fixed version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceComponent {
+    pub uid: String,
+    pub version: String,
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Time between verifications with [`AssetVerification::Weekly`].
+const ASSET_VERIFICATION_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Uids of mod loaders with the name mod repositories use for them.
+const MOD_LOADERS: &[(&str, &str)] = &[
+    ("net.fabricmc.fabric-loader", "fabric"),
+    ("org.quiltmc.quilt-loader", "quilt"),
+    ("net.minecraftforge", "forge"),
+];
+
+impl InstanceInfo {
+    /// Load the info of the instance in `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(dir.as_ref().join(INSTANCE_INFO_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Load the info of the instance in `dir`, `None` if it has none yet.
+    ///
+    /// Unlike a missing file, an unreadable one is an error, so it isn't replaced by a new info.
+    pub fn load_existing<P: AsRef<Path>>(dir: P) -> Result<Option<Self>> {
+        match Self::load(dir) {
+            Ok(info) => Ok(Some(info)),
+            Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the info into the instance directory `dir`.
+    ///
+    /// The info is replaced at once, so an interrupted save leaves the previous one intact.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let mut file = PartialFile::create(dir.as_ref().join(INSTANCE_INFO_FILE))?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.persist()?;
+        Ok(())
+    }
+
+    /// Version of the component `uid`.
+    pub fn component(&self, uid: &str) -> Option<&str> {
+        self.components
+            .iter()
+            .find(|c| c.uid == uid)
+            .map(|c| c.version.as_str())
+    }
+
+    /// Mod loader of the instance as named by mod repositories, e.g. `fabric`.
+    pub fn mod_loader(&self) -> Option<&'static str> {
+        MOD_LOADERS
+            .iter()
+            .find(|(uid, _)| self.component(uid).is_some())
+            .map(|(_, name)| *name)
+    }
+
+    /// Record a start of the game at `started` with the account `account`.
+    pub fn record_launch(&mut self, started: SystemTime, account: &str) {
+        let started = started.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.last_played = Some(started.as_secs());
+        self.last_exit_code = None;
+        self.last_account = Some(account.to_string());
+        self.last_version = Some(self.version.clone());
+    }
+
+    /// Time of the last start of the game.
+    pub fn last_played_time(&self) -> Option<SystemTime> {
+        self.last_played
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Whether the policy of the instance asks to verify the assets before a launch at `now`.
+    pub fn should_verify_assets(&self, now: SystemTime) -> bool {
+        let verified = match self.assets_verified {
+            Some(verified) => UNIX_EPOCH + Duration::from_secs(verified),
+            None => return self.asset_verification != AssetVerification::Never,
+        };
+
+        match self.asset_verification {
+            AssetVerification::Always => true,
+            AssetVerification::OnFirstLaunch | AssetVerification::Never => false,
+            AssetVerification::Weekly => {
+                now.duration_since(verified).unwrap_or_default() >= ASSET_VERIFICATION_INTERVAL
+            }
+        }
+    }
+
+    /// Record that all assets were verified at `at`.
+    pub fn record_assets_verified(&mut self, at: SystemTime) {
+        let at = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.assets_verified = Some(at.as_secs());
+    }
+
+    /// Record a game session which started at `started` and just ended.
+    pub fn record_session(&mut self, started: SystemTime) {
+        let started = started.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.last_played = Some(started.as_secs());
+        let played = now.saturating_sub(started).as_secs();
+        self.playtime += played;
+
+        let today = local_day(now.as_secs());
+        if self.playtime_day != today {
+            self.playtime_day = today;
+            self.playtime_today = 0;
+        }
+        self.playtime_today += played;
+    }
+
+    /// Seconds played on the day of `now`, in local time.
+    pub fn played_today(&self, now: SystemTime) -> u64 {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        if local_day(now.as_secs()) == self.playtime_day {
+            self.playtime_today
+        } else {
+            0
+        }
+    }
+
+    /// Time left to play at `now` under the session limit, `None` without a limit.
+    pub fn remaining_play_time(&self, now: SystemTime) -> Option<Duration> {
+        self.session_limit.remaining(self.played_today(now))
+    }
+}
+
+/// Day of `secs` since the unix epoch in the local time zone, so daily limits reset at midnight.
+fn local_day(secs: u64) -> u64 {
+    day(secs, utc_offset(secs))
+}
+
+/// Day of `secs` since the unix epoch in a time zone `utc_offset` seconds ahead of UTC.
+fn day(secs: u64, utc_offset: i64) -> u64 {
+    (secs as i64 + utc_offset).max(0) as u64 / DAY
+}
+
+/// Offset of the local time zone from UTC at `secs` since the unix epoch, in seconds.
+#[cfg(target_family = "unix")]
+fn utc_offset(secs: u64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: tm is plain data, localtime_r only writes to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(target_family = "unix"))]
+fn utc_offset(_secs: u64) -> i64 {
+    // TODO: use GetTimeZoneInformation on windows
+    0
+}
+
+/// Instance found by [`list_instances`].
+#[derive(Debug, Clone)]
+pub struct InstanceEntry {
+    pub path: PathBuf,
+    pub info: InstanceInfo,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InstanceSort {
+    /// Alphabetically by name, ignoring case.
+    Name,
+    /// Most recently played first, never played instances last.
+    LastPlayed,
+}
+
+/// List all instances in the subdirectories of `root`.
+///
+/// Directories without an [`INSTANCE_INFO_FILE`] are skipped, as are instances whose info can't
+/// be read, so one broken instance doesn't hide all others.
+pub fn list_instances<P: AsRef<Path>>(root: P) -> Result<Vec<InstanceEntry>> {
+    let mut ret = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        if !path.join(INSTANCE_INFO_FILE).is_file() {
+            continue;
+        }
+
+        match InstanceInfo::load(&path) {
+            Ok(info) => ret.push(InstanceEntry { path, info }),
+            Err(e) => warn!("Skipping the instance in {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(ret)
+}
+
+pub fn sort_instances(instances: &mut [InstanceEntry], sort: InstanceSort) {
+    match sort {
+        InstanceSort::Name => {
+            instances.sort_by_key(|i| i.info.name.to_lowercase());
+        }
+        InstanceSort::LastPlayed => {
+            instances.sort_by_key(|i| std::cmp::Reverse(i.info.last_played));
+        }
+    }
+}
+
+/// Group instances by their group, instances without one are grouped under `None`.
+pub fn group_instances(
+    instances: Vec<InstanceEntry>,
+    sort: InstanceSort,
+) -> BTreeMap<Option<String>, Vec<InstanceEntry>> {
+    let mut ret: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for instance in instances {
+        ret.entry(instance.info.group.clone())
+            .or_default()
+            .push(instance);
+    }
+
+    for instances in ret.values_mut() {
+        sort_instances(instances, sort);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn entry(name: &str, group: Option<&str>, last_played: Option<u64>) -> InstanceEntry {
+        InstanceEntry {
+            path: PathBuf::from(name),
+            info: InstanceInfo {
+                name: name.to_string(),
+                group: group.map(str::to_string),
+                last_played,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn group_and_sort() {
+        let instances = vec![
+            entry("b", Some("Modded"), Some(1)),
+            entry("a", Some("Modded"), Some(2)),
+            entry("c", None, None),
+        ];
+
+        let groups = group_instances(instances.clone(), InstanceSort::Name);
+        assert_eq!(groups.len(), 2);
+        let modded = &groups[&Some("Modded".to_string())];
+        assert_eq!(modded[0].info.name, "a");
+        assert_eq!(modded[1].info.name, "b");
+
+        let mut instances = instances;
+        sort_instances(&mut instances, InstanceSort::LastPlayed);
+        let names: Vec<_> = instances.iter().map(|i| i.info.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn record_launch() {
+        let mut info = InstanceInfo {
+            version: "1.18.2".to_string(),
+            last_exit_code: Some(1),
+            ..Default::default()
+        };
+        let started = UNIX_EPOCH + Duration::from_secs(1000);
+        info.record_launch(started, "steve");
+        assert_eq!(info.last_played_time(), Some(started));
+        assert_eq!(info.last_exit_code, None);
+        assert_eq!(info.last_account.as_deref(), Some("steve"));
+        assert_eq!(info.last_version.as_deref(), Some("1.18.2"));
+
+        // Instances saved before the launch was recorded
+        let info: InstanceInfo =
+            serde_json::from_str(r#"{"name": "a", "version": "1.0"}"#).unwrap();
+        assert_eq!(info.last_exit_code, None);
+        assert_eq!(info.last_account, None);
+    }
+
+    #[test]
+    fn played_today() {
+        let now = SystemTime::now();
+        let mut info = InstanceInfo {
+            playtime_day: 1,
+            playtime_today: 500,
+            session_limit: SessionLimit {
+                per_day: Some(60),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(info.played_today(now), 0);
+        assert_eq!(
+            info.remaining_play_time(now),
+            Some(Duration::from_secs(3600))
+        );
+
+        // Sessions of another day are not counted
+        info.record_session(now - Duration::from_secs(600));
+        assert!((600..610).contains(&info.played_today(now)));
+        info.record_session(now - Duration::from_secs(3000));
+        assert_eq!(info.remaining_play_time(now), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn local_days() {
+        // 23:00 UTC is the next day two hours east of it
+        assert_eq!(day(DAY - 3600, 0), 0);
+        assert_eq!(day(DAY - 3600, 7200), 1);
+        // 01:00 UTC is the previous day two hours west of it
+        assert_eq!(day(DAY + 3600, -7200), 0);
+        assert_eq!(day(0, -7200), 0);
+    }
+
+    #[test]
+    fn asset_verification() {
+        let now = UNIX_EPOCH + ASSET_VERIFICATION_INTERVAL * 2;
+        let policy = |asset_verification, verified: Option<Duration>| InstanceInfo {
+            asset_verification,
+            assets_verified: verified
+                .map(|d| (now - d).duration_since(UNIX_EPOCH).unwrap().as_secs()),
+            ..Default::default()
+        };
+        let day = Duration::from_secs(24 * 60 * 60);
+
+        assert!(policy(AssetVerification::Always, Some(day)).should_verify_assets(now));
+        assert!(policy(AssetVerification::OnFirstLaunch, None).should_verify_assets(now));
+        assert!(!policy(AssetVerification::OnFirstLaunch, Some(day)).should_verify_assets(now));
+        assert!(!policy(AssetVerification::Weekly, Some(day)).should_verify_assets(now));
+        assert!(policy(AssetVerification::Weekly, Some(day * 8)).should_verify_assets(now));
+        assert!(!policy(AssetVerification::Never, None).should_verify_assets(now));
+
+        let mut info = policy(AssetVerification::Weekly, None);
+        info.record_assets_verified(now);
+        assert!(!info.should_verify_assets(now));
+        let info: InstanceInfo = serde_json::from_str(
+            r#"{"name": "a", "version": "1.0", "assetVerification": "on-first-launch"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.asset_verification, AssetVerification::OnFirstLaunch);
+    }
+
+    #[test]
+    fn load_existing() {
+        let dir = TempDir::new("instance-info");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(InstanceInfo::load_existing(&dir).unwrap(), None);
+
+        let info = InstanceInfo {
+            name: "a".to_string(),
+            ..Default::default()
+        };
+        info.save(&dir).unwrap();
+        assert_eq!(InstanceInfo::load_existing(&dir).unwrap(), Some(info));
+
+        // A corrupt info is not taken for a missing one
+        std::fs::write(dir.join(INSTANCE_INFO_FILE), "{").unwrap();
+        assert!(InstanceInfo::load_existing(&dir).is_err());
+    }
+
+    #[test]
+    fn list_skips_broken() {
+        let root = TempDir::new("instance-list");
+        for name in ["a", "b"] {
+            let info = InstanceInfo {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            std::fs::create_dir_all(root.join(name)).unwrap();
+            info.save(root.join(name)).unwrap();
+        }
+        std::fs::write(root.join("b").join(INSTANCE_INFO_FILE), "{").unwrap();
+
+        let instances = list_instances(&root).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].info.name, "a");
+        // Nothing but the info is left behind by saving
+        assert_eq!(std::fs::read_dir(root.join("a")).unwrap().count(), 1);
+    }
+}
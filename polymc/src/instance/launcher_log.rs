@@ -0,0 +1,266 @@
+//! Capture of the game output in the instance directory, for crashes of games started without
+//! a console attached.
+//!
+//! Like the [`StartupWatchdog`](crate::watchdog::StartupWatchdog), the library does not read the
+//! output of the game itself: the frontend writes everything it reads from stdout and stderr to
+//! the [`LauncherLog`] of the launch, whether it shows the output somewhere else or not.
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "ctypes")]
+use std::ffi::CStr;
+#[cfg(feature = "ctypes")]
+use std::os::raw::c_char;
+
+#[cfg(feature = "ctypes")]
+use libc::c_int;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Directory of the logs in the instance directory, next to the logs of the game.
+pub const LAUNCHER_LOG_DIR: &str = "logs/launcher";
+
+/// How many logs are kept and how large they get.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LauncherLogPolicy {
+    /// Number of log files kept, older ones are deleted. The output is not captured if 0.
+    pub keep: usize,
+    /// Size in bytes after which the output continues in a new file.
+    pub max_size: u64,
+}
+
+impl Default for LauncherLogPolicy {
+    fn default() -> Self {
+        Self {
+            keep: 10,
+            max_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Log file of one launch, named after the time of the launch, e.g.
+/// `logs/launcher/2022-04-01_12-30-00.log`.
+#[derive(Debug)]
+pub struct LauncherLog {
+    dir: PathBuf,
+    name: String,
+    path: PathBuf,
+    file: File,
+    written: u64,
+    part: u32,
+    policy: LauncherLogPolicy,
+}
+
+impl LauncherLog {
+    /// Start the log of a launch at `now` in the instance directory `game_dir`, deleting the
+    /// logs exceeding the policy. Returns `None` if the policy keeps no logs.
+    pub fn create<P: AsRef<Path>>(
+        game_dir: P,
+        policy: &LauncherLogPolicy,
+        now: SystemTime,
+    ) -> Result<Option<Self>> {
+        if policy.keep == 0 {
+            return Ok(None);
+        }
+
+        let dir = game_dir.as_ref().join(LAUNCHER_LOG_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let (name, path, file) = create_unique(&dir, &timestamp(now))?;
+        prune(&dir, policy.keep)?;
+
+        Ok(Some(Self {
+            dir,
+            name,
+            path,
+            file,
+            written: 0,
+            part: 0,
+            policy: policy.clone(),
+        }))
+    }
+
+    /// File currently written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append output of the game, continuing in a new file once the current one is full.
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        let size = data.len() as u64;
+        if self.written > 0 && self.written + size > self.policy.max_size {
+            self.part += 1;
+            let (_, path, file) =
+                create_unique(&self.dir, &format!("{}.{}", self.name, self.part))?;
+            self.path = path;
+            self.file = file;
+            self.written = 0;
+            prune(&self.dir, self.policy.keep)?;
+        }
+
+        self.file.write_all(data)?;
+        self.written += size;
+        Ok(())
+    }
+
+    /// Start the log of a launch now in *game_dir*, keeping *keep* logs of at most *max_size*
+    /// bytes. Returns null if *keep* is 0 or the log can't be created.
+    ///
+    /// # Safety
+    /// game_dir has to be a valid CStr.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "launcher_log_create"]
+    pub unsafe extern "C" fn create_c(
+        game_dir: *const c_char,
+        keep: usize,
+        max_size: u64,
+    ) -> *mut Self {
+        let game_dir = match unsafe { CStr::from_ptr(game_dir) }.to_str() {
+            Ok(game_dir) => game_dir,
+            Err(_) => return core::ptr::null_mut(),
+        };
+        let policy = LauncherLogPolicy { keep, max_size };
+        match Self::create(game_dir, &policy, SystemTime::now()) {
+            Ok(Some(log)) => Box::into_raw(Box::new(log)),
+            _ => core::ptr::null_mut(),
+        }
+    }
+
+    /// Append *len* bytes of output at *data*.
+    ///
+    /// # Safety
+    /// data has to point to at least len bytes.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "launcher_log_write"]
+    pub unsafe extern "C" fn write_c(&mut self, data: *const u8, len: usize) -> c_int {
+        let data = unsafe { std::slice::from_raw_parts(data, len) };
+        match self.write(data) {
+            Ok(()) => 0,
+            Err(e) => -e.as_c_error(),
+        }
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "launcher_log_free"]
+    pub unsafe extern "C" fn free(v: *mut Self) {
+        if v.is_null() {
+            return;
+        }
+        let _ = unsafe { Box::from_raw(v) };
+    }
+}
+
+/// Create `name.log` in `dir`, or `name-1.log` and so on if it exists, e.g. for a restart within
+/// the same second.
+fn create_unique(dir: &Path, name: &str) -> Result<(String, PathBuf, File)> {
+    let mut unique = name.to_string();
+    for n in 1.. {
+        let path = dir.join(format!("{}.log", unique));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((unique, path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => unique = format!("{}-{}", name, n),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+/// Delete the oldest logs in `dir` beyond the newest `keep`.
+fn prune(dir: &Path, keep: usize) -> Result<()> {
+    let mut logs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("log") {
+            logs.push((entry.metadata()?.modified()?, path));
+        }
+    }
+
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for (_, path) in &logs[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// `at` in UTC as `YYYY-MM-DD_HH-MM-SS`, which sorts like the times.
+fn timestamp(at: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(at);
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Date and time of `at` in UTC: year, month, day, hour, minute and second.
+pub(crate) fn civil_time(at: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::time::Duration;
+
+    #[test]
+    fn launcher_log() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01_00-00-00");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(1709210096)),
+            "2024-02-29_12-34-56"
+        );
+
+        let dir = TempDir::new("launcher-log");
+        let policy = LauncherLogPolicy {
+            keep: 3,
+            max_size: 8,
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1709210096);
+        let mut log = LauncherLog::create(&dir, &policy, now).unwrap().unwrap();
+        log.write(b"[main/INFO]").unwrap();
+        log.write(b"crash").unwrap();
+        let logs = dir.join(LAUNCHER_LOG_DIR);
+        assert_eq!(
+            std::fs::read(logs.join("2024-02-29_12-34-56.log")).unwrap(),
+            b"[main/INFO]"
+        );
+        assert_eq!(log.path(), logs.join("2024-02-29_12-34-56.1.log"));
+        assert_eq!(std::fs::read(log.path()).unwrap(), b"crash");
+
+        // A restart within the same second gets its own file, the oldest ones are deleted
+        let log = LauncherLog::create(&dir, &policy, now).unwrap().unwrap();
+        assert_eq!(log.path(), logs.join("2024-02-29_12-34-56-1.log"));
+        std::thread::sleep(Duration::from_millis(10));
+        LauncherLog::create(&dir, &policy, now).unwrap().unwrap();
+        assert_eq!(std::fs::read_dir(&logs).unwrap().count(), 3);
+
+        let disabled = LauncherLogPolicy {
+            keep: 0,
+            ..Default::default()
+        };
+        assert!(LauncherLog::create(&dir, &disabled, now).unwrap().is_none());
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::meta::manifest::{Library, LibraryName, Sha1Sum, OS};
+use crate::{Error, Result};
+
+/// Client jar to use instead of the main jar of the components, e.g. a patched client.
+#[derive(Debug, Clone)]
+pub enum MainJar {
+    /// Jar at the given path.
+    Path {
+        path: PathBuf,
+        sha1: Option<Sha1Sum>,
+    },
+    /// Library in the library directory of the instance, given by its maven coordinate.
+    Library {
+        name: LibraryName,
+        sha1: Option<Sha1Sum>,
+    },
+}
+
+impl MainJar {
+    /// Path of the jar, libraries are resolved relative to `libraries_path`.
+    pub fn path_at(&self, libraries_path: &str) -> PathBuf {
+        match self {
+            Self::Path { path, .. } => path.clone(),
+            Self::Library { name, .. } => name.path_at(libraries_path),
+        }
+    }
+
+    pub fn sha1(&self) -> Option<&Sha1Sum> {
+        match self {
+            Self::Path { sha1, .. } | Self::Library { sha1, .. } => sha1.as_ref(),
+        }
+    }
+
+    /// Check that the jar exists and matches the pinned hash, if there is one.
+    pub fn verify_at(&self, libraries_path: &str) -> Result<()> {
+        let path = self.path_at(libraries_path);
+        if !Path::new(&path).is_file() {
+            return Err(Error::LibraryMissing);
+        }
+
+        let sha1 = match self.sha1() {
+            Some(sha1) => sha1,
+            None => return Ok(()),
+        };
+
+        if &crate::hash::sha1_file(&path)? == sha1 {
+            Ok(())
+        } else {
+            Err(Error::LibraryInvalidHash)
+        }
+    }
+}
+
+/// Hash and size of the client jar of the components, recorded in the [`InstanceInfo`] when the
+/// instance is resolved and checked before every launch, even if verification is skipped.
+///
+/// A corrupted client jar makes the game fail without a useful message, and is cheap to check
+/// compared to all libraries and assets.
+///
+/// [`InstanceInfo`]: super::InstanceInfo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MainJarPin {
+    /// Library of the main jar in the manifest, the pin does not apply once it changed.
+    pub name: LibraryName,
+    pub sha1: Sha1Sum,
+    pub size: u64,
+}
+
+impl MainJarPin {
+    /// Pin the main jar `library` at `path`, using the hash and size of the meta data if it has
+    /// them and of the file otherwise.
+    pub fn new(library: &Library, path: &Path) -> Result<Self> {
+        let download = library.select_for(&OS::get());
+        let size = download.as_ref().and_then(|d| u64::try_from(d.size).ok());
+        let sha1 = download.as_ref().and_then(|d| d.sha1.clone());
+        Ok(Self {
+            name: library.name.clone(),
+            sha1: match sha1 {
+                Some(sha1) => sha1,
+                None => crate::hash::sha1_file(path)?,
+            },
+            size: match size {
+                Some(size) => size,
+                None => path.metadata()?.len(),
+            },
+        })
+    }
+
+    /// Check the jar at `path` against the pin.
+    pub fn verify(&self, path: &Path) -> Result<()> {
+        let corrupted = || Error::MainJarCorrupted(path.display().to_string());
+        let size = match path.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(corrupted()),
+            Err(e) => return Err(e.into()),
+        };
+        if size != self.size || crate::hash::sha1_file(path)? != self.sha1 {
+            return Err(corrupted());
+        }
+        Ok(())
+    }
+}
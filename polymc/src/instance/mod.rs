@@ -0,0 +1,1501 @@
+use crate::auth::Auth;
+use crate::gpu::GpuPreference;
+use crate::lock::DirLock;
+use crate::meta::manifest::{join_class_path, Library, Manifest, OS};
+use crate::meta::{AssetIndex, ComponentSet, SearchResult};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+mod agent;
+mod clone;
+mod import;
+mod info;
+mod launcher_log;
+mod main_jar;
+mod mods;
+mod natives;
+mod overrides;
+mod sandbox;
+mod session_limit;
+mod template;
+
+pub use agent::*;
+pub use clone::*;
+pub use import::*;
+pub use info::*;
+pub use launcher_log::*;
+pub use main_jar::*;
+pub use mods::*;
+pub use natives::*;
+pub use overrides::*;
+pub use sandbox::*;
+pub use session_limit::*;
+pub use template::*;
+
+/// Component providing the asset index if no other component overrides it.
+pub const MINECRAFT_UID: &str = "net.minecraft";
+
+/// Asset index used by old versions whose meta data does not declare one.
+pub const LEGACY_ASSET_INDEX: &str = "legacy";
+
+/// Main class used when no component declares one.
+pub const DEFAULT_MAIN_CLASS: &str = "net.minecraft.client.main.Main";
+
+/// Main class of LaunchWrapper, which old mod loaders start the game through.
+pub const LAUNCHWRAPPER_MAIN_CLASS: &str = "net.minecraft.launchwrapper.Launch";
+
+/// Heap sizes used when the instance does not set them and automatic sizing is off or fails.
+pub const DEFAULT_MIN_HEAP: &str = "512M";
+pub const DEFAULT_MAX_HEAP: &str = "1024M";
+
+/// How long to wait for another process extracting natives into the same directory.
+pub(crate) const NATIVES_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct InstanceGameConfig {
+    /// Initial heap size, e.g. `512M`.
+    pub min: Option<String>, // TODO: create enum type?
+    /// Maximum heap size, e.g. `2G`.
+    pub max: Option<String>,
+    /// Compute the heap sizes which are not set from the memory of the system: at most half of
+    /// it, between 1 GiB and 8 GiB.
+    pub auto_memory: bool,
+
+    pub width: u32,
+    pub height: u32,
+
+    /// Name of the game in the macOS dock, to tell multiple instances apart. The game sets the
+    /// title of its window itself and reads no argument for it.
+    pub title: Option<String>,
+    /// Path to an image used as window icon where the platform allows setting it.
+    pub icon: Option<String>,
+
+    /// Only pass the arguments needed to start the game: the JVM settings, the classpath and
+    /// main class, the game directory, the account and the version. Assets, window settings
+    /// and the launcher brand are left out. Used for automated tests, where the assets are
+    /// usually not downloaded.
+    pub minimal_args: bool,
+    /// Start the JVM with `-Djava.awt.headless=true`.
+    pub headless: bool,
+    /// Don't verify the main jar before launching.
+    pub skip_verify: bool,
+    /// Pass the logging configuration of the game, which makes it log XML events instead of
+    /// plain text. Off by default, as the output is harder to read. Needs the assets, so it is
+    /// off with `minimal_args`.
+    pub log_config: bool,
+    /// Launcher the game reports in crash reports and its statistics, not passed if `None`.
+    pub launcher_brand: Option<LauncherBrand>,
+    /// GPU to render on, the one chosen by the system if `None`.
+    pub gpu: Option<GpuPreference>,
+}
+
+/// Name and version of the launcher passed as `-Dminecraft.launcher.brand` and
+/// `-Dminecraft.launcher.version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LauncherBrand {
+    pub name: String,
+    pub version: String,
+}
+
+impl Default for LauncherBrand {
+    fn default() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl InstanceGameConfig {
+    /// Initial and maximum heap size passed to the JVM as `-Xms` and `-Xmx`.
+    pub fn heap_size(&self) -> (String, String) {
+        let auto = if self.auto_memory && (self.min.is_none() || self.max.is_none()) {
+            crate::memory::auto_heap()
+        } else {
+            None
+        };
+        let min = self.min.clone().unwrap_or_else(|| match auto {
+            Some(auto) => crate::memory::jvm_size(auto.min),
+            None => DEFAULT_MIN_HEAP.to_string(),
+        });
+        let max = self.max.clone().unwrap_or_else(|| match auto {
+            Some(auto) => crate::memory::jvm_size(auto.max),
+            None => DEFAULT_MAX_HEAP.to_string(),
+        });
+
+        (min, max)
+    }
+}
+
+impl Default for InstanceGameConfig {
+    fn default() -> Self {
+        Self {
+            min: None,
+            max: None,
+            auto_memory: false,
+            width: 854,
+            height: 480,
+            title: None,
+            icon: None,
+            minimal_args: false,
+            headless: false,
+            skip_verify: false,
+            log_config: false,
+            launcher_brand: Some(LauncherBrand::default()),
+            gpu: None,
+        }
+    }
+}
+
+/// Restart the game when it exits with an error.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Number of restarts after which the launcher gives up.
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for every following restart.
+    pub backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct Instance {
+    /// Name of the Minecraft instance given by the user.
+    pub name: String,
+    /// The version string of the instance.
+    pub version: String,
+    /// Path to the base directory.
+    pub minecraft_path: String,
+    /// Path to Minecraft's assets.
+    pub assets_path: Option<String>,
+    /// Path to Minecraft's Java libraries.
+    pub libraries_path: Option<String>,
+    /// Path to Minecraft's native libraries.
+    pub natives_path: Option<String>,
+    /// Java options to pass to the JVM.
+    pub java_opts: Vec<String>,
+    /// Extra arguments to pass to Minecraft.
+    pub extra_args: Vec<String>,
+    /// Java agents attached to the game.
+    pub java_agents: Vec<JavaAgent>,
+
+    pub config: InstanceGameConfig,
+    /// Restart the game if it crashes.
+    pub restart_policy: Option<RestartPolicy>,
+    /// Group the instance is listed in.
+    pub group: Option<String>,
+    /// Client jar used instead of the main jar of the components.
+    pub main_jar: Option<MainJar>,
+    /// Restrictions applied when launching the game.
+    pub sandbox: Option<Sandbox>,
+    /// Changes to the command taking precedence over the components.
+    pub overrides: LaunchOverrides,
+
+    pub uid: String,
+    pub manifests: HashMap<String, Manifest>,
+}
+
+impl Instance {
+    pub fn new(
+        name: &str,
+        version: &str,
+        minecraft_path: &str,
+        search_result: SearchResult,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            minecraft_path: minecraft_path.to_string(),
+            assets_path: None,
+            libraries_path: None,
+            natives_path: None,
+            java_opts: Vec::new(),
+            extra_args: Vec::new(),
+            java_agents: Vec::new(),
+            config: Default::default(),
+            restart_policy: None,
+            group: None,
+            main_jar: None,
+            sandbox: None,
+            overrides: Default::default(),
+
+            uid: search_result.uid,
+            manifests: search_result.manifests,
+        }
+    }
+
+    /// Set the assets path.
+    pub fn set_assets_path(&mut self, path: &str) {
+        self.assets_path = Some(path.to_string());
+    }
+
+    /// Get the current asset path.
+    /// This will default onto the assets folder inside the minecraft path.
+    pub fn get_assets_path(&self) -> String {
+        if let Some(path) = &self.assets_path {
+            path.to_string()
+        } else {
+            let mut path = Path::new(&self.minecraft_path).to_path_buf();
+            path.push("assets");
+            path.to_str().unwrap().to_string()
+        }
+    }
+
+    /// Set the libraries path.
+    pub fn set_libraries_path(&mut self, path: &str) {
+        self.libraries_path = Some(path.to_string())
+    }
+
+    /// Get the current libraries path.
+    /// This will default onto the default .minecraft/libraries path.
+    pub fn get_libraries_path(&self) -> String {
+        if let Some(path) = &self.libraries_path {
+            path.to_string()
+        } else {
+            let mut path = Path::new(&self.minecraft_path).to_path_buf();
+            path.push("libraries");
+            path.to_str().unwrap().to_string()
+        }
+    }
+
+    pub fn set_extra_args(&mut self, args: Vec<String>) {
+        self.extra_args = args.to_vec();
+    }
+
+    /// Set the Java agents attached to the game.
+    pub fn set_java_agents(&mut self, agents: Vec<JavaAgent>) {
+        self.java_agents = agents;
+    }
+
+    /// Set the changes to the command taking precedence over the components.
+    pub fn set_overrides(&mut self, overrides: LaunchOverrides) {
+        self.overrides = overrides;
+    }
+
+    /// Check that the jars of all Java agents exist.
+    pub(crate) fn verify_java_agents(&self) -> Result<()> {
+        self.java_agents
+            .iter()
+            .try_for_each(|agent| agent.verify_at(&self.minecraft_path))
+    }
+
+    /// `-javaagent` arguments of the Java agents, relative paths resolved against the game
+    /// directory.
+    pub(crate) fn get_java_agent_args(&self) -> Vec<String> {
+        self.java_agents
+            .iter()
+            .map(|agent| agent.arg_at(&self.minecraft_path))
+            .collect()
+    }
+
+    /// JVM argument passing the logging configuration of the components, if they declare one
+    /// and [`InstanceGameConfig::log_config`] is set.
+    pub(crate) fn get_logging_args(&self) -> Vec<String> {
+        if !self.config.log_config || self.config.minimal_args {
+            return Vec::new();
+        }
+
+        self.components()
+            .logging()
+            .and_then(|logging| match logging.arg_at(self.get_assets_path()) {
+                Ok(arg) => Some(arg),
+                Err(e) => {
+                    warn!("not passing the logging configuration: {}", e);
+                    None
+                }
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// Set the policy to restart the game after a crash.
+    pub fn set_restart_policy(&mut self, policy: Option<RestartPolicy>) {
+        self.restart_policy = policy;
+    }
+
+    /// Use a custom client jar instead of the main jar of the components.
+    pub fn set_main_jar(&mut self, jar: Option<MainJar>) {
+        self.main_jar = jar;
+    }
+
+    /// Set the restrictions applied when launching the game.
+    pub fn set_sandbox(&mut self, sandbox: Option<Sandbox>) {
+        self.sandbox = sandbox;
+    }
+
+    /// Check the custom client jar, if one is set.
+    pub fn verify_main_jar(&self) -> Result<()> {
+        match &self.main_jar {
+            Some(jar) => jar.verify_at(&self.get_libraries_path()),
+            None => Ok(()),
+        }
+    }
+
+    /// Pin of the main jar of the components, `None` with a custom client jar.
+    pub fn main_jar_pin(&self) -> Result<Option<MainJarPin>> {
+        if self.main_jar.is_some() {
+            return Ok(None);
+        }
+        match self
+            .manifests
+            .get(&self.uid)
+            .and_then(|m| m.main_jar.as_ref())
+        {
+            Some(library) => MainJarPin::new(library, Path::new(&self.get_jar_path()?)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Record the pin of the main jar in the instance info, once the instance is resolved.
+    pub fn pin_main_jar(&self) -> Result<()> {
+        let pin = self.main_jar_pin()?;
+        let mut info = self.info()?;
+        if pin.is_none() || info.main_jar_pin == pin {
+            return Ok(());
+        }
+        info.main_jar_pin = pin;
+        fs::create_dir_all(&self.minecraft_path)?;
+        info.save(&self.minecraft_path)
+    }
+
+    /// Record the asset index in the instance info once its assets are resolved, for the next
+    /// resolution to compare with, see [`MetaManager::set_previous_asset_index`]. `verified` is
+    /// whether the assets were verified, see [`MetaManager::verifies_assets`].
+    ///
+    /// [`MetaManager::set_previous_asset_index`]: crate::meta::MetaManager::set_previous_asset_index
+    /// [`MetaManager::verifies_assets`]: crate::meta::MetaManager::verifies_assets
+    pub fn record_asset_index(&self, verified: bool) -> Result<()> {
+        let reference = self
+            .components()
+            .asset_index()
+            .filter(|index| index.cache.is_some())
+            .map(|index| index.reference(verified));
+        let mut info = self.info()?;
+        if reference.is_none() || info.asset_index == reference {
+            return Ok(());
+        }
+        info.asset_index = reference;
+        fs::create_dir_all(&self.minecraft_path)?;
+        info.save(&self.minecraft_path)
+    }
+
+    /// Check the main jar of the components against the pin recorded when the instance was
+    /// resolved. Pins of another main jar, e.g. of a previous game version, are ignored.
+    pub fn verify_pinned_main_jar(&self) -> Result<()> {
+        let pin = match self.info()?.main_jar_pin {
+            Some(pin) if self.main_jar.is_none() => pin,
+            _ => return Ok(()),
+        };
+        let library = self
+            .manifests
+            .get(&self.uid)
+            .and_then(|m| m.main_jar.as_ref());
+        if library.map(|l| &l.name) != Some(&pin.name) {
+            debug!("the client jar {} is not pinned", self.version);
+            return Ok(());
+        }
+        pin.verify(Path::new(&self.get_jar_path()?))
+    }
+
+    /// Set the natives path.
+    pub fn set_natives_path(&mut self, path: &str) {
+        self.natives_path = Some(path.to_string())
+    }
+
+    /// Get the current natives path.
+    /// This will default onto .minecraft/natives/<version>, so instances and versions sharing a
+    /// directory never extract over each other while running.
+    pub fn get_natives_path(&self) -> String {
+        if let Some(path) = &self.natives_path {
+            path.to_string()
+        } else {
+            Self::default_natives_path(&self.minecraft_path, &self.version)
+        }
+    }
+
+    /// Natives path of the instance in `game_dir` requested in `version`, unless it is set with
+    /// [`Instance::set_natives_path`].
+    pub fn default_natives_path(game_dir: &str, version: &str) -> String {
+        let mut path = Path::new(game_dir).to_path_buf();
+        path.push("natives");
+        path.push(version);
+        path.to_str().unwrap().to_string()
+    }
+
+    /// Components of the instance, merged by their `order`.
+    pub fn components(&self) -> ComponentSet<'_> {
+        ComponentSet::new(&self.manifests)
+    }
+
+    /// Manifests of all components, see [`ComponentSet::ordered_components`].
+    pub fn sorted_manifests(&self) -> Vec<&Manifest> {
+        self.components().ordered_components()
+    }
+
+    pub fn get_natives(&self, platform: &OS) -> Vec<&Library> {
+        let mut ret = Vec::new();
+        for v in self.sorted_manifests() {
+            for lib in &v.libraries {
+                if lib.natives.get(&platform.name).is_some() {
+                    ret.push(lib);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Extract natives into the natives path.
+    ///
+    /// The extraction is recorded in the natives path, natives which did not change since are
+    /// not extracted again.
+    pub fn build_natives(&self) -> Result<String> {
+        NativesExtractor::new(self.get_natives_path())?.finish(self)
+    }
+
+    /// Info of the instance, including the play statistics saved in its directory.
+    ///
+    /// Fails if the saved info can't be read, see [`InstanceInfo::load_existing`].
+    pub fn info(&self) -> Result<InstanceInfo> {
+        let mut info = InstanceInfo::load_existing(&self.minecraft_path)?.unwrap_or_default();
+        info.name = self.name.to_string();
+        info.version = self.version.to_string();
+        if self.group.is_some() {
+            info.group = self.group.clone();
+        }
+        Ok(info)
+    }
+
+    /// Update last-played and playtime for a game session which started at `started`.
+    pub fn record_session(&self, started: SystemTime) -> Result<()> {
+        let mut info = self.info()?;
+        info.record_session(started);
+        info.save(&self.minecraft_path)
+    }
+
+    /// Store the GPU preference of the instance for `java` in the registry, removing the one an
+    /// earlier launch stored once the instance has none or runs on another java.
+    #[cfg(windows)]
+    pub(crate) fn register_gpu_preference(&self, java: &Path) -> Result<()> {
+        let mut info = self.info()?;
+        let registered = match self.config.gpu {
+            Some(gpu) => Some(crate::gpu::register_preference(java, gpu)?),
+            None => None,
+        };
+        if info.gpu_registered == registered {
+            return Ok(());
+        }
+        if let Some(old) = &info.gpu_registered {
+            crate::gpu::unregister_preference(old)?;
+        }
+        info.gpu_registered = registered;
+        info.save(&self.minecraft_path)
+    }
+
+    /// Save the start of the game at `started` with `auth` in the instance info.
+    pub fn record_launch(&self, started: SystemTime, auth: &Auth) -> Result<()> {
+        let mut info = self.info()?;
+        info.record_launch(started, auth.get_username());
+        info.save(&self.minecraft_path)
+    }
+
+    /// Update the play statistics and the last exit code for a game session which started at
+    /// `started` and exited with `status`.
+    pub fn record_exit(&self, started: SystemTime, status: ExitStatus) -> Result<()> {
+        let mut info = self.info()?;
+        info.record_session(started);
+        info.last_exit_code = status.code();
+        info.save(&self.minecraft_path)
+    }
+
+    /// Lock the instance directory, so no other launcher can start it at the same time.
+    pub fn lock(&self) -> Result<DirLock> {
+        DirLock::try_lock(&self.minecraft_path)
+    }
+
+    /// Check if the instance is currently locked (e.g. running) by a launcher.
+    pub fn is_locked(&self) -> Result<bool> {
+        DirLock::is_locked(&self.minecraft_path)
+    }
+
+    /// Id of the asset index of the instance.
+    ///
+    /// The component with the highest order declaring an asset index provides it. Old versions of
+    /// `net.minecraft` without an asset index in their meta data fall back to
+    /// [`LEGACY_ASSET_INDEX`], without `net.minecraft` this fails with
+    /// [`Error::MissingAssetIndex`].
+    pub fn get_asset_index_id(&self) -> Result<String> {
+        if !self.manifests.contains_key(&self.uid) {
+            return Err(Error::PendingDownloads);
+        }
+
+        if let Some(info) = self.components().asset_index() {
+            return Ok(info.id.to_string());
+        }
+
+        match self.manifests.get(MINECRAFT_UID) {
+            Some(manifest) => {
+                warn!(
+                    "{} {} has no asset index, using {}",
+                    MINECRAFT_UID, manifest.version, LEGACY_ASSET_INDEX
+                );
+                Ok(LEGACY_ASSET_INDEX.to_string())
+            }
+            None => Err(Error::MissingAssetIndex(MINECRAFT_UID.to_string())),
+        }
+    }
+
+    /// Id of the game version passed as `--version`, the one of the resolved Minecraft component
+    /// or the requested one before it was resolved.
+    pub fn get_version_id(&self) -> &str {
+        match self.manifests.get(MINECRAFT_UID) {
+            Some(manifest) => &manifest.version,
+            None => &self.version,
+        }
+    }
+
+    /// Type of the game version passed as `--versionType`, e.g. `release` or `snapshot`.
+    pub fn get_version_type(&self) -> Option<&str> {
+        self.manifests
+            .get(MINECRAFT_UID)
+            .map(|manifest| manifest.release_type.as_str())
+            .filter(|release_type| !release_type.is_empty())
+    }
+
+    /// Whether the game takes the argument `flag`, e.g. `--versionType`.
+    ///
+    /// Versions with a legacy argument template only take the arguments in it, 1.6 has no asset
+    /// index and versions before 1.12 no version type. Without a template the game is new enough
+    /// for all of them.
+    pub(crate) fn takes_game_arg(&self, flag: &str) -> bool {
+        match self.components().minecraft_arguments() {
+            Some(template) => template.split_whitespace().any(|arg| arg == flag),
+            None => true,
+        }
+    }
+
+    /// Game arguments from the legacy argument template, if the game is started through
+    /// LaunchWrapper.
+    ///
+    /// Mod loaders started that way declare their tweak class and the arguments they need in the
+    /// template, so it is passed in full instead of picking the known arguments from it.
+    pub(crate) fn get_launchwrapper_game_args(&self, auth: &Auth) -> Result<Option<Vec<String>>> {
+        let template = match self.components().minecraft_arguments() {
+            Some(template) if self.get_main_class() == LAUNCHWRAPPER_MAIN_CLASS => template,
+            _ => return Ok(None),
+        };
+
+        let token = auth.get_token().unwrap_or("0");
+        let uuid = auth
+            .game_uuid()
+            .unwrap_or_else(|| "00000000000000000000000000000000".to_string());
+        let mut ret = Vec::new();
+        for arg in template.split_whitespace() {
+            let value = match arg {
+                "${auth_player_name}" => auth.get_username().to_string(),
+                "${auth_uuid}" => uuid.clone(),
+                "${auth_access_token}" => token.to_string(),
+                "${auth_session}" => format!("token:{}:{}", token, uuid),
+                "${user_type}" => match auth {
+                    Auth::MSFT { .. } => "msa".to_string(),
+                    _ => "mojang".to_string(),
+                },
+                "${user_properties}" => "{}".to_string(),
+                "${version_name}" => self.get_version_id().to_string(),
+                "${version_type}" => self.get_version_type().unwrap_or("release").to_string(),
+                "${game_directory}" => self.minecraft_path.clone(),
+                "${assets_root}" | "${game_assets}" => self.get_assets_path(),
+                "${assets_index_name}" => self.get_asset_index_id()?,
+                _ => {
+                    if arg.starts_with("${") {
+                        warn!("unknown game argument: '{}'", arg);
+                    }
+                    arg.to_string()
+                }
+            };
+            ret.push(value);
+        }
+
+        Ok(Some(ret))
+    }
+
+    /// Main class of the game, the overridden one or the one declared by the component with the
+    /// highest order.
+    pub fn get_main_class(&self) -> &str {
+        match &self.overrides.main_class {
+            Some(main_class) => main_class,
+            None => self.components().main_class().unwrap_or(DEFAULT_MAIN_CLASS),
+        }
+    }
+
+    /// Loaded asset index of the instance, see [`Instance::get_asset_index_id`].
+    pub fn asset_index(&self) -> Option<&AssetIndex> {
+        self.components().asset_index()?.cache.as_ref()
+    }
+
+    /// Copy the assets into the `resources` directory of the game, if its asset index asks for
+    /// it.
+    pub fn map_resources(&self) -> Result<()> {
+        match self.asset_index() {
+            Some(index) if index.map_to_resources => {
+                index.map_to_resources_at(&self.get_assets_path(), &self.minecraft_path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the current minecraft.jar path.
+    /// This will default onto the default versions/<version>/<version>.jar path.
+    pub fn get_jar_path(&self) -> Result<String> {
+        if let Some(jar) = &self.main_jar {
+            return Ok(jar
+                .path_at(&self.get_libraries_path())
+                .display()
+                .to_string());
+        }
+
+        let manifest = self
+            .manifests
+            .get(&self.uid)
+            .ok_or(Error::PendingDownloads)?;
+        let os = OS::get();
+        Ok(manifest
+            .main_jar
+            .as_ref()
+            .ok_or(Error::MetaNotFound)?
+            .path_at_for(&self.get_libraries_path(), &os)
+            .display()
+            .to_string())
+    }
+
+    /// Class path of all components, the custom client jar replaces their main jars.
+    pub fn get_class_paths(&self) -> String {
+        join_class_path(&self.class_path_for(&OS::get()))
+    }
+
+    /// Class path entries for `platform`.
+    ///
+    /// The order is part of the launch behaviour, as mods rely on classes shadowing others:
+    /// components sorted by `order` (then uid), each with its libraries in declared order
+    /// followed by its main jar, and the main jar override last. Changes to it show up in the
+    /// golden files in `testdata/classpath`.
+    pub fn class_path_for(&self, platform: &OS) -> Vec<PathBuf> {
+        let mut ret = Vec::new();
+        let libraries_path = self.get_libraries_path();
+        for v in self.sorted_manifests() {
+            if self.main_jar.is_some() {
+                ret.extend(v.libraries_class_path_at(&libraries_path, platform));
+            } else {
+                ret.extend(v.class_path_at(&libraries_path, platform));
+            }
+        }
+        if let Some(jar) = &self.main_jar {
+            ret.push(jar.path_at(&libraries_path));
+        }
+
+        ret
+    }
+
+    /// JVM arguments from the traits and `+jvmArgs` of all components.
+    pub(crate) fn get_manifest_extra_jvm_args(&self, platform: &OS) -> Vec<String> {
+        let mut ret = Vec::new();
+
+        for v in self.sorted_manifests() {
+            for v in &v.traits {
+                if let Some(v) = Self::parse_trait(v, platform) {
+                    ret.push(v)
+                }
+            }
+            ret.extend(v.jvm_args.iter().cloned());
+        }
+
+        ret
+    }
+
+    /// Highest major version of Java required by any component.
+    pub fn required_java_major(&self) -> Option<u32> {
+        self.manifests
+            .values()
+            .filter_map(Manifest::required_java_major)
+            .max()
+    }
+
+    /// JVM arguments setting the window title and icon.
+    ///
+    /// Only macOS allows setting them from outside of the game, through the dock.
+    pub(crate) fn get_window_jvm_args(&self, platform: &OS) -> Vec<String> {
+        let mut ret = Vec::new();
+        if platform.name != "osx" {
+            return ret;
+        }
+
+        if let Some(title) = &self.config.title {
+            ret.push(format!("-Xdock:name={}", title));
+        }
+        if let Some(icon) = &self.config.icon {
+            ret.push(format!("-Xdock:icon={}", icon));
+        }
+
+        ret
+    }
+
+    /// Tweak classes of all components in component order, each only listed once.
+    pub(crate) fn get_tweakers(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+
+        for v in self.sorted_manifests() {
+            for tweaker in &v.tweakers {
+                if !ret.contains(tweaker) {
+                    ret.push(tweaker.to_string());
+                }
+            }
+        }
+
+        ret
+    }
+
+    fn parse_trait(jvm_trait: &str, platform: &OS) -> Option<String> {
+        Some(match jvm_trait {
+            "FirstThreadOnMacOS" if platform.name == "osx" => "-XstartOnFirstThread".to_string(),
+            _ => {
+                info!("unknown jvm trait: '{jvm_trait}'");
+                return None;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DownloadRequest;
+
+    use super::*;
+    use crate::test_util::{manifest, TempDir};
+    use std::path::Path;
+
+    #[cfg(unix)]
+    #[test]
+    fn start_concurrently() {
+        use crate::auth::Auth;
+        use crate::java_wrapper::Java;
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::{Arc, Barrier};
+
+        let dir = TempDir::new("concurrent");
+        fs::create_dir_all(&dir).unwrap();
+        // Stands in for java, only succeeds if the other instance runs at the same time
+        let java = dir.join("java");
+        fs::write(
+            &java,
+            r#"#!/bin/sh
+echo "$@" > "args-$$"
+touch "started-$$"
+for i in $(seq 100); do
+    [ "$(ls started-* | wc -l)" -ge 2 ] && exit 0
+    sleep 0.1
+done
+exit 1
+"#,
+        )
+        .unwrap();
+        fs::set_permissions(&java, fs::Permissions::from_mode(0o755)).unwrap();
+        let java = Java::new(&java);
+
+        let game_dir = dir.join("game");
+        let instance = |version: &str| {
+            let fields = format!(
+                r#""assetIndex": {{
+                    "id": "{version}", "sha1": "0000000000000000000000000000000000000000",
+                    "size": 1, "totalSize": 1, "url": "https://example.com"
+                }}"#
+            );
+            let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+            result.manifests.insert(
+                "net.minecraft".to_string(),
+                manifest("net.minecraft", 0, &fields),
+            );
+            Instance::new("test", version, &game_dir.display().to_string(), result)
+        };
+        let instances = [instance("1.17.1"), instance("1.18.1")];
+        let natives: Vec<String> = instances.iter().map(Instance::get_natives_path).collect();
+        assert_ne!(natives[0], natives[1]);
+
+        let barrier = Arc::new(Barrier::new(instances.len()));
+        let threads: Vec<_> = instances
+            .into_iter()
+            .map(|instance| {
+                let (java, barrier) = (java.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let mut running = java.start(&instance, Auth::new_offline("Steve")).unwrap();
+                    running.process.wait().unwrap().success()
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert!(
+                thread.join().unwrap(),
+                "the instances did not run concurrently"
+            );
+        }
+
+        // Each instance got its own natives
+        let mut args: Vec<String> = fs::read_dir(&game_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("args-")
+            })
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        args.sort_by_key(|args| !args.contains(&natives[0]));
+        assert_eq!(args.len(), 2);
+        for (args, natives) in args.iter().zip(&natives) {
+            assert!(args.contains(&format!("-Djava.library.path={}", natives)));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_async() {
+        use crate::auth::Auth;
+        use crate::java_wrapper::Java;
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, Instant};
+
+        let dir = TempDir::new("wait-async");
+        fs::create_dir_all(&dir).unwrap();
+        let java = dir.join("java");
+        fs::write(&java, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        fs::set_permissions(&java, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let fields = r#""assetIndex": {"id": "1.18",
+            "sha1": "0000000000000000000000000000000000000000",
+            "size": 1, "totalSize": 1, "url": "https://example.com"}"#;
+        let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+        result.manifests.insert(
+            MINECRAFT_UID.to_string(),
+            manifest(MINECRAFT_UID, 0, fields),
+        );
+        let game_dir = dir.join("game").display().to_string();
+        let instance = Instance::new("test", "1.18", &game_dir, result);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        let mut running = Java::new(&java)
+            .start(&instance, Auth::new_offline("Steve"))
+            .unwrap();
+        // The runtime keeps running other futures while the game runs
+        let waited = runtime.block_on(async {
+            tokio::time::timeout(Duration::from_millis(200), running.wait_async()).await
+        });
+        assert!(waited.is_err());
+        running.process.kill().unwrap();
+        assert!(!runtime.block_on(running.wait_async()).unwrap().success());
+
+        // The game is ended when the play time is up, one second after the start
+        let mut info = instance.info().unwrap();
+        info.session_limit = SessionLimit {
+            per_day: Some(1),
+            grace: 0,
+            ..Default::default()
+        };
+        info.record_session(SystemTime::now() - Duration::from_secs(59));
+        info.save(&game_dir).unwrap();
+        let started = Instant::now();
+        let mut running = Java::new(&java)
+            .start(&instance, Auth::new_offline("Steve"))
+            .unwrap();
+        assert!(!runtime.block_on(running.wait_async()).unwrap().success());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Prints what the JVM received instead of starting a game.
+    #[cfg(feature = "launch-tests")]
+    const LAUNCH_PROBE: &str = r#"
+        public class LaunchProbe {
+            public static void main(String[] args) {
+                System.out.println("cp=" + System.getProperty("java.class.path"));
+                System.out.println("natives=" + System.getProperty("java.library.path"));
+                System.out.println("brand=" + System.getProperty("minecraft.launcher.brand"));
+                System.out.println("headless=" + System.getProperty("java.awt.headless"));
+                for (String arg : args) {
+                    System.out.println("arg=" + arg);
+                }
+            }
+        }
+    "#;
+
+    #[cfg(feature = "launch-tests")]
+    #[test]
+    fn launch_smoke_test() {
+        use crate::auth::Auth;
+        use crate::java_wrapper::Java;
+        use std::io::Read;
+        use std::process::Command;
+
+        let dir = TempDir::new("launch");
+        let classes = dir.join("classes");
+        fs::create_dir_all(&classes).unwrap();
+        fs::write(dir.join("LaunchProbe.java"), LAUNCH_PROBE).unwrap();
+        let javac = Command::new("javac")
+            .arg("-d")
+            .arg(&classes)
+            .arg(dir.join("LaunchProbe.java"))
+            .status()
+            .expect("javac is needed for the launch tests");
+        assert!(javac.success());
+        let jar = dir.join("probe with space.jar");
+        let packed = Command::new("jar")
+            .arg("cf")
+            .arg(&jar)
+            .arg("-C")
+            .arg(&classes)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(packed.success());
+
+        // A library next to the probe, so the class path has more than one entry
+        let manifest = r#"{"name": "Minecraft", "uid": "net.minecraft", "version": "1.18",
+            "order": 0, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "mainClass": "LaunchProbe",
+            "libraries": [{"name": "org.example:lib:1.0", "downloads": {"artifact": {
+                "sha1": "0000000000000000000000000000000000000000", "size": 1,
+                "url": "https://example.com/lib.jar"
+            }}}],
+            "assetIndex": {"id": "1.18", "sha1": "0000000000000000000000000000000000000000",
+                "size": 1, "totalSize": 1, "url": "https://example.com"}
+        }"#;
+        let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+        result
+            .manifests
+            .insert(MINECRAFT_UID.to_string(), manifest.parse().unwrap());
+        let game_dir = dir.join("game").display().to_string();
+        let mut instance = Instance::new("test", "1.18", &game_dir, result);
+        instance.set_libraries_path(&dir.join("libraries").display().to_string());
+        instance.set_main_jar(Some(MainJar::Path {
+            path: jar.clone(),
+            sha1: None,
+        }));
+        instance.set_extra_args(vec!["--demo".to_string(), "--quickPlay x".to_string()]);
+        instance.config.headless = true;
+
+        let mut running = Java::new("java")
+            .start(&instance, Auth::new_offline("Probe"))
+            .unwrap();
+        let mut output = String::new();
+        running
+            .process
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert!(running.wait().unwrap().success(), "{}", output);
+
+        let lines: Vec<&str> = output.lines().collect();
+        let class_path = instance.class_path_for(&OS::get());
+        let received: Vec<PathBuf> = lines[0]
+            .strip_prefix("cp=")
+            .unwrap()
+            .split(crate::meta::manifest::CLASS_PATH_SEPARATOR)
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(received, class_path);
+        assert_eq!(class_path.last(), Some(&jar));
+        assert_eq!(lines[1], format!("natives={}", instance.get_natives_path()));
+        assert_eq!(lines[2], format!("brand={}", env!("CARGO_PKG_NAME")));
+        assert_eq!(lines[3], "headless=true");
+
+        let args: Vec<&str> = lines[4..]
+            .iter()
+            .map(|l| l.strip_prefix("arg=").unwrap())
+            .collect();
+        let after = |flag: &str| args[args.iter().position(|a| *a == flag).unwrap() + 1];
+        assert_eq!(after("--gameDir"), game_dir);
+        assert_eq!(after("--username"), "Probe");
+        assert_eq!(after("--assetIndex"), "1.18");
+        assert_eq!(args[args.len() - 2..], ["--demo", "--quickPlay x"]);
+    }
+
+    #[test]
+    fn extension_fields() {
+        let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+        result.manifests.insert(
+            "net.minecraft".to_string(),
+            manifest("net.minecraft", 0, r#""+jvmArgs": ["-Dfoo=bar"]"#),
+        );
+        result.manifests.insert(
+            "net.minecraftforge".to_string(),
+            manifest(
+                "net.minecraftforge",
+                5,
+                r#""+tweakers": ["cpw.mods.fml.common.launcher.FMLTweaker"]"#,
+            ),
+        );
+        result.manifests.insert(
+            "com.mumfrey.liteloader".to_string(),
+            manifest(
+                "com.mumfrey.liteloader",
+                10,
+                r#""+tweakers": ["com.mumfrey.liteloader.launch.LiteLoaderTweaker",
+                    "cpw.mods.fml.common.launcher.FMLTweaker"]"#,
+            ),
+        );
+        let instance = Instance::new("test", "1.7.10", "/not/existing", result);
+
+        assert_eq!(
+            instance.get_manifest_extra_jvm_args(&OS::new("linux")),
+            vec!["-Dfoo=bar"]
+        );
+        assert_eq!(
+            instance.get_tweakers(),
+            vec![
+                "cpw.mods.fml.common.launcher.FMLTweaker",
+                "com.mumfrey.liteloader.launch.LiteLoaderTweaker"
+            ]
+        );
+    }
+
+    #[test]
+    fn launchwrapper_args() {
+        use crate::auth::{offline_uuid, Auth};
+        use crate::java_wrapper::Java;
+
+        let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+        result.manifests.insert(
+            MINECRAFT_UID.to_string(),
+            manifest(
+                MINECRAFT_UID,
+                -2,
+                r#""assetIndex": {"id": "1.7.10",
+                    "sha1": "0000000000000000000000000000000000000000",
+                    "size": 1, "totalSize": 1, "url": "https://example.com"}"#,
+            ),
+        );
+        result.manifests.insert(
+            "net.minecraftforge".to_string(),
+            manifest(
+                "net.minecraftforge",
+                5,
+                r#""mainClass": "net.minecraft.launchwrapper.Launch",
+                    "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userProperties ${user_properties} --userType ${user_type} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker""#,
+            ),
+        );
+        result.manifests.insert(
+            "com.mumfrey.liteloader".to_string(),
+            manifest(
+                "com.mumfrey.liteloader",
+                10,
+                r#""+tweakers": ["com.mumfrey.liteloader.launch.LiteLoaderTweaker",
+                    "cpw.mods.fml.common.launcher.FMLTweaker"]"#,
+            ),
+        );
+        let instance = Instance::new("test", "1.7.10", "/not/existing", result);
+        let args: Vec<_> = Java::new("java")
+            .command_builder(&instance, &Auth::new_offline("Steve"))
+            .unwrap()
+            .game_args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let after = |flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .map(|i| args[i + 1].as_str())
+        };
+
+        assert_eq!(after("--username"), Some("Steve"));
+        assert_eq!(after("--uuid"), Some(offline_uuid("Steve").as_str()));
+        assert_eq!(after("--gameDir"), Some("/not/existing"));
+        assert_eq!(after("--assetIndex"), Some("1.7.10"));
+        assert_eq!(after("--userProperties"), Some("{}"));
+        assert_eq!(after("--userType"), Some("mojang"));
+        // The tweaker of the template is not passed twice
+        let tweakers: Vec<_> = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--tweakClass")
+            .map(|pair| pair[1].as_str())
+            .collect();
+        assert_eq!(
+            tweakers,
+            [
+                "cpw.mods.fml.common.launcher.FMLTweaker",
+                "com.mumfrey.liteloader.launch.LiteLoaderTweaker"
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_session_redacted() {
+        use crate::auth::{Auth, GameProfile};
+        use crate::java_wrapper::Java;
+
+        let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+        result.manifests.insert(
+            MINECRAFT_UID.to_string(),
+            manifest(
+                MINECRAFT_UID,
+                -2,
+                r#""mainClass": "net.minecraft.launchwrapper.Launch",
+                    "minecraftArguments": "--username ${auth_player_name} --session ${auth_session}""#,
+            ),
+        );
+        let instance = Instance::new("test", "1.5.2", "/not/existing", result);
+        let auth = Auth::new_msft(
+            "secret-token",
+            GameProfile {
+                id: "b50ad385829d3141a2167e7d7539ba7f".to_string(),
+                name: "Steve".to_string(),
+            },
+        );
+        let command = Java::new("java").build_command(&instance, &auth).unwrap();
+        assert!(command
+            .get_args()
+            .any(|arg| arg.to_string_lossy().contains("secret-token")));
+
+        let args = Java::redacted_args(&command, &auth);
+        assert!(args.iter().all(|arg| !arg.contains("secret-token")));
+        assert!(
+            args.contains(&"token:<redacted>:b50ad385829d3141a2167e7d7539ba7f".to_string()),
+            "{:?}",
+            args
+        );
+    }
+
+    #[test]
+    fn window_title() {
+        let mut instance = Instance::new(
+            "test",
+            "1.18",
+            "/not/existing",
+            SearchResult::new(Vec::new(), "net.minecraft"),
+        );
+        instance.config.title = Some("Modded".to_string());
+        instance.config.icon = Some("/icons/modded.png".to_string());
+        assert!(instance.get_window_jvm_args(&OS::new("linux")).is_empty());
+        assert_eq!(
+            instance.get_window_jvm_args(&OS::new("osx")),
+            vec!["-Xdock:name=Modded", "-Xdock:icon=/icons/modded.png"]
+        );
+    }
+
+    #[test]
+    fn asset_index_fallback() {
+        let asset_index = r#""assetIndex": {"id": "1.18",
+            "sha1": "0000000000000000000000000000000000000000",
+            "size": 1, "totalSize": 1, "url": "https://example.com"}"#;
+
+        let mut result = SearchResult::new(Vec::new(), "net.fabricmc.fabric-loader");
+        result.manifests.insert(
+            "net.fabricmc.fabric-loader".to_string(),
+            manifest("net.fabricmc.fabric-loader", 10, ""),
+        );
+        let mut instance = Instance::new("test", "1.0", "/not/existing", result);
+        assert!(matches!(
+            instance.get_asset_index_id(),
+            Err(Error::MissingAssetIndex(uid)) if uid == MINECRAFT_UID
+        ));
+
+        instance
+            .manifests
+            .insert(MINECRAFT_UID.to_string(), manifest(MINECRAFT_UID, 0, ""));
+        assert_eq!(instance.get_asset_index_id().unwrap(), LEGACY_ASSET_INDEX);
+
+        instance.manifests.insert(
+            MINECRAFT_UID.to_string(),
+            manifest(MINECRAFT_UID, 0, asset_index),
+        );
+        assert_eq!(instance.get_asset_index_id().unwrap(), "1.18");
+    }
+
+    #[test]
+    fn version_args() {
+        use crate::auth::Auth;
+        use crate::java_wrapper::Java;
+
+        let game_args = |version: &str, release_type: &str, template: Option<&str>| {
+            let template = template
+                .map(|t| format!(r#", "minecraftArguments": "{}""#, t))
+                .unwrap_or_default();
+            let manifest = format!(
+                r#"{{"name": "Minecraft", "uid": "net.minecraft", "version": "{version}",
+                    "order": -2, "releaseTime": "2022-01-01T00:00:00+00:00",
+                    "type": "{release_type}", "libraries": [],
+                    "assetIndex": {{"id": "{version}",
+                        "sha1": "0000000000000000000000000000000000000000",
+                        "size": 1, "totalSize": 1, "url": "https://example.com"}}
+                    {template}
+                }}"#
+            );
+            let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+            result
+                .manifests
+                .insert(MINECRAFT_UID.to_string(), manifest.parse().unwrap());
+            // Requested by an alias of the version
+            let instance = Instance::new("test", "latest", "/not/existing", result);
+            let builder = Java::new("java")
+                .command_builder(&instance, &Auth::new_offline("Steve"))
+                .unwrap();
+            builder
+                .game_args
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let after = |args: &[String], flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .map(|i| args[i + 1].clone())
+        };
+
+        // Meta data converted from the arguments of new versions
+        let args = game_args(
+            "22w16b",
+            "snapshot",
+            Some(concat!(
+                "--version ${version_name} --assetIndex ${assets_index_name} ",
+                "--versionType ${version_type}"
+            )),
+        );
+        assert_eq!(after(&args, "--version").as_deref(), Some("22w16b"));
+        assert_eq!(after(&args, "--versionType").as_deref(), Some("snapshot"));
+        assert_eq!(after(&args, "--assetIndex").as_deref(), Some("22w16b"));
+
+        let args = game_args("1.18.2", "release", None);
+        assert_eq!(after(&args, "--versionType").as_deref(), Some("release"));
+        assert_eq!(after(&args, "--assetIndex").as_deref(), Some("1.18.2"));
+
+        let args = game_args(
+            "1.7.10",
+            "release",
+            Some("--version ${version_name} --assetIndex ${assets_index_name}"),
+        );
+        assert_eq!(after(&args, "--versionType"), None);
+        assert_eq!(after(&args, "--assetIndex").as_deref(), Some("1.7.10"));
+
+        let args = game_args(
+            "1.6.4",
+            "release",
+            Some("--version ${version_name} --assetsDir ${game_assets}"),
+        );
+        assert_eq!(after(&args, "--assetIndex"), None);
+        assert_eq!(after(&args, "--version").as_deref(), Some("1.6.4"));
+    }
+
+    #[test]
+    fn jvm_arg_order() {
+        use crate::auth::Auth;
+        use crate::java_wrapper::Java;
+
+        let manifest = r#"{"name": "Minecraft", "uid": "net.minecraft", "version": "1.18",
+            "order": 0, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": []}"#;
+        let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+        result
+            .manifests
+            .insert("net.minecraft".to_string(), manifest.parse().unwrap());
+        let mut instance = Instance::new("test", "1.18", "/not/existing", result);
+        instance.config.minimal_args = true;
+        instance.config.max = Some("2G".to_string());
+        instance.java_opts.push("-Xmx1G".to_string());
+        instance.set_overrides(LaunchOverrides {
+            jvm_args_prepend: vec!["-Dfirst".to_string()],
+            jvm_args_append: vec!["-Xmx4G".to_string()],
+            ..Default::default()
+        });
+        let args: Vec<_> = Java::new("java")
+            .command_builder(&instance, &Auth::new_offline("Steve"))
+            .unwrap()
+            .args()
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let position = |arg: &str| args.iter().position(|a| a == arg).unwrap();
+
+        // The heap size of the instance wins over the Java options, the appended arguments
+        // over both
+        assert_eq!(args[0], "-Dfirst");
+        assert!(position("-Xmx1G") < position("-Xmx2G"));
+        assert!(position("-Xmx2G") < position("-Xmx4G"));
+        assert!(position("-Xmx4G") < position("-cp"));
+    }
+
+    #[test]
+    fn main_jar_override() {
+        let dir = TempDir::new("main-jar");
+        let manifest = r#"{"name": "Minecraft", "uid": "net.minecraft", "version": "1.18",
+            "order": 0, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": [],
+            "mainJar": {"name": "com.mojang:minecraft:1.18:client", "downloads": {"artifact": {
+                "sha1": "0000000000000000000000000000000000000000", "size": 1,
+                "url": "https://example.com/client.jar"
+            }}}
+        }"#;
+        let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+        result
+            .manifests
+            .insert("net.minecraft".to_string(), manifest.parse().unwrap());
+        let mut instance = Instance::new("test", "1.18", &dir.display().to_string(), result);
+        assert!(instance
+            .get_class_paths()
+            .ends_with("minecraft-1.18-client.jar"));
+
+        let jar = dir.join("patched.jar");
+        instance.set_main_jar(Some(MainJar::Path {
+            path: jar.clone(),
+            // sha1 of "patched"
+            sha1: Some("cea8b75f1ccb56cf8d11f004254622abb8cbcdb2".parse().unwrap()),
+        }));
+        let class_path = instance.get_class_paths();
+        assert!(class_path.ends_with("patched.jar"));
+        assert!(!class_path.contains("minecraft-1.18-client.jar"));
+        assert!(matches!(
+            instance.verify_main_jar(),
+            Err(Error::LibraryMissing)
+        ));
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&jar, "patched").unwrap();
+        instance.verify_main_jar().unwrap();
+
+        fs::write(&jar, "modified").unwrap();
+        assert!(matches!(
+            instance.verify_main_jar(),
+            Err(Error::LibraryInvalidHash)
+        ));
+    }
+
+    #[test]
+    fn main_jar_pin() {
+        let dir = TempDir::new("jar-pin");
+        // sha1 of "client"
+        let manifest = r#"{"name": "Minecraft", "uid": "net.minecraft", "version": "1.18",
+            "order": 0, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": [],
+            "mainJar": {"name": "com.mojang:minecraft:1.18:client", "downloads": {"artifact": {
+                "sha1": "d2a04d71301a8915217dd5faf81d12cffd6cd958", "size": 6,
+                "url": "https://example.com/client.jar"
+            }}}
+        }"#;
+        let mut result = SearchResult::new(Vec::new(), "net.minecraft");
+        result
+            .manifests
+            .insert("net.minecraft".to_string(), manifest.parse().unwrap());
+        let mut instance = Instance::new("test", "1.18", &dir.display().to_string(), result);
+        instance.set_libraries_path(&dir.join("libraries").display().to_string());
+        let jar = PathBuf::from(instance.get_jar_path().unwrap());
+        fs::create_dir_all(jar.parent().unwrap()).unwrap();
+        fs::write(&jar, "client").unwrap();
+
+        // Nothing pinned yet
+        instance.verify_pinned_main_jar().unwrap();
+        instance.pin_main_jar().unwrap();
+        let pin = instance.info().unwrap().main_jar_pin.unwrap();
+        assert_eq!(pin.size, 6);
+        instance.verify_pinned_main_jar().unwrap();
+
+        // Same size, different content
+        fs::write(&jar, "CLIENT").unwrap();
+        assert!(matches!(
+            instance.verify_pinned_main_jar(),
+            Err(Error::MainJarCorrupted(_))
+        ));
+        fs::remove_file(&jar).unwrap();
+        assert!(matches!(
+            instance.verify_pinned_main_jar(),
+            Err(Error::MainJarCorrupted(_))
+        ));
+
+        // A custom client jar is not checked against the pin
+        instance.set_main_jar(Some(MainJar::Path {
+            path: dir.join("patched.jar"),
+            sha1: None,
+        }));
+        instance.verify_pinned_main_jar().unwrap();
+        assert_eq!(instance.main_jar_pin().unwrap(), None);
+    }
+
+    /// Compare the class path of the components in `testdata/classpath` against the golden
+    /// file of each OS. Run with `UPDATE_GOLDEN=1` to accept an intended change.
+    #[test]
+    fn class_path_golden() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/classpath");
+        let components = fs::read_to_string(testdata.join("components.json")).unwrap();
+        let components: Vec<Manifest> = serde_json::from_str(&components).unwrap();
+
+        let mut result = SearchResult::new(Vec::new(), MINECRAFT_UID);
+        for manifest in components {
+            result.manifests.insert(manifest.uid.clone(), manifest);
+        }
+        let mut instance = Instance::new("test", "1.18.2", "/game", result);
+        instance.set_libraries_path("/libraries");
+
+        for os in ["linux", "osx", "windows"] {
+            let mut actual = String::new();
+            for entry in instance.class_path_for(&OS::new(os)) {
+                let entry = entry.strip_prefix("/libraries").unwrap();
+                let entry: Vec<_> = entry.iter().map(|c| c.to_string_lossy()).collect();
+                actual.push_str(&entry.join("/"));
+                actual.push('\n');
+            }
+
+            let golden = testdata.join(format!("{}.txt", os));
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                fs::write(&golden, &actual).unwrap();
+            }
+            assert_eq!(
+                actual,
+                fs::read_to_string(&golden).unwrap_or_default(),
+                "class path order for {} changed",
+                os
+            );
+        }
+    }
+
+    /*
+    these tests are broken because we also need to make a fake downloader and idk how to do that
+    #[test]
+    fn get_path() {
+        let instance = Instance::new("test", "0.0.0", "/not/existing", SearchResult::new(DownloadRequest::new("test", "0.0.0", "test", "test", "test")));
+
+        assert_eq!(
+            instance.get_assets_path(),
+            Path::new("/not/existing/assets")
+        );
+        assert_eq!(
+            instance.get_libraries_path(),
+            Path::new("/not/existing/libraries")
+        );
+
+        let mut instance = instance;
+        instance.set_assets_path("/assets/path");
+        assert_eq!(instance.get_assets_path(), Path::new("/assets/path"));
+        assert_eq!(
+            instance.get_libraries_path(),
+            Path::new("/not/existing/libraries/")
+        );
+
+        instance.set_libraries_path("/libraries/path");
+        assert_eq!(instance.get_assets_path(), Path::new("/assets/path"));
+        assert_eq!(instance.get_libraries_path(), Path::new("/libraries/path"));
+    }*/
+}
@@ -0,0 +1,330 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use crate::download::PartialFile;
+use crate::meta::manifest::Sha1Sum;
+use crate::{Error, Result};
+
+/// Directory in the game directory mods are loaded from.
+pub const MODS_DIR: &str = "mods";
+
+/// Name of the file in the game directory holding its [`ModLock`].
+pub const MODS_LOCK_FILE: &str = "mods.lock.json";
+
+/// Directory in [`MODS_DIR`] replaced mods are kept in until all updates are applied.
+const BACKUP_DIR: &str = ".backup";
+
+/// Check that `name` is a plain file name, so names from mod repositories, templates or a
+/// [`ModLock`] can't place or remove files outside of the [`MODS_DIR`].
+///
+/// Fails with [`Error::InvalidFileName`] for empty names, hidden files and names containing a
+/// path separator.
+pub fn check_file_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':', '\0']) {
+        return Err(Error::InvalidFileName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Mod jar in the mods directory of an instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledMod {
+    pub path: PathBuf,
+    pub sha1: Sha1Sum,
+    /// Hash CurseForge identifies files by, see [`curseforge_fingerprint`].
+    pub fingerprint: u32,
+}
+
+impl InstalledMod {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read(path.as_ref())?;
+        let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            sha1: hex::encode(digest.as_ref()).parse()?,
+            fingerprint: curseforge_fingerprint(&data),
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// List the enabled mods in the game directory `game_dir`, sorted by file name.
+pub fn list_mods<P: AsRef<Path>>(game_dir: P) -> Result<Vec<InstalledMod>> {
+    let dir = game_dir.as_ref().join(MODS_DIR);
+    let mut ret = Vec::new();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ret),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && matches!(path.extension(), Some(e) if e == "jar") {
+            ret.push(InstalledMod::load(&path)?);
+        }
+    }
+
+    ret.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(ret)
+}
+
+/// Mods installed from Modrinth with their exact versions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModLock {
+    pub mods: Vec<LockedMod>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedMod {
+    pub project_id: String,
+    pub version_id: String,
+    /// File in [`MODS_DIR`].
+    pub file_name: String,
+    pub sha1: Sha1Sum,
+    /// Installed because another mod requires it.
+    #[serde(default)]
+    pub dependency: bool,
+}
+
+impl ModLock {
+    /// Load the lock of the game directory `dir`, an empty one if there is none yet.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        match OpenOptions::new()
+            .read(true)
+            .open(dir.as_ref().join(MODS_LOCK_FILE))
+        {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save the lock into the game directory `dir`, replacing the previous one at once.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let mut file = PartialFile::create(dir.as_ref().join(MODS_LOCK_FILE))?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.persist()?;
+        Ok(())
+    }
+
+    pub fn get(&self, project_id: &str) -> Option<&LockedMod> {
+        self.mods.iter().find(|m| m.project_id == project_id)
+    }
+
+    /// The mod whose file has the hash `sha1`.
+    pub fn by_sha1(&self, sha1: &Sha1Sum) -> Option<&LockedMod> {
+        self.mods.iter().find(|m| &m.sha1 == sha1)
+    }
+
+    /// Add `locked`, replacing the entry of the same project.
+    ///
+    /// Mods installed explicitly stay marked as such when they are added again as a dependency.
+    pub fn insert(&mut self, mut locked: LockedMod) {
+        if let Some(i) = self
+            .mods
+            .iter()
+            .position(|m| m.project_id == locked.project_id)
+        {
+            locked.dependency &= self.mods[i].dependency;
+            self.mods[i] = locked;
+        } else {
+            self.mods.push(locked);
+        }
+    }
+}
+
+/// Murmur2 hash of `data` without whitespace, which CurseForge uses to look up files.
+pub fn curseforge_fingerprint(data: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+
+    let data: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !matches!(b, 9 | 10 | 13 | 32))
+        .collect();
+    let mut hash = 1 ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        hash = hash.wrapping_mul(M) ^ k;
+    }
+
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, b) in rest.iter().enumerate() {
+            hash ^= (*b as u32) << (8 * i);
+        }
+        hash = hash.wrapping_mul(M);
+    }
+
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(M);
+    hash ^ (hash >> 15)
+}
+
+/// Replacement of an installed mod by a downloaded file.
+#[derive(Debug, Clone)]
+pub struct ModUpdate {
+    /// Installed file, removed by the update.
+    pub old: PathBuf,
+    /// Downloaded file moved into the mods directory.
+    pub staged: PathBuf,
+    /// File name of the new version in the mods directory.
+    pub file_name: String,
+}
+
+/// Replace the mods in `mods_dir` as described by `updates`.
+///
+/// Either all updates are applied or none, if moving one of the files fails the replaced mods
+/// are restored.
+pub fn apply_updates<P: AsRef<Path>>(mods_dir: P, updates: &[ModUpdate]) -> Result<()> {
+    let mods_dir = mods_dir.as_ref();
+    let backup_dir = mods_dir.join(BACKUP_DIR);
+    let _ = std::fs::remove_dir_all(&backup_dir);
+    std::fs::create_dir_all(&backup_dir)?;
+
+    let mut backups = Vec::new();
+    let mut installed = Vec::new();
+    let result: Result<()> = (|| {
+        for update in updates {
+            let backup = backup_dir.join(update.old.file_name().unwrap_or_default());
+            std::fs::rename(&update.old, &backup)?;
+            backups.push((backup, update.old.clone()));
+
+            let new = mods_dir.join(&update.file_name);
+            std::fs::rename(&update.staged, &new)?;
+            installed.push((new, &update.staged));
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        warn!(
+            "updating mods failed, restoring the previous versions: {}",
+            e
+        );
+        for (new, staged) in installed {
+            let _ = std::fs::rename(new, staged);
+        }
+        for (backup, old) in backups {
+            std::fs::rename(&backup, &old)?;
+        }
+        let _ = std::fs::remove_dir_all(&backup_dir);
+        return Err(e);
+    }
+
+    std::fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn file_names() {
+        check_file_name("sodium-0.4.jar").unwrap();
+        for invalid in [
+            "", ".", "..", "../x.jar", "/etc/x", "a\\b.jar", "C:x.jar", "a\0.jar",
+        ] {
+            assert!(
+                matches!(check_file_name(invalid), Err(Error::InvalidFileName(_))),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn update_mods() {
+        assert_eq!(curseforge_fingerprint(b""), 1540447798);
+        assert_eq!(curseforge_fingerprint(b"hello world"), 2824650221);
+        assert_eq!(curseforge_fingerprint(b"hello\r\n\tworld"), 2824650221);
+
+        let dir = TempDir::new("mods");
+        let mods_dir = dir.join(MODS_DIR);
+        std::fs::create_dir_all(&mods_dir).unwrap();
+        for name in ["a-1.0.jar", "b-1.0.jar"] {
+            std::fs::write(mods_dir.join(name), name).unwrap();
+        }
+        std::fs::write(mods_dir.join("c.jar.disabled"), "c").unwrap();
+        std::fs::write(dir.join("a-2.0.jar"), "a-2.0.jar").unwrap();
+
+        let mods = list_mods(&dir).unwrap();
+        assert_eq!(mods.len(), 2);
+        assert_eq!(mods[0].file_name(), "a-1.0.jar");
+
+        let update = |name: &str, staged: &str, file_name: &str| ModUpdate {
+            old: mods_dir.join(name),
+            staged: dir.join(staged),
+            file_name: file_name.to_string(),
+        };
+
+        // The second download is missing, the first update is rolled back
+        let updates = [
+            update("a-1.0.jar", "a-2.0.jar", "a-2.0.jar"),
+            update("b-1.0.jar", "b-2.0.jar", "b-2.0.jar"),
+        ];
+        assert!(apply_updates(&mods_dir, &updates).is_err());
+        let names: Vec<_> = list_mods(&dir)
+            .unwrap()
+            .iter()
+            .map(InstalledMod::file_name)
+            .collect();
+        assert_eq!(names, ["a-1.0.jar", "b-1.0.jar"]);
+
+        apply_updates(&mods_dir, &updates[..1]).unwrap();
+        let names: Vec<_> = list_mods(&dir)
+            .unwrap()
+            .iter()
+            .map(InstalledMod::file_name)
+            .collect();
+        assert_eq!(names, ["a-2.0.jar", "b-1.0.jar"]);
+        assert!(!mods_dir.join(BACKUP_DIR).exists());
+    }
+
+    #[test]
+    fn mod_lock() {
+        let dir = TempDir::new("mod-lock");
+        std::fs::create_dir_all(&dir).unwrap();
+        let locked = |project: &str, dependency: bool| LockedMod {
+            project_id: project.to_string(),
+            version_id: format!("{}-1", project),
+            file_name: format!("{}.jar", project),
+            sha1: "0000000000000000000000000000000000000000".parse().unwrap(),
+            dependency,
+        };
+
+        let mut lock = ModLock::load(&dir).unwrap();
+        assert!(lock.mods.is_empty());
+        lock.insert(locked("a", false));
+        lock.insert(locked("b", true));
+        // Required by another mod, but installed explicitly before
+        lock.insert(locked("a", true));
+        lock.save(&dir).unwrap();
+
+        let lock = ModLock::load(&dir).unwrap();
+        assert_eq!(lock.mods.len(), 2);
+        assert!(!lock.get("a").unwrap().dependency);
+        assert!(lock.get("b").unwrap().dependency);
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use super::Instance;
+use crate::lock::DirLock;
+use crate::meta::manifest::{ExtractOptions, Library, Sha1Sum, OS};
+use crate::meta::DownloadRequest;
+use crate::Result;
+
+/// Name of the file in the natives directory recording what was extracted into it.
+pub(crate) const NATIVES_STAMP_FILE: &str = ".natives.json";
+
+/// Record of an extraction, so the natives are only extracted again if they changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NativesStamp {
+    /// Hash of the extracted jars, see [`NativesStamp::sources_hash`].
+    pub sources: String,
+    /// Extracted files relative to the natives directory, with the sha1 of their content.
+    pub files: BTreeMap<String, Sha1Sum>,
+}
+
+impl NativesStamp {
+    /// Load the stamp of the natives directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(dir.as_ref().join(NATIVES_STAMP_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(dir.as_ref().join(NATIVES_STAMP_FILE))?;
+        Ok(serde_json::to_writer(file, self)?)
+    }
+
+    /// Hash identifying the natives jars of `libs` for `platform` in `libraries_path`.
+    ///
+    /// Uses the hashes from the meta data, so the jars are not read. Jars without one are
+    /// identified by their size and modification time.
+    pub fn sources_hash(libs: &[&Library], libraries_path: &str, platform: &OS) -> String {
+        let mut digest = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+        for lib in libs {
+            digest.update(lib.name.to_string().as_bytes());
+            match lib.select_for(platform).and_then(|d| d.sha1.clone()) {
+                Some(sha1) => digest.update(sha1.as_ref()),
+                None => {
+                    let jar = lib.path_at_for(libraries_path, platform);
+                    if let Ok(metadata) = std::fs::metadata(jar) {
+                        digest.update(format!("{:?}", metadata.modified().ok()).as_bytes());
+                        digest.update(&metadata.len().to_le_bytes());
+                    }
+                }
+            }
+            for exclude in lib.extract.iter().flat_map(|e| &e.exclude) {
+                digest.update(exclude.as_bytes());
+            }
+            digest.update(b"\n");
+        }
+
+        hex::encode(digest.finish().as_ref())
+    }
+
+    /// Whether the stamp was made for `sources` and all extracted files in `dir` are unchanged.
+    ///
+    /// Hashing the extracted files is much cheaper than extracting the jars again.
+    pub fn is_current<P: AsRef<Path>>(&self, dir: P, sources: &str) -> bool {
+        self.sources == sources
+            && self.files.iter().all(|(name, sha1)| {
+                matches!(crate::hash::sha1_file(dir.as_ref().join(name)), Ok(h) if h == *sha1)
+            })
+    }
+}
+
+/// Extract the natives jar read from `reader` into `dest`, skipping the excluded paths.
+///
+/// Works on any seekable reader, e.g. a downloaded jar still in memory. Returns the extracted
+/// files relative to `dest` with the sha1 of their content.
+pub fn extract_natives<R: Read + Seek, P: AsRef<Path>>(
+    reader: R,
+    extract: Option<&ExtractOptions>,
+    dest: P,
+) -> Result<BTreeMap<String, Sha1Sum>> {
+    let dest = dest.as_ref();
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut ret = BTreeMap::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = match file.enclosed_name() {
+            Some(name) => name.to_path_buf(),
+            None => continue,
+        };
+        let excluded = extract
+            .iter()
+            .flat_map(|e| &e.exclude)
+            .any(|x| file.name().starts_with(x.as_str()));
+        if excluded {
+            trace!("Skipping: {}", file.name());
+            continue;
+        }
+
+        let outpath = dest.join(&name);
+        if file.is_dir() {
+            std::fs::create_dir_all(&outpath)?;
+            continue;
+        }
+
+        trace!("extracting file: {}", file.name());
+        if let Some(p) = outpath.parent() {
+            std::fs::create_dir_all(p)?;
+        }
+        let mut outfile = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&outpath)?;
+        let mut digest = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+        let mut buf = [0; 8192];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            digest.update(&buf[..n]);
+            outfile.write_all(&buf[..n])?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = file.unix_mode() {
+                std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+
+        let name: Vec<_> = name.iter().map(|c| c.to_string_lossy()).collect();
+        ret.insert(name.join("/"), hex::encode(digest.finish()).parse()?);
+    }
+
+    Ok(ret)
+}
+
+/// Extraction of natives into a natives directory, locked until it is finished.
+///
+/// Frontends can extract the natives jars right after their download with
+/// [`NativesExtractor::extract`], while the data is still cached by the system, instead of
+/// reading them from the library store again when the game starts.
+pub struct NativesExtractor {
+    dir: PathBuf,
+    /// Jars extracted so far.
+    jars: HashSet<PathBuf>,
+    files: BTreeMap<String, Sha1Sum>,
+    _lock: DirLock,
+}
+
+impl NativesExtractor {
+    /// Lock the natives directory `dir` to extract into it, e.g. [`Instance::get_natives_path`].
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let lock = DirLock::lock_timeout(&dir, super::NATIVES_LOCK_TIMEOUT)?;
+        Ok(Self::locked(dir, lock))
+    }
+
+    /// Like [`NativesExtractor::new`], without blocking while another process extracts.
+    pub async fn new_async<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let lock = DirLock::lock_timeout_async(&dir, super::NATIVES_LOCK_TIMEOUT).await?;
+        Ok(Self::locked(dir, lock))
+    }
+
+    fn locked(dir: PathBuf, lock: DirLock) -> Self {
+        Self {
+            dir,
+            jars: HashSet::new(),
+            files: BTreeMap::new(),
+            _lock: lock,
+        }
+    }
+
+    /// Extract the jar downloaded for `request`, returning if it holds natives at all.
+    pub fn extract(&mut self, request: &DownloadRequest) -> Result<bool> {
+        let (extract, jar) = match (request.natives(), request.get_path()) {
+            (Some(extract), Some(jar)) => (extract, PathBuf::from(jar)),
+            _ => return Ok(false),
+        };
+        if self.jars.is_empty() {
+            let _ = std::fs::remove_file(self.dir.join(NATIVES_STAMP_FILE));
+        }
+
+        trace!(
+            "extracting natives {} to: {}",
+            jar.display(),
+            self.dir.display()
+        );
+        let file = OpenOptions::new().read(true).open(&jar)?;
+        self.files
+            .extend(extract_natives(file, Some(extract), &self.dir)?);
+        self.jars.insert(jar);
+        Ok(true)
+    }
+
+    /// Extract the natives of `instance` which were not extracted yet and record the extraction
+    /// in the directory, returning it.
+    ///
+    /// If nothing was extracted and the stamp of the directory is current, nothing is extracted
+    /// at all.
+    pub fn finish(mut self, instance: &Instance) -> Result<String> {
+        let os = OS::get();
+        let libs = instance.get_natives(&os);
+        let libraries_path = instance.get_libraries_path();
+        let sources = NativesStamp::sources_hash(&libs, &libraries_path, &os);
+        let current = matches!(
+            NativesStamp::load(&self.dir),
+            Ok(stamp) if stamp.is_current(&self.dir, &sources)
+        );
+        let dir = self.dir.display().to_string();
+        if self.jars.is_empty() && current {
+            trace!("natives in {} are up to date", dir);
+            return Ok(dir);
+        }
+
+        let _ = std::fs::remove_file(self.dir.join(NATIVES_STAMP_FILE));
+        for lib in libs {
+            let jar = lib.path_at_for(&libraries_path, &os);
+            if self.jars.contains(&jar) {
+                continue;
+            }
+            trace!("extracting natives {} to: {}", jar.display(), dir);
+            let file = OpenOptions::new().read(true).open(&jar)?;
+            self.files
+                .extend(extract_natives(file, lib.extract.as_ref(), &self.dir)?);
+        }
+        NativesStamp {
+            sources,
+            files: self.files,
+        }
+        .save(&self.dir)?;
+
+        Ok(dir)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instance::Instance;
+    use crate::meta::SearchResult;
+    use crate::test_util::TempDir;
+    use std::io::Write;
+
+    #[test]
+    fn natives_stamp() {
+        let dir = TempDir::new("natives");
+        let manifest = r#"{"name": "LWJGL", "uid": "org.lwjgl", "version": "2.9.4",
+            "order": -1, "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": [{"name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                "natives": {"linux": "natives-linux", "osx": "natives-osx",
+                    "windows": "natives-windows"},
+                "extract": {"exclude": ["META-INF/"]},
+                "downloads": {"classifiers": {
+                    "natives-linux": {"size": -1, "url": "https://example.com/natives.jar"},
+                    "natives-osx": {"size": -1, "url": "https://example.com/natives.jar"},
+                    "natives-windows": {"size": -1, "url": "https://example.com/natives.jar"}
+                }}
+            }]
+        }"#;
+        let mut result = SearchResult::new(Vec::new(), "org.lwjgl");
+        result
+            .manifests
+            .insert("org.lwjgl".to_string(), manifest.parse().unwrap());
+        let mut instance = Instance::new("test", "2.9.4", &dir.display().to_string(), result);
+        instance.set_libraries_path(&dir.join("lib").display().to_string());
+
+        let os = OS::get();
+        let jar = instance.get_natives(&os)[0].path_at_for(&instance.get_libraries_path(), &os);
+        std::fs::create_dir_all(jar.parent().unwrap()).unwrap();
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&jar).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file("liblwjgl.so", options).unwrap();
+        zip.write_all(b"natives").unwrap();
+        zip.start_file("META-INF/MANIFEST.MF", options).unwrap();
+        zip.write_all(b"Manifest-Version: 1.0").unwrap();
+        zip.finish().unwrap();
+
+        let natives = Path::new(&instance.build_natives().unwrap()).to_path_buf();
+        assert!(natives.join("liblwjgl.so").is_file());
+        assert!(!natives.join("META-INF").exists());
+        let stamp = NativesStamp::load(&natives).unwrap();
+        assert_eq!(stamp.files.len(), 1);
+
+        // Up to date, not extracted again
+        let modified = std::fs::metadata(natives.join("liblwjgl.so"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        instance.build_natives().unwrap();
+        let metadata = std::fs::metadata(natives.join("liblwjgl.so")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+
+        // Changed and removed files are restored
+        std::fs::write(natives.join("liblwjgl.so"), b"patched").unwrap();
+        instance.build_natives().unwrap();
+        assert_eq!(
+            std::fs::read(natives.join("liblwjgl.so")).unwrap(),
+            b"natives"
+        );
+        std::fs::remove_file(natives.join("liblwjgl.so")).unwrap();
+        instance.build_natives().unwrap();
+        assert_eq!(
+            std::fs::read(natives.join("liblwjgl.so")).unwrap(),
+            b"natives"
+        );
+
+        // Extracted right after the download, the jar is not read again
+        std::fs::remove_dir_all(&natives).unwrap();
+        let lib = instance.get_natives(&os)[0];
+        let request = DownloadRequest::new_natives(
+            lib.select_for(&os).unwrap().into_owned(),
+            jar.clone(),
+            lib.extract.clone().unwrap(),
+        );
+        let mut extractor = NativesExtractor::new(&natives).unwrap();
+        assert!(extractor.extract(&request).unwrap());
+        std::fs::remove_file(&jar).unwrap();
+        extractor.finish(&instance).unwrap();
+        assert!(natives.join("liblwjgl.so").is_file());
+        assert!(!natives.join("META-INF").exists());
+        instance.build_natives().unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Changes to the command of an instance taking precedence over its components, for debugging
+/// and mod setups the meta does not describe, e.g. a custom launch wrapper.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LaunchOverrides {
+    /// Main class started instead of the one declared by the components.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main_class: Option<String>,
+    /// JVM arguments passed before all others, so every later one wins over them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub jvm_args_prepend: Vec<String>,
+    /// JVM arguments passed after all others, so they win over earlier ones like `-Xmx`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub jvm_args_append: Vec<String>,
+    /// Game arguments passed right after the main class.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub game_args_prepend: Vec<String>,
+    /// Game arguments passed after all others.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub game_args_append: Vec<String>,
+}
+
+impl LaunchOverrides {
+    /// Whether the overrides leave the command as it is.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -0,0 +1,278 @@
+use std::ffi::OsString;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::instance::Instance;
+use crate::Result;
+
+/// Environment variables kept when the environment is cleaned, needed to show a window, play
+/// sound and find the user's locale.
+pub const DEFAULT_KEEP_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TZ",
+    "DISPLAY",
+    "XAUTHORITY",
+    "WAYLAND_DISPLAY",
+    "XDG_RUNTIME_DIR",
+    "PULSE_SERVER",
+];
+
+/// System directories mounted read only by bubblewrap.
+#[cfg(target_os = "linux")]
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr", "/etc", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/opt", "/sys",
+];
+
+/// Program used to isolate the game from the rest of the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxWrapper {
+    /// [bubblewrap](https://github.com/containers/bubblewrap)
+    Bubblewrap,
+    /// [firejail](https://firejail.wordpress.com)
+    Firejail,
+}
+
+impl SandboxWrapper {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Bubblewrap => "bwrap",
+            Self::Firejail => "firejail",
+        }
+    }
+}
+
+/// Restrictions applied when launching an instance.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    /// Only pass [`DEFAULT_KEEP_ENV`] and [`Sandbox::keep_env`] to the game.
+    pub clean_env: bool,
+    /// Additional environment variables to keep in a cleaned environment.
+    pub keep_env: Vec<String>,
+    /// Run the game through a wrapper (Linux only), which only allows writing to the instance
+    /// directory.
+    pub wrapper: Option<SandboxWrapper>,
+    /// Additional paths the game may read, e.g. a shared resource pack directory.
+    pub read_only_paths: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    /// Apply the sandbox to the command starting the game of `instance`.
+    pub fn apply(&self, command: Command, instance: &Instance) -> Result<Command> {
+        let mut command = match self.wrapper {
+            Some(wrapper) => self.wrap(wrapper, &command, instance)?,
+            None => command,
+        };
+
+        if self.clean_env {
+            self.clean_env(&mut command, |name| std::env::var_os(name));
+        }
+
+        Ok(command)
+    }
+
+    /// Only pass the variables to keep to `command`, looking up their values with `var`.
+    fn clean_env<F: Fn(&str) -> Option<OsString>>(&self, command: &mut Command, var: F) {
+        // Variables set on the command itself are meant for the game
+        let explicit: Vec<(OsString, OsString)> = command
+            .get_envs()
+            .filter_map(|(name, value)| Some((name.to_owned(), value?.to_owned())))
+            .collect();
+        command.env_clear();
+        command.envs(explicit);
+        let keep = DEFAULT_KEEP_ENV
+            .iter()
+            .copied()
+            .chain(self.keep_env.iter().map(String::as_str));
+        for name in keep {
+            if let Some(value) = var(name) {
+                command.env(name, value);
+            }
+        }
+    }
+
+    /// Paths the game has to read, besides the system directories.
+    #[cfg(target_os = "linux")]
+    fn read_paths(&self, command: &Command, instance: &Instance) -> Vec<PathBuf> {
+        let mut ret = vec![
+            PathBuf::from(instance.get_libraries_path()),
+            PathBuf::from(instance.get_assets_path()),
+            PathBuf::from(instance.get_natives_path()),
+        ];
+
+        // java/bin/java -> java
+        let java = std::fs::canonicalize(command.get_program())
+            .unwrap_or_else(|_| PathBuf::from(command.get_program()));
+        if let Some(home) = java.parent().and_then(Path::parent) {
+            ret.push(home.to_path_buf());
+        }
+        if let Some(jar) = &instance.main_jar {
+            ret.push(jar.path_at(&instance.get_libraries_path()));
+        }
+        // Agents outside of the game directory
+        ret.extend(
+            instance
+                .java_agents
+                .iter()
+                .map(|agent| agent.path_at(&instance.minecraft_path)),
+        );
+        ret.extend(self.read_only_paths.iter().cloned());
+
+        // Binding `/` would expose the whole system, e.g. for a java in `/bin`
+        ret.retain(|p| p.exists() && p.parent().is_some());
+        ret
+    }
+
+    #[cfg(target_os = "linux")]
+    fn wrap(
+        &self,
+        wrapper: SandboxWrapper,
+        command: &Command,
+        instance: &Instance,
+    ) -> Result<Command> {
+        let mut args: Vec<OsString> = Vec::new();
+        let instance_dir = OsString::from(&instance.minecraft_path);
+        let read_paths = self.read_paths(command, instance);
+
+        match wrapper {
+            SandboxWrapper::Bubblewrap => {
+                args.extend(["--die-with-parent", "--unshare-all", "--share-net"].map(Into::into));
+                for dir in SYSTEM_DIRS.iter().filter(|d| Path::new(d).exists()) {
+                    args.extend(["--ro-bind", dir, dir].map(Into::into));
+                }
+                args.extend(["--dev-bind", "/dev", "/dev", "--proc", "/proc"].map(Into::into));
+                args.extend(["--tmpfs", "/tmp"].map(Into::into));
+                // X11 socket
+                if Path::new("/tmp/.X11-unix").exists() {
+                    args.extend(["--ro-bind", "/tmp/.X11-unix", "/tmp/.X11-unix"].map(Into::into));
+                }
+                // Wayland and pulseaudio sockets
+                if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+                    args.extend(["--bind".into(), runtime_dir.clone(), runtime_dir]);
+                }
+                for path in read_paths {
+                    args.extend([
+                        "--ro-bind".into(),
+                        path.clone().into_os_string(),
+                        path.into_os_string(),
+                    ]);
+                }
+                args.extend(["--bind".into(), instance_dir.clone(), instance_dir.clone()]);
+                args.extend(["--chdir".into(), instance_dir]);
+                args.push("--".into());
+            }
+            SandboxWrapper::Firejail => {
+                args.extend(["--quiet", "--noroot"].map(Into::into));
+                for path in read_paths {
+                    let mut whitelist = OsString::from("--whitelist=");
+                    whitelist.push(&path);
+                    let mut read_only = OsString::from("--read-only=");
+                    read_only.push(&path);
+                    args.extend([whitelist, read_only]);
+                }
+                let mut whitelist = OsString::from("--whitelist=");
+                whitelist.push(&instance_dir);
+                args.push(whitelist);
+                args.push("--".into());
+            }
+        }
+
+        let mut wrapped = Command::new(wrapper.program());
+        wrapped
+            .args(args)
+            .arg(command.get_program())
+            .args(command.get_args())
+            .current_dir(&instance.minecraft_path);
+        for (name, value) in command.get_envs() {
+            match value {
+                Some(value) => wrapped.env(name, value),
+                None => wrapped.env_remove(name),
+            };
+        }
+
+        Ok(wrapped)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn wrap(
+        &self,
+        wrapper: SandboxWrapper,
+        _command: &Command,
+        _instance: &Instance,
+    ) -> Result<Command> {
+        Err(crate::Error::SandboxNotSupported(
+            wrapper.program().to_string(),
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::instance::JavaAgent;
+    use crate::meta::SearchResult;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn bubblewrap() {
+        let libraries = TempDir::new("sandbox-libraries");
+        std::fs::create_dir_all(&libraries).unwrap();
+        let mut instance = Instance::new(
+            "test",
+            "1.18",
+            "/tmp",
+            SearchResult::new(Vec::new(), "net.minecraft"),
+        );
+        instance.set_libraries_path(&libraries.display().to_string());
+        let agent = std::env::current_exe().unwrap();
+        instance.set_java_agents(vec![JavaAgent {
+            path: agent.clone(),
+            options: None,
+        }]);
+        let sandbox = Sandbox {
+            clean_env: true,
+            keep_env: vec!["PLMC_SANDBOX_TEST".to_string()],
+            wrapper: Some(SandboxWrapper::Bubblewrap),
+            read_only_paths: Vec::new(),
+        };
+
+        let mut command = Command::new("/usr/bin/java");
+        command.arg("-version").env("PLMC_SANDBOX_EXPLICIT", "1");
+        let mut command = sandbox.apply(command, &instance).unwrap();
+
+        assert_eq!(command.get_program(), "bwrap");
+        let args: Vec<_> = command.get_args().collect();
+        assert!(args.windows(3).any(|a| a == ["--bind", "/tmp", "/tmp"]));
+        let libraries = libraries.as_os_str();
+        assert!(args
+            .windows(3)
+            .any(|a| a == ["--ro-bind".as_ref(), libraries, libraries]));
+        let agent = agent.as_os_str();
+        assert!(args
+            .windows(3)
+            .any(|a| a == ["--ro-bind".as_ref(), agent, agent]));
+        assert_eq!(args[args.len() - 2..], ["/usr/bin/java", "-version"]);
+
+        sandbox.clean_env(&mut command, |name| match name {
+            "PLMC_SANDBOX_TEST" => Some("1".into()),
+            _ => None,
+        });
+        assert!(command
+            .get_envs()
+            .any(|(k, v)| k == "PLMC_SANDBOX_TEST" && v.is_some()));
+        assert!(command
+            .get_envs()
+            .any(|(k, v)| k == "PLMC_SANDBOX_EXPLICIT" && v.is_some()));
+
+        // The root is never exposed
+        instance.set_libraries_path("/");
+        let command = sandbox.apply(Command::new("/bin/java"), &instance).unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert!(!args.windows(3).any(|a| a == ["--ro-bind", "/", "/"]));
+    }
+}
@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds the game gets to exit after the time is up, before it is killed.
+pub const DEFAULT_SESSION_GRACE: u64 = 60;
+
+/// How long before the end of a session the player is warned.
+const WARNINGS: &[Duration] = &[Duration::from_secs(5 * 60), Duration::from_secs(60)];
+
+/// Limits on the time an instance may be played, e.g. set by parents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionLimit {
+    /// Minutes a single launch may last, restarts after a crash included.
+    #[serde(default)]
+    pub per_launch: Option<u64>,
+    /// Minutes the instance may be played per day, in UTC.
+    #[serde(default)]
+    pub per_day: Option<u64>,
+    /// Write the warnings to stdin of the game as `say` command, for servers and mods reading
+    /// commands from there. They are logged by the launcher either way.
+    #[serde(default)]
+    pub chat_warnings: bool,
+    /// Seconds between asking the game to exit once the time is up and killing it.
+    #[serde(default = "default_grace")]
+    pub grace: u64,
+}
+
+fn default_grace() -> u64 {
+    DEFAULT_SESSION_GRACE
+}
+
+impl Default for SessionLimit {
+    fn default() -> Self {
+        Self {
+            per_launch: None,
+            per_day: None,
+            chat_warnings: false,
+            grace: DEFAULT_SESSION_GRACE,
+        }
+    }
+}
+
+impl SessionLimit {
+    /// Whether the limit does not restrict anything.
+    pub fn is_empty(&self) -> bool {
+        self.per_launch.is_none() && self.per_day.is_none()
+    }
+
+    /// Time left for a launch after `played_today` seconds of play, `None` without a limit.
+    pub fn remaining(&self, played_today: u64) -> Option<Duration> {
+        let per_launch = self.per_launch.map(|minutes| minutes * 60);
+        let per_day = self
+            .per_day
+            .map(|minutes| (minutes * 60).saturating_sub(played_today));
+        match (per_launch, per_day) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+        .map(Duration::from_secs)
+    }
+}
+
+/// Step of ending a session, returned by [`SessionTimer::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionAction {
+    /// Tell the player the time left.
+    Warn(Duration),
+    /// Ask the game to exit.
+    Terminate,
+    /// Kill the game, it did not exit within the grace period.
+    Kill,
+}
+
+/// Deadline of a running game under a [`SessionLimit`].
+#[derive(Debug, Clone)]
+pub struct SessionTimer {
+    deadline: Instant,
+    grace: Duration,
+    /// Whether warnings are written to stdin of the game.
+    pub chat_warnings: bool,
+    warned: usize,
+    terminated: bool,
+    killed: bool,
+}
+
+impl SessionTimer {
+    /// Timer for a game started at `started` with `remaining` time to play.
+    pub fn new(limit: &SessionLimit, remaining: Duration, started: Instant) -> Self {
+        Self {
+            deadline: started + remaining,
+            grace: Duration::from_secs(limit.grace),
+            chat_warnings: limit.chat_warnings,
+            // Warnings for more time than there is are skipped
+            warned: WARNINGS.iter().filter(|w| **w >= remaining).count(),
+            terminated: false,
+            killed: false,
+        }
+    }
+
+    /// Time left to play at `now`.
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+
+    /// Whether the game was ended because the time was up.
+    pub fn expired(&self) -> bool {
+        self.terminated
+    }
+
+    /// When [`SessionTimer::poll`] returns its next action, `None` once the game was killed.
+    pub fn next_action(&self) -> Option<Instant> {
+        if self.killed {
+            return None;
+        }
+        if self.terminated {
+            return Some(self.deadline + self.grace);
+        }
+        let warning = WARNINGS
+            .get(self.warned)
+            .and_then(|warning| self.deadline.checked_sub(*warning));
+        Some(warning.unwrap_or(self.deadline))
+    }
+
+    /// What to do with the game at `now`, each action is returned once.
+    pub fn poll(&mut self, now: Instant) -> Option<SessionAction> {
+        if self.terminated {
+            if !self.killed && now >= self.deadline + self.grace {
+                self.killed = true;
+                return Some(SessionAction::Kill);
+            }
+            return None;
+        }
+        if now >= self.deadline {
+            self.terminated = true;
+            return Some(SessionAction::Terminate);
+        }
+
+        let remaining = self.remaining(now);
+        let due = WARNINGS.iter().filter(|w| **w >= remaining).count();
+        if due > self.warned {
+            self.warned = due;
+            return Some(SessionAction::Warn(remaining));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remaining() {
+        let limit = SessionLimit {
+            per_launch: Some(60),
+            per_day: Some(90),
+            ..Default::default()
+        };
+        assert_eq!(limit.remaining(0), Some(Duration::from_secs(3600)));
+        assert_eq!(limit.remaining(60 * 60), Some(Duration::from_secs(1800)));
+        assert_eq!(limit.remaining(100 * 60), Some(Duration::ZERO));
+        assert_eq!(SessionLimit::default().remaining(0), None);
+
+        let limit: SessionLimit = serde_json::from_str(r#"{"perDay": 30}"#).unwrap();
+        assert_eq!(limit.grace, DEFAULT_SESSION_GRACE);
+        assert_eq!(limit.remaining(0), Some(Duration::from_secs(1800)));
+    }
+
+    #[test]
+    fn timer() {
+        let start = Instant::now();
+        let limit = SessionLimit {
+            per_launch: Some(10),
+            grace: 30,
+            ..Default::default()
+        };
+        let mut timer = SessionTimer::new(&limit, Duration::from_secs(600), start);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(timer.poll(at(10)), None);
+        assert_eq!(timer.next_action(), Some(at(300)));
+        assert_eq!(
+            timer.poll(at(300)),
+            Some(SessionAction::Warn(Duration::from_secs(300)))
+        );
+        assert_eq!(timer.poll(at(301)), None);
+        assert_eq!(timer.next_action(), Some(at(540)));
+        // Both warnings at once only warn once
+        let mut late = SessionTimer::new(&limit, Duration::from_secs(600), start);
+        assert_eq!(
+            late.poll(at(590)),
+            Some(SessionAction::Warn(Duration::from_secs(10)))
+        );
+        assert_eq!(late.poll(at(591)), None);
+
+        assert_eq!(
+            timer.poll(at(540)),
+            Some(SessionAction::Warn(Duration::from_secs(60)))
+        );
+        assert_eq!(timer.poll(at(600)), Some(SessionAction::Terminate));
+        assert!(timer.expired());
+        assert_eq!(timer.next_action(), Some(at(630)));
+        assert_eq!(timer.poll(at(610)), None);
+        assert_eq!(timer.poll(at(630)), Some(SessionAction::Kill));
+        assert_eq!(timer.next_action(), None);
+        assert_eq!(timer.poll(at(700)), None);
+
+        // Less time than the first warning
+        let mut short = SessionTimer::new(&limit, Duration::from_secs(120), start);
+        assert_eq!(short.poll(at(1)), None);
+        assert_eq!(
+            short.poll(at(60)),
+            Some(SessionAction::Warn(Duration::from_secs(60)))
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+
+use crate::meta::manifest::Sha1Sum;
+use crate::meta::{MetaIndex, PackageIndex};
+use crate::{Error, Result};
+
+use super::InstanceComponent;
+
+/// Templates shipped with the library, see [`InstanceTemplate::builtin`].
+const BUILTIN_TEMPLATES: &str = include_str!("templates.json");
+
+/// Named recipe for a new instance, e.g. "fabric-performance".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Components in the order they are resolved, see [`InstanceTemplate::resolve`].
+    pub components: Vec<TemplateComponent>,
+    /// Mods installed into the `mods` directory of the instance.
+    #[serde(default)]
+    pub mods: Vec<TemplateMod>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateComponent {
+    pub uid: String,
+    /// Fixed version, the latest release if not set.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Use the version chosen for this earlier component, e.g. the intermediary mappings
+    /// of Fabric follow `net.minecraft`.
+    #[serde(default)]
+    pub same_as: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplateMod {
+    /// Slug or id of a Modrinth project, installed in its newest version for the game version
+    /// and mod loader of the instance.
+    Modrinth { modrinth: String },
+    /// File downloaded as is.
+    Url {
+        url: String,
+        #[serde(default)]
+        sha1: Option<Sha1Sum>,
+        /// Name of the file in the `mods` directory, the last segment of the url if not set.
+        #[serde(default, rename = "fileName", skip_serializing_if = "Option::is_none")]
+        file_name: Option<String>,
+    },
+}
+
+impl TemplateMod {
+    /// Name of the file a [`TemplateMod::Url`] is saved as in the `mods` directory, `None` for
+    /// Modrinth projects.
+    ///
+    /// Characters of the url which are not safe in file names are replaced. Fails with
+    /// [`Error::InvalidFileName`] if the name isn't a plain file name, see
+    /// [`super::check_file_name`].
+    pub fn file_name(&self) -> Result<Option<String>> {
+        let name = match self {
+            Self::Modrinth { .. } => return Ok(None),
+            Self::Url {
+                file_name: Some(name),
+                ..
+            } => name.to_string(),
+            Self::Url { url, .. } => {
+                let path = url.split(['?', '#']).next().unwrap_or_default();
+                let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+                let path = path.split_once('/').map_or("", |(_, rest)| rest);
+                path.rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .map(|c| match c {
+                        'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' | '+' => c,
+                        _ => '_',
+                    })
+                    .collect()
+            }
+        };
+        super::check_file_name(&name)?;
+        Ok(Some(name))
+    }
+}
+
+impl InstanceTemplate {
+    /// Templates shipped with the library.
+    pub fn builtin() -> Vec<Self> {
+        serde_json::from_str(BUILTIN_TEMPLATES).expect("Invalid builtin templates")
+    }
+
+    /// Find the template `name` in `templates`.
+    pub fn find<'a>(templates: &'a [Self], name: &str) -> Result<&'a Self> {
+        templates
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| Error::UnknownTemplate(name.to_string()))
+    }
+
+    /// Choose the version of each component, using the package indices loaded into `index`.
+    ///
+    /// Fails with [`Error::PendingDownloads`] if the package index of a component is not
+    /// loaded yet.
+    pub fn resolve(&self, index: &MetaIndex) -> Result<Vec<InstanceComponent>> {
+        let mut ret: Vec<InstanceComponent> = Vec::new();
+        for component in &self.components {
+            let package = index
+                .get_uid(&component.uid)?
+                .index
+                .as_ref()
+                .ok_or(Error::PendingDownloads)?;
+
+            let version = if let Some(uid) = &component.same_as {
+                let version = ret
+                    .iter()
+                    .find(|c| &c.uid == uid)
+                    .ok_or_else(|| Error::UnknownUid(uid.to_string()))?;
+                package.find_version(&version.version)?.version.clone()
+            } else {
+                match component.version.as_deref() {
+                    None | Some("latest") => latest_release(package)?,
+                    Some(version) => package.find_version(version)?.version.clone(),
+                }
+            };
+
+            ret.push(InstanceComponent {
+                uid: component.uid.clone(),
+                version,
+            });
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Newest version of `package` released as stable.
+fn latest_release(package: &PackageIndex) -> Result<String> {
+    package
+        .versions
+        .iter()
+        .filter(|v| v.release_type == "release")
+        .max_by(|a, b| a.release_time.cmp(&b.release_time))
+        .map(|v| v.version.clone())
+        .ok_or_else(|| Error::UnknownVersion {
+            uid: package.uid.clone(),
+            version: "latest".to_string(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn package(uid: &str, versions: &[(&str, &str, &str)]) -> PackageIndex {
+        let versions: Vec<_> = versions
+            .iter()
+            .map(|(version, release_type, time)| {
+                format!(
+                    r#"{{"releaseTime": "{}", "sha256": "{}", "type": "{}", "version": "{}"}}"#,
+                    time, HASH, release_type, version
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"formatVersion": 1, "name": "{uid}", "uid": "{uid}", "versions": [{}]}}"#,
+            versions.join(","),
+            uid = uid
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn mod_file_name() {
+        let url = |url: &str, file_name: Option<&str>| TemplateMod::Url {
+            url: url.to_string(),
+            sha1: None,
+            file_name: file_name.map(ToString::to_string),
+        };
+        let file_name = |u: &str| url(u, None).file_name();
+
+        assert_eq!(
+            file_name("https://example.com/mods/extra-1.0.jar?download=1").unwrap(),
+            Some("extra-1.0.jar".to_string())
+        );
+        assert_eq!(
+            file_name("https://example.com/mods/my%20mod:1.jar").unwrap(),
+            Some("my_20mod_1.jar".to_string())
+        );
+        for invalid in [
+            "https://example.com",
+            "https://example.com/mods/",
+            "https://example.com/mods/..",
+            "https://example.com/mods/%2e%2e/../.bashrc",
+        ] {
+            assert!(
+                matches!(file_name(invalid), Err(Error::InvalidFileName(_))),
+                "{}",
+                invalid
+            );
+        }
+
+        let named = url("https://example.com/download?id=1", Some("extra.jar"));
+        assert_eq!(named.file_name().unwrap(), Some("extra.jar".to_string()));
+        let outside = url("https://example.com/extra.jar", Some("../extra.jar"));
+        assert!(matches!(
+            outside.file_name(),
+            Err(Error::InvalidFileName(_))
+        ));
+        let modrinth = TemplateMod::Modrinth {
+            modrinth: "sodium".to_string(),
+        };
+        assert_eq!(modrinth.file_name().unwrap(), None);
+    }
+
+    #[test]
+    fn resolve_builtin() {
+        let templates = InstanceTemplate::builtin();
+        assert!(InstanceTemplate::find(&templates, "vanilla-latest").is_ok());
+        assert!(matches!(
+            InstanceTemplate::find(&templates, "missing"),
+            Err(Error::UnknownTemplate(_))
+        ));
+        let fabric = InstanceTemplate::find(&templates, "fabric-performance").unwrap();
+        assert_eq!(
+            fabric.mods[0],
+            TemplateMod::Modrinth {
+                modrinth: "sodium".to_string()
+            }
+        );
+
+        let uids = [
+            "net.minecraft",
+            "net.fabricmc.intermediary",
+            "net.fabricmc.fabric-loader",
+        ];
+        let packages: Vec<_> = uids
+            .iter()
+            .map(|uid| {
+                format!(
+                    r#"{{"name": "{0}", "sha256": "{1}", "uid": "{0}"}}"#,
+                    uid, HASH
+                )
+            })
+            .collect();
+        let mut index: MetaIndex = format!(
+            r#"{{"formatVersion": 1, "packages": [{}]}}"#,
+            packages.join(",")
+        )
+        .parse()
+        .unwrap();
+        assert!(matches!(
+            fabric.resolve(&index),
+            Err(Error::PendingDownloads)
+        ));
+
+        index.packages[0].index = Some(package(
+            "net.minecraft",
+            &[
+                ("1.19-pre1", "snapshot", "2022-05-18T00:00:00+00:00"),
+                ("1.18.2", "release", "2022-02-28T00:00:00+00:00"),
+                ("1.18.1", "release", "2021-12-10T00:00:00+00:00"),
+            ],
+        ));
+        index.packages[1].index = Some(package(
+            "net.fabricmc.intermediary",
+            &[
+                ("1.18.2", "release", "2022-02-28T00:00:00+00:00"),
+                ("1.18.1", "release", "2021-12-10T00:00:00+00:00"),
+            ],
+        ));
+        index.packages[2].index = Some(package(
+            "net.fabricmc.fabric-loader",
+            &[("0.13.3", "release", "2022-02-20T00:00:00+00:00")],
+        ));
+
+        let components = fabric.resolve(&index).unwrap();
+        let versions: Vec<_> = components.iter().map(|c| c.version.as_str()).collect();
+        assert_eq!(versions, ["1.18.2", "1.18.2", "0.13.3"]);
+    }
+}
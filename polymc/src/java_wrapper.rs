@@ -1,29 +1,51 @@
 //use std::os::raw::c_int;
+use std::borrow::Cow;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 #[cfg(target_family = "unix")]
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
 
-use log::*;
+use tracing::*;
 
 use crate::auth::Auth;
-use crate::instance::Instance;
+use crate::crash::ExitReport;
+use crate::instance::{Instance, SessionAction, SessionTimer};
+use crate::launch_command::LaunchCommandBuilder;
 use crate::meta::manifest::OS;
+use crate::watchdog::StartupWatchdog;
 use crate::{Error, Result};
 
+/// How often [`RunningInstance::wait_for_startup`] checks the watchdog.
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the blocking [`RunningInstance::wait`] checks the session limit.
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often [`RunningInstance::wait_async`] checks whether the game exited.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct RunningInstance<'a> {
     pub process: Child,
-    pub instance: &'a Instance,
+    /// Borrowed from the frontend, or owned if started with [`Java::start_owned`].
+    pub instance: Cow<'a, Instance>,
+    started: SystemTime,
+    command: LaunchCommandBuilder,
+    /// Deadline of the session limit of the instance, kept across restarts.
+    session: Option<SessionTimer>,
 }
 
 impl<'a> RunningInstance<'a> {
-    /// Return raw fd of stdin of the java process.
+    /// Return raw fd of stdin of the java process, `-ENOENT` if it was taken.
     ///
     /// # Safety
-    /// The returned fd has to be closed after use.
+    /// The fd is owned by the running instance and must not be closed, it is only valid until
+    /// the instance is freed. Use `running_instance_take_stdin_fd` to keep it.
     #[cfg(target_family = "unix")]
     #[no_mangle]
     pub unsafe extern "C" fn running_instance_get_stdin_fd(&self) -> RawFd {
@@ -34,10 +56,23 @@ impl<'a> RunningInstance<'a> {
             .unwrap_or(-libc::ENOENT)
     }
 
-    /// Return raw fd of stdout of the java process.
+    /// Take ownership of stdin of the java process, the caller has to close the returned fd.
+    /// It can only be taken once, `-ENOENT` is returned afterwards.
+    #[cfg(target_family = "unix")]
+    #[no_mangle]
+    pub extern "C" fn running_instance_take_stdin_fd(&mut self) -> RawFd {
+        self.process
+            .stdin
+            .take()
+            .map(IntoRawFd::into_raw_fd)
+            .unwrap_or(-libc::ENOENT)
+    }
+
+    /// Return raw fd of stdout of the java process, `-ENOENT` if it was taken.
     ///
     /// # Safety
-    /// The returned fd has to be closed after use.
+    /// The fd is owned by the running instance and must not be closed, it is only valid until
+    /// the instance is freed. Use `running_instance_take_stdout_fd` to keep it.
     #[cfg(target_family = "unix")]
     #[no_mangle]
     pub unsafe extern "C" fn running_instance_get_stdout_fd(&self) -> RawFd {
@@ -48,10 +83,23 @@ impl<'a> RunningInstance<'a> {
             .unwrap_or(-libc::ENOENT)
     }
 
-    /// Return raw fd of stderr of the java process.
+    /// Take ownership of stdout of the java process, the caller has to close the returned fd.
+    /// It can only be taken once, `-ENOENT` is returned afterwards.
+    #[cfg(target_family = "unix")]
+    #[no_mangle]
+    pub extern "C" fn running_instance_take_stdout_fd(&mut self) -> RawFd {
+        self.process
+            .stdout
+            .take()
+            .map(IntoRawFd::into_raw_fd)
+            .unwrap_or(-libc::ENOENT)
+    }
+
+    /// Return raw fd of stderr of the java process, `-ENOENT` if it was taken.
     ///
     /// # Safety
-    /// The returned fd has to be closed after use.
+    /// The fd is owned by the running instance and must not be closed, it is only valid until
+    /// the instance is freed. Use `running_instance_take_stderr_fd` to keep it.
     #[cfg(target_family = "unix")]
     #[no_mangle]
     pub unsafe extern "C" fn running_instance_get_stderr_fd(&self) -> RawFd {
@@ -62,6 +110,203 @@ impl<'a> RunningInstance<'a> {
             .unwrap_or(-libc::ENOENT)
     }
 
+    /// Take ownership of stderr of the java process, the caller has to close the returned fd.
+    /// It can only be taken once, `-ENOENT` is returned afterwards.
+    #[cfg(target_family = "unix")]
+    #[no_mangle]
+    pub extern "C" fn running_instance_take_stderr_fd(&mut self) -> RawFd {
+        self.process
+            .stderr
+            .take()
+            .map(IntoRawFd::into_raw_fd)
+            .unwrap_or(-libc::ENOENT)
+    }
+
+    /// Command the game was started with.
+    pub fn command(&self) -> &LaunchCommandBuilder {
+        &self.command
+    }
+
+    /// Time left to play under the session limit of the instance, `None` without a limit.
+    pub fn remaining_play_time(&self) -> Option<Duration> {
+        Some(self.session.as_ref()?.remaining(Instant::now()))
+    }
+
+    /// Wait for the game to exit and record the session in the instance's play statistics.
+    ///
+    /// With a session limit the player is warned before the time is up, then the game is asked
+    /// to exit and killed after the grace period of the limit.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        let status = match self.session {
+            Some(_) => loop {
+                if let Some(status) = self.process.try_wait()? {
+                    break status;
+                }
+                self.check_session()?;
+                std::thread::sleep(SESSION_POLL_INTERVAL);
+            },
+            None => self.process.wait()?,
+        };
+        self.record_exit(status);
+        Ok(status)
+    }
+
+    /// Wait like [`RunningInstance::wait`] without blocking the thread, for async frontends.
+    ///
+    /// The session limit is enforced with a timeout until its next action.
+    pub async fn wait_async(&mut self) -> Result<ExitStatus> {
+        let status = loop {
+            let exited = match self.session.as_ref().and_then(SessionTimer::next_action) {
+                Some(due) => {
+                    let timeout = due.saturating_duration_since(Instant::now());
+                    tokio::time::timeout(timeout, poll_exit(&mut self.process))
+                        .await
+                        .ok()
+                }
+                None => Some(poll_exit(&mut self.process).await),
+            };
+            match exited {
+                Some(status) => break status?,
+                None => self.check_session()?,
+            }
+        };
+        self.record_exit(status);
+        Ok(status)
+    }
+
+    /// Record the session in the instance's play statistics.
+    fn record_exit(&self, status: ExitStatus) {
+        if let Err(e) = self.instance.record_exit(self.started, status) {
+            warn!(
+                "Failed to save play statistics of {}: {}",
+                self.instance.name, e
+            );
+        }
+    }
+
+    /// Wait until `watchdog` saw the game start or the game exited.
+    ///
+    /// The output of the game has to be passed to the watchdog meanwhile, e.g. from other
+    /// threads. Fails with [`Error::LaunchStalled`] after the timeout of the watchdog, killing
+    /// the game first if it says so.
+    pub async fn wait_for_startup(&mut self, watchdog: &Mutex<StartupWatchdog>) -> Result<()> {
+        loop {
+            if self.process.try_wait()?.is_some() {
+                return Ok(());
+            }
+
+            self.check_session()?;
+            let stalled = {
+                let watchdog = watchdog.lock().unwrap();
+                if watchdog.started() {
+                    return Ok(());
+                }
+                watchdog
+                    .check(Instant::now())
+                    .err()
+                    .map(|e| (e, watchdog.kill))
+            };
+            if let Some((e, kill)) = stalled {
+                error!("{} stalled during startup: {}", self.instance.name, e);
+                if kill {
+                    self.process.kill()?;
+                    self.wait_async().await?;
+                }
+                return Err(e);
+            }
+            tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Warn the player or end the game as the session limit says.
+    fn check_session(&mut self) -> Result<()> {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        match session.poll(Instant::now()) {
+            Some(SessionAction::Warn(remaining)) => {
+                let minutes = (remaining.as_secs_f64() / 60.0).ceil() as u64;
+                warn!(
+                    "{} has {} minute(s) of play time left",
+                    self.instance.name, minutes
+                );
+                if let (true, Some(stdin)) = (session.chat_warnings, &mut self.process.stdin) {
+                    let said = writeln!(stdin, "say {} minute(s) of play time left", minutes)
+                        .and_then(|_| stdin.flush());
+                    if let Err(e) = said {
+                        debug!("failed to warn in the chat: {}", e);
+                    }
+                }
+            }
+            Some(SessionAction::Terminate) => {
+                warn!("The play time of {} is up, stopping it", self.instance.name);
+                terminate(&mut self.process)?;
+            }
+            Some(SessionAction::Kill) => {
+                warn!("{} did not stop in time, killing it", self.instance.name);
+                self.process.kill()?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Analyze why the game exited with `status`, see [`ExitReport::analyze`].
+    pub fn exit_report(&self, status: ExitStatus, output: &str) -> ExitReport {
+        ExitReport::analyze(&self.instance, status, self.started, output)
+    }
+
+    /// Wait for the game to exit, restarting it according to the restart policy of the instance.
+    ///
+    /// `on_start` is called with every restarted process, e.g. to take its stdio.
+    /// Returns [`Error::CrashLoop`] once the game crashed more often than the policy allows.
+    pub async fn supervise<F>(
+        &mut self,
+        java: &Java,
+        auth: &Auth,
+        mut on_start: F,
+    ) -> Result<ExitStatus>
+    where
+        F: FnMut(&mut Child),
+    {
+        let mut restarts = 0;
+        loop {
+            let status = self.wait_async().await?;
+            let expired = matches!(&self.session, Some(session) if session.expired());
+            let policy = match &self.instance.restart_policy {
+                Some(policy) if !status.success() && !expired => policy,
+                _ => return Ok(status),
+            };
+
+            if restarts >= policy.max_restarts {
+                error!(
+                    "{} crashed {} times, giving up",
+                    self.instance.name,
+                    restarts + 1
+                );
+                return Err(Error::CrashLoop(restarts + 1));
+            }
+
+            let backoff = policy.backoff * 2u32.saturating_pow(restarts);
+            warn!(
+                "{} exited with {}, restarting in {}s",
+                self.instance.name,
+                status,
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+
+            restarts += 1;
+            let mut restarted =
+                java.start_with(&self.instance, auth.clone(), self.command.clone())?;
+            // The exited process is dropped with `restarted`
+            std::mem::swap(&mut self.process, &mut restarted.process);
+            self.started = restarted.started;
+            on_start(&mut self.process);
+        }
+    }
+
     /*#[no_mangle]
     pub unsafe extern "C" fn running_instance_kill(mut self) -> c_int {
         if let Err(e) = self.process.kill() {
@@ -73,6 +318,62 @@ impl<'a> RunningInstance<'a> {
     */
 }
 
+impl Drop for RunningInstance<'_> {
+    fn drop(&mut self) {
+        // Dropping a `Child` neither waits for the process nor kills it, so a game that is still
+        // running would be left as a zombie once it exits
+        if let Ok(None) = self.process.try_wait() {
+            reap_in_background(&self.process);
+        }
+    }
+}
+
+/// Wait for `process` to exit without blocking the thread.
+async fn poll_exit(process: &mut Child) -> Result<ExitStatus> {
+    loop {
+        if let Some(status) = process.try_wait()? {
+            return Ok(status);
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Wait for `process` on a background thread, releasing it once it exits.
+#[cfg(target_family = "unix")]
+fn reap_in_background(process: &Child) {
+    let pid = process.id() as libc::pid_t;
+    let reaper = std::thread::Builder::new()
+        .name(format!("reap-{}", pid))
+        .spawn(move || {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            debug!("reaped detached process {}", pid);
+        });
+    if let Err(e) = reaper {
+        warn!("Failed to wait for process {}: {}", pid, e);
+    }
+}
+
+/// Windows releases an exited process once its handle is closed.
+#[cfg(not(target_family = "unix"))]
+fn reap_in_background(_process: &Child) {}
+
+/// Ask `process` to exit with `SIGTERM`, which runs the shutdown hooks of the JVM.
+#[cfg(target_family = "unix")]
+fn terminate(process: &mut Child) -> Result<()> {
+    if unsafe { libc::kill(process.id() as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Windows has no signal to ask a process to exit, it is killed right away.
+#[cfg(not(target_family = "unix"))]
+fn terminate(process: &mut Child) -> Result<()> {
+    Ok(process.kill()?)
+}
+
+#[derive(Debug, Clone)]
 pub struct Java {
     java: PathBuf,
 }
@@ -84,71 +385,256 @@ impl Java {
         }
     }
 
-    pub fn start<'a>(&self, instance: &'a Instance, auth: Auth) -> Result<RunningInstance<'a>> {
-        // TODO: check java version before starting minecraft
+    /// Path of the java executable.
+    pub fn path(&self) -> &Path {
+        &self.java
+    }
+
+    /// Major version of the runtime, e.g. 8 for `1.8.0_292` or 17 for `17.0.2`.
+    pub fn major_version(&self) -> Result<u32> {
+        let version = self.version()?;
+        crate::runtime::parse_version_output(&version).ok_or_else(|| {
+            Error::JavaFailed(format!(
+                "unknown version of {}: {}",
+                self.java.display(),
+                version
+            ))
+        })
+    }
+
+    /// Output of `java -version`.
+    pub fn version(&self) -> Result<String> {
+        let output = Command::new(&self.java).arg("-version").output()?;
+        if !output.status.success() {
+            return Err(Error::JavaFailed(format!(
+                "{} -version exited with {}",
+                self.java.display(),
+                output.status
+            )));
+        }
+        // java prints its version to stderr
+        let mut version = String::from_utf8_lossy(&output.stderr).into_owned();
+        version.push_str(&String::from_utf8_lossy(&output.stdout));
+        Ok(version.trim().to_string())
+    }
+
+    /// Parts of the command to start the instance, for frontends to change before
+    /// [`Java::start_with`].
+    ///
+    /// Fails with [`Error::InvalidUsername`] for offline accounts the game would not accept.
+    pub fn command_builder(
+        &self,
+        instance: &Instance,
+        auth: &Auth,
+    ) -> Result<LaunchCommandBuilder> {
+        auth.validate()?;
         // TODO: propagate OS from here into every leaf functions
         let platform = OS::get();
 
-        let mut command = Command::new(&self.java);
-        command
-            .args(instance.get_manifest_extra_jvm_args(&platform))
-            .args(&instance.java_opts)
-            .arg(format!("-Xms{}", instance.config.min))
-            .arg(format!("-Xmx{}", instance.config.max))
-            .arg(format!("-Djava.library.path={}", instance.build_natives()?))
-            .arg(format!(
-                "-Dminecraft.launcher.brand={}",
-                env!("CARGO_PKG_NAME")
-            )) // TODO: read from come config
-            .arg(format!(
-                "-Dminecraft.launcher.version={}",
-                env!("CARGO_PKG_VERSION")
-            ))
-            .arg("-XX:+UnlockExperimentalVMOptions")
-            .arg("-XX:+UseG1GC")
-            .arg("-XX:G1NewSizePercent=20")
-            .arg("-XX:G1ReservePercent=20")
-            .arg("-XX:MaxGCPauseMillis=50")
-            .arg("-XX:G1HeapRegionSize=32M")
-            .arg("-cp")
-            .arg(&instance.get_class_paths())
-            .arg("net.minecraft.client.main.Main")
-            .arg("--gameDir")
-            .arg(&instance.minecraft_path)
-            .arg("--assetsDir")
-            .arg(&instance.get_assets_path())
-            .arg("--accessToken")
-            .arg(auth.get_token().unwrap_or("0"))
-            .arg("--assetIndex")
-            .arg(
-                &instance
-                    .manifests
-                    .get(&instance.uid)
-                    .ok_or(Error::MetaNotFound)?
-                    .asset_index
-                    .as_ref()
-                    .ok_or(Error::MetaNotFound)?
-                    .id,
-            )
-            .arg("--width")
-            .arg(instance.config.width.to_string())
-            .arg("--height")
-            .arg(instance.config.height.to_string())
-            .arg("--username")
-            .arg(auth.get_username())
-            .arg("--version")
-            .arg(&instance.version)
-            .arg(&instance.extra_args.join(" "))
+        let (min_heap, max_heap) = instance.config.heap_size();
+
+        let minimal = instance.config.minimal_args;
+
+        let overrides = &instance.overrides;
+        let mut builder = LaunchCommandBuilder::new(&self.java, instance.get_main_class());
+        builder
+            .memory(min_heap, max_heap)
+            .natives_dir(instance.get_natives_path())
+            .jvm_args(&overrides.jvm_args_prepend)
+            .jvm_args(instance.get_manifest_extra_jvm_args(&platform))
+            .jvm_args(instance.get_java_agent_args())
+            .jvm_args(instance.get_logging_args());
+        if !minimal {
+            builder.jvm_args(instance.get_window_jvm_args(&platform));
+        }
+        if instance.config.headless {
+            builder.jvm_arg("-Djava.awt.headless=true");
+        }
+        builder.jvm_args(&instance.java_opts);
+        if let Some(brand) = instance.config.launcher_brand.as_ref().filter(|_| !minimal) {
+            builder
+                .late_jvm_arg(format!("-Dminecraft.launcher.brand={}", brand.name))
+                .late_jvm_arg(format!("-Dminecraft.launcher.version={}", brand.version));
+        }
+        builder
+            .late_jvm_arg("-XX:+UnlockExperimentalVMOptions")
+            .late_jvm_arg("-XX:+UseG1GC")
+            .late_jvm_arg("-XX:G1NewSizePercent=20")
+            .late_jvm_arg("-XX:G1ReservePercent=20")
+            .late_jvm_arg("-XX:MaxGCPauseMillis=50")
+            .late_jvm_arg("-XX:G1HeapRegionSize=32M")
+            .late_jvm_args(&overrides.jvm_args_append)
+            .class_path(instance.class_path_for(&platform))
+            .game_args(&overrides.game_args_prepend);
+        if let Some(args) = instance.get_launchwrapper_game_args(auth)? {
+            builder.game_args(args);
+            if !minimal {
+                builder
+                    .game_arg("--width")
+                    .game_arg(instance.config.width.to_string())
+                    .game_arg("--height")
+                    .game_arg(instance.config.height.to_string());
+            }
+        } else {
+            Self::game_args(&mut builder, instance, auth)?;
+        }
+        for tweaker in instance.get_tweakers() {
+            // Also declared in the argument template
+            let declared = builder
+                .game_args
+                .windows(2)
+                .any(|pair| pair[0] == "--tweakClass" && pair[1] == tweaker.as_str());
+            if !declared {
+                builder.game_arg("--tweakClass").game_arg(tweaker);
+            }
+        }
+        builder
+            .game_args(&instance.extra_args)
+            .game_args(&overrides.game_args_append)
             .current_dir(&instance.minecraft_path);
+        if let Some(gpu) = instance.config.gpu {
+            for (name, value) in crate::gpu::env(gpu) {
+                builder.env(name, value);
+            }
+        }
+
+        Ok(builder)
+    }
+
+    /// Game arguments of versions started without LaunchWrapper, left out where the version
+    /// doesn't take them.
+    fn game_args(
+        builder: &mut LaunchCommandBuilder,
+        instance: &Instance,
+        auth: &Auth,
+    ) -> Result<()> {
+        let minimal = instance.config.minimal_args;
+        builder
+            .game_arg("--gameDir")
+            .game_arg(&instance.minecraft_path)
+            .game_arg("--accessToken")
+            .game_arg(auth.get_token().unwrap_or("0"));
+        if !minimal {
+            builder
+                .game_arg("--assetsDir")
+                .game_arg(instance.get_assets_path());
+            if instance.takes_game_arg("--assetIndex") {
+                builder
+                    .game_arg("--assetIndex")
+                    .game_arg(instance.get_asset_index_id()?);
+            }
+            builder
+                .game_arg("--width")
+                .game_arg(instance.config.width.to_string())
+                .game_arg("--height")
+                .game_arg(instance.config.height.to_string());
+        }
+        builder
+            .game_arg("--username")
+            .game_arg(auth.get_username())
+            .game_arg("--version")
+            .game_arg(instance.get_version_id());
+        let version_type = instance
+            .get_version_type()
+            .filter(|_| !minimal && instance.takes_game_arg("--versionType"));
+        if let Some(version_type) = version_type {
+            builder.game_arg("--versionType").game_arg(version_type);
+        }
+        if let Some(uuid) = auth.game_uuid() {
+            builder.game_arg("--uuid").game_arg(uuid);
+        }
+        Ok(())
+    }
+
+    /// Build the command to start the instance, without launching it.
+    ///
+    /// The natives are expected to be extracted already, see [`Instance::build_natives`].
+    /// Fails with [`Error::InvalidUsername`] for offline accounts the game would not accept.
+    pub fn build_command(&self, instance: &Instance, auth: &Auth) -> Result<Command> {
+        let builder = self.command_builder(instance, auth)?;
+        Self::finish_command(&builder, instance)
+    }
+
+    /// Turn `builder` into the command, applying the sandbox of `instance`.
+    fn finish_command(builder: &LaunchCommandBuilder, instance: &Instance) -> Result<Command> {
+        let command = builder.build();
+        match &instance.sandbox {
+            Some(sandbox) => sandbox.apply(command, instance),
+            None => Ok(command),
+        }
+    }
+
+    /// Arguments of the command, with the access token of `auth` redacted, also where it is part
+    /// of an argument like the `token:<token>:<uuid>` session of old versions.
+    pub fn redacted_args(command: &Command, auth: &Auth) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy())
+            .map(|arg| match auth.get_token().filter(|t| !t.is_empty()) {
+                Some(token) => arg.replace(token, "<redacted>"),
+                None => arg.to_string(),
+            })
+            .collect()
+    }
+
+    pub fn start<'a>(&self, instance: &'a Instance, auth: Auth) -> Result<RunningInstance<'a>> {
+        self.start_inner(Cow::Borrowed(instance), auth, None)
+    }
+
+    /// Start the game like [`Java::start`], the running instance keeps `instance`.
+    pub fn start_owned(&self, instance: Instance, auth: Auth) -> Result<RunningInstance<'static>> {
+        self.start_inner(Cow::Owned(instance), auth, None)
+    }
+
+    /// Start the game like [`Java::start`] with a command changed by the frontend, see
+    /// [`Java::command_builder`]. Restarts of [`RunningInstance::supervise`] use it as well.
+    pub fn start_with<'a>(
+        &self,
+        instance: &'a Instance,
+        auth: Auth,
+        builder: LaunchCommandBuilder,
+    ) -> Result<RunningInstance<'a>> {
+        self.start_inner(Cow::Borrowed(instance), auth, Some(builder))
+    }
+
+    fn start_inner<'a>(
+        &self,
+        kept: Cow<'a, Instance>,
+        auth: Auth,
+        builder: Option<LaunchCommandBuilder>,
+    ) -> Result<RunningInstance<'a>> {
+        let instance = &*kept;
+        let _span = info_span!("launch", instance = %instance.name).entered();
+        let info = instance.info()?;
+        let remaining = info.remaining_play_time(SystemTime::now());
+        if remaining == Some(Duration::ZERO) {
+            return Err(Error::SessionLimitReached(instance.name.clone()));
+        }
+        // TODO: check java version before starting minecraft
+        if !instance.config.skip_verify {
+            instance.verify_main_jar()?;
+        }
+        // Also when verification is skipped, it is the most common cause of failing launches
+        instance.verify_pinned_main_jar()?;
+        instance.verify_java_agents()?;
+        instance.build_natives()?;
+        if !instance.config.minimal_args {
+            instance.map_resources()?;
+        }
+        let builder = match builder {
+            Some(builder) => builder,
+            None => self.command_builder(instance, &auth)?,
+        };
+        #[cfg(windows)]
+        if let Err(e) = instance.register_gpu_preference(&builder.java) {
+            warn!("Failed to set the GPU of {}: {}", builder.java.display(), e);
+        }
+        let mut command = Self::finish_command(&builder, instance)?;
 
         debug!(
             "Starting minecraft: {} {}",
             command.get_program().to_str().unwrap_or("error"),
-            command
-                .get_args()
-                .map(|s| s.to_str().unwrap_or("error"))
-                .collect::<Vec<&str>>()
-                .join(" ")
+            Self::redacted_args(&command, &auth).join(" ")
         );
         trace!("in workdir: {}", &instance.minecraft_path);
 
@@ -158,6 +644,63 @@ impl Java {
             .stderr(Stdio::piped())
             .spawn()?;
 
-        Ok(RunningInstance { process, instance })
+        let started = SystemTime::now();
+        if let Err(e) = instance.record_launch(started, &auth) {
+            warn!("Failed to save the launch of {}: {}", instance.name, e);
+        }
+
+        Ok(RunningInstance {
+            process,
+            instance: kept,
+            started,
+            command: builder,
+            session: remaining
+                .map(|remaining| SessionTimer::new(&info.session_limit, remaining, Instant::now())),
+        })
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod test {
+    use super::*;
+    use crate::meta::SearchResult;
+
+    #[test]
+    fn reap_dropped_instance() {
+        let process = Command::new("sh")
+            .args(["-c", "cat >/dev/null"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = process.id() as libc::pid_t;
+        let search = SearchResult::new(Vec::new(), "net.minecraft");
+        let instance = Instance::new("test", "1.0", "/nonexistent", search);
+        let mut running = RunningInstance {
+            process,
+            instance: Cow::Borrowed(&instance),
+            started: SystemTime::now(),
+            command: LaunchCommandBuilder::new("sh", "none"),
+            session: None,
+        };
+
+        let stdin = running.running_instance_take_stdin_fd();
+        assert!(stdin >= 0);
+        assert_eq!(running.running_instance_take_stdin_fd(), -libc::ENOENT);
+        assert_eq!(
+            unsafe { running.running_instance_get_stdin_fd() },
+            -libc::ENOENT
+        );
+        assert!(unsafe { running.running_instance_get_stdout_fd() } >= 0);
+        drop(running);
+
+        // cat exits once its stdin is closed, the zombie is reaped without waiting for it
+        unsafe { libc::close(stdin) };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(Instant::now() < deadline, "process {} was not reaped", pid);
+            std::thread::sleep(Duration::from_millis(10));
+        }
     }
 }
@@ -0,0 +1,448 @@
+//! Launching the game in one call, for frontends which don't need control over the search.
+//!
+//! The library does no network requests itself, [`launch`] downloads everything through the
+//! [`Fetch`] implementation of the frontend and verifies it against the meta data:
+//!
+//! ```no_run
+//! use polymc::prelude::*;
+//! # struct Client;
+//! # impl Fetch for Client {
+//! #     fn fetch<'a>(&'a self, _url: &'a str) -> FetchFuture<'a> {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//!
+//! async fn play(client: &Client) -> Result<()> {
+//!     let options = LaunchOptions::new("https://meta.polymc.org/v1", "/tmp/polymc");
+//!     let auth = Auth::new_offline("Steve");
+//!     let mut running = launch("net.minecraft", "1.18.2", auth, &options, client).await?;
+//!     running.wait()?;
+//!     Ok(())
+//! }
+//! ```
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing::*;
+
+use crate::auth::Auth;
+use crate::cancel::CancellationToken;
+use crate::download::{parse_sha1, FileDownload, PartialFile};
+use crate::i18n::Phase;
+use crate::instance::{Instance, InstanceInfo, NativesExtractor};
+use crate::java_wrapper::{Java, RunningInstance};
+use crate::lock::DirLock;
+use crate::meta::lwjgl::LwjglOverride;
+use crate::meta::{DownloadRequest, MetaManager, Wants};
+use crate::{Error, Result};
+
+/// How long to wait for another launcher to release the stores.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Future returned by [`Fetch::fetch`].
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>>;
+
+/// HTTP client of the frontend used by [`launch`].
+pub trait Fetch {
+    /// Download the body of `url`.
+    ///
+    /// Fails with [`Error::Download`] if the server does not respond with success, or with
+    /// [`Error::Throttled`] if it keeps rate limiting after backing off as described in
+    /// [`crate::Backoff`].
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a>;
+}
+
+/// Where [`launch`] keeps its files and where it gets the meta data from.
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// Base url of the meta server.
+    pub base_url: String,
+    pub meta_dir: PathBuf,
+    pub libraries_dir: PathBuf,
+    pub assets_dir: PathBuf,
+    /// Game directory of the instance.
+    pub game_dir: PathBuf,
+    /// Java to start the game with, found in `runtime_dirs` for the version the game requires
+    /// if not set.
+    pub java: Option<Java>,
+    pub runtime_dirs: Vec<PathBuf>,
+    /// Verify all assets, even if the [`AssetVerification`](crate::instance::AssetVerification)
+    /// of the instance says not to.
+    pub verify: bool,
+    /// Extract the natives jars right after their download and the others once everything is
+    /// downloaded, so starting the game finds them up to date, see [`NativesExtractor`].
+    pub extract_natives: bool,
+    pub cancel: CancellationToken,
+}
+
+impl LaunchOptions {
+    /// Options keeping all files in subdirectories of `root`, laid out like plmc does.
+    pub fn new<P: AsRef<Path>>(base_url: &str, root: P) -> Self {
+        let root = root.as_ref();
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            meta_dir: root.join("meta"),
+            libraries_dir: root.join("lib"),
+            assets_dir: root.join("assets"),
+            game_dir: root.join("game"),
+            java: None,
+            runtime_dirs: vec![root.join("runtimes")],
+            verify: false,
+            extract_natives: true,
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+/// Download and verify everything needed to start the component `uid` in `version`, then
+/// start the game with `auth`.
+pub async fn launch<F: Fetch + ?Sized>(
+    uid: &str,
+    version: &str,
+    auth: Auth,
+    options: &LaunchOptions,
+    fetch: &F,
+) -> Result<RunningInstance<'static>> {
+    let instance = prepare(uid, version, options, fetch).await?;
+    let java = match (&options.java, instance.required_java_major()) {
+        (Some(java), _) => java.clone(),
+        (None, Some(major)) => crate::runtime::find(&options.runtime_dirs, major)?,
+        (None, None) => Java::new("java"),
+    };
+
+    // Restarts of the game need it as long as it runs
+    java.start_owned(instance, auth)
+}
+
+/// Download and verify everything needed to start the component `uid` in `version`.
+///
+/// Components pinned in the [`InstanceInfo`] of the game directory, e.g. a mod loader, are
+/// resolved as well.
+pub async fn prepare<F: Fetch + ?Sized>(
+    uid: &str,
+    version: &str,
+    options: &LaunchOptions,
+    fetch: &F,
+) -> Result<Instance> {
+    let path = |p: &Path| p.display().to_string();
+    let (libraries_dir, assets_dir) = (path(&options.libraries_dir), path(&options.assets_dir));
+
+    let mut manager = MetaManager::new(&libraries_dir, &assets_dir, &options.base_url);
+    manager.set_cancellation_token(options.cancel.clone());
+    manager.set_lwjgl_override(Some(LwjglOverride::for_host()));
+    manager.search(Wants::new(uid, version))?;
+    let info = InstanceInfo::load_existing(&options.game_dir)?;
+    if let Some(info) = &info {
+        for component in info.components.iter().filter(|c| c.uid != uid) {
+            manager.search(Wants::new(&component.uid, &component.version))?;
+        }
+    }
+    let now = SystemTime::now();
+    let verify_assets = options.verify
+        || info
+            .as_ref()
+            .map(|info| info.should_verify_assets(now))
+            .unwrap_or(true);
+    manager.set_skip_asset_verify(!verify_assets);
+    manager.set_previous_asset_index(info.as_ref().and_then(|info| info.asset_index.clone()));
+
+    let _locks = [
+        DirLock::lock_timeout_async(&options.meta_dir, LOCK_TIMEOUT).await?,
+        DirLock::lock_timeout_async(&options.libraries_dir, LOCK_TIMEOUT).await?,
+        DirLock::lock_timeout_async(&options.assets_dir, LOCK_TIMEOUT).await?,
+    ];
+    let mut natives = None;
+    let search = loop {
+        let search = manager.continue_search()?;
+        if search.is_ready() {
+            break search;
+        }
+
+        for request in &search.requests {
+            options.cancel.check()?;
+            let start = Instant::now();
+            if request.is_file() {
+                let bytes = download_file(fetch, request).await?;
+                if options.extract_natives && request.natives().is_some() {
+                    if natives.is_none() {
+                        let dir = Instance::default_natives_path(&path(&options.game_dir), version);
+                        natives = Some(NativesExtractor::new_async(dir).await?);
+                    }
+                    if let Some(natives) = &mut natives {
+                        natives.extract(request)?;
+                    }
+                }
+                let stats = manager.stats_mut();
+                stats.record_download(request, bytes);
+                stats.add_time(Phase::Download, start.elapsed());
+            } else {
+                match download_meta(fetch, request, &options.meta_dir).await {
+                    Ok((data, cached)) => {
+                        manager.load_data(&data, request.request_type())?;
+                        let stats = manager.stats_mut();
+                        if cached {
+                            stats.record_cached(request);
+                        } else {
+                            stats.record_download(request, data.len() as u64);
+                        }
+                    }
+                    Err(e) if manager.skip_optional(request, &e.to_string()) => {}
+                    Err(e) => return Err(e),
+                }
+                manager
+                    .stats_mut()
+                    .add_time(Phase::Resolve, start.elapsed());
+            }
+        }
+    };
+
+    if verify_assets {
+        let mut info = info.clone().unwrap_or_else(|| InstanceInfo {
+            name: uid.to_string(),
+            version: version.to_string(),
+            ..Default::default()
+        });
+        info.record_assets_verified(now);
+        std::fs::create_dir_all(&options.game_dir)?;
+        info.save(&options.game_dir)?;
+    }
+
+    let name = match &info {
+        Some(info) if !info.name.is_empty() => info.name.as_str(),
+        _ => uid,
+    };
+    let mut instance = Instance::new(name, version, &path(&options.game_dir), search);
+    instance.set_libraries_path(&libraries_dir);
+    instance.set_assets_path(&assets_dir);
+    if let Some(info) = &info {
+        instance.set_overrides(info.overrides.clone());
+    }
+    instance.pin_main_jar()?;
+    instance.record_asset_index(manager.verifies_assets())?;
+    match natives {
+        Some(natives) => {
+            natives.finish(&instance)?;
+        }
+        None if options.extract_natives => {
+            instance.build_natives()?;
+        }
+        None => {}
+    }
+
+    Ok(instance)
+}
+
+/// Download the library or asset of `request` to its path, returning its size.
+async fn download_file<F: Fetch + ?Sized>(fetch: &F, request: &DownloadRequest) -> Result<u64> {
+    let fetched = if request.has_hash() {
+        None
+    } else {
+        fetch_sha1(fetch, request.get_url()).await
+    };
+
+    debug!("downloading {}", request.get_url());
+    let data = fetch.fetch(request.get_url()).await?;
+    let mut download = FileDownload::create(request, fetched)?;
+    download.write(&data)?;
+    download.finish()
+}
+
+/// Hash maven repositories publish next to an artifact as `<artifact>.sha1`.
+async fn fetch_sha1<F: Fetch + ?Sized>(fetch: &F, url: &str) -> Option<Vec<u8>> {
+    let data = fetch.fetch(&format!("{}.sha1", url)).await.ok()?;
+    Some(parse_sha1(&data)?.as_ref().to_vec())
+}
+
+/// Meta data of `request`, from the cache in `meta_dir` if its hash matches.
+///
+/// Returns whether the data came from the cache along with it.
+async fn download_meta<F: Fetch + ?Sized>(
+    fetch: &F,
+    request: &DownloadRequest,
+    meta_dir: &Path,
+) -> Result<(Vec<u8>, bool)> {
+    let path = match request {
+        DownloadRequest::AssetIndex { path, .. } => PathBuf::from(path),
+        _ => request.meta_path_at(meta_dir).ok_or(Error::MetaNotFound)?,
+    };
+
+    if request.has_hash() {
+        if let Ok(data) = std::fs::read(&path) {
+            if verify(request, &data, request.get_hash()).is_ok() {
+                trace!("found {} in cache", request.get_url());
+                return Ok((data, true));
+            }
+        }
+    }
+
+    debug!("downloading {}", request.get_url());
+    let data = match fetch.fetch(request.get_url()).await {
+        Ok(data) => data,
+        // Without a pinned hash the meta index is always downloaded, use the cached one offline
+        Err(e) if !request.has_hash() => match std::fs::read(&path) {
+            Ok(data) => {
+                warn!("{}, using the cached {}", e, path.display());
+                return Ok((data, true));
+            }
+            Err(_) => return Err(e),
+        },
+        Err(e) => return Err(e),
+    };
+    if request.has_hash() {
+        verify(request, &data, request.get_hash())?;
+    }
+    let mut file = PartialFile::create(&path)?;
+    file.write_all(&data)?;
+    file.persist()?;
+
+    Ok((data, false))
+}
+
+fn verify(request: &DownloadRequest, data: &[u8], expected: &[u8]) -> Result<()> {
+    let algo = request.get_hash_algo().ok_or(Error::LibraryInvalidHash)?;
+    if ring::digest::digest(algo, data).as_ref() == expected {
+        Ok(())
+    } else if matches!(request, DownloadRequest::MetaIndex { .. }) {
+        Err(Error::MetaIndexInvalidHash)
+    } else {
+        Err(Error::LibraryInvalidHash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    const BASE_URL: &str = "http://meta.test";
+    const LIBRARY: &[u8] = b"library";
+    const CLIENT: &[u8] = b"client";
+
+    struct Files(HashMap<String, Vec<u8>>);
+
+    impl Fetch for Files {
+        fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+            let result = self.0.get(url).cloned().ok_or_else(|| Error::Download {
+                url: url.to_string(),
+                reason: "404 Not Found".to_string(),
+            });
+            Box::pin(async move { result })
+        }
+    }
+
+    /// Run a future whose fetches complete immediately.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn raw() -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| {}, |_| {}, |_| {});
+        let waker = unsafe { Waker::from_raw(raw()) };
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(ret) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                return ret;
+            }
+        }
+    }
+
+    fn hash(algo: &'static ring::digest::Algorithm, data: &[u8]) -> String {
+        hex::encode(ring::digest::digest(algo, data))
+    }
+
+    fn files() -> HashMap<String, Vec<u8>> {
+        let sha1 = |data: &[u8]| hash(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, data);
+        let sha256 = |data: &[u8]| hash(&ring::digest::SHA256, data);
+        let artifact = |name: &str, data: &[u8]| {
+            json!({"artifact": {
+                "sha1": sha1(data),
+                "size": data.len(),
+                "url": format!("{}/{}", BASE_URL, name),
+            }})
+        };
+        let manifest = json!({
+            "formatVersion": 1,
+            "name": "Minecraft",
+            "uid": "net.minecraft",
+            "version": "1.0",
+            "order": -2,
+            "releaseTime": "2022-01-01T00:00:00+00:00",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "libraries": [{
+                "name": "org.example:lib:1.0",
+                "downloads": artifact("lib-1.0.jar", LIBRARY),
+            }],
+            "mainJar": {
+                "name": "com.mojang:minecraft:1.0:client",
+                "downloads": artifact("client.jar", CLIENT),
+            },
+        })
+        .to_string();
+        let package_index = json!({
+            "formatVersion": 1,
+            "name": "Minecraft",
+            "uid": "net.minecraft",
+            "versions": [{
+                "releaseTime": "2022-01-01T00:00:00+00:00",
+                "sha256": sha256(manifest.as_bytes()),
+                "type": "release",
+                "version": "1.0",
+            }],
+        })
+        .to_string();
+        let index = json!({
+            "formatVersion": 1,
+            "packages": [{
+                "name": "Minecraft",
+                "uid": "net.minecraft",
+                "sha256": sha256(package_index.as_bytes()),
+            }],
+        })
+        .to_string();
+
+        let mut files = HashMap::new();
+        let mut insert = |path: &str, data: &[u8]| {
+            files.insert(format!("{}/{}", BASE_URL, path), data.to_vec());
+        };
+        insert("index.json", index.as_bytes());
+        insert("net.minecraft/index.json", package_index.as_bytes());
+        insert("net.minecraft/1.0.json", manifest.as_bytes());
+        insert("lib-1.0.jar", LIBRARY);
+        insert("client.jar", CLIENT);
+        files
+    }
+
+    #[test]
+    fn prepare_instance() {
+        let dir = TempDir::new("launch");
+        let options = LaunchOptions::new(BASE_URL, &dir);
+        let mut files = files();
+
+        // A tampered library is rejected
+        let library_url = format!("{}/lib-1.0.jar", BASE_URL);
+        files.insert(library_url.clone(), b"tampered".to_vec());
+        let fetch = Files(files);
+        assert!(matches!(
+            block_on(prepare("net.minecraft", "1.0", &options, &fetch)),
+            Err(Error::LibraryInvalidSize { .. })
+        ));
+
+        let mut files = fetch.0;
+        files.insert(library_url, LIBRARY.to_vec());
+        let fetch = Files(files);
+        let instance = block_on(prepare("net.minecraft", "1.0", &options, &fetch)).unwrap();
+        assert_eq!(instance.name, "net.minecraft");
+        assert!(dir.join("meta/net.minecraft/1.0.json").is_file());
+        instance.verify_main_jar().unwrap();
+
+        // Everything is cached now
+        let fetch = Files(HashMap::new());
+        block_on(prepare("net.minecraft", "1.0", &options, &fetch)).unwrap();
+    }
+}
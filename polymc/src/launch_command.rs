@@ -0,0 +1,210 @@
+//! Parts of the command starting the game, before they are turned into a [`Command`].
+//!
+//! [`crate::java_wrapper::Java::command_builder`] fills a [`LaunchCommandBuilder`] from an
+//! instance, frontends can change it before starting the game with
+//! [`crate::java_wrapper::Java::start_with`].
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::meta::manifest::join_class_path;
+
+/// Command line of the game, split into the parts a frontend may want to change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchCommandBuilder {
+    /// The java executable.
+    pub java: PathBuf,
+    /// Initial and maximum heap size, e.g. `512M` and `2G`.
+    pub memory: Option<(String, String)>,
+    /// Directory the native libraries are loaded from.
+    pub natives_dir: Option<String>,
+    /// Arguments to the JVM passed first, the heap size and natives directory win over them.
+    pub jvm_args: Vec<OsString>,
+    /// Arguments to the JVM passed after the heap size and natives directory, so they win over
+    /// them.
+    pub late_jvm_args: Vec<OsString>,
+    /// Entries of the class path, joined with the separator of the platform.
+    pub class_path: Vec<PathBuf>,
+    /// Class started by the JVM.
+    pub main_class: String,
+    /// Arguments to the main class.
+    pub game_args: Vec<OsString>,
+    /// Environment variables set for the game, in addition to the inherited ones.
+    pub env: Vec<(OsString, OsString)>,
+    /// Working directory of the game.
+    pub current_dir: Option<PathBuf>,
+}
+
+impl LaunchCommandBuilder {
+    /// Empty command running `main_class` with `java`.
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(java: P, main_class: S) -> Self {
+        Self {
+            java: java.into(),
+            memory: None,
+            natives_dir: None,
+            jvm_args: Vec::new(),
+            late_jvm_args: Vec::new(),
+            class_path: Vec::new(),
+            main_class: main_class.into(),
+            game_args: Vec::new(),
+            env: Vec::new(),
+            current_dir: None,
+        }
+    }
+
+    /// Set the initial and maximum heap size.
+    pub fn memory<S: Into<String>>(&mut self, min: S, max: S) -> &mut Self {
+        self.memory = Some((min.into(), max.into()));
+        self
+    }
+
+    /// Set the directory the native libraries are loaded from.
+    pub fn natives_dir<S: Into<String>>(&mut self, dir: S) -> &mut Self {
+        self.natives_dir = Some(dir.into());
+        self
+    }
+
+    /// Add an argument to the JVM.
+    pub fn jvm_arg<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.jvm_args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to the JVM.
+    pub fn jvm_args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.jvm_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add an argument to the JVM after the heap size and natives directory.
+    pub fn late_jvm_arg<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.late_jvm_args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to the JVM after the heap size and natives directory.
+    pub fn late_jvm_args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.late_jvm_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the entries of the class path.
+    pub fn class_path<I, S>(&mut self, entries: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<PathBuf>,
+    {
+        self.class_path = entries.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add an argument to the game.
+    pub fn game_arg<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.game_args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to the game.
+    pub fn game_args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.game_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an environment variable for the game.
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(&mut self, name: K, value: V) -> &mut Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the working directory of the game.
+    pub fn current_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// All arguments passed to java, in order.
+    pub fn args(&self) -> Vec<OsString> {
+        let mut args = self.jvm_args.clone();
+        if let Some((min, max)) = &self.memory {
+            args.push(format!("-Xms{}", min).into());
+            args.push(format!("-Xmx{}", max).into());
+        }
+        if let Some(dir) = &self.natives_dir {
+            args.push(format!("-Djava.library.path={}", dir).into());
+        }
+        args.extend(self.late_jvm_args.iter().cloned());
+        args.push("-cp".into());
+        args.push(join_class_path(&self.class_path).into());
+        args.push(self.main_class.clone().into());
+        args.extend(self.game_args.iter().cloned());
+        args
+    }
+
+    /// Command starting the game, without redirecting its stdio.
+    pub fn build(&self) -> Command {
+        let mut command = Command::new(&self.java);
+        command.args(self.args());
+        command.envs(self.env.iter().map(|(name, value)| (name, value)));
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::manifest::CLASS_PATH_SEPARATOR;
+
+    #[test]
+    fn build() {
+        let mut builder = LaunchCommandBuilder::new("/usr/bin/java", "net.minecraft.client.Main");
+        builder
+            .memory("512M", "2G")
+            .natives_dir("/natives")
+            .jvm_arg("-Dfoo=bar")
+            .late_jvm_arg("-XX:+UseG1GC")
+            .class_path(["a.jar", "b.jar"])
+            .game_args(["--username", "Player"])
+            .env("DRI_PRIME", "1")
+            .current_dir("/game");
+
+        let command = builder.build();
+        let args: Vec<_> = command.get_args().collect();
+        let class_path = format!("a.jar{}b.jar", CLASS_PATH_SEPARATOR);
+        assert_eq!(
+            args,
+            [
+                "-Dfoo=bar",
+                "-Xms512M",
+                "-Xmx2G",
+                "-Djava.library.path=/natives",
+                "-XX:+UseG1GC",
+                "-cp",
+                &class_path,
+                "net.minecraft.client.Main",
+                "--username",
+                "Player",
+            ]
+        );
+        assert_eq!(command.get_program(), "/usr/bin/java");
+        assert_eq!(command.get_current_dir(), Some("/game".as_ref()));
+        assert!(command
+            .get_envs()
+            .any(|(k, v)| k == "DRI_PRIME" && v == Some("1".as_ref())));
+    }
+}
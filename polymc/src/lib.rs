@@ -1,13 +1,70 @@
+//! Library for Minecraft launchers: resolving the game from a meta server, verifying its
+//! files, logging in and starting it.
+//!
+//! [`prelude`] has the stable high-level API, start with [`launch`] to get the game running in
+//! one call or with a [`meta::MetaManager`] for control over the downloads.
 #![deny(unsafe_op_in_unsafe_fn)]
+pub mod adopt;
 pub mod auth;
+pub mod cancel;
+pub mod crash;
+pub mod debug;
+pub(crate) mod download;
 pub mod error;
+pub mod gpu;
+pub(crate) mod hash;
+pub mod host_policy;
+pub mod i18n;
 pub mod instance;
 pub mod java_wrapper;
+pub mod launch;
+pub mod launch_command;
+pub mod local_source;
+pub mod lock;
+pub mod log_forward;
+pub mod logger;
+pub(crate) mod memory;
 pub mod meta;
+pub(crate) mod peer;
+pub mod prelude;
+pub mod resources_proxy;
+pub mod runtime;
+pub mod state;
+#[cfg(test)]
+mod test_util;
+pub(crate) mod throttle;
+pub(crate) mod updater;
+pub mod watchdog;
 
-pub use error::{Error, Result};
 use std::os::raw::c_char;
 
+pub use error::{Error, Result};
+
+// Building blocks of the frontends, their modules are internal
+pub use download::{parse_sha1, FileDownload, PartialFile};
+pub use hash::{hash_file, hash_reader};
+pub use peer::{
+    discovery_reply, parse_discovery_reply, peer_path, store_path, store_relative_path,
+    DISCOVERY_PORT, DISCOVERY_QUERY,
+};
+pub use throttle::{is_throttled, parse_retry_after, Backoff};
+pub use updater::{
+    current_target, parse_checksum, replace_binary, verify_checksum, verify_release, Release,
+    ReleaseAsset, DEFAULT_RELEASES_URL, RELEASE_PUBLIC_KEY,
+};
+
+/// User agent to identify the launcher with on HTTP requests.
+///
+/// Frontends should pass their own identification (e.g. `plmc/0.1.0`), which is put in front
+/// of the library's, as some APIs require identifiable user agents.
+pub fn user_agent(app: Option<&str>) -> String {
+    let lib = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+    match app {
+        Some(app) => format!("{} {}", app, lib),
+        None => lib.to_string(),
+    }
+}
+
 /// Helper for C code to free a CString
 #[cfg(feature = "ctypes")]
 #[doc(hidden)]
@@ -0,0 +1,185 @@
+//! Installing from a directory instead of the meta server, e.g. a USB stick on an offline machine.
+//!
+//! The directory has the layout of the meta server, like a snapshot saved with
+//! `plmc meta snapshot`, with the libraries and assets next to the meta data in `libraries` and
+//! `assets`, see [`crate::store_relative_path`]. Files read from it are verified against
+//! the meta data like downloaded ones.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::meta::DownloadRequest;
+use crate::{Error, Result};
+
+/// Directory given as base url of the meta server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalSource {
+    dir: PathBuf,
+    /// Library and assets directory whose files are taken from the source.
+    stores: Option<(PathBuf, PathBuf)>,
+}
+
+/// Whether the base url `url` is a `file:` url or the path of an existing directory.
+pub(crate) fn is_local(url: &str) -> bool {
+    url.starts_with("file:") || (!url.contains("://") && Path::new(url).exists())
+}
+
+/// Whether `url` has another scheme than `file:`, so it isn't read from a local source.
+pub fn is_remote(url: &str) -> bool {
+    url.contains("://") && !url.starts_with("file:")
+}
+
+/// Path of the `file:` url or path `url`.
+fn url_path(url: &str) -> &str {
+    url.strip_prefix("file://")
+        .or_else(|| url.strip_prefix("file:"))
+        .unwrap_or(url)
+}
+
+/// Absolute `path` without `.` and `..` components, without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut ret = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        std::env::current_dir().unwrap_or_default()
+    };
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                ret.pop();
+            }
+            component => ret.push(component),
+        }
+    }
+    ret
+}
+
+impl LocalSource {
+    /// The directory `base_url` points to, if it is local.
+    pub fn from_base_url(base_url: &str) -> Option<Self> {
+        if !is_local(base_url) {
+            return None;
+        }
+        Some(Self {
+            dir: normalize(Path::new(url_path(base_url))),
+            stores: None,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Take the files of the stores at `libraries_dir` and `assets_dir` from the source.
+    pub fn set_stores<P: Into<PathBuf>>(&mut self, libraries_dir: P, assets_dir: P) {
+        self.stores = Some((libraries_dir.into(), assets_dir.into()));
+    }
+
+    /// File of the local `url` in the directory.
+    ///
+    /// Relative paths are taken from the working directory and `..` is resolved before checking,
+    /// fails with [`Error::OutsideLocalSource`] for files outside of the directory, so meta data
+    /// can't point to other local files.
+    pub fn resolve(&self, url: &str) -> Result<PathBuf> {
+        let path = normalize(Path::new(url_path(url)));
+        if is_remote(url) || !path.starts_with(&self.dir) {
+            return Err(Error::OutsideLocalSource(url.to_string()));
+        }
+        Ok(path)
+    }
+
+    /// Url of the library or asset of `request` in the directory.
+    ///
+    /// Returns `None` for meta data, which is requested from the directory already, or before
+    /// the stores were set with [`LocalSource::set_stores`].
+    pub fn file_url(&self, request: &DownloadRequest) -> Option<String> {
+        let (libraries_dir, assets_dir) = self.stores.as_ref()?;
+        let path = crate::peer::store_relative_path(request, libraries_dir, assets_dir)?;
+        Some(format!("{}/{}", self.dir.display(), path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::manifest::LibraryDownload;
+
+    #[test]
+    fn resolve() {
+        assert!(LocalSource::from_base_url("https://meta.example.com").is_none());
+        let source = LocalSource::from_base_url("file:///media/usb/").unwrap();
+        assert_eq!(source.dir(), Path::new("/media/usb"));
+        assert_eq!(
+            source,
+            LocalSource::from_base_url("file:/media/usb").unwrap()
+        );
+
+        assert_eq!(
+            source
+                .resolve("file:///media/usb/net.minecraft/1.0.json")
+                .unwrap(),
+            Path::new("/media/usb/net.minecraft/1.0.json")
+        );
+        assert_eq!(
+            source.resolve("/media/usb/index.json").unwrap(),
+            Path::new("/media/usb/index.json")
+        );
+        assert_eq!(
+            source.resolve("/media/usb/./meta/../index.json").unwrap(),
+            Path::new("/media/usb/index.json")
+        );
+        for url in [
+            "/media/usb/../../etc/passwd",
+            "file:///etc/passwd",
+            "/media/usb-other/index.json",
+            "https://meta.example.com/index.json",
+        ] {
+            assert!(
+                matches!(source.resolve(url), Err(Error::OutsideLocalSource(_))),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn relative() {
+        // Paths need to exist to be taken for a directory instead of a url
+        assert!(!is_local("meta.example.com/v1"));
+        assert!(is_local("file:meta.example.com/v1"));
+        assert!(is_local("./src"));
+
+        let cwd = std::env::current_dir().unwrap();
+        let source = LocalSource::from_base_url("./src/").unwrap();
+        assert_eq!(source.dir(), cwd.join("src"));
+        assert_eq!(
+            source.resolve("./src/lib.rs").unwrap(),
+            cwd.join("src/lib.rs")
+        );
+        assert!(matches!(
+            source.resolve("./src/../Cargo.toml"),
+            Err(Error::OutsideLocalSource(_))
+        ));
+    }
+
+    #[test]
+    fn file_url() {
+        let mut source = LocalSource::from_base_url("file:///media/usb").unwrap();
+        let library = DownloadRequest::new_library(
+            LibraryDownload {
+                sha1: None,
+                size: 1,
+                url: "https://maven.example.com/org/example/lib.jar".to_string(),
+            },
+            "/store/lib/org/example/lib.jar".into(),
+        );
+        assert_eq!(source.file_url(&library), None);
+
+        source.set_stores("/store/lib", "/store/assets");
+        // Unlike peers, the source is trusted with files without a hash
+        assert_eq!(
+            source.file_url(&library).unwrap(),
+            "/media/usb/libraries/org/example/lib.jar"
+        );
+    }
+}
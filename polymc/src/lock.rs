@@ -0,0 +1,223 @@
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(target_family = "unix")]
+use std::os::unix::io::AsRawFd;
+
+use libc::c_int;
+use tracing::*;
+
+use crate::{Error, Result};
+
+/// Name of the lock file created inside of a locked directory.
+pub const LOCK_FILE_NAME: &str = ".polymc.lock";
+
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Advisory lock on a directory shared between launcher processes (meta cache, libraries,
+/// assets, natives or an instance directory).
+///
+/// The lock is released when this is dropped.
+#[derive(Debug)]
+pub struct DirLock {
+    path: PathBuf,
+    #[allow(dead_code)]
+    file: File,
+}
+
+impl DirLock {
+    /// Try to lock the directory, returns [`Error::Locked`] if it is already locked.
+    pub fn try_lock<S: AsRef<std::ffi::OsStr> + ?Sized>(dir: &S) -> Result<Self> {
+        let dir = Path::new(dir);
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE_NAME);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        if !Self::lock_file(&file, &path)? {
+            return Err(Error::Locked(dir.display().to_string()));
+        }
+
+        trace!("locked {}", dir.display());
+        Ok(Self { path, file })
+    }
+
+    /// Lock the directory, waiting up to `timeout` for another process to release it.
+    pub fn lock_timeout<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        dir: &S,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        loop {
+            match Self::try_lock(dir) {
+                Err(Error::Locked(_)) if started.elapsed() < timeout => {
+                    std::thread::sleep(LOCK_POLL_INTERVAL)
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Lock the directory like [`DirLock::lock_timeout`], without blocking the thread while
+    /// waiting.
+    ///
+    /// Has to be awaited within a tokio runtime.
+    pub async fn lock_timeout_async<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        dir: &S,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        loop {
+            match Self::try_lock(dir) {
+                Err(Error::Locked(_)) if started.elapsed() < timeout => {
+                    tokio::time::sleep(LOCK_POLL_INTERVAL).await
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Check if the directory is currently locked by someone.
+    pub fn is_locked<S: AsRef<std::ffi::OsStr> + ?Sized>(dir: &S) -> Result<bool> {
+        let path = Path::new(dir).join(LOCK_FILE_NAME);
+        if !path.is_file() {
+            return Ok(false);
+        }
+
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let locked = !Self::lock_file(&file, &path)?;
+        // The lock taken for checking is released with the file, except for the marker
+        #[cfg(not(target_family = "unix"))]
+        if !locked {
+            let _ = std::fs::remove_file(marker_path(&path));
+        }
+        Ok(locked)
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[cfg(target_family = "unix")]
+    fn lock_file(file: &File, _path: &Path) -> Result<bool> {
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret == 0 {
+            return Ok(true);
+        }
+
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            Ok(false)
+        } else {
+            Err(e.into())
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    fn lock_file(_file: &File, path: &Path) -> Result<bool> {
+        // TODO: use LockFileEx on windows
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(marker_path(path))
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(marker_path(&self.path));
+    }
+}
+
+/// File marking the lock file at `path` as taken where there is no `flock`.
+#[cfg(not(target_family = "unix"))]
+fn marker_path(path: &Path) -> PathBuf {
+    let mut marker = path.as_os_str().to_owned();
+    marker.push(".pid");
+    marker.into()
+}
+
+/// Check if the directory is locked by a launcher.
+/// Returns 1 if locked, 0 if not and a negative errno on error.
+///
+/// # Safety
+/// dir has to be a valid CStr.
+#[cfg(feature = "ctypes")]
+#[no_mangle]
+pub unsafe extern "C" fn dir_is_locked(dir: *const c_char) -> c_int {
+    let dir = unsafe { CStr::from_ptr(dir) }.to_str();
+    if dir.is_err() {
+        return -libc::EINVAL;
+    }
+
+    match DirLock::is_locked(dir.unwrap()) {
+        Ok(locked) => locked as c_int,
+        Err(e) => -e.as_c_error(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn lock_dir() {
+        let dir = TempDir::new("lock-test");
+
+        assert!(!DirLock::is_locked(&dir).unwrap());
+
+        let lock = DirLock::try_lock(&dir).unwrap();
+        assert!(DirLock::is_locked(&dir).unwrap());
+        assert!(matches!(DirLock::try_lock(&dir), Err(Error::Locked(_))));
+        assert!(matches!(
+            DirLock::lock_timeout(&dir, Duration::from_millis(200)),
+            Err(Error::Locked(_))
+        ));
+
+        drop(lock);
+        assert!(!DirLock::is_locked(&dir).unwrap());
+        // Checking does not leave the directory locked
+        assert!(!DirLock::is_locked(&dir).unwrap());
+        DirLock::try_lock(&dir).unwrap();
+    }
+
+    #[test]
+    fn lock_dir_async() {
+        let dir = TempDir::new("lock-async");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        let lock = DirLock::try_lock(&dir).unwrap();
+        let waited = runtime.block_on(DirLock::lock_timeout_async(
+            &dir,
+            Duration::from_millis(200),
+        ));
+        assert!(matches!(waited, Err(Error::Locked(_))));
+
+        drop(lock);
+        runtime
+            .block_on(DirLock::lock_timeout_async(
+                &dir,
+                Duration::from_millis(200),
+            ))
+            .unwrap();
+    }
+}
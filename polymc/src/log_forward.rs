@@ -0,0 +1,445 @@
+//! Forwarding the output of the game and events of the launcher to a log collector, for
+//! instances running on machines nobody watches.
+//!
+//! Like for the [`LauncherLog`](crate::instance::LauncherLog), the frontend reads the output of
+//! the game. It turns the lines into [`LogEvent`]s, keeps them in a [`LogBuffer`] and sends
+//! them to the [`LogTarget`] of the instance. The buffer is bounded, a target which is down or
+//! slower than the game drops the oldest events instead of stalling the game.
+//!
+//! With the logging configuration of the components the game prints XML events over several
+//! lines instead of plain text, a [`Log4jParser`] turns them into one event each.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::instance::civil_time;
+use crate::logger::LogLevel;
+
+/// Events kept by default while the target is unreachable.
+pub const DEFAULT_LOG_BUFFER: usize = 10_000;
+
+/// XML events longer than this are forwarded as they are, in case one is never closed.
+const MAX_XML_EVENT: usize = 64 * 1024;
+
+/// Where and how the log events of an instance are forwarded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogForwarding {
+    pub target: LogTarget,
+    /// Number of events kept while the target is unreachable or slow, the oldest ones are
+    /// dropped beyond it.
+    #[serde(default = "default_buffer")]
+    pub buffer: usize,
+}
+
+fn default_buffer() -> usize {
+    DEFAULT_LOG_BUFFER
+}
+
+impl LogForwarding {
+    pub fn new(target: LogTarget) -> Self {
+        Self {
+            target,
+            buffer: DEFAULT_LOG_BUFFER,
+        }
+    }
+}
+
+/// Log collector receiving the events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LogTarget {
+    /// RFC 5424 messages over UDP to `address`, e.g. `logs.example.com:514`, or to the syslog
+    /// daemon of the machine if `None`.
+    Syslog {
+        #[serde(default)]
+        address: Option<String>,
+    },
+    /// JSON arrays of [`LogEvent`]s sent with POST requests to `url`.
+    Http { url: String },
+}
+
+/// Producer of a [`LogEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogSource {
+    Launcher,
+    GameStdout,
+    GameStderr,
+}
+
+/// One line of output or launcher event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEvent {
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    /// Name of the instance.
+    pub instance: String,
+    pub source: LogSource,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogEvent {
+    /// Event of the launcher about `instance`, e.g. that it started.
+    pub fn launcher(instance: &str, level: LogLevel, message: &str, at: SystemTime) -> Self {
+        Self {
+            timestamp: millis(at),
+            instance: instance.to_string(),
+            source: LogSource::Launcher,
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    /// Line of output of the game, with the level of its log4j layout, e.g.
+    /// `[12:00:00] [Server thread/WARN]: ...`. Other lines on stderr are warnings.
+    pub fn game(instance: &str, line: &str, stderr: bool, at: SystemTime) -> Self {
+        let level = LEVELS
+            .iter()
+            .find(|(name, _)| line.contains(&format!("/{}]", name)))
+            .map(|(_, level)| *level);
+        Self {
+            timestamp: millis(at),
+            instance: instance.to_string(),
+            source: if stderr {
+                LogSource::GameStderr
+            } else {
+                LogSource::GameStdout
+            },
+            level: level.unwrap_or(if stderr {
+                LogLevel::Warn
+            } else {
+                LogLevel::Info
+            }),
+            message: line.trim_end().to_string(),
+        }
+    }
+
+    /// The event as RFC 5424 syslog message from `hostname`, with the user facility.
+    pub fn to_syslog(&self, hostname: &str) -> String {
+        let severity = match self.level {
+            LogLevel::Error => 3,
+            LogLevel::Warn => 4,
+            LogLevel::Info => 6,
+            LogLevel::Debug | LogLevel::Trace => 7,
+        };
+        let at = UNIX_EPOCH + std::time::Duration::from_millis(self.timestamp);
+        let (year, month, day, hour, minute, second) = civil_time(at);
+        let app: String = self
+            .instance
+            .chars()
+            .filter(|c| c.is_ascii_graphic())
+            .take(48)
+            .collect();
+        format!(
+            "<{}>1 {:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z {} {} - {} - {}",
+            8 + severity,
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            self.timestamp % 1000,
+            if hostname.is_empty() { "-" } else { hostname },
+            if app.is_empty() { "-" } else { &app },
+            match self.source {
+                LogSource::Launcher => "launcher",
+                LogSource::GameStdout => "stdout",
+                LogSource::GameStderr => "stderr",
+            },
+            self.message
+        )
+    }
+}
+
+/// Log4j levels with the level of the launcher they map to.
+const LEVELS: &[(&str, LogLevel)] = &[
+    ("FATAL", LogLevel::Error),
+    ("ERROR", LogLevel::Error),
+    ("WARN", LogLevel::Warn),
+    ("INFO", LogLevel::Info),
+    ("DEBUG", LogLevel::Debug),
+    ("TRACE", LogLevel::Trace),
+];
+
+/// Collects the `<log4j:Event>` elements the game prints with the logging configuration into
+/// one [`LogEvent`] each, other lines are taken like by [`LogEvent::game`].
+#[derive(Debug, Clone, Default)]
+pub struct Log4jParser {
+    /// Lines of the event read so far.
+    event: Option<String>,
+}
+
+impl Log4jParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a line of the standard output of the game, returns the event once it is complete.
+    pub fn line(&mut self, instance: &str, line: &str, at: SystemTime) -> Option<LogEvent> {
+        let event = match &mut self.event {
+            Some(event) => event,
+            None if line.trim_start().starts_with("<log4j:Event") => {
+                self.event.insert(String::new())
+            }
+            None => return Some(LogEvent::game(instance, line, false, at)),
+        };
+        event.push_str(line);
+        event.push('\n');
+        if line.contains("</log4j:Event>") {
+            let xml = self.event.take().unwrap();
+            return Some(
+                parse_log4j_event(instance, &xml)
+                    .unwrap_or_else(|| LogEvent::game(instance, &xml, false, at)),
+            );
+        }
+        if event.len() > MAX_XML_EVENT {
+            let xml = self.event.take().unwrap();
+            return Some(LogEvent::game(instance, &xml, false, at));
+        }
+        None
+    }
+}
+
+/// Event of the `<log4j:Event>` element `xml`, as `[thread/LEVEL]: message` like the plain
+/// layout of the game.
+fn parse_log4j_event(instance: &str, xml: &str) -> Option<LogEvent> {
+    let start = xml.find("<log4j:Event")?;
+    let tag = &xml[start..start + xml[start..].find('>')?];
+    let attribute = |name: &str| {
+        let value = &tag[tag.find(&format!(" {}=\"", name))? + name.len() + 3..];
+        Some(xml_unescape(&value[..value.find('"')?]))
+    };
+    let level_name = attribute("level")?;
+    let level = LEVELS
+        .iter()
+        .find(|(name, _)| *name == level_name)
+        .map(|(_, level)| *level)?;
+    let timestamp = attribute("timestamp")?.parse().ok()?;
+    let thread = attribute("thread").unwrap_or_default();
+
+    let mut message = format!(
+        "[{}/{}]: {}",
+        thread,
+        level_name,
+        element_text(xml, "log4j:Message").unwrap_or_default()
+    );
+    if let Some(throwable) = element_text(xml, "log4j:Throwable") {
+        message.push('\n');
+        message.push_str(throwable.trim_end());
+    }
+
+    Some(LogEvent {
+        timestamp,
+        instance: instance.to_string(),
+        source: LogSource::GameStdout,
+        level,
+        message,
+    })
+}
+
+/// Text of the first element `name` in `xml`, from CDATA or escaped.
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    let text = &xml[start..end];
+    Some(match text.trim().strip_prefix("<![CDATA[") {
+        Some(data) => data.strip_suffix("]]>").unwrap_or(data).to_string(),
+        None => xml_unescape(text),
+    })
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Events waiting to be sent, dropping the oldest ones beyond its capacity.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    events: VecDeque<LogEvent>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity: capacity.max(1),
+            dropped: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Add an event, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, event: LogEvent) {
+        if self.events.len() >= self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    /// Take up to `max` of the oldest events to send them.
+    pub fn take(&mut self, max: usize) -> Vec<LogEvent> {
+        let count = max.min(self.events.len());
+        self.events.drain(..count).collect()
+    }
+
+    /// Put back events taken with [`LogBuffer::take`] which could not be sent, in front of the
+    /// ones added meanwhile. Events beyond the capacity are dropped, the oldest first.
+    pub fn requeue(&mut self, events: Vec<LogEvent>) {
+        for event in events.into_iter().rev() {
+            if self.events.len() >= self.capacity {
+                self.dropped += 1;
+                continue;
+            }
+            self.events.push_front(event);
+        }
+    }
+
+    /// Number of events dropped since the last call.
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn game_events() {
+        let at = UNIX_EPOCH + Duration::from_millis(1_649_000_000_123);
+        let event = LogEvent::game(
+            "server",
+            "[12:00:00] [Server thread/WARN]: Can't keep up!\n",
+            false,
+            at,
+        );
+        assert_eq!(event.level, LogLevel::Warn);
+        assert_eq!(
+            event.message,
+            "[12:00:00] [Server thread/WARN]: Can't keep up!"
+        );
+        assert_eq!(
+            event.to_syslog("box"),
+            "<12>1 2022-04-03T15:33:20.123Z box server - stdout - \
+             [12:00:00] [Server thread/WARN]: Can't keep up!"
+        );
+        assert_eq!(
+            LogEvent::game("server", "Exception in thread", true, at).level,
+            LogLevel::Warn
+        );
+
+        let json = serde_json::to_value(LogEvent::launcher(
+            "my server",
+            LogLevel::Info,
+            "started",
+            at,
+        ))
+        .unwrap();
+        assert_eq!(json["source"], "launcher");
+        assert_eq!(json["level"], "info");
+        assert_eq!(json["timestamp"], 1_649_000_000_123u64);
+
+        let forwarding: LogForwarding =
+            serde_json::from_str(r#"{"target": {"type": "syslog"}}"#).unwrap();
+        assert_eq!(forwarding.target, LogTarget::Syslog { address: None });
+        assert_eq!(forwarding.buffer, DEFAULT_LOG_BUFFER);
+    }
+
+    #[test]
+    fn log4j_events() {
+        let at = UNIX_EPOCH;
+        let mut parser = Log4jParser::new();
+        let mut events = Vec::new();
+        for line in [
+            r#"<log4j:Event logger="dhs" timestamp="1649000000123" level="WARN" thread="Render thread">"#,
+            "  <log4j:Message><![CDATA[Can't keep up! <5 ticks>]]></log4j:Message>",
+            "  <log4j:Throwable><![CDATA[java.lang.Exception: slow",
+            "\tat a.b(C.java:1)",
+            "]]></log4j:Throwable>",
+            "</log4j:Event>",
+            "Plain line",
+            r#"<log4j:Event logger="a" timestamp="1649000000124" level="INFO" thread="main">"#,
+            "  <log4j:Message>a &amp; b</log4j:Message>",
+            "</log4j:Event>",
+        ] {
+            events.extend(parser.line("server", line, at));
+        }
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].timestamp, 1_649_000_000_123);
+        assert_eq!(events[0].level, LogLevel::Warn);
+        assert_eq!(
+            events[0].message,
+            "[Render thread/WARN]: Can't keep up! <5 ticks>\n\
+             java.lang.Exception: slow\n\tat a.b(C.java:1)"
+        );
+        assert_eq!(events[1].message, "Plain line");
+        assert_eq!(events[1].level, LogLevel::Info);
+        assert_eq!(events[2].message, "[main/INFO]: a & b");
+
+        // Events which are never closed are not kept forever
+        assert!(parser
+            .line("server", "<log4j:Event level=\"INFO\">", at)
+            .is_none());
+        let long = "x".repeat(MAX_XML_EVENT);
+        let event = parser.line("server", &long, at).unwrap();
+        assert!(event.message.starts_with("<log4j:Event"));
+    }
+
+    #[test]
+    fn buffer() {
+        let event = |message: &str| LogEvent::launcher("a", LogLevel::Info, message, UNIX_EPOCH);
+        let mut buffer = LogBuffer::new(3);
+        for message in ["1", "2", "3", "4"] {
+            buffer.push(event(message));
+        }
+        assert_eq!(buffer.take_dropped(), 1);
+        assert_eq!(buffer.take_dropped(), 0);
+
+        let batch = buffer.take(2);
+        assert_eq!(batch[0].message, "2");
+        buffer.push(event("5"));
+        buffer.push(event("6"));
+        // Sending failed, the newest events are kept
+        buffer.requeue(batch);
+        assert_eq!(buffer.take_dropped(), 2);
+        let messages: Vec<_> = buffer.take(10).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, ["4", "5", "6"]);
+        assert!(buffer.is_empty());
+
+        let mut buffer = LogBuffer::new(3);
+        buffer.push(event("1"));
+        let batch = buffer.take(1);
+        buffer.push(event("2"));
+        buffer.requeue(batch);
+        assert_eq!(buffer.take(1)[0].message, "1");
+        assert_eq!(buffer.len(), 1);
+    }
+}
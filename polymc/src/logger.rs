@@ -0,0 +1,284 @@
+//! Forwarding the log records of the library to frontends which can't install a `tracing`
+//! subscriber, e.g. C and C++ applications.
+//!
+//! [`set_log_callback`] installs a subscriber passing each record to a callback of the host.
+//! The callback is called on whichever thread logged the record, but never concurrently: calls
+//! are serialized, so the callback does not have to be thread safe itself. Records logged while
+//! the callback runs, e.g. by calling back into the library, are dropped instead of deadlocking.
+
+use std::cell::Cell;
+use std::ffi::CString;
+use std::fmt::{Debug, Write};
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[cfg(feature = "ctypes")]
+use libc::c_int;
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(C)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warn,
+            Level::INFO => Self::Info,
+            Level::DEBUG => Self::Debug,
+            Level::TRACE => Self::Trace,
+        }
+    }
+}
+
+/// Callback receiving the level, target (the module path) and message of a record, and the
+/// `user_data` it was registered with. The strings are only valid during the call.
+pub type LogCallback = unsafe extern "C" fn(
+    level: LogLevel,
+    target: *const c_char,
+    message: *const c_char,
+    user_data: *mut c_void,
+);
+
+struct Sink {
+    callback: LogCallback,
+    user_data: *mut c_void,
+}
+
+// The host guarantees user_data can be used from any thread, see `set_log_callback`.
+unsafe impl Send for Sink {}
+
+/// Subscriber forwarding events to the [`Sink`], spans are not forwarded.
+struct LogBridge {
+    sink: Mutex<Option<Sink>>,
+    max_level: AtomicUsize,
+    next_span: AtomicU64,
+}
+
+thread_local! {
+    /// The callback is running on this thread.
+    // `const` thread local initializers need a newer Rust than the MSRV
+    #[allow(clippy::missing_const_for_thread_local)]
+    static IN_CALLBACK: Cell<bool> = Cell::new(false);
+}
+
+impl LogBridge {
+    fn set(&self, sink: Option<Sink>, max_level: LogLevel) {
+        *self.sink.lock().unwrap_or_else(|e| e.into_inner()) = sink;
+        self.max_level.store(max_level as usize, Ordering::SeqCst);
+        tracing::callsite::rebuild_interest_cache();
+    }
+}
+
+impl Subscriber for LogBridge {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        LogLevel::from(metadata.level()) as usize <= self.max_level.load(Ordering::SeqCst)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if IN_CALLBACK.with(Cell::get) {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let target = c_string(metadata.target());
+        let message = c_string(&visitor.finish());
+
+        let sink = self.sink.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sink) = &*sink {
+            IN_CALLBACK.with(|c| c.set(true));
+            unsafe {
+                (sink.callback)(
+                    metadata.level().into(),
+                    target.as_ptr(),
+                    message.as_ptr(),
+                    sink.user_data,
+                )
+            };
+            IN_CALLBACK.with(|c| c.set(false));
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// Formats the message of an event followed by its other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.message.is_empty() {
+            self.fields
+        } else if self.fields.is_empty() {
+            self.message
+        } else {
+            format!("{} {}", self.message, self.fields)
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+            return;
+        }
+        if !self.fields.is_empty() {
+            self.fields.push(' ');
+        }
+        let _ = write!(self.fields, "{}={:?}", field.name(), value);
+    }
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).unwrap_or_default()
+}
+
+/// Forward the records up to `max_level` to `callback`, or stop forwarding them if it is
+/// `None`. Calling it again replaces the callback.
+///
+/// Fails with [`Error::LoggerInstalled`] if the application installed another `tracing`
+/// subscriber.
+///
+/// # Safety
+/// `callback` has to be safe to call from any thread with `user_data`, which has to stay valid
+/// until the callback is replaced or removed.
+pub unsafe fn set_log_callback(
+    callback: Option<LogCallback>,
+    max_level: LogLevel,
+    user_data: *mut c_void,
+) -> Result<()> {
+    let mut sink = Some(callback.map(|callback| Sink {
+        callback,
+        user_data,
+    }));
+    let replaced =
+        tracing::dispatcher::get_default(|dispatch| match dispatch.downcast_ref::<LogBridge>() {
+            Some(bridge) => {
+                bridge.set(sink.take().flatten(), max_level);
+                true
+            }
+            None => false,
+        });
+    if replaced {
+        return Ok(());
+    }
+
+    let bridge = LogBridge {
+        sink: Mutex::new(sink.flatten()),
+        max_level: AtomicUsize::new(max_level as usize),
+        next_span: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(bridge).map_err(|_| Error::LoggerInstalled)
+}
+
+/// Forward the log records up to *max_level* (1 for errors to 5 for trace) to *callback*, or
+/// stop forwarding them if it is null. See [`set_log_callback`].
+///
+/// # Safety
+/// callback has to be safe to call from any thread with user_data, which has to stay valid
+/// until the callback is replaced or removed.
+#[cfg(feature = "ctypes")]
+#[doc(hidden)]
+#[export_name = "log_set_callback"]
+pub unsafe extern "C" fn set_log_callback_c(
+    callback: Option<LogCallback>,
+    max_level: c_int,
+    user_data: *mut c_void,
+) -> c_int {
+    let max_level = match max_level {
+        1 => LogLevel::Error,
+        2 => LogLevel::Warn,
+        3 => LogLevel::Info,
+        4 => LogLevel::Debug,
+        5 => LogLevel::Trace,
+        _ => return -libc::EINVAL,
+    };
+
+    match unsafe { set_log_callback(callback, max_level, user_data) } {
+        Ok(()) => 0,
+        Err(e) => -e.as_c_error(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    type Records = Mutex<Vec<(LogLevel, String)>>;
+
+    unsafe extern "C" fn collect(
+        level: LogLevel,
+        target: *const c_char,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ) {
+        let records = unsafe { &*(user_data as *const Records) };
+        // Other tests log concurrently
+        if unsafe { CStr::from_ptr(target) }.to_str() == Ok(module_path!()) {
+            let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+            records.lock().unwrap().push((level, message.into_owned()));
+        }
+    }
+
+    #[test]
+    fn log_callback() {
+        let records: &'static Records = Box::leak(Box::new(Mutex::new(Vec::new())));
+        let user_data = records as *const Records as *mut c_void;
+        unsafe { set_log_callback(Some(collect), LogLevel::Info, user_data) }.unwrap();
+
+        tracing::warn!(attempt = 2, "retrying {}", "download");
+        tracing::debug!("below the level");
+        unsafe { set_log_callback(Some(collect), LogLevel::Debug, user_data) }.unwrap();
+        tracing::debug!("above the level");
+        unsafe { set_log_callback(None, LogLevel::Debug, std::ptr::null_mut()) }.unwrap();
+        tracing::error!("not forwarded");
+
+        assert_eq!(
+            *records.lock().unwrap(),
+            vec![
+                (LogLevel::Warn, "retrying download attempt=2".to_string()),
+                (LogLevel::Debug, "above the level".to_string()),
+            ]
+        );
+    }
+}
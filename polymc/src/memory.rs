@@ -0,0 +1,72 @@
+//! Heap sizes for instances which don't set them, computed from the memory of the system.
+
+use sysinfo::{RefreshKind, System, SystemExt};
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+
+/// Smallest maximum heap given to the game.
+pub const MIN_AUTO_HEAP: u64 = GIB;
+/// Largest maximum heap given to the game, more mostly makes garbage collection pauses longer.
+pub const MAX_AUTO_HEAP: u64 = 8 * GIB;
+
+/// Initial and maximum heap size in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapSize {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl HeapSize {
+    /// Heap size for a system with `total` bytes of memory.
+    ///
+    /// The maximum is half of the memory, clamped to [`MIN_AUTO_HEAP`] and [`MAX_AUTO_HEAP`], the
+    /// initial size a quarter of the maximum.
+    pub fn for_total(total: u64) -> Self {
+        let max = (total / 2).clamp(MIN_AUTO_HEAP, MAX_AUTO_HEAP);
+        // Round down to whole MiB, the JVM rejects sizes which are not a multiple of 1024
+        let max = max / MIB * MIB;
+
+        Self { min: max / 4, max }
+    }
+}
+
+/// Total memory of the system in bytes, `None` if it can't be detected.
+pub fn total_memory() -> Option<u64> {
+    let system = System::new_with_specifics(RefreshKind::new().with_memory());
+    match system.total_memory() {
+        0 => None,
+        // sysinfo reports KiB
+        kib => Some(kib * 1024),
+    }
+}
+
+/// Heap size for this system, see [`HeapSize::for_total`].
+pub fn auto_heap() -> Option<HeapSize> {
+    total_memory().map(HeapSize::for_total)
+}
+
+/// Format a size in bytes for `-Xms` and `-Xmx`.
+pub fn jvm_size(bytes: u64) -> String {
+    format!("{}M", bytes / MIB)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heap_size() {
+        assert_eq!(
+            HeapSize::for_total(16 * GIB),
+            HeapSize {
+                min: 2 * GIB,
+                max: 8 * GIB
+            }
+        );
+        assert_eq!(HeapSize::for_total(64 * GIB).max, MAX_AUTO_HEAP);
+        assert_eq!(HeapSize::for_total(GIB).max, MIN_AUTO_HEAP);
+        assert_eq!(jvm_size(HeapSize::for_total(6 * GIB).max), "3072M");
+        assert_eq!(jvm_size(HeapSize::for_total(6 * GIB).min), "768M");
+    }
+}
@@ -1,12 +1,13 @@
-use log::*;
-use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
-use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::*;
 
 use serde::{Deserialize, Serialize};
 
+use crate::cancel::CancellationToken;
 use crate::meta::manifest::Sha1Sum;
 use crate::{Error, Result};
 
@@ -23,13 +24,118 @@ pub struct AssetIndexInfo {
     pub cache: Option<AssetIndex>,
 }
 
+impl AssetIndexInfo {
+    /// Reference to remember the index by, see [`AssetIndexRef`].
+    pub fn reference(&self, verified: bool) -> AssetIndexRef {
+        AssetIndexRef {
+            id: self.id.clone(),
+            sha1: self.sha1.clone(),
+            verified,
+        }
+    }
+}
+
+/// Asset index an instance was last resolved with, the next index is compared with it to only
+/// verify the objects added since, see [`AssetIndex::diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetIndexRef {
+    pub id: String,
+    pub sha1: Sha1Sum,
+    /// Whether the objects were verified when the index was resolved. The objects of an index
+    /// which was only checked to exist are all verified again.
+    #[serde(default)]
+    pub verified: bool,
+}
+
+impl AssetIndexRef {
+    /// Whether it references the index `info`.
+    pub fn is_of(&self, info: &AssetIndexInfo) -> bool {
+        self.id == info.id && self.sha1 == info.sha1
+    }
+
+    /// Load the index from the asset store at `at`, `None` if it is gone or was replaced by a
+    /// different index with the same id.
+    pub fn load_at(&self, at: &str) -> Option<AssetIndex> {
+        let path = Path::new(at)
+            .join("indexes")
+            .join(format!("{}.json", self.id));
+        let data = std::fs::read(&path).ok()?;
+        let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+        if digest.as_ref() != self.sha1.as_ref() {
+            debug!("{} changed since the last launch", path.display());
+            return None;
+        }
+        AssetIndex::from_data(&data).ok()
+    }
+}
+
+/// Objects two asset indexes have in common, counted once per hash as the store keeps them by
+/// hash.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AssetDiff {
+    /// Objects of the previous index which are still used.
+    pub kept: usize,
+    pub kept_bytes: u64,
+    /// Objects the previous index did not have, the only ones which may need a download.
+    pub added: usize,
+    pub added_bytes: u64,
+    /// Objects of the previous index which are not used anymore.
+    pub removed: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AssetIndex {
     pub objects: HashMap<String, Asset>,
+    /// Assets are expected by name in a directory instead of the object store (1.6 and 1.7).
+    #[serde(
+        rename = "virtual",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub is_virtual: bool,
+    /// Assets are expected by name in the `resources` directory of the game (before 1.6).
+    #[serde(
+        rename = "map_to_resources",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub map_to_resources: bool,
 }
 
+/// Directory in the game directory [`AssetIndex::map_to_resources`] copies assets to.
+pub const RESOURCES_DIR: &str = "resources";
+
 impl AssetIndex {
+    /// Copy the assets in the object store at `at` to their names in the `resources` directory
+    /// of `game_dir`, for versions reading them from there.
+    ///
+    /// Files of the same size are kept, missing assets are skipped.
+    pub fn map_to_resources_at<P: AsRef<Path>>(&self, at: &str, game_dir: P) -> Result<()> {
+        let resources = game_dir.as_ref().join(RESOURCES_DIR);
+        for (name, asset) in &self.objects {
+            let from = asset.path_at(at);
+            let to = resources.join(name);
+            let size = match std::fs::metadata(&from) {
+                Ok(metadata) => metadata.len(),
+                Err(e) => {
+                    trace!("not mapping asset {}: {}", name, e);
+                    continue;
+                }
+            };
+            if matches!(std::fs::metadata(&to), Ok(m) if m.len() == size) {
+                continue;
+            }
+
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(&from, &to)?;
+        }
+
+        Ok(())
+    }
+
     pub fn verify_at(&self, at: &str) -> Result<Vec<(Asset, Error)>> {
         let mut ret = Vec::new();
         for (_name, asset) in &self.objects {
@@ -37,6 +143,7 @@ impl AssetIndex {
                 match e {
                     Error::LibraryMissing => ret.push((asset.clone(), e)),
                     Error::LibraryInvalidHash => ret.push((asset.clone(), e)),
+                    Error::LibraryInvalidSize { .. } => ret.push((asset.clone(), e)),
                     _ => return Err(e),
                 }
             }
@@ -45,16 +152,75 @@ impl AssetIndex {
         Ok(ret)
     }
 
-    /// Verify all data.
-    /// # Safety
-    /// This uses write without synchronization, so only run one instance on a given dataset.
-    pub unsafe fn verify_caching_at(&self, at: &str) -> Result<Vec<(Asset, Error)>> {
+    /// Assets which don't exist, without checking hashes.
+    pub fn missing_at(&self, at: &str) -> Vec<(Asset, Error)> {
+        self.objects
+            .values()
+            .filter(|asset| !Path::new(&asset.path_at(at)).is_file())
+            .map(|asset| (asset.clone(), Error::LibraryMissing))
+            .collect()
+    }
+
+    /// Compare the objects with those of the `previous` index.
+    pub fn diff(&self, previous: &AssetIndex) -> AssetDiff {
+        let sizes = |index: &AssetIndex| -> HashMap<_, _> {
+            index
+                .objects
+                .values()
+                .map(|asset| (*asset.hash.as_ref(), u64::try_from(asset.size).unwrap_or(0)))
+                .collect()
+        };
+        let (objects, previous) = (sizes(self), sizes(previous));
+        let mut diff = AssetDiff::default();
+        for (hash, size) in &objects {
+            if previous.contains_key(hash) {
+                diff.kept += 1;
+                diff.kept_bytes += size;
+            } else {
+                diff.added += 1;
+                diff.added_bytes += size;
+            }
+        }
+        diff.removed = previous.len() - diff.kept;
+        diff
+    }
+
+    /// Verify all data, skipping files which were already verified.
+    pub fn verify_caching_at(
+        &self,
+        at: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<(Asset, Error)>> {
+        self.verify_since_at(None, at, cancel)
+    }
+
+    /// Like [`AssetIndex::verify_caching_at`], but the objects of the `previous` index were
+    /// verified when it was used and are only checked to exist.
+    pub fn verify_since_at(
+        &self,
+        previous: Option<&AssetIndex>,
+        at: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<(Asset, Error)>> {
+        let known: HashSet<_> = previous
+            .iter()
+            .flat_map(|index| index.objects.values())
+            .map(|asset| *asset.hash.as_ref())
+            .collect();
         let mut ret = Vec::new();
         for (_name, asset) in &self.objects {
-            if let Err(e) = unsafe { asset.verify_caching_at(at) } {
+            cancel.check()?;
+            if known.contains(asset.hash.as_ref()) {
+                if !Path::new(&asset.path_at(at)).is_file() {
+                    ret.push((asset.clone(), Error::LibraryMissing));
+                }
+                continue;
+            }
+            if let Err(e) = asset.verify_caching_at(at) {
                 match e {
                     Error::LibraryMissing => ret.push((asset.clone(), e)),
                     Error::LibraryInvalidHash => ret.push((asset.clone(), e)),
+                    Error::LibraryInvalidSize { .. } => ret.push((asset.clone(), e)),
                     _ => return Err(e),
                 }
             }
@@ -73,7 +239,7 @@ pub struct Asset {
     pub size: i64,
 
     #[serde(skip)]
-    verified: std::rc::Rc<UnsafeCell<bool>>,
+    verified: Arc<AtomicBool>,
 }
 
 impl Asset {
@@ -96,20 +262,10 @@ impl Asset {
             return Err(Error::LibraryMissing);
         }
 
-        let mut file = OpenOptions::new().read(true).open(path)?;
-
-        let mut digest = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
-
-        loop {
-            let mut buf = [0u8; 8192];
-            let read = file.read(&mut buf)?;
-            digest.update(&buf[..read]);
-            if read < buf.len() {
-                break;
-            }
-        }
+        let file = OpenOptions::new().read(true).open(path)?;
+        crate::meta::verify_size(&file, self.size)?;
 
-        let digest = digest.finish();
+        let digest = crate::hash::hash_reader(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, file)?;
 
         if digest.as_ref() == self.hash.as_ref() {
             trace!("{} is valid", hex::encode(self.hash.as_ref()));
@@ -119,22 +275,12 @@ impl Asset {
         }
     }
 
-    /// Verify all data.
-    /// # Safety
-    /// This uses write without synchronization, so only run one instance on a given dataset.
-    pub unsafe fn verify_caching_at(&self, at: &str) -> Result<()> {
-        if unsafe { *self.verified.get() } {
-            Ok(())
-        } else {
-            if let Err(e) = self.verify_at(at) {
-                Err(e)
-            } else {
-                unsafe {
-                    let verified = &mut *self.verified.get();
-                    *verified = true;
-                }
-                Ok(())
-            }
+    /// Verify all data, skipping files which were already verified.
+    pub fn verify_caching_at(&self, at: &str) -> Result<()> {
+        if !self.verified.load(Ordering::Relaxed) {
+            self.verify_at(at)?;
+            self.verified.store(true, Ordering::Relaxed);
         }
+        Ok(())
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::meta::manifest::{LoggingConfig, Manifest};
+use crate::meta::AssetIndexInfo;
+
+/// Components of an instance, merged by their `order`.
+///
+/// Prism Launcher keeps the components of an instance sorted by `order` in `mmc-pack.json` and
+/// applies them one after another, so a component with a higher order overrides the single
+/// valued fields (main class, `minecraftArguments`, asset index) of the ones before it, while the
+/// list fields (libraries, `+jvmArgs`, `+tweakers`, `+traits`) are appended. The uid breaks ties
+/// between equal orders, so the result does not depend on the order the manifests were resolved
+/// in.
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentSet<'a> {
+    manifests: &'a HashMap<String, Manifest>,
+}
+
+impl<'a> ComponentSet<'a> {
+    pub fn new(manifests: &'a HashMap<String, Manifest>) -> Self {
+        Self { manifests }
+    }
+
+    /// Manifests in ascending `order`, then uid. Later components take precedence.
+    pub fn ordered_components(&self) -> Vec<&'a Manifest> {
+        let mut ret: Vec<&Manifest> = self.manifests.values().collect();
+        ret.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.uid.cmp(&b.uid)));
+        ret
+    }
+
+    /// Main class of the component with the highest order declaring one.
+    pub fn main_class(&self) -> Option<&'a str> {
+        self.last(|m| m.main_class.as_deref())
+    }
+
+    /// Legacy game argument template of the component with the highest order declaring one.
+    pub fn minecraft_arguments(&self) -> Option<&'a str> {
+        self.last(|m| m.minecraft_arguments.as_deref())
+    }
+
+    /// Asset index of the component with the highest order declaring one.
+    pub fn asset_index(&self) -> Option<&'a AssetIndexInfo> {
+        self.last(|m| m.asset_index.as_ref())
+    }
+
+    /// Client logging configuration of the component with the highest order declaring one.
+    pub fn logging(&self) -> Option<&'a LoggingConfig> {
+        self.last(|m| m.logging.as_ref()?.client.as_ref())
+    }
+
+    fn last<T>(&self, field: impl Fn(&'a Manifest) -> Option<T>) -> Option<T> {
+        self.ordered_components().into_iter().rev().find_map(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::manifest;
+
+    #[test]
+    fn merge_order() {
+        let mut manifests = HashMap::new();
+        for m in [
+            manifest(
+                "net.minecraftforge",
+                5,
+                r#""mainClass": "net.minecraft.launchwrapper.Launch""#,
+            ),
+            manifest("org.lwjgl", -1, ""),
+            manifest(
+                "net.minecraft",
+                -2,
+                r#""mainClass": "net.minecraft.client.main.Main",
+                    "minecraftArguments": "--username ${auth_player_name}""#,
+            ),
+            manifest(
+                "com.mumfrey.liteloader",
+                5,
+                r#""minecraftArguments": "--tweakClass ${tweaker}""#,
+            ),
+        ] {
+            manifests.insert(m.uid.clone(), m);
+        }
+        let components = ComponentSet::new(&manifests);
+
+        let uids: Vec<_> = components
+            .ordered_components()
+            .iter()
+            .map(|m| m.uid.as_str())
+            .collect();
+        assert_eq!(
+            uids,
+            [
+                "net.minecraft",
+                "org.lwjgl",
+                "com.mumfrey.liteloader",
+                "net.minecraftforge"
+            ]
+        );
+        assert_eq!(
+            components.main_class(),
+            Some("net.minecraft.launchwrapper.Launch")
+        );
+        // Declared by the component sorted before forge
+        assert_eq!(
+            components.minecraft_arguments(),
+            Some("--tweakClass ${tweaker}")
+        );
+        assert!(components.asset_index().is_none());
+    }
+}
@@ -0,0 +1,100 @@
+//! Versions of the meta format understood by the library.
+//!
+//! Meta files carry a `formatVersion`, which the meta server bumps on incompatible changes.
+//! Files are checked against [`FORMAT_VERSIONS`] before they are parsed, so pointing the launcher
+//! at a newer or older meta server fails with [`Error::MetaFormatUnsupported`] instead of an
+//! error about some field.
+
+use serde::Deserialize;
+
+use super::FileType;
+use crate::{Error, Result};
+
+/// Range of format versions supported for one type of meta file.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatVersions {
+    pub file_type: FileType,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Compatibility table of the meta files carrying a `formatVersion`.
+pub const FORMAT_VERSIONS: &[FormatVersions] = &[
+    FormatVersions {
+        file_type: FileType::MetaIndex,
+        min: 1,
+        max: 1,
+    },
+    FormatVersions {
+        file_type: FileType::Index,
+        min: 1,
+        max: 1,
+    },
+    FormatVersions {
+        file_type: FileType::Manifest,
+        min: 1,
+        max: 1,
+    },
+];
+
+/// Supported format versions of `file_type`, `None` if it carries no format version.
+pub fn format_versions(file_type: FileType) -> Option<&'static FormatVersions> {
+    FORMAT_VERSIONS.iter().find(|v| v.file_type == file_type)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FormatVersion {
+    format_version: Option<u64>,
+}
+
+/// Fail with [`Error::MetaFormatUnsupported`] if `data` has a format version not supported for
+/// `file_type`.
+///
+/// Files without a format version are accepted, parsing them reports what is missing.
+pub fn check_format_version(data: &[u8], file_type: FileType) -> Result<()> {
+    let supported = match format_versions(file_type) {
+        Some(supported) => supported,
+        None => return Ok(()),
+    };
+    let version = match serde_json::from_slice::<FormatVersion>(data) {
+        Ok(FormatVersion {
+            format_version: Some(version),
+        }) => version,
+        _ => return Ok(()),
+    };
+
+    if (supported.min..=supported.max).contains(&version) {
+        Ok(())
+    } else {
+        Err(Error::MetaFormatUnsupported {
+            file_type: file_type.to_string(),
+            version,
+            min: supported.min,
+            max: supported.max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::MetaManager;
+
+    #[test]
+    fn format_version() {
+        check_format_version(br#"{"formatVersion": 1}"#, FileType::Index).unwrap();
+        check_format_version(br#"{"objects": {}}"#, FileType::AssetIndex).unwrap();
+        check_format_version(br#"{"name": "a"}"#, FileType::Manifest).unwrap();
+
+        let mut manager = MetaManager::new("lib", "assets", "https://example.com");
+        let index = br#"{"formatVersion": 2, "packages": {"a": "renamed"}}"#;
+        match manager.load_data(index, FileType::MetaIndex) {
+            Err(e @ Error::MetaFormatUnsupported { .. }) => assert_eq!(
+                e.to_string(),
+                "Unsupported meta_index format version 2, this launcher reads versions 1 to 1"
+            ),
+            other => panic!("format version not checked: {:?}", other),
+        }
+    }
+}
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use crate::meta::manifest::{Manifest, Requirement, Sha256Sum};
+use crate::meta::Wants;
 use crate::{Error, Result};
 
 macro_rules! from_str_json {
@@ -44,7 +45,7 @@ impl MetaIndex {
             }
         }
 
-        Err(Error::MetaNotFound)
+        Err(Error::UnknownUid(uid.to_string()))
     }
 
     pub fn get_uid(&self, uid: &str) -> Result<&MetaIndexPackage> {
@@ -54,7 +55,18 @@ impl MetaIndex {
             }
         }
 
-        Err(Error::MetaNotFound)
+        Err(Error::UnknownUid(uid.to_string()))
+    }
+
+    /// Find packages whose name or uid contains `query`, ignoring case.
+    pub fn find_packages(&self, query: &str) -> Vec<&MetaIndexPackage> {
+        let query = query.to_lowercase();
+        self.packages
+            .iter()
+            .filter(|p| {
+                p.name.to_lowercase().contains(&query) || p.uid.to_lowercase().contains(&query)
+            })
+            .collect()
     }
 }
 
@@ -89,7 +101,10 @@ impl PackageIndex {
             }
         }
 
-        Err(Error::MetaNotFound)
+        Err(Error::UnknownVersion {
+            uid: self.uid.clone(),
+            version: version.to_string(),
+        })
     }
 
     pub fn find_version(&self, version: &str) -> Result<&PackageVersion> {
@@ -99,7 +114,30 @@ impl PackageIndex {
             }
         }
 
-        Err(Error::MetaNotFound)
+        Err(Error::UnknownVersion {
+            uid: self.uid.clone(),
+            version: version.to_string(),
+        })
+    }
+
+    /// Version to use for `wants`: its version if that is in [`Wants::range`], otherwise the
+    /// newest version in the range.
+    pub fn select_version(&self, wants: &Wants) -> Result<&PackageVersion> {
+        let range = match &wants.range {
+            Some(range) => range,
+            None => return self.find_version(&wants.version),
+        };
+
+        let matching = self.versions.iter().filter(|v| range.matches(&v.version));
+        if let Some(version) = matching.clone().find(|v| v.version == wants.version) {
+            return Ok(version);
+        }
+        matching
+            .max_by(|a, b| a.release_time.cmp(&b.release_time))
+            .ok_or_else(|| Error::UnknownVersion {
+                uid: self.uid.clone(),
+                version: range.to_string(),
+            })
     }
 }
 
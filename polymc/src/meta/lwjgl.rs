@@ -0,0 +1,168 @@
+//! Override of the LWJGL component, to run versions whose manifests have no natives for the
+//! architecture of the host, e.g. on Apple Silicon or a Raspberry Pi.
+
+use tracing::*;
+
+use crate::meta::manifest::{LibraryDownload, Manifest, Requirement, OS};
+use crate::meta::Wants;
+
+/// Uids of the LWJGL 2 and 3 components.
+pub const LWJGL_UIDS: &[&str] = &["org.lwjgl", "org.lwjgl3"];
+
+/// Maven repository of the official LWJGL builds, which has arm natives since 3.3.0.
+pub const DEFAULT_NATIVES_URL: &str = "https://repo1.maven.org/maven2";
+
+/// Changes applied to the LWJGL component when it is merged into the search result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LwjglOverride {
+    /// Version used instead of the one required by the game.
+    pub version: Option<String>,
+    /// Architecture appended to the natives classifiers, e.g. `arm64` for
+    /// `natives-linux-arm64`. Natives are not substituted if `None`.
+    pub natives_arch: Option<String>,
+    /// Maven repository the substituted natives are downloaded from.
+    pub natives_url: String,
+}
+
+impl Default for LwjglOverride {
+    fn default() -> Self {
+        Self {
+            version: None,
+            natives_arch: None,
+            natives_url: DEFAULT_NATIVES_URL.to_string(),
+        }
+    }
+}
+
+impl LwjglOverride {
+    /// Override substituting the natives for the architecture of the host, if the manifests
+    /// don't have natives for it.
+    pub fn for_host() -> Self {
+        Self {
+            natives_arch: natives_arch(std::env::consts::ARCH).map(ToString::to_string),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_lwjgl(uid: &str) -> bool {
+        LWJGL_UIDS.contains(&uid)
+    }
+
+    /// Whether the version of `uid` is replaced, requirements on it are not checked then.
+    pub fn overrides(&self, uid: &str) -> bool {
+        self.version.is_some() && Self::is_lwjgl(uid)
+    }
+
+    /// What to search for the requirement `req`, with the version replaced for LWJGL.
+    pub fn wants_for(&self, req: &Requirement) -> Wants {
+        let mut wants: Wants = req.clone().into();
+        if let Some(version) = self.version.as_ref().filter(|_| Self::is_lwjgl(&req.uid)) {
+            debug!("using {} {} instead of {}", req.uid, version, wants.version);
+            wants.version = version.to_string();
+            wants.range = None;
+        }
+        wants
+    }
+
+    /// Substitute the natives of `platform` in the LWJGL manifest with the ones for
+    /// [`LwjglOverride::natives_arch`]. Other manifests are not changed.
+    ///
+    /// The substituted natives are maven artifacts without a hash, e.g. `natives-linux` of
+    /// `org.lwjgl:lwjgl:3.3.1` is replaced by `natives-linux-arm64` from the repository.
+    pub fn apply(&self, manifest: &mut Manifest, platform: &OS) {
+        let arch = match &self.natives_arch {
+            Some(arch) if Self::is_lwjgl(&manifest.uid) => arch,
+            _ => return,
+        };
+
+        for lib in &mut manifest.libraries {
+            let classifier = match lib.natives.get(&platform.name) {
+                Some(classifier) if !classifier.ends_with(arch.as_str()) => {
+                    format!("{}-{}", classifier, arch)
+                }
+                _ => continue,
+            };
+
+            let mut name = lib.name.clone();
+            name.classifier = Some(classifier.clone());
+            trace!("substituting natives of {} with {}", lib.name, name);
+            lib.downloads.classifiers.insert(
+                classifier.clone(),
+                LibraryDownload {
+                    sha1: None,
+                    size: -1,
+                    url: name.maven_url(&self.natives_url),
+                },
+            );
+            lib.natives.insert(platform.name.clone(), classifier);
+        }
+    }
+}
+
+/// Suffix of the LWJGL natives for `arch`, `None` for architectures the manifests cover.
+fn natives_arch(arch: &str) -> Option<&'static str> {
+    match arch {
+        "aarch64" => Some("arm64"),
+        "arm" => Some("arm32"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn substitute_natives() {
+        let mut manifest: Manifest = r#"{"name": "LWJGL 3", "uid": "org.lwjgl3",
+            "version": "3.3.1", "order": -1, "releaseTime": "2022-01-01T00:00:00+00:00",
+            "type": "release",
+            "libraries": [{
+                "name": "org.lwjgl:lwjgl:3.3.1",
+                "downloads": {"classifiers": {"natives-linux": {
+                    "sha1": "0000000000000000000000000000000000000000",
+                    "size": 1,
+                    "url": "https://example.com/lwjgl-3.3.1-natives-linux.jar"
+                }}},
+                "natives": {"linux": "natives-linux"}
+            }]
+        }"#
+        .parse()
+        .unwrap();
+        let linux = OS::new("linux");
+
+        let lwjgl = LwjglOverride {
+            version: Some("3.3.1".to_string()),
+            natives_arch: Some("arm64".to_string()),
+            natives_url: "https://maven.example.com/".to_string(),
+        };
+        lwjgl.apply(&mut manifest, &linux);
+        // Applying twice does not substitute again
+        lwjgl.apply(&mut manifest, &linux);
+
+        let lib = &manifest.libraries[0];
+        let download = lib.select_for(&linux).unwrap();
+        assert_eq!(
+            download.url,
+            "https://maven.example.com/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux-arm64.jar"
+        );
+        assert!(download.sha1.is_none());
+        assert!(lib
+            .path_at_for("/lib", &linux)
+            .ends_with("lwjgl-3.3.1-natives-linux-arm64.jar"));
+
+        let req = |uid: &str| Requirement {
+            equals: Some("3.2.2".to_string()),
+            suggests: "3.2.2".to_string(),
+            range: None,
+            uid: uid.to_string(),
+            optional: false,
+        };
+        assert_eq!(lwjgl.wants_for(&req("org.lwjgl3")).version, "3.3.1");
+        assert_eq!(
+            lwjgl.wants_for(&req("net.fabricmc.intermediary")).version,
+            "3.2.2"
+        );
+        assert_eq!(natives_arch("x86_64"), None);
+    }
+}
@@ -1,15 +1,18 @@
-use log::{debug, trace};
 use ring::digest::{SHA1_OUTPUT_LEN, SHA256_OUTPUT_LEN};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
 
-use std::cell::UnsafeCell;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::OpenOptions;
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use crate::meta::AssetIndexInfo;
+use crate::cancel::CancellationToken;
+use crate::hash::read_sha1_sidecar;
+use crate::meta::{AssetIndexInfo, VersionRange};
 use crate::{Error, Result};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,9 +20,24 @@ use crate::{Error, Result};
 pub struct Manifest {
     #[serde(rename = "+traits", default)]
     pub traits: Vec<String>,
+    /// Extra arguments for the JVM, added by the component.
+    #[serde(rename = "+jvmArgs", default)]
+    pub jvm_args: Vec<String>,
+    /// LaunchWrapper tweak classes added by the component (e.g. Forge or LiteLoader).
+    #[serde(rename = "+tweakers", default)]
+    pub tweakers: Vec<String>,
 
     #[serde(default)]
     pub asset_index: Option<AssetIndexInfo>,
+    /// Java runtime the component needs, in the format of Mojang's manifests.
+    #[serde(default)]
+    pub java_version: Option<JavaVersion>,
+    /// Major versions of Java the component is known to run on.
+    #[serde(default)]
+    pub compatible_java_majors: Vec<u32>,
+    /// Log4j configurations of the game, in the format of Mojang's manifests.
+    #[serde(default)]
+    pub logging: Option<Logging>,
     pub libraries: Vec<Library>,
     #[serde(default)]
     pub main_class: Option<String>,
@@ -31,31 +49,185 @@ pub struct Manifest {
     pub release_time: String, // FIXME: time type
     #[serde(default)]
     pub requires: Vec<Requirement>,
+    /// Components which can't be used together with this one, matched with
+    /// [`Requirement::accepts`].
+    #[serde(default)]
+    pub conflicts: Vec<Requirement>,
     #[serde(rename = "type")]
     pub release_type: String, // TODO: enum
     pub uid: String,
     pub version: String, // FIXME: SemVer type
 }
 
+/// Uid of the requirement on a Java runtime in the meta format.
+pub const JAVA_UID: &str = "net.minecraft.java";
+
+/// Separator of class path entries, `File.pathSeparator` in Java.
+pub const CLASS_PATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
+/// Join class path entries for the `-cp` argument.
+pub fn join_class_path(entries: &[PathBuf]) -> String {
+    entries
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(CLASS_PATH_SEPARATOR)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaVersion {
+    #[serde(default)]
+    pub component: Option<String>,
+    pub major_version: u32,
+}
+
+/// Directory in the assets directory holding the logging configurations.
+pub const LOG_CONFIGS_DIR: &str = "log_configs";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Logging {
+    #[serde(default)]
+    pub client: Option<LoggingConfig>,
+}
+
+/// Log4j configuration making the game log structured XML events instead of plain text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggingConfig {
+    /// JVM argument template, with `${path}` standing for the configuration file.
+    pub argument: String,
+    pub file: LoggingFile,
+    /// Format of the configuration, e.g. `log4j2-xml`.
+    #[serde(rename = "type")]
+    pub config_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggingFile {
+    pub id: String,
+    pub sha1: Sha1Sum,
+    pub size: i64,
+    pub url: String,
+}
+
+impl LoggingConfig {
+    /// Path of the configuration file in the assets directory `at`.
+    ///
+    /// Fails with [`Error::InvalidFileName`] if the id of the file isn't a plain file name, so
+    /// the meta data can't place it outside of [`LOG_CONFIGS_DIR`].
+    pub fn path_at<P: AsRef<Path>>(&self, at: P) -> Result<PathBuf> {
+        let id = &self.file.id;
+        let mut components = Path::new(id).components();
+        let plain = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+        if !plain || id.contains(['/', '\\', ':']) {
+            return Err(Error::InvalidFileName(id.to_string()));
+        }
+        Ok(at.as_ref().join(LOG_CONFIGS_DIR).join(id))
+    }
+
+    /// JVM argument pointing the game at the configuration file in the assets directory `at`.
+    pub fn arg_at<P: AsRef<Path>>(&self, at: P) -> Result<String> {
+        Ok(self
+            .argument
+            .replace("${path}", &self.path_at(at)?.display().to_string()))
+    }
+
+    /// Download of the configuration file, verified like a library.
+    pub fn download(&self) -> LibraryDownload {
+        LibraryDownload {
+            sha1: Some(self.file.sha1.clone()),
+            size: self.file.size,
+            url: self.file.url.clone(),
+        }
+    }
+
+    /// Check the cached configuration file in the assets directory `at` against its sha1.
+    pub fn verify_at<P: AsRef<Path>>(&self, at: P) -> Result<()> {
+        let path = self.path_at(at)?;
+        if !path.is_file() {
+            return Err(Error::LibraryMissing);
+        }
+
+        let file = OpenOptions::new().read(true).open(&path)?;
+        crate::meta::verify_size(&file, self.file.size)?;
+        let digest = crate::hash::hash_reader(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, file)?;
+        if digest.as_ref() == self.file.sha1.as_ref() {
+            Ok(())
+        } else {
+            Err(Error::LibraryInvalidHash)
+        }
+    }
+}
+
 impl Manifest {
-    pub fn build_class_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
-        &self,
-        path: &S,
-        platform: &OS,
-    ) -> String {
-        let mut ret = Vec::new();
+    /// Major version of Java the component needs.
+    ///
+    /// Taken from `javaVersion`, a requirement on [`JAVA_UID`] or the newest of the compatible
+    /// majors, in that order.
+    pub fn required_java_major(&self) -> Option<u32> {
+        if let Some(java) = &self.java_version {
+            return Some(java.major_version);
+        }
 
-        for lib in &self.libraries {
-            if lib.required_for(platform) {
-                ret.push(lib.name.path_at(path).display().to_string());
+        if let Some(req) = self.requires.iter().find(|r| r.uid == JAVA_UID) {
+            let version = req.equals.as_ref().unwrap_or(&req.suggests);
+            if let Some(major) = crate::runtime::parse_major(version) {
+                return Some(major);
             }
         }
 
+        self.compatible_java_majors.iter().max().copied()
+    }
+
+    /// Class path entries of the libraries followed by the main jar, in the order they are
+    /// declared.
+    pub fn class_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        &self,
+        path: &S,
+        platform: &OS,
+    ) -> Vec<PathBuf> {
+        let mut ret = self.libraries_class_path_at(path, platform);
         if let Some(jar) = &self.main_jar {
-            ret.push(jar.name.path_at(path).display().to_string())
+            ret.push(jar.name.path_at(path));
         }
 
-        ret.join(":")
+        ret
+    }
+
+    /// Class path entries of the libraries, without the main jar.
+    pub fn libraries_class_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        &self,
+        path: &S,
+        platform: &OS,
+    ) -> Vec<PathBuf> {
+        self.libraries
+            .iter()
+            .filter(|lib| lib.is_on_class_path(platform))
+            .map(|lib| lib.name.path_at(path))
+            .collect()
+    }
+
+    pub fn build_class_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        &self,
+        path: &S,
+        platform: &OS,
+    ) -> String {
+        join_class_path(&self.class_path_at(path, platform))
+    }
+
+    /// Class path of the libraries, without the main jar.
+    pub fn build_libraries_class_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        &self,
+        path: &S,
+        platform: &OS,
+    ) -> String {
+        join_class_path(&self.libraries_class_path_at(path, platform))
     }
 
     pub fn assets_path_at(&self, at: &str) -> Option<String> {
@@ -83,6 +255,7 @@ impl Manifest {
                     match e {
                         Error::LibraryMissing => ret.push((lib.clone(), e)),
                         Error::LibraryInvalidHash => ret.push((lib.clone(), e)),
+                        Error::LibraryInvalidSize { .. } => ret.push((lib.clone(), e)),
                         _ => return Err(e),
                     }
                 }
@@ -94,6 +267,7 @@ impl Manifest {
                 match e {
                     Error::LibraryMissing => ret.push((jar.clone(), e)),
                     Error::LibraryInvalidHash => ret.push((jar.clone(), e)),
+                    Error::LibraryInvalidSize { .. } => ret.push((jar.clone(), e)),
                     _ => return Err(e),
                 }
             }
@@ -102,46 +276,65 @@ impl Manifest {
         Ok(ret)
     }
 
-    /// Verify all data.
-    /// # Safety
-    /// This uses write without synchronization, so only run one instance on a given dataset.
-    pub unsafe fn verify_caching_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+    /// Libraries which can't be used on `platform`, with the reason.
+    pub fn unsupported_libraries(&self, platform: &OS) -> Vec<(&Library, Unsupported)> {
+        self.libraries
+            .iter()
+            .filter_map(|lib| Some((lib, lib.unsupported_for(platform)?)))
+            .collect()
+    }
+
+    /// Libraries required for `platform` which don't exist, without checking hashes.
+    pub fn missing_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
+        &self,
+        path: &S,
+        platform: &OS,
+    ) -> Vec<(Library, Error)> {
+        self.libraries
+            .iter()
+            .filter(|lib| lib.required_for(platform))
+            .chain(&self.main_jar)
+            .filter(|lib| !lib.path_at_for(path, platform).is_file())
+            .map(|lib| (lib.clone(), Error::LibraryMissing))
+            .collect()
+    }
+
+    /// Verify all data, skipping files which were already verified.
+    pub fn verify_caching_at<S: AsRef<std::ffi::OsStr> + ?Sized>(
         &self,
         path: &S,
         platform: &OS,
+        cancel: &CancellationToken,
     ) -> Result<Vec<(Library, Error)>> {
         let mut ret = Vec::new();
 
         for lib in &self.libraries {
-            if !unsafe { *lib.verified.get() } && lib.required_for(platform) {
+            cancel.check()?;
+            if !lib.verified.load(Ordering::Relaxed) && lib.required_for(platform) {
                 if let Err(e) = lib.verify_at(path, platform) {
                     match e {
                         Error::LibraryMissing => ret.push((lib.clone(), e)),
                         Error::LibraryInvalidHash => ret.push((lib.clone(), e)),
+                        Error::LibraryInvalidSize { .. } => ret.push((lib.clone(), e)),
                         _ => return Err(e),
                     }
                 } else {
-                    unsafe {
-                        let verified = &mut *lib.verified.get();
-                        *verified = true;
-                    }
+                    lib.verified.store(true, Ordering::Relaxed);
                 }
             }
         }
 
         if let Some(jar) = &self.main_jar {
-            if !unsafe { *jar.verified.get() } {
+            if !jar.verified.load(Ordering::Relaxed) {
                 if let Err(e) = jar.verify_at(path, platform) {
                     match e {
                         Error::LibraryMissing => ret.push((jar.clone(), e)),
                         Error::LibraryInvalidHash => ret.push((jar.clone(), e)),
+                        Error::LibraryInvalidSize { .. } => ret.push((jar.clone(), e)),
                         _ => return Err(e),
                     }
                 } else {
-                    unsafe {
-                        let verified = &mut *jar.verified.get();
-                        *verified = true;
-                    }
+                    jar.verified.store(true, Ordering::Relaxed);
                 }
             }
         }
@@ -156,7 +349,12 @@ crate::meta::index::from_str_json!(Manifest);
 #[serde(rename_all = "camelCase")]
 pub struct Library {
     pub name: LibraryName,
+    #[serde(default)]
     pub downloads: LibraryDownloads,
+    /// Base url of the maven repository hosting the library, used if `downloads` does not list
+    /// an artifact (e.g. Fabric and Quilt libraries).
+    #[serde(default)]
+    pub url: Option<String>,
     #[serde(default)]
     pub natives: HashMap<String, String>,
 
@@ -167,7 +365,7 @@ pub struct Library {
     pub rules: Vec<Rule>,
 
     #[serde(skip)]
-    verified: std::rc::Rc<UnsafeCell<bool>>,
+    verified: Arc<AtomicBool>,
 }
 
 impl Library {
@@ -187,22 +385,21 @@ impl Library {
             return Err(Error::LibraryMissing);
         }
 
-        let mut file = OpenOptions::new().read(true).open(path)?;
-
-        let mut digest = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+        let file = OpenOptions::new().read(true).open(&path)?;
+        crate::meta::verify_size(&file, artifact.size)?;
 
-        loop {
-            let mut buf = [0u8; 8192];
-            let read = file.read(&mut buf)?;
-            digest.update(&buf[..read]);
-            if read < buf.len() {
-                break;
+        // Libraries from maven repositories may only have the hash they published
+        let sha1 = match artifact.sha1.clone().or_else(|| read_sha1_sidecar(&path)) {
+            Some(sha1) => sha1,
+            None => {
+                trace!("{} has no hash, only checked its size", self.name);
+                return Ok(());
             }
-        }
+        };
 
-        let digest = digest.finish();
+        let digest = crate::hash::hash_reader(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, file)?;
 
-        if digest.as_ref() == artifact.sha1.as_ref() {
+        if digest.as_ref() == sha1.as_ref() {
             trace!("{} is valid", self.name);
             Ok(())
         } else {
@@ -211,6 +408,16 @@ impl Library {
     }
 
     pub fn required_for(&self, platform: &OS) -> bool {
+        // Natives only libraries have nothing to download for platforms without natives
+        if !self.has_artifact() && !self.natives.contains_key(&platform.name) {
+            return false;
+        }
+
+        self.allowed_for(platform)
+    }
+
+    /// The rules of the library allow `platform`.
+    fn allowed_for(&self, platform: &OS) -> bool {
         let mut allow = false;
         if self.rules.is_empty() {
             allow = true;
@@ -225,14 +432,51 @@ impl Library {
         allow
     }
 
-    pub fn select_for(&self, os: &OS) -> Option<&LibraryDownload> {
+    /// Why the library can't be used on `platform`, `None` if it can.
+    pub fn unsupported_for(&self, platform: &OS) -> Option<Unsupported> {
+        if !self.allowed_for(platform) {
+            return Some(Unsupported::ExcludedByRules);
+        }
+        if !self.has_artifact() && !self.natives.contains_key(&platform.name) {
+            return Some(Unsupported::NoNatives);
+        }
+        if self.select_for(platform).is_none() {
+            return Some(Unsupported::MissingDownload);
+        }
+
+        None
+    }
+
+    /// The download for `os`, either the natives of the platform or the artifact.
+    ///
+    /// Maven style libraries without `downloads` get their artifact url from the repository in
+    /// [`Library::url`], without a hash or size.
+    pub fn select_for(&self, os: &OS) -> Option<Cow<'_, LibraryDownload>> {
         if let Some(name) = self.natives.get(&os.name) {
-            self.downloads.classifiers.get(name)
-        } else {
-            Some(&self.downloads.artifact)
+            return self.downloads.classifiers.get(name).map(Cow::Borrowed);
+        }
+
+        match (&self.downloads.artifact, &self.url) {
+            (Some(artifact), _) => Some(Cow::Borrowed(artifact)),
+            (None, Some(url)) => Some(Cow::Owned(LibraryDownload {
+                sha1: None,
+                size: -1,
+                url: self.name.maven_url(url),
+            })),
+            (None, None) => None,
         }
     }
 
+    /// The library has a jar besides its natives, either in `downloads` or in a maven repository.
+    pub fn has_artifact(&self) -> bool {
+        self.downloads.artifact.is_some() || self.url.is_some()
+    }
+
+    /// The library provides classes, libraries only holding natives are extracted instead.
+    pub fn is_on_class_path(&self, platform: &OS) -> bool {
+        self.has_artifact() && self.required_for(platform)
+    }
+
     pub fn path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(&self, at: &S) -> PathBuf {
         self.name.path_at(at)
     }
@@ -250,10 +494,33 @@ impl Library {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Reason a library can't be used on a platform, see [`Library::unsupported_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsupported {
+    /// The rules of the library don't allow the platform, it is not needed there.
+    ExcludedByRules,
+    /// The library only holds natives, and none for the platform.
+    NoNatives,
+    /// The natives for the platform are declared without a download.
+    MissingDownload,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ExcludedByRules => "excluded by its rules",
+            Self::NoNatives => "no natives for the platform",
+            Self::MissingDownload => "natives declared without a download",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LibraryDownloads {
-    pub artifact: LibraryDownload,
+    /// Missing for libraries which only provide natives in [`LibraryDownloads::classifiers`].
+    #[serde(default)]
+    pub artifact: Option<LibraryDownload>,
     #[serde(default)]
     pub classifiers: HashMap<String, LibraryDownload>,
 }
@@ -261,7 +528,10 @@ pub struct LibraryDownloads {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LibraryDownload {
-    pub sha1: Sha1Sum,
+    /// Missing for maven style libraries, whose hash is only published next to the artifact.
+    #[serde(default)]
+    pub sha1: Option<Sha1Sum>,
+    /// Negative if unknown.
     pub size: i64,
     pub url: String,
 }
@@ -328,11 +598,28 @@ impl OS {
 pub struct Requirement {
     #[serde(default)]
     pub equals: Option<String>,
+    #[serde(default)]
     pub suggests: String,
+    /// Versions the component works with, the newest matching one is used if the suggested
+    /// version is not in the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<VersionRange>,
     pub uid: String,
+    /// The component works without it, it is skipped with a warning if it can't be resolved.
+    #[serde(default)]
+    pub optional: bool,
 }
 
-#[derive(Debug, Clone, serde_with::SerializeDisplay, serde_with::DeserializeFromStr)]
+impl Requirement {
+    /// Whether `version` of the component satisfies the requirement.
+    pub fn accepts(&self, version: &str) -> bool {
+        self.equals.iter().all(|e| e == version) && self.range.iter().all(|r| r.matches(version))
+    }
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde_with::SerializeDisplay, serde_with::DeserializeFromStr,
+)]
 pub struct Sha1Sum([u8; ring::digest::SHA1_OUTPUT_LEN]);
 
 impl std::fmt::Display for Sha1Sum {
@@ -398,15 +685,26 @@ impl AsRef<[u8; ring::digest::SHA256_OUTPUT_LEN]> for Sha256Sum {
     }
 }
 
-#[derive(Debug, Clone, serde_with::SerializeDisplay, serde_with::DeserializeFromStr)]
+/// Maven coordinate of a library, `namespace:name:version[:classifier][@extension]`.
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde_with::SerializeDisplay, serde_with::DeserializeFromStr,
+)]
 pub struct LibraryName {
     pub namespace: String,
     pub name: String,
     pub version: String,
-    pub extra_versions: Vec<String>,
+    /// Variant of the artifact, e.g. `client` or `universal`.
+    pub classifier: Option<String>,
+    /// File extension given after `@`, see [`LibraryName::extension`].
+    pub extension: Option<String>,
 }
 
 impl LibraryName {
+    /// File extension of the artifact, `jar` unless the coordinate says otherwise.
+    pub fn extension(&self) -> &str {
+        self.extension.as_deref().unwrap_or("jar")
+    }
+
     pub fn base_path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(&self, path: &S) -> PathBuf {
         let mut path = Path::new(path).to_path_buf();
         self.namespace
@@ -420,41 +718,44 @@ impl LibraryName {
         path
     }
 
+    /// File name of the artifact, with `natives` appended to the classifier.
+    fn file_name(&self, natives: Option<&str>) -> String {
+        let mut ret = format!("{}-{}", self.name, self.version);
+        for classifier in self.classifier.iter().map(String::as_str).chain(natives) {
+            ret.push('-');
+            ret.push_str(classifier);
+        }
+        ret.push('.');
+        ret.push_str(self.extension());
+
+        ret
+    }
+
     pub fn path_at<S: AsRef<std::ffi::OsStr> + ?Sized>(&self, path: &S) -> PathBuf {
         let mut path = self.base_path_at(path);
-
-        if !self.extra_versions.is_empty() {
-            path.push(format!(
-                "{}-{}-{}.jar",
-                self.name,
-                self.version,
-                self.extra_versions.join("-")
-            ));
-        } else {
-            path.push(format!("{}-{}.jar", self.name, self.version));
-        }
+        path.push(self.file_name(None));
 
         path
     }
 
+    /// Url of the library in the maven repository at `repository`.
+    pub fn maven_url(&self, repository: &str) -> String {
+        let path: Vec<_> = self
+            .path_at("")
+            .iter()
+            .map(|c| c.to_string_lossy().into_owned())
+            .collect();
+
+        format!("{}/{}", repository.trim_end_matches('/'), path.join("/"))
+    }
+
     pub fn path_at_natives<S: AsRef<std::ffi::OsStr> + ?Sized>(
         &self,
         path: &S,
         natives: &str,
     ) -> PathBuf {
         let mut path = self.base_path_at(path);
-
-        if !self.extra_versions.is_empty() {
-            path.push(format!(
-                "{}-{}-{}-{}.jar",
-                self.name,
-                self.version,
-                self.extra_versions.join("-"),
-                natives
-            ));
-        } else {
-            path.push(format!("{}-{}-{}.jar", self.name, self.version, natives));
-        }
+        path.push(self.file_name(Some(natives)));
 
         path
     }
@@ -462,18 +763,15 @@ impl LibraryName {
 
 impl std::fmt::Display for LibraryName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if !self.extra_versions.is_empty() {
-            write!(
-                f,
-                "{}:{}:{}:{}",
-                self.namespace,
-                self.name,
-                self.version,
-                self.extra_versions.join(":")
-            )
-        } else {
-            write!(f, "{}:{}:{}", self.namespace, self.name, self.version)
+        write!(f, "{}:{}:{}", self.namespace, self.name, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{}", classifier)?;
         }
+        if let Some(extension) = &self.extension {
+            write!(f, "@{}", extension)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -481,21 +779,23 @@ impl std::str::FromStr for LibraryName {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, extension) = match s.split_once('@') {
+            Some((s, extension)) if !extension.is_empty() => (s, Some(extension.to_string())),
+            Some(_) => return Err(Error::LibraryInvalidName),
+            None => (s, None),
+        };
+
         let s: Vec<&str> = s.split(':').collect();
-        if s.len() < 3 {
+        if s.len() < 3 || s.len() > 4 || s.iter().any(|p| p.is_empty()) {
             return Err(Error::LibraryInvalidName);
         }
 
-        let mut extra_versions = Vec::new();
-        for s in &s[3..] {
-            extra_versions.push(s.to_string());
-        }
-
         Ok(Self {
             namespace: s[0].to_owned(),
             name: s[1].to_owned(),
             version: s[2].to_owned(),
-            extra_versions,
+            classifier: s.get(3).map(ToString::to_string),
+            extension,
         })
     }
 }
@@ -510,6 +810,8 @@ pub struct ExtractOptions {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::meta::DownloadRequest;
+    use crate::test_util::TempDir;
 
     #[test]
     fn libraryname() {
@@ -532,7 +834,7 @@ mod test {
         assert_eq!(name_parsed.namespace, "com.mojang");
         assert_eq!(name_parsed.name, "minecraft");
         assert_eq!(name_parsed.version, "1.18.1");
-        assert_eq!(name_parsed.extra_versions, vec!["client"]);
+        assert_eq!(name_parsed.classifier.as_deref(), Some("client"));
 
         assert_eq!(name_parsed.to_string(), name);
 
@@ -541,4 +843,161 @@ mod test {
             Path::new("com/mojang/minecraft/1.18.1/minecraft-1.18.1-client.jar")
         )
     }
+
+    #[test]
+    fn libraryname_extension() {
+        let name = "net.minecraftforge:forge:1.18.2-40.0.12:universal@zip";
+        let name_parsed: LibraryName = name.parse().unwrap();
+        assert_eq!(name_parsed.version, "1.18.2-40.0.12");
+        assert_eq!(name_parsed.classifier.as_deref(), Some("universal"));
+        assert_eq!(name_parsed.extension(), "zip");
+        assert_eq!(name_parsed.to_string(), name);
+        assert_eq!(
+            name_parsed.path_at(""),
+            Path::new("net/minecraftforge/forge/1.18.2-40.0.12/forge-1.18.2-40.0.12-universal.zip")
+        );
+        assert_eq!(
+            name_parsed.path_at_natives("", "natives-linux"),
+            Path::new("net/minecraftforge/forge/1.18.2-40.0.12/forge-1.18.2-40.0.12-universal-natives-linux.zip")
+        );
+
+        let name = "de.oceanlabs.mcp:mcp_config:1.18.2-20220228.144236@zip";
+        let name_parsed: LibraryName = name.parse().unwrap();
+        assert_eq!(name_parsed.classifier, None);
+        assert_eq!(name_parsed.to_string(), name);
+        assert_eq!(
+            name_parsed.path_at(""),
+            Path::new("de/oceanlabs/mcp/mcp_config/1.18.2-20220228.144236/mcp_config-1.18.2-20220228.144236.zip")
+        );
+
+        for invalid in ["a:b:c@", "a:b:c:d:e", "a::c", "a:b"] {
+            assert!(
+                matches!(
+                    invalid.parse::<LibraryName>(),
+                    Err(Error::LibraryInvalidName)
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn natives_only_library() {
+        let library: Library = serde_json::from_str(
+            r#"{"name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                "natives": {"linux": "natives-linux", "osx": "natives-osx"},
+                "downloads": {"classifiers": {
+                    "natives-linux": {
+                        "sha1": "0000000000000000000000000000000000000000", "size": 1,
+                        "url": "https://example.com/lwjgl-platform-2.9.4-natives-linux.jar"
+                    },
+                    "natives-osx": {
+                        "sha1": "0000000000000000000000000000000000000000", "size": 1,
+                        "url": "https://example.com/lwjgl-platform-2.9.4-natives-osx.jar"
+                    }
+                }}
+            }"#,
+        )
+        .unwrap();
+
+        let linux = OS::new("linux");
+        assert!(library.required_for(&linux));
+        assert!(!library.is_on_class_path(&linux));
+        assert!(library
+            .select_for(&linux)
+            .unwrap()
+            .url
+            .ends_with("natives-linux.jar"));
+
+        let windows = OS::new("windows");
+        assert!(!library.required_for(&windows));
+        assert!(library.select_for(&windows).is_none());
+
+        assert_eq!(library.unsupported_for(&linux), None);
+        assert_eq!(
+            library.unsupported_for(&windows),
+            Some(Unsupported::NoNatives)
+        );
+        let mut library = library;
+        library.downloads.classifiers.remove("natives-osx");
+        assert_eq!(
+            library.unsupported_for(&OS::new("osx")),
+            Some(Unsupported::MissingDownload)
+        );
+        library.rules =
+            serde_json::from_str(r#"[{"action": "allow", "os": {"name": "osx"}}]"#).unwrap();
+        assert_eq!(
+            library.unsupported_for(&linux),
+            Some(Unsupported::ExcludedByRules)
+        );
+    }
+
+    #[test]
+    fn maven_library() {
+        let library: Library = serde_json::from_str(
+            r#"{"name": "net.fabricmc:fabric-loader:0.13.3", "url": "https://maven.fabricmc.net/"}"#,
+        )
+        .unwrap();
+
+        let linux = OS::new("linux");
+        assert!(library.is_on_class_path(&linux));
+        let download = library.select_for(&linux).unwrap();
+        assert_eq!(
+            download.url,
+            "https://maven.fabricmc.net/net/fabricmc/fabric-loader/0.13.3/fabric-loader-0.13.3.jar"
+        );
+        assert!(download.sha1.is_none());
+
+        let request = DownloadRequest::new_library(download.into_owned(), PathBuf::new());
+        assert!(!request.has_hash());
+        assert_eq!(request.get_size(), None);
+
+        let dir = TempDir::new("maven");
+        assert!(matches!(
+            library.verify_at(&dir, &linux),
+            Err(Error::LibraryMissing)
+        ));
+        let path = library.path_at_for(&dir, &linux);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "jar").unwrap();
+        library.verify_at(&dir, &linux).unwrap();
+    }
+
+    #[test]
+    fn logging_path() {
+        let logging = |id: &str| LoggingConfig {
+            argument: "-Dlog4j.configurationFile=${path}".to_string(),
+            file: LoggingFile {
+                id: id.to_string(),
+                sha1: "da39a3ee5e6b4b0d3255bfef95601890afd80709".parse().unwrap(),
+                size: 0,
+                url: "https://launcher.example.com/client-1.12.xml".to_string(),
+            },
+            config_type: "log4j2-xml".to_string(),
+        };
+
+        assert_eq!(
+            logging("client-1.12.xml").arg_at("/assets").unwrap(),
+            "-Dlog4j.configurationFile=/assets/log_configs/client-1.12.xml"
+        );
+        for id in [
+            "../../options.txt",
+            "a/b.xml",
+            "a\\b.xml",
+            "C:b.xml",
+            "..",
+            ".",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    logging(id).path_at("/assets"),
+                    Err(Error::InvalidFileName(_))
+                ),
+                "{}",
+                id
+            );
+        }
+    }
 }
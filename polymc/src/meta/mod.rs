@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
 use std::fs::{File, OpenOptions};
 use std::io::Read;
 use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
 #[cfg(all(feature = "ctypes", target_family = "unix"))]
 use std::os::unix::io::{FromRawFd, RawFd};
@@ -13,19 +16,56 @@ use std::os::windows::io::{FromRawHandle, RawHandle};
 pub const ASSET_DEFAULT_URL: &'static str = "https://resources.download.minecraft.net";
 
 use libc::c_int;
-use log::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::*;
 
+use crate::cancel::CancellationToken;
+use crate::i18n::Phase;
 use crate::{Error, Result};
 
 mod asset;
+mod components;
+pub mod format;
 mod index;
+pub mod lwjgl;
 pub mod manifest;
+mod plan;
+mod range;
 mod request;
+mod servers;
+mod snapshot;
+mod stats;
+pub mod strict;
 
-use crate::meta::manifest::{Manifest, Requirement, OS};
+use crate::meta::lwjgl::LwjglOverride;
+use crate::meta::manifest::{Manifest, Requirement, Sha256Sum, OS};
 pub use asset::*;
+pub use components::*;
 pub use index::*;
+pub use plan::*;
+pub use range::*;
 pub use request::*;
+pub use servers::*;
+pub use snapshot::*;
+pub use stats::*;
+
+/// Check the size of a file against the size declared in the meta data, before hashing it.
+///
+/// Negative sizes are treated as unknown and not checked.
+pub(crate) fn verify_size(file: &File, expected: i64) -> Result<()> {
+    let expected = match u64::try_from(expected) {
+        Ok(expected) => expected,
+        Err(_) => return Ok(()),
+    };
+
+    let actual = file.metadata()?.len();
+    if actual != expected {
+        return Err(Error::LibraryInvalidSize { expected, actual });
+    }
+
+    Ok(())
+}
 
 pub struct MetaManager {
     pub library_path: String,
@@ -34,8 +74,32 @@ pub struct MetaManager {
     pub assets_url: Option<String>,
     wants: Vec<Wants>,
     extra_wants: Vec<Wants>,
+    missing: Vec<MissingComponent>,
     pub manifests: HashMap<String, Manifest>,
     pub index: Option<MetaIndex>,
+    /// Pinned hash the meta index has to match.
+    pub index_sha256: Option<Sha256Sum>,
+    /// Reject meta files with fields unknown to the library.
+    pub strict: bool,
+    /// Changes to the LWJGL component, see [`lwjgl`].
+    pub lwjgl: Option<LwjglOverride>,
+    /// Don't resolve the asset index and assets, e.g. for automated tests.
+    pub skip_assets: bool,
+    /// Only check that files exist instead of verifying their hashes.
+    pub skip_verify: bool,
+    /// Only check that assets exist instead of verifying their hashes.
+    pub skip_asset_verify: bool,
+    /// Asset index of the last resolution of the instance, see
+    /// [`MetaManager::set_previous_asset_index`].
+    pub previous_asset_index: Option<AssetIndexRef>,
+    /// The previous asset index once loaded, `None` inside if it is not in the store anymore.
+    previous_assets: Option<Option<Arc<AssetIndex>>>,
+    /// Directory of the snapshot to resolve from, see [`MetaSnapshot`].
+    pub snapshot: Option<PathBuf>,
+    /// What to do if a component is wanted in different versions.
+    pub version_policy: VersionPolicy,
+    cancel: CancellationToken,
+    stats: DownloadStats,
 }
 
 impl MetaManager {
@@ -48,11 +112,231 @@ impl MetaManager {
             assets_url: None,
             wants: Vec::new(),
             extra_wants: Vec::new(),
+            missing: Vec::new(),
             manifests: HashMap::new(),
             index: None,
+            index_sha256: None,
+            strict: false,
+            lwjgl: None,
+            skip_assets: false,
+            skip_verify: false,
+            skip_asset_verify: false,
+            previous_asset_index: None,
+            previous_assets: None,
+            snapshot: None,
+            version_policy: VersionPolicy::default(),
+            cancel: CancellationToken::new(),
+            stats: DownloadStats::default(),
+        }
+    }
+
+    /// Pin the sha256 of the meta index, an index not matching it is refused.
+    ///
+    /// This protects against tampered mirrors, as the index holds the hashes of all other files.
+    pub fn set_index_sha256(&mut self, hash: Option<Sha256Sum>) {
+        self.index_sha256 = hash;
+    }
+
+    /// Pin the sha256 of the meta index, given as hex string.
+    ///
+    /// # Safety
+    /// hash has to be a valid CStr.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_index_sha256"]
+    pub unsafe extern "C" fn set_index_sha256_c(&mut self, hash: *const c_char) -> c_int {
+        let hash = unsafe { CStr::from_ptr(hash) }.to_str();
+        match hash.map(str::parse) {
+            Ok(Ok(hash)) => {
+                self.set_index_sha256(Some(hash));
+                0
+            }
+            Ok(Err(e)) => -e.as_c_error(),
+            Err(_) => -libc::EINVAL,
         }
     }
 
+    /// Reject meta files containing fields unknown to the library, see [`strict`].
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_strict"]
+    pub extern "C" fn set_strict_c(&mut self, strict: bool) {
+        self.set_strict(strict)
+    }
+
+    /// Override the version or natives of the LWJGL component, see [`LwjglOverride`].
+    pub fn set_lwjgl_override(&mut self, lwjgl: Option<LwjglOverride>) {
+        self.lwjgl = lwjgl;
+    }
+
+    /// Override the LWJGL component, any argument may be null to keep the default.
+    ///
+    /// # Safety
+    /// The arguments have to be valid CStrs or null.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_lwjgl_override"]
+    pub unsafe extern "C" fn set_lwjgl_override_c(
+        &mut self,
+        version: *const c_char,
+        natives_arch: *const c_char,
+        natives_url: *const c_char,
+    ) -> c_int {
+        let string = |s: *const c_char| -> std::result::Result<Option<String>, ()> {
+            if s.is_null() {
+                return Ok(None);
+            }
+            let s = unsafe { CStr::from_ptr(s) }.to_str().map_err(drop)?;
+            Ok(Some(s.to_string()))
+        };
+        let (version, natives_arch, natives_url) =
+            match (string(version), string(natives_arch), string(natives_url)) {
+                (Ok(version), Ok(natives_arch), Ok(natives_url)) => {
+                    (version, natives_arch, natives_url)
+                }
+                _ => return -libc::EINVAL,
+            };
+
+        let default = LwjglOverride::default();
+        self.set_lwjgl_override(Some(LwjglOverride {
+            version,
+            natives_arch,
+            natives_url: natives_url.unwrap_or(default.natives_url),
+        }));
+        0
+    }
+
+    /// Don't resolve assets, for launches which don't need them (see
+    /// [`crate::instance::InstanceGameConfig::minimal_args`]).
+    pub fn set_skip_assets(&mut self, skip: bool) {
+        self.skip_assets = skip;
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_skip_assets"]
+    pub extern "C" fn set_skip_assets_c(&mut self, skip: bool) {
+        self.set_skip_assets(skip)
+    }
+
+    /// Only check that libraries and assets exist, which is faster than hashing them but does
+    /// not detect corrupted files.
+    pub fn set_skip_verify(&mut self, skip: bool) {
+        self.skip_verify = skip;
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_skip_verify"]
+    pub extern "C" fn set_skip_verify_c(&mut self, skip: bool) {
+        self.set_skip_verify(skip)
+    }
+
+    /// Only check that assets exist, libraries are still verified. Assets are the bulk of the
+    /// files, see [`crate::instance::AssetVerification`] for when to skip them.
+    pub fn set_skip_asset_verify(&mut self, skip: bool) {
+        self.skip_asset_verify = skip;
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_skip_asset_verify"]
+    pub extern "C" fn set_skip_asset_verify_c(&mut self, skip: bool) {
+        self.set_skip_asset_verify(skip)
+    }
+
+    /// Compare the asset index with the one the instance was resolved with before, e.g. the
+    /// one of the previous game version.
+    ///
+    /// If the objects of the previous index were verified, the ones both indexes share are
+    /// only checked to exist and the added ones are verified. The savings are reported in
+    /// [`DownloadStats::asset_diffs`].
+    pub fn set_previous_asset_index(&mut self, previous: Option<AssetIndexRef>) {
+        self.previous_asset_index = previous;
+        self.previous_assets = None;
+    }
+
+    /// Resolve only from the snapshot in `dir` instead of the meta server, see [`MetaSnapshot`].
+    ///
+    /// The meta data is loaded from the snapshot while searching, so searches only request
+    /// libraries and assets. Pins the hash of the snapshotted meta index, unless one is pinned
+    /// already.
+    pub fn set_snapshot<P: AsRef<Path>>(&mut self, dir: Option<P>) -> Result<()> {
+        let dir = match dir {
+            Some(dir) => dir.as_ref().to_path_buf(),
+            None => {
+                self.snapshot = None;
+                return Ok(());
+            }
+        };
+
+        let snapshot = MetaSnapshot::load(&dir)?;
+        if self.index_sha256.is_none() {
+            self.index_sha256 = Some(snapshot.index_sha256);
+        }
+        self.snapshot = Some(dir);
+        Ok(())
+    }
+
+    /// Resolve only from the snapshot in *dir*, or from the meta server again if *dir* is null.
+    ///
+    /// # Safety
+    /// dir has to be a valid CStr or null.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_snapshot"]
+    pub unsafe extern "C" fn set_snapshot_c(&mut self, dir: *const c_char) -> c_int {
+        let dir = if dir.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(dir) }.to_str() {
+                Ok(dir) => Some(dir),
+                Err(_) => return -libc::EINVAL,
+            }
+        };
+
+        match self.set_snapshot(dir) {
+            Ok(()) => 0,
+            Err(e) => -e.as_c_error(),
+        }
+    }
+
+    /// Set what to do if a component is wanted in different versions, e.g. if the user asks for
+    /// another version of the mod loader than a modpack pins.
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.version_policy = policy;
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_set_version_policy"]
+    pub extern "C" fn set_version_policy_c(&mut self, policy: VersionPolicy) {
+        self.set_version_policy(policy)
+    }
+
+    /// Get the token to cancel searches of this manager.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Replace the cancellation token, e.g. to search again after a cancelled search.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel = token;
+    }
+
+    /// Get the token to cancel searches of this manager.
+    /// The returned token has to be freed with `cancellation_token_free`.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_get_cancellation_token"]
+    pub extern "C" fn cancellation_token_c(&self) -> *mut CancellationToken {
+        Box::into_raw(Box::new(self.cancellation_token()))
+    }
+
     pub fn set_assets_url(&mut self, url: &str) {
         self.assets_url = Some(url.to_string())
     }
@@ -77,38 +361,167 @@ impl MetaManager {
         }
     }
 
+    /// Add a component to search for.
+    ///
+    /// Fails with [`Error::DuplicateUid`] if the component is wanted in another version already,
+    /// unless [`VersionPolicy::FirstRequested`] is set, which keeps the first version.
     pub fn search(&mut self, what: Wants) -> Result<()> {
+        if let Some(other) = self.wants.iter().find(|w| w.uid == what.uid) {
+            if other.version == what.version && other.range == what.range {
+                return Ok(());
+            }
+            let error = Error::DuplicateUid {
+                uid: what.uid.to_string(),
+                first: other.version.to_string(),
+                second: what.version.to_string(),
+            };
+            if self.version_policy == VersionPolicy::Fail {
+                return Err(error);
+            }
+            warn!("{}, using {}", error, other.version);
+            return Ok(());
+        }
         self.wants.push(what);
 
         Ok(())
     }
 
+    /// Add several components to search for at once, e.g. Minecraft, a mod loader and a newer
+    /// LWJGL.
+    ///
+    /// Like [`MetaManager::search`], but nothing is added if one of `wants` fails, so a batch
+    /// listing a component in different versions is rejected as a whole.
+    pub fn search_all(&mut self, wants: &[Wants]) -> Result<()> {
+        let previous = self.wants.clone();
+        for what in wants {
+            if let Err(e) = self.search(what.clone()) {
+                self.wants = previous;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start a search for the wanted component.
+    ///
+    /// # Safety
+    /// wants has to be a pointer returned by `meta_wants_new`, it is freed by this function.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_search"]
+    pub unsafe extern "C" fn search_c(&mut self, wants: *mut Wants) -> c_int {
+        if wants.is_null() {
+            return -libc::EINVAL;
+        }
+        let wants = unsafe { Box::from_raw(wants) };
+
+        if let Err(e) = self.search(*wants) {
+            -e.as_c_error()
+        } else {
+            0
+        }
+    }
+
+    /// Statistics of the resolution so far, see [`DownloadStats`].
+    pub fn stats(&self) -> &DownloadStats {
+        &self.stats
+    }
+
+    /// Statistics to report the downloads of the frontend to.
+    pub fn stats_mut(&mut self) -> &mut DownloadStats {
+        &mut self.stats
+    }
+
+    /// Whether the assets are verified, otherwise they are only checked to exist.
+    pub fn verifies_assets(&self) -> bool {
+        !self.skip_assets && !self.skip_verify && !self.skip_asset_verify
+    }
+
+    /// The previous asset index, if it differs from `current` and is still in the store.
+    fn previous_assets(&mut self, current: &AssetIndexInfo) -> Option<Arc<AssetIndex>> {
+        let reference = self.previous_asset_index.as_ref()?;
+        if reference.is_of(current) {
+            return None;
+        }
+        let assets_path = &self.assets_path;
+        self.previous_assets
+            .get_or_insert_with(|| reference.load_at(assets_path).map(Arc::new))
+            .clone()
+    }
+
     /// continue search
+    ///
+    /// Returns the files which have to be loaded before the search can continue, the search is
+    /// done once [`SearchResult::is_ready`] is true. Fails with [`Error::UnknownUid`] or
+    /// [`Error::UnknownVersion`] if a requested package does not exist.
     pub fn continue_search(&mut self) -> Result<SearchResult> {
+        let snapshot = match self.snapshot.clone() {
+            Some(snapshot) => snapshot,
+            None => return self.search_step(),
+        };
+
+        loop {
+            let search = self.search_step()?;
+            let mut loaded = false;
+            let meta = search.requests.iter().filter(|r| {
+                matches!(
+                    r.request_type(),
+                    FileType::MetaIndex | FileType::Index | FileType::Manifest
+                )
+            });
+            for request in meta {
+                match MetaSnapshot::read_at(&snapshot, request) {
+                    Ok(data) => self.load_data(&data, request.request_type())?,
+                    Err(e) if self.skip_optional(request, &e.to_string()) => {}
+                    Err(e) => return Err(e),
+                }
+                loaded = true;
+            }
+            if !loaded {
+                return Ok(search);
+            }
+        }
+    }
+
+    fn search_step(&mut self) -> Result<SearchResult> {
         if self.wants.is_empty() {
             return Err(Error::MetaNotFound);
         }
 
         if self.index.is_none() {
-            let index = DownloadRequest::new_meta_index(self.index_url());
             return Ok(SearchResult::new(
-                vec![index],
+                vec![self.index_request()],
                 &self.wants.get(0).ok_or(Error::MetaNotFound)?.uid,
             ));
         }
 
+        let _span = debug_span!("resolve", uid = %self.wants[0].uid).entered();
         let mut ret = Vec::new();
 
         for what in self.wants.clone() {
+            self.cancel.check()?;
             let mut requires = self.search_for(&what)?;
             ret.append(&mut requires);
         }
 
         for what in self.extra_wants.clone() {
-            let mut requires = self.search_for(&what)?;
-            ret.append(&mut requires);
+            self.cancel.check()?;
+            match self.search_for(&what) {
+                Ok(mut requires) => ret.append(&mut requires),
+                Err(e @ (Error::UnknownUid(_) | Error::UnknownVersion { .. })) if what.optional => {
+                    self.skip_want(&what, &e.to_string())
+                }
+                Err(e) => return Err(e),
+            }
         }
 
+        // Multiple components can require the same file
+        let mut seen = HashSet::new();
+        ret.retain(|r| seen.insert(r.get_path().unwrap_or_else(|| r.get_url()).to_string()));
+        // Keeps the order of the components within a priority
+        ret.sort_by_key(|r| r.priority());
+
         /*Ok(SearchResult::new(
             ret,
             &self.wants.get(0).ok_or(Error::MetaNotFound)?.uid,
@@ -117,6 +530,7 @@ impl MetaManager {
             requests: ret,
             manifests: self.manifests.clone(),
             uid: self.wants.get(0).ok_or(Error::MetaNotFound)?.uid.clone(),
+            stats: self.stats.clone(),
         })
     }
 
@@ -130,14 +544,10 @@ impl MetaManager {
             return Ok(ret);
         }
 
-        let version = package_index
-            .index
-            .as_ref()
-            .unwrap()
-            .find_version(&what.version)?;
+        let version = package_index.index.as_ref().unwrap().select_version(what)?;
 
-        self.extra_wants
-            .append(&mut self.check_requirements(&version.requires));
+        let wants = self.check_requirements(&what.uid, &version.version, &version.requires)?;
+        add_requirements(&mut self.extra_wants, &what.uid, &version.requires, wants);
 
         if version.manifest.is_none() {
             let download =
@@ -146,27 +556,87 @@ impl MetaManager {
             return Ok(ret);
         }
 
-        let manifest = version.manifest.as_ref().unwrap();
-
-        self.extra_wants
-            .append(&mut self.check_requirements(&manifest.requires));
+        let os = OS::get();
+        let mut manifest = version.manifest.clone().unwrap();
+        if let Some(lwjgl) = &self.lwjgl {
+            lwjgl.apply(&mut manifest, &os);
+        }
 
+        let wants =
+            self.check_requirements(&manifest.uid, &manifest.version, &manifest.requires)?;
+        add_requirements(
+            &mut self.extra_wants,
+            &manifest.uid,
+            &manifest.requires,
+            wants,
+        );
+
+        if let Some(other) = self.manifests.get(&manifest.uid) {
+            if other.version != manifest.version {
+                return Err(Error::DuplicateUid {
+                    uid: manifest.uid.to_string(),
+                    first: other.version.to_string(),
+                    second: manifest.version.to_string(),
+                });
+            }
+        }
         self.manifests
             .insert(manifest.uid.to_string(), manifest.clone());
+        self.check_constraints()?;
 
-        let os = OS::get();
-        let verify_result = unsafe { manifest.verify_caching_at(&self.library_path, &os)? };
+        let _span = debug_span!("verify", uid = %manifest.uid).entered();
+        let verify_start = Instant::now();
+        let verify_result = if self.skip_verify {
+            manifest.missing_at(&self.library_path, &os)
+        } else {
+            manifest.verify_caching_at(&self.library_path, &os, &self.cancel)?
+        };
+        let libraries = manifest
+            .libraries
+            .iter()
+            .filter(|lib| lib.required_for(&os))
+            .chain(&manifest.main_jar)
+            .count();
+        let mut cached_libraries = libraries.saturating_sub(verify_result.len());
         for (lib, _error) in &verify_result {
             let at = lib.path_at_for(&self.library_path, &os);
-            ret.push(DownloadRequest::new_library(
-                lib.select_for(&os).ok_or(Error::MetaNotFound)?.clone(),
-                at,
-            ))
+            let download = lib.select_for(&os).ok_or(Error::MetaNotFound)?.into_owned();
+            let main_jar = manifest.main_jar.as_ref().map(|j| &j.name) == Some(&lib.name);
+            let request = if main_jar {
+                DownloadRequest::new_main_jar(download, at)
+            } else if lib.natives.contains_key(&os.name) {
+                let extract = lib.extract.clone().unwrap_or_default();
+                DownloadRequest::new_natives(download, at, extract)
+            } else {
+                DownloadRequest::new_library(download, at)
+            };
+            self.stats.track_library(&request, &manifest.uid);
+            ret.push(request);
         }
 
-        if let Some(asset) = &manifest.asset_index {
+        if let Some(asset) = manifest.asset_index.as_ref().filter(|_| !self.skip_assets) {
             if let Some(asset_index) = &asset.cache {
-                let asset_results = unsafe { asset_index.verify_caching_at(&self.assets_path)? };
+                let previous = self.previous_assets(asset);
+                if let Some(previous) = &previous {
+                    self.stats
+                        .record_asset_diff(&manifest.uid, asset_index.diff(previous));
+                }
+                // Shared objects are only trusted if the previous resolution verified them
+                let verified = matches!(&self.previous_asset_index, Some(r) if r.verified);
+                let trusted = previous.as_deref().filter(|_| verified);
+                let asset_results = if self.skip_verify || self.skip_asset_verify {
+                    asset_index.missing_at(&self.assets_path)
+                } else {
+                    asset_index.verify_since_at(trusted, &self.assets_path, &self.cancel)?
+                };
+                self.stats.record_verified(
+                    &manifest.uid,
+                    FileType::Asset,
+                    asset_index
+                        .objects
+                        .len()
+                        .saturating_sub(asset_results.len()),
+                );
                 for (asset, _error) in asset_results {
                     ret.push(DownloadRequest::Asset {
                         url: format!(
@@ -192,47 +662,278 @@ impl MetaManager {
             }
         }
 
+        let logging = manifest.logging.as_ref().and_then(|l| l.client.as_ref());
+        if let Some(logging) = logging.filter(|_| !self.skip_assets) {
+            let at = &self.assets_path;
+            let path = logging.path_at(at)?;
+            let missing = if self.skip_verify {
+                !path.is_file()
+            } else {
+                logging.verify_at(at).is_err()
+            };
+            if missing {
+                let request = DownloadRequest::new_library(logging.download(), path);
+                self.stats.track_library(&request, &manifest.uid);
+                ret.push(request);
+            } else {
+                cached_libraries += 1;
+            }
+        }
+        self.stats
+            .record_verified(&manifest.uid, FileType::Library, cached_libraries);
+        self.stats.add_time(Phase::Verify, verify_start.elapsed());
+
         Ok(ret)
     }
 
-    pub fn check_requirements(&self, reqs: &[Requirement]) -> Vec<Wants> {
-        let mut ret = Vec::new();
+    /// Continue the search, see [`MetaManager::continue_search`].
+    ///
+    /// On success the result is stored in *result* and has to be freed with
+    /// `search_result_free`. The requests are downloaded by the caller, meta data is loaded with
+    /// one of the `meta_manager_load` functions before continuing the search again.
+    ///
+    /// # Safety
+    /// result has to be a valid pointer.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_continue_search"]
+    pub unsafe extern "C" fn continue_search_c(&mut self, result: *mut *mut SearchResult) -> c_int {
+        if result.is_null() {
+            return -libc::EINVAL;
+        }
+
+        match self.continue_search() {
+            Ok(search) => {
+                unsafe { *result = Box::into_raw(Box::new(search)) };
+                0
+            }
+            Err(e) => -e.as_c_error(),
+        }
+    }
+
+    /// Components to search for the requirements `reqs` of `uid` in `version`.
+    ///
+    /// Fails with [`Error::RequirementConflict`] if a requirement can't be satisfied by the
+    /// version a component is wanted in already, unless [`VersionPolicy::FirstRequested`] is set.
+    pub(crate) fn check_requirements(
+        &self,
+        uid: &str,
+        version: &str,
+        reqs: &[Requirement],
+    ) -> Result<Vec<Wants>> {
+        let mut ret: Vec<Wants> = Vec::new();
 
         for req in reqs {
-            for wants in &self.wants {
-                if wants.uid == req.uid {
-                    return ret;
+            let wanted = self
+                .wants
+                .iter()
+                .chain(&self.extra_wants)
+                .chain(&ret)
+                .find(|w| w.uid == req.uid);
+            if let Some(wanted) = wanted {
+                let pinned = wanted.range.is_none() && !wanted.version.is_empty();
+                let overridden = self.lwjgl.iter().any(|l| l.overrides(&req.uid));
+                if pinned && !overridden && !req.accepts(&wanted.version) {
+                    let error = Error::RequirementConflict {
+                        uid: req.uid.to_string(),
+                        version: wanted.version.to_string(),
+                        reason: format!(
+                            "{} needs {}",
+                            self.required_by(uid, version),
+                            constraint(req)
+                        ),
+                    };
+                    if self.version_policy == VersionPolicy::Fail {
+                        return Err(error);
+                    }
+                    warn!("{}", error);
                 }
+                continue;
             }
-            for wants in &self.extra_wants {
-                if wants.uid == req.uid {
-                    return ret;
-                }
+            if req.optional && self.is_missing(&req.uid) {
+                continue;
             }
             trace!("adding {:?} to extra_wants", req);
-            ret.push(req.clone().into())
+            ret.push(match &self.lwjgl {
+                Some(lwjgl) => lwjgl.wants_for(req),
+                None => req.clone().into(),
+            })
         }
 
-        ret
+        Ok(ret)
+    }
+
+    /// Check the resolved components against the requirements and conflicts of each other.
+    ///
+    /// Fails with [`Error::RequirementConflict`] naming the components which lead to the
+    /// conflict.
+    fn check_constraints(&self) -> Result<()> {
+        for manifest in self.manifests.values() {
+            for req in &manifest.requires {
+                let other = match self.manifests.get(&req.uid) {
+                    Some(other) => other,
+                    None => continue,
+                };
+                let overridden = self.lwjgl.iter().any(|l| l.overrides(&req.uid));
+                if overridden || req.accepts(&other.version) {
+                    continue;
+                }
+                let error = Error::RequirementConflict {
+                    uid: other.uid.to_string(),
+                    version: other.version.to_string(),
+                    reason: format!(
+                        "{} needs {}",
+                        self.required_by(&manifest.uid, &manifest.version),
+                        constraint(req)
+                    ),
+                };
+                if self.version_policy == VersionPolicy::Fail {
+                    return Err(error);
+                }
+                debug!("{}", error);
+            }
+
+            for conflict in &manifest.conflicts {
+                let other = self.manifests.get(&conflict.uid);
+                if let Some(other) = other.filter(|o| conflict.accepts(&o.version)) {
+                    return Err(Error::RequirementConflict {
+                        uid: other.uid.to_string(),
+                        version: other.version.to_string(),
+                        reason: format!(
+                            "it conflicts with {}",
+                            self.required_by(&manifest.uid, &manifest.version)
+                        ),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describe why `uid` in `version` is resolved, e.g. `fabric-api 0.1 required by modpack 1.0`.
+    fn required_by(&self, uid: &str, version: &str) -> String {
+        let mut chain = vec![format!("{} {}", uid, version)];
+        let mut seen: Vec<String> = Vec::new();
+        let mut next = Some(uid.to_string());
+        while let Some(uid) = next.take().filter(|u| !seen.contains(u)) {
+            if !seen.is_empty() {
+                match self.manifests.get(&uid) {
+                    Some(manifest) => chain.push(format!("{} {}", uid, manifest.version)),
+                    None => chain.push(uid.clone()),
+                }
+            }
+            next = self
+                .wants
+                .iter()
+                .chain(&self.extra_wants)
+                .find(|w| w.uid == uid)
+                .and_then(|w| w.required_by.clone());
+            seen.push(uid);
+        }
+
+        chain.join(" required by ")
+    }
+
+    /// Optional components which were skipped because they could not be resolved.
+    pub fn missing(&self) -> &[MissingComponent] {
+        &self.missing
+    }
+
+    fn is_missing(&self, uid: &str) -> bool {
+        self.missing.iter().any(|m| m.uid == uid)
+    }
+
+    /// Skip the component of the meta data `request` which failed to download for `reason`.
+    ///
+    /// Returns false if the component is required, the search can't continue without it then.
+    pub fn skip_optional(&mut self, request: &DownloadRequest, reason: &str) -> bool {
+        let uid = match request {
+            DownloadRequest::Index { uid, .. } | DownloadRequest::Manifest { uid, .. } => uid,
+            _ => return false,
+        };
+        match self
+            .extra_wants
+            .iter()
+            .find(|w| &w.uid == uid && w.optional)
+            .cloned()
+        {
+            Some(what) => {
+                self.skip_want(&what, reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Skip the optional component of *request* after its download failed, see
+    /// [`MetaManager::skip_optional`].
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "meta_manager_skip_optional"]
+    pub extern "C" fn skip_optional_c(&mut self, request: &DownloadRequest) -> bool {
+        self.skip_optional(request, "the download failed")
+    }
+
+    fn skip_want(&mut self, what: &Wants, reason: &str) {
+        warn!(
+            "skipping optional component {} {}: {}",
+            what.uid, what.version, reason
+        );
+        self.extra_wants.retain(|w| w.uid != what.uid);
+        self.missing.push(MissingComponent {
+            uid: what.uid.clone(),
+            version: what.version.clone(),
+            reason: reason.to_string(),
+        });
     }
 
     pub fn index_url(&self) -> String {
         format!("{}/index.json", self.base_url)
     }
 
+    /// Request to download the meta index, with the pinned hash if one is set.
+    pub fn index_request(&self) -> DownloadRequest {
+        DownloadRequest::new_meta_index(self.index_url(), self.index_sha256.clone())
+    }
+
+    /// Find packages in the meta index by name or uid, ignoring case.
+    ///
+    /// Fails with [`Error::MetaNotFound`] if the meta index is not loaded yet.
+    pub fn find_packages(&self, query: &str) -> Result<Vec<&MetaIndexPackage>> {
+        Ok(self
+            .index
+            .as_ref()
+            .ok_or(Error::MetaNotFound)?
+            .find_packages(query))
+    }
+
     pub fn load_meta_index(&mut self, index: MetaIndex) -> Result<()> {
         trace!("loaded meta index");
         self.index = Some(index);
         Ok(())
     }
 
+    /// Verify the meta index against the pinned hash and load it.
+    pub fn load_meta_index_data(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(hash) = &self.index_sha256 {
+            let digest = ring::digest::digest(&ring::digest::SHA256, data);
+            if digest.as_ref() != hash.as_ref() {
+                return Err(Error::MetaIndexInvalidHash);
+            }
+        }
+
+        let index = self.parse(data, FileType::MetaIndex)?;
+        self.load_meta_index(index)
+    }
+
     pub fn load_index(&mut self, package: PackageIndex) -> Result<()> {
         trace!("loaded index: {}", package.uid);
 
         let index = self
             .index
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .get_uid_mut(&package.uid)?;
 
         index.index = Some(package);
@@ -245,12 +946,12 @@ impl MetaManager {
         let index = self
             .index
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .get_uid_mut(&manifest.uid)?;
         let package = index
             .index
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .find_version_mut(&manifest.version)?;
 
         package.manifest = Some(manifest);
@@ -268,15 +969,15 @@ impl MetaManager {
         let index = self
             .index
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .get_uid_mut(uid)?
             .index
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .find_version_mut(version)?
             .manifest
             .as_mut()
-            .ok_or(Error::MetaNotFound)?
+            .ok_or(Error::PendingDownloads)?
             .asset_index
             .as_mut()
             .ok_or(Error::MetaNotFound)?;
@@ -296,64 +997,81 @@ impl MetaManager {
         self.load_asset_index(uid, version, index)
     }
 
-    pub fn load(&mut self, data: &str, file_type: FileType) -> Result<()> {
-        debug!("Loading(str) {:?}", file_type);
-        match file_type {
-            FileType::MetaIndex => {
-                let index = data.parse()?;
-                self.load_meta_index(index)
-            }
-            FileType::Index => {
-                let package = data.parse()?;
-                self.load_index(package)
-            }
-            FileType::Manifest => {
-                let manifest = data.parse()?;
-                self.load_manifest(manifest)
+    /// Load an asset index into every manifest referencing it.
+    ///
+    /// Manifests are matched by the sha1 they declare for their asset index.
+    pub fn load_asset_index_data(&mut self, data: &[u8]) -> Result<()> {
+        let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, data);
+        let asset_index: AssetIndex = self.parse(data, FileType::AssetIndex)?;
+
+        let mut found = false;
+        let packages = &mut self.index.as_mut().ok_or(Error::PendingDownloads)?.packages;
+        for package in packages.iter_mut().filter_map(|p| p.index.as_mut()) {
+            for info in package
+                .versions
+                .iter_mut()
+                .filter_map(|v| v.manifest.as_mut())
+                .filter_map(|m| m.asset_index.as_mut())
+                .filter(|i| i.sha1.as_ref() == digest.as_ref())
+            {
+                trace!("loaded asset index: {}", info.id);
+                info.cache = Some(asset_index.clone());
+                found = true;
             }
-            _ => Err(Error::MetaNotFound),
         }
+
+        if found {
+            Ok(())
+        } else {
+            Err(Error::MetaNotFound)
+        }
+    }
+
+    pub fn load(&mut self, data: &str, file_type: FileType) -> Result<()> {
+        debug!("Loading(str) {:?}", file_type);
+        self.load_data(data.as_bytes(), file_type)
     }
 
     /// The user has to ensure the hash does match
     pub fn load_reader<R: Read>(&mut self, reader: &mut R, file_type: FileType) -> Result<()> {
         debug!("Loading {:?}", file_type);
-        match file_type {
-            FileType::MetaIndex => {
-                let index = MetaIndex::from_reader(reader)?;
-                self.load_meta_index(index)
-            }
-            FileType::Index => {
-                let package = PackageIndex::from_reader(reader)?;
-                self.load_index(package)
-            }
-            FileType::Manifest => {
-                let manifest = Manifest::from_reader(reader)?;
-                self.load_manifest(manifest)
-            }
-            _ => Err(Error::MetaNotFound),
-        }
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.load_data(&data, file_type)
     }
 
     pub fn load_data(&mut self, data: &[u8], file_type: FileType) -> Result<()> {
         debug!("Loading(data) {:?}", file_type);
         match file_type {
-            FileType::MetaIndex => {
-                let index = MetaIndex::from_data(data)?;
-                self.load_meta_index(index)
-            }
+            FileType::MetaIndex => self.load_meta_index_data(data),
             FileType::Index => {
-                let package = PackageIndex::from_data(data)?;
+                let package = self.parse(data, file_type)?;
                 self.load_index(package)
             }
             FileType::Manifest => {
-                let manifest = Manifest::from_data(data)?;
+                let manifest = self.parse(data, file_type)?;
                 self.load_manifest(manifest)
             }
+            FileType::AssetIndex => self.load_asset_index_data(data),
             _ => Err(Error::MetaNotFound),
         }
     }
 
+    /// Parse a meta file after checking its format version, rejecting unknown fields in strict
+    /// mode.
+    fn parse<T: DeserializeOwned + Serialize>(
+        &self,
+        data: &[u8],
+        file_type: FileType,
+    ) -> Result<T> {
+        format::check_format_version(data, file_type)?;
+        if self.strict {
+            strict::parse_strict(data)
+        } else {
+            Ok(serde_json::from_slice(data)?)
+        }
+    }
+
     pub fn load_file(&mut self, file: &str, file_type: FileType) -> Result<()> {
         debug!("Loading file {file} for type {file_type}");
         let mut file = OpenOptions::new().read(true).open(file)?;
@@ -502,11 +1220,64 @@ impl MetaManager {
     }
 }
 
+/// Versions accepted by `req`, e.g. `=1.0` or `>=0.14 <0.15`.
+fn constraint(req: &Requirement) -> String {
+    let equals = req.equals.as_ref().map(|e| format!("={}", e));
+    let range = req.range.as_ref().map(ToString::to_string);
+    let parts: Vec<String> = equals.into_iter().chain(range).collect();
+    format!("{} {}", req.uid, parts.join(" "))
+}
+
+/// Add `wants` for the requirements `reqs` of `uid` to `extra_wants`.
+///
+/// A component wanted optionally before becomes required if one of `reqs` requires it.
+fn add_requirements(
+    extra_wants: &mut Vec<Wants>,
+    uid: &str,
+    reqs: &[Requirement],
+    mut wants: Vec<Wants>,
+) {
+    for req in reqs.iter().filter(|r| !r.optional) {
+        for what in extra_wants.iter_mut().filter(|w| w.uid == req.uid) {
+            what.optional = false;
+        }
+    }
+    for what in &mut wants {
+        what.required_by = Some(uid.to_string());
+    }
+    extra_wants.append(&mut wants);
+}
+
+/// What to do if a component is wanted in different versions, by the frontend or by the
+/// requirements of other components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum VersionPolicy {
+    /// Fail the search before anything is downloaded.
+    Fail,
+    /// Use the version requested first, the frontend's wants come before requirements.
+    FirstRequested,
+}
+
+// `#[default]` on variants needs a newer Rust than the MSRV
+#[allow(clippy::derivable_impls)]
+impl Default for VersionPolicy {
+    fn default() -> Self {
+        Self::Fail
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Wants {
     pub uid: String,
     pub version: String,
     pub release_type: Option<String>,
+    /// Wanted by an optional [`Requirement`], see [`MetaManager::missing`].
+    pub optional: bool,
+    /// Versions the component has to be in, see [`PackageIndex::select_version`].
+    pub range: Option<VersionRange>,
+    /// Uid of the component requiring this one, to describe conflicts.
+    pub required_by: Option<String>,
 }
 
 impl Wants {
@@ -515,6 +1286,9 @@ impl Wants {
             uid: uid.to_string(),
             version: version.to_string(),
             release_type: None,
+            optional: false,
+            range: None,
+            required_by: None,
         }
     }
 
@@ -547,8 +1321,11 @@ impl From<Requirement> for Wants {
     fn from(req: Requirement) -> Self {
         Self {
             uid: req.uid,
-            version: req.suggests,
+            version: req.equals.unwrap_or(req.suggests),
             release_type: None,
+            optional: req.optional,
+            range: req.range,
+            required_by: None,
         }
     }
 }
@@ -557,6 +1334,8 @@ pub struct SearchResult {
     pub requests: Vec<DownloadRequest>,
     pub manifests: HashMap<String, Manifest>,
     pub uid: String,
+    /// Statistics of the resolution until this result, see [`MetaManager::stats`].
+    pub stats: DownloadStats,
 }
 
 impl SearchResult {
@@ -565,6 +1344,7 @@ impl SearchResult {
             requests,
             manifests: HashMap::new(),
             uid: uid.to_string(),
+            stats: DownloadStats::default(),
         }
     }
 
@@ -572,4 +1352,626 @@ impl SearchResult {
     pub extern "C" fn is_ready(&self) -> bool {
         self.requests.is_empty()
     }
+
+    /// Whether the game can already start, with only assets left to download.
+    ///
+    /// The game loads assets once it needs them, so they can be downloaded while it runs. This
+    /// does not hold for old versions expecting their assets copied by name, see
+    /// [`AssetIndex::is_virtual`] and [`AssetIndex::map_to_resources`].
+    #[export_name = "search_result_is_launchable"]
+    pub extern "C" fn is_launchable(&self) -> bool {
+        self.requests.iter().all(|r| match r {
+            DownloadRequest::Asset { uid, .. } => {
+                let index = self
+                    .manifests
+                    .get(uid)
+                    .and_then(|m| m.asset_index.as_ref())
+                    .and_then(|i| i.cache.as_ref());
+                matches!(index, Some(index) if !index.is_virtual && !index.map_to_resources)
+            }
+            _ => false,
+        })
+    }
+
+    /// Total size in bytes of all pending requests.
+    /// Requests without a known size (like meta data) are not counted.
+    #[export_name = "search_result_total_download_size"]
+    pub extern "C" fn total_download_size(&self) -> u64 {
+        self.requests.iter().map(|r| r.size()).sum()
+    }
+
+    /// Number of pending requests.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "search_result_len"]
+    pub extern "C" fn len_c(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Get the pending request at *index*, `NULL` if out of range.
+    /// The request is owned by the search result and valid until the request is completed or
+    /// the result is freed.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "search_result_get"]
+    pub extern "C" fn get_c(&self, index: usize) -> *const DownloadRequest {
+        self.requests
+            .get(index)
+            .map(|r| r as *const _)
+            .unwrap_or(core::ptr::null())
+    }
+
+    /// Remove the request at *index* after the caller downloaded it, following requests move
+    /// down by one. Once all requests are completed the result is ready.
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "search_result_complete"]
+    pub extern "C" fn complete_c(&mut self, index: usize) -> c_int {
+        if index >= self.requests.len() {
+            return -libc::EINVAL;
+        }
+
+        self.requests.remove(index);
+        0
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[doc(hidden)]
+    #[export_name = "search_result_free"]
+    pub unsafe extern "C" fn free(v: *mut Self) {
+        let _ = unsafe { Box::from_raw(v) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn package_index(uid: &str) -> String {
+        format!(
+            r#"{{"formatVersion": 1, "name": "{uid}", "uid": "{uid}", "versions": [
+                {{"releaseTime": "2022-01-01T00:00:00+00:00", "sha256": "{HASH}", "type": "release", "version": "1.0"}}
+            ]}}"#
+        )
+    }
+
+    fn manifest(uid: &str, requires: &str) -> String {
+        format!(
+            r#"{{"name": "{uid}", "uid": "{uid}", "version": "1.0", "order": 0,
+                "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+                "requires": [{requires}],
+                "libraries": [{{
+                    "name": "org.example:shared:1.0",
+                    "downloads": {{"artifact": {{
+                        "sha1": "0000000000000000000000000000000000000000",
+                        "size": 1,
+                        "url": "https://example.com/shared-1.0.jar"
+                    }}}}
+                }}]
+            }}"#
+        )
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let path = TempDir::new("size");
+        std::fs::write(&path, b"abc").unwrap();
+        let file = File::open(&path).unwrap();
+
+        verify_size(&file, 3).unwrap();
+        verify_size(&file, -1).unwrap();
+        assert!(matches!(
+            verify_size(&file, 4),
+            Err(Error::LibraryInvalidSize {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+
+    #[cfg(feature = "ctypes")]
+    #[test]
+    fn search_result_c() {
+        let mut manager = MetaManager::new("", "", "https://example.com");
+        let wants = Box::into_raw(Box::new(Wants::new("net.minecraft", "1.18.2")));
+        assert_eq!(unsafe { manager.search_c(wants) }, 0);
+
+        let mut search = core::ptr::null_mut();
+        assert_eq!(unsafe { manager.continue_search_c(&mut search) }, 0);
+        let result = unsafe { &mut *search };
+        assert_eq!(result.len_c(), 1);
+        assert!(result.get_c(1).is_null());
+        let request = unsafe { &*result.get_c(0) };
+        assert_eq!(request.get_url(), manager.index_url());
+
+        assert_eq!(result.complete_c(1), -libc::EINVAL);
+        assert_eq!(result.complete_c(0), 0);
+        assert!(result.is_ready());
+        unsafe { SearchResult::free(search) };
+    }
+
+    #[test]
+    fn find_packages() {
+        let mut manager = MetaManager::new("", "", "https://example.com");
+        assert!(matches!(
+            manager.find_packages("fabric"),
+            Err(Error::MetaNotFound)
+        ));
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "Fabric Loader", "sha256": "{HASH}", "uid": "net.fabricmc.fabric-loader"}},
+                {{"name": "Minecraft", "sha256": "{HASH}", "uid": "net.minecraft"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+
+        let found = manager.find_packages("FABRIC").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].uid, "net.fabricmc.fabric-loader");
+        assert_eq!(manager.find_packages("net.").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_uid() {
+        let lib_dir = TempDir::new("dup");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        assert!(matches!(
+            manager.search(Wants::new("a", "2.0")),
+            Err(Error::DuplicateUid { .. })
+        ));
+
+        // The version wanted first is used, without downloading the other manifest
+        manager.set_version_policy(VersionPolicy::FirstRequested);
+        manager.search(Wants::new("a", "2.0")).unwrap();
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [{{"name": "a", "sha256": "{HASH}", "uid": "a"}}]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        let package_index = format!(
+            r#"{{"formatVersion": 1, "name": "a", "uid": "a", "versions": [
+                {{"releaseTime": "2022-01-01T00:00:00+00:00", "sha256": "{HASH}", "type": "release", "version": "1.0"}},
+                {{"releaseTime": "2022-01-01T00:00:00+00:00", "sha256": "{HASH}", "type": "release", "version": "2.0"}}
+            ]}}"#
+        );
+        manager.load(&package_index, FileType::Index).unwrap();
+        let search = manager.continue_search().unwrap();
+        assert_eq!(search.requests.len(), 1);
+        assert!(search.requests[0].get_url().ends_with("/a/1.0.json"));
+        manager
+            .load(&manifest("a", ""), FileType::Manifest)
+            .unwrap();
+        let search = manager.continue_search().unwrap();
+        assert_eq!(search.manifests["a"].version, "1.0");
+    }
+
+    #[test]
+    fn requirement_conflict() {
+        let lib_dir = TempDir::new("range");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        manager.search(Wants::new("c", "1.0")).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "a", "sha256": "{HASH}", "uid": "a"}},
+                {{"name": "b", "sha256": "{HASH}", "uid": "b"}},
+                {{"name": "c", "sha256": "{HASH}", "uid": "c"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        manager.load(&package_index("a"), FileType::Index).unwrap();
+        manager.load(&package_index("c"), FileType::Index).unwrap();
+        let b_index = format!(
+            r#"{{"formatVersion": 1, "name": "b", "uid": "b", "versions": [
+                {{"releaseTime": "2022-01-01T00:00:00+00:00", "sha256": "{HASH}", "type": "release", "version": "1.0"}},
+                {{"releaseTime": "2022-02-01T00:00:00+00:00", "sha256": "{HASH}", "type": "release", "version": "1.1"}}
+            ]}}"#
+        );
+        manager.load(&b_index, FileType::Index).unwrap();
+        manager
+            .load(
+                &manifest("a", r#"{"uid": "b", "suggests": "1.0", "range": ">=1.1"}"#),
+                FileType::Manifest,
+            )
+            .unwrap();
+        manager
+            .load(
+                &manifest("b", r#"{"uid": "c", "equals": "2.0", "suggests": "2.0"}"#)
+                    .replace(r#""version": "1.0""#, r#""version": "1.1""#),
+                FileType::Manifest,
+            )
+            .unwrap();
+        manager
+            .load(&manifest("c", ""), FileType::Manifest)
+            .unwrap();
+
+        match manager.continue_search() {
+            Err(Error::RequirementConflict {
+                uid,
+                version,
+                reason,
+            }) => {
+                assert_eq!((uid.as_str(), version.as_str()), ("c", "1.0"));
+                assert_eq!(reason, "b 1.1 required by a 1.0 needs c =2.0");
+            }
+            _ => panic!("the requirement of b on c is not checked"),
+        }
+    }
+
+    #[test]
+    fn deduplicate_requests() {
+        let lib_dir = TempDir::new("dedup");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        manager.search(Wants::new("b", "1.0")).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "a", "sha256": "{HASH}", "uid": "a"}},
+                {{"name": "b", "sha256": "{HASH}", "uid": "b"}},
+                {{"name": "c", "sha256": "{HASH}", "uid": "c"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        for uid in ["a", "b", "c"] {
+            manager.load(&package_index(uid), FileType::Index).unwrap();
+        }
+
+        let requires = r#"{"uid": "c", "suggests": "1.0"}"#;
+        manager
+            .load(&manifest("a", requires), FileType::Manifest)
+            .unwrap();
+        manager
+            .load(&manifest("b", requires), FileType::Manifest)
+            .unwrap();
+        manager
+            .load(&manifest("c", ""), FileType::Manifest)
+            .unwrap();
+
+        // Let the requirements of a and b be discovered
+        manager.continue_search().unwrap();
+        let search = manager.continue_search().unwrap();
+
+        assert_eq!(manager.extra_wants.len(), 1);
+        assert_eq!(search.requests.len(), 1);
+        assert!(search.requests[0].is_library());
+    }
+
+    #[test]
+    fn strict_manager() {
+        let lib_dir = TempDir::new("strict");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+        manager.set_strict(true);
+        manager.search(Wants::new("a", "1.0")).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [{{"name": "a", "sha256": "{HASH}", "uid": "a"}}]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        manager.load(&package_index("a"), FileType::Index).unwrap();
+
+        let unknown = manifest("a", "").replacen('{', r#"{"+extension": 1, "#, 1);
+        assert!(matches!(
+            manager.load(&unknown, FileType::Manifest),
+            Err(Error::MetaUnknownFields(fields)) if fields == ["+extension"]
+        ));
+        manager
+            .load(&manifest("a", ""), FileType::Manifest)
+            .unwrap();
+
+        // Without strict mode unknown fields are ignored
+        manager.set_strict(false);
+        manager.load(&unknown, FileType::Manifest).unwrap();
+    }
+
+    #[test]
+    fn download_priority() {
+        let lib_dir = TempDir::new("priority");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+        manager.search(Wants::new("a", "1.0")).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [{{"name": "a", "sha256": "{HASH}", "uid": "a"}}]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        manager.load(&package_index("a"), FileType::Index).unwrap();
+        let mut manifest: serde_json::Value = serde_json::from_str(&manifest("a", "")).unwrap();
+        manifest["assetIndex"] = serde_json::json!({
+            "id": "1.0",
+            "sha1": "0000000000000000000000000000000000000000",
+            "size": 1,
+            "totalSize": 1,
+            "url": "https://example.com/assets.json"
+        });
+        manifest["mainJar"] = serde_json::json!({
+            "name": "org.example:client:1.0",
+            "downloads": {"artifact": {
+                "sha1": "0000000000000000000000000000000000000000",
+                "size": 1,
+                "url": "https://example.com/client-1.0.jar"
+            }}
+        });
+        manager
+            .load(&manifest.to_string(), FileType::Manifest)
+            .unwrap();
+
+        let search = manager.continue_search().unwrap();
+        let priorities: Vec<_> = search.requests.iter().map(|r| r.priority()).collect();
+        assert_eq!(
+            priorities,
+            [
+                DownloadPriority::MainJar,
+                DownloadPriority::Library,
+                DownloadPriority::AssetIndex
+            ]
+        );
+    }
+
+    #[test]
+    fn launch_with_pending_assets() {
+        let mut manifest: Manifest = serde_json::from_str(&manifest("a", "")).unwrap();
+        let info = r#"{"id": "1.0", "sha1": "0000000000000000000000000000000000000000",
+            "size": 1, "totalSize": 1, "url": "https://example.com/assets.json"}"#;
+        manifest.asset_index = Some(serde_json::from_str(info).unwrap());
+        let index: AssetIndex = serde_json::from_str(
+            r#"{"objects": {"icons/icon_16x16.png": {
+                "hash": "0000000000000000000000000000000000000000", "size": 1
+            }}}"#,
+        )
+        .unwrap();
+        let asset = index.objects.values().next().unwrap().clone();
+
+        let mut search = SearchResult::new(
+            vec![DownloadRequest::Asset {
+                asset,
+                uid: "a".to_string(),
+                url: "https://example.com/asset".to_string(),
+                path: "asset".to_string(),
+            }],
+            "a",
+        );
+        // The asset index is not loaded yet
+        search.manifests.insert("a".to_string(), manifest);
+        assert!(!search.is_launchable());
+
+        let info = search.manifests.get_mut("a").unwrap().asset_index.as_mut();
+        info.unwrap().cache = Some(index);
+        assert!(search.is_launchable());
+
+        let info = search.manifests.get_mut("a").unwrap().asset_index.as_mut();
+        info.unwrap().cache.as_mut().unwrap().map_to_resources = true;
+        assert!(!search.is_launchable());
+    }
+
+    #[test]
+    fn asset_diff() {
+        let dir = TempDir::new("asset-diff");
+        let at = dir.display().to_string();
+        // sha1 of "shared", "old" and "new"
+        let previous = r#"{"objects": {
+            "a.ogg": {"hash": "d18aac96b905b4b3c839891b7a91c9414149514c", "size": 6},
+            "b.ogg": {"hash": "c00dbbc9dadfbe1e232e93a729dd4752fade0abf", "size": 3}
+        }}"#;
+        let index: AssetIndex = r#"{"objects": {
+            "a.ogg": {"hash": "d18aac96b905b4b3c839891b7a91c9414149514c", "size": 6},
+            "c.ogg": {"hash": "c2a6b03f190dfb2b4aa91f8af8d477a9bc3401dc", "size": 3}
+        }}"#
+        .parse()
+        .unwrap();
+        std::fs::create_dir_all(dir.join("indexes")).unwrap();
+        std::fs::write(dir.join("indexes/1.20.json"), previous).unwrap();
+        let digest =
+            ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, previous.as_bytes());
+        let reference = AssetIndexRef {
+            id: "1.20".to_string(),
+            sha1: hex::encode(digest).parse().unwrap(),
+            verified: true,
+        };
+        let previous = reference.load_at(&at).unwrap();
+        // References recorded before it was known are not trusted
+        let json = serde_json::json!({"id": "1.20", "sha1": reference.sha1});
+        assert!(
+            !serde_json::from_value::<AssetIndexRef>(json)
+                .unwrap()
+                .verified
+        );
+
+        let diff = index.diff(&previous);
+        assert_eq!((diff.kept, diff.kept_bytes), (1, 6));
+        assert_eq!((diff.added, diff.added_bytes), (1, 3));
+        assert_eq!(diff.removed, 1);
+
+        // Objects of the previous index are only checked to exist
+        let cancel = CancellationToken::new();
+        for asset in index.objects.values() {
+            std::fs::create_dir_all(Path::new(&asset.path_at(&at)).parent().unwrap()).unwrap();
+            std::fs::write(asset.path_at(&at), "broken").unwrap();
+        }
+        let failed = index
+            .verify_since_at(Some(&previous), &at, &cancel)
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0.size, 3);
+        assert_eq!(index.verify_caching_at(&at, &cancel).unwrap().len(), 2);
+
+        // An index replaced under the same id is not compared with
+        std::fs::write(dir.join("indexes/1.20.json"), r#"{"objects": {}}"#).unwrap();
+        assert!(reference.load_at(&at).is_none());
+    }
+
+    #[test]
+    fn plan() {
+        let lib_dir = TempDir::new("plan");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+
+        let plan = manager.plan(Wants::new("a", "1.0")).unwrap();
+        assert!(matches!(plan.pending[0], DownloadRequest::MetaIndex { .. }));
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "a", "sha256": "{HASH}", "uid": "a"}},
+                {{"name": "b", "sha256": "{HASH}", "uid": "b"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        for uid in ["a", "b"] {
+            manager.load(&package_index(uid), FileType::Index).unwrap();
+        }
+        let requires = r#"{"uid": "b", "suggests": "1.0"}"#;
+        manager
+            .load(&manifest("a", requires), FileType::Manifest)
+            .unwrap();
+
+        let plan = manager.plan(Wants::new("a", "1.0")).unwrap();
+        assert!(!plan.is_complete());
+        assert_eq!(plan.pending.len(), 1);
+
+        manager
+            .load(&manifest("b", ""), FileType::Manifest)
+            .unwrap();
+        let plan = manager.plan(Wants::new("a", "1.0")).unwrap();
+        assert!(plan.is_complete());
+        assert_eq!(plan.components.len(), 2);
+        assert_eq!(plan.requirements.len(), 1);
+        assert_eq!(plan.requirements[0].0, "a");
+        assert_eq!(plan.libraries.len(), 1);
+        assert_eq!(plan.missing_libraries_size(), 1);
+        assert!(!std::path::Path::new(&lib_dir).exists());
+    }
+
+    #[test]
+    fn plan_all() {
+        let lib_dir = TempDir::new("plan-all");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "a", "sha256": "{HASH}", "uid": "a"}},
+                {{"name": "b", "sha256": "{HASH}", "uid": "b"}},
+                {{"name": "c", "sha256": "{HASH}", "uid": "c"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        for uid in ["a", "b", "c"] {
+            manager.load(&package_index(uid), FileType::Index).unwrap();
+        }
+        let suggests = r#"{"uid": "b", "suggests": "2.0"}"#;
+        manager
+            .load(&manifest("a", suggests), FileType::Manifest)
+            .unwrap();
+        manager
+            .load(&manifest("b", ""), FileType::Manifest)
+            .unwrap();
+        let equals = r#"{"uid": "b", "equals": "2.0"}"#;
+        manager
+            .load(&manifest("c", equals), FileType::Manifest)
+            .unwrap();
+
+        // The wanted version of b wins over the suggestion of a
+        let wants = [Wants::new("a", "1.0"), Wants::new("b", "1.0")];
+        let plan = manager.plan_all(&wants).unwrap();
+        assert!(plan.is_complete());
+        assert_eq!(
+            plan.components,
+            [
+                ("a".to_string(), "1.0".to_string()),
+                ("b".to_string(), "1.0".to_string())
+            ]
+        );
+
+        let wants = [Wants::new("c", "1.0"), Wants::new("b", "1.0")];
+        assert!(matches!(
+            manager.plan_all(&wants),
+            Err(Error::RequirementConflict { .. })
+        ));
+
+        let wants = [Wants::new("a", "1.0"), Wants::new("a", "2.0")];
+        assert!(matches!(
+            manager.plan_all(&wants),
+            Err(Error::DuplicateUid { .. })
+        ));
+        manager.search(Wants::new("b", "1.0")).unwrap();
+        assert!(manager.search_all(&wants).is_err());
+        assert_eq!(manager.wants.len(), 1);
+    }
+
+    #[test]
+    fn optional_requirements() {
+        let lib_dir = TempDir::new("optional");
+        let lib_dir = lib_dir.display().to_string();
+        let mut manager = MetaManager::new(&lib_dir, &lib_dir, "https://example.com");
+        manager.search(Wants::new("a", "1.0")).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [
+                {{"name": "a", "sha256": "{HASH}", "uid": "a"}},
+                {{"name": "b", "sha256": "{HASH}", "uid": "b"}}
+            ]}}"#
+        );
+        manager.load(&index, FileType::MetaIndex).unwrap();
+        for uid in ["a", "b"] {
+            manager.load(&package_index(uid), FileType::Index).unwrap();
+        }
+        // b is on the meta server but its manifest fails to download, c is not there at all
+        let requires = r#"{"uid": "b", "suggests": "1.0", "optional": true},
+            {"uid": "c", "suggests": "1.0", "optional": true}"#;
+        manager
+            .load(&manifest("a", requires), FileType::Manifest)
+            .unwrap();
+
+        let search = manager.continue_search().unwrap();
+        assert!(!manager.skip_optional(&manager.index_request(), "404 Not Found"));
+        let manifest_b = search
+            .requests
+            .iter()
+            .find(|r| matches!(r, DownloadRequest::Manifest { uid, .. } if uid == "b"))
+            .unwrap();
+        assert!(manager.skip_optional(manifest_b, "404 Not Found"));
+
+        let search = manager.continue_search().unwrap();
+        assert_eq!(search.requests.len(), 1);
+        assert!(search.requests[0].is_library());
+        let missing: Vec<_> = manager.missing().iter().map(|m| m.uid.as_str()).collect();
+        assert_eq!(missing, ["c", "b"]);
+
+        let plan = manager.plan(Wants::new("a", "1.0")).unwrap();
+        assert!(plan.is_complete());
+        assert_eq!(plan.components.len(), 1);
+        assert_eq!(plan.missing.len(), 2);
+        assert_eq!(plan.missing[0].reason, "404 Not Found");
+
+        // A required component is never skipped
+        let requires = r#"{"uid": "c", "suggests": "1.0"}"#;
+        manager
+            .load(&manifest("a", requires), FileType::Manifest)
+            .unwrap();
+        assert!(matches!(
+            manager.plan(Wants::new("a", "1.0")),
+            Err(Error::UnknownUid(_))
+        ));
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<MetaManager>();
+        assert_send_sync::<SearchResult>();
+        assert_send_sync::<DownloadRequest>();
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use crate::meta::manifest::{Library, Requirement, OS};
+use crate::meta::{Asset, AssetIndexInfo, DownloadRequest, MetaManager, VersionPolicy, Wants};
+use crate::{Error, Result};
+
+/// Everything needed to install a component, as resolved by [`MetaManager::plan`].
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionPlan {
+    /// Components in the order they were resolved, as `(uid, version)`.
+    pub components: Vec<(String, String)>,
+    /// Requirements between the components, as `(uid of the requiring component, requirement)`.
+    pub requirements: Vec<(String, Requirement)>,
+    pub libraries: Vec<PlannedLibrary>,
+    pub asset_indexes: Vec<AssetIndexInfo>,
+    /// Assets of all loaded asset indexes.
+    pub assets: Vec<Asset>,
+    /// Meta data which is not loaded yet, the plan is incomplete until it is.
+    pub pending: Vec<DownloadRequest>,
+    /// Optional components which could not be resolved, the plan is complete without them.
+    pub missing: Vec<MissingComponent>,
+}
+
+/// Optional component skipped by the resolution, see [`MetaManager::skip_optional`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingComponent {
+    pub uid: String,
+    pub version: String,
+    /// Why it could not be resolved, e.g. the error of its download.
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedLibrary {
+    pub library: Library,
+    pub path: PathBuf,
+    pub size: u64,
+    /// The file exists in the library directory, its hash is not checked.
+    pub present: bool,
+}
+
+impl ResolutionPlan {
+    /// The plan covers all components, no meta data has to be loaded anymore.
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Size in bytes of all libraries and assets.
+    ///
+    /// Assets of asset indexes which are not loaded are counted with the size declared in the
+    /// manifest.
+    pub fn total_size(&self) -> u64 {
+        let libraries: u64 = self.libraries.iter().map(|l| l.size).sum();
+        let assets: u64 = self.asset_indexes.iter().map(asset_index_size).sum();
+
+        libraries + assets
+    }
+
+    /// Size in bytes of the libraries which are not in the library directory yet.
+    pub fn missing_libraries_size(&self) -> u64 {
+        self.libraries
+            .iter()
+            .filter(|l| !l.present)
+            .map(|l| l.size)
+            .sum()
+    }
+
+    fn add_missing(&mut self, missing: MissingComponent) {
+        if !self.missing.contains(&missing) {
+            self.missing.push(missing);
+        }
+    }
+
+    fn add_requirements(&mut self, uid: &str, requires: &[Requirement]) {
+        for req in requires {
+            if !self
+                .requirements
+                .iter()
+                .any(|(from, r)| from == uid && r.uid == req.uid)
+            {
+                self.requirements.push((uid.to_string(), req.clone()));
+            }
+        }
+    }
+}
+
+impl MissingComponent {
+    fn new(what: &Wants, error: &Error) -> Self {
+        Self {
+            uid: what.uid.clone(),
+            version: what.version.clone(),
+            reason: error.to_string(),
+        }
+    }
+}
+
+fn asset_index_size(info: &AssetIndexInfo) -> u64 {
+    let size = match &info.cache {
+        Some(index) => index.objects.values().map(|a| a.size).sum(),
+        None => info.total_size,
+    };
+
+    u64::try_from(size).unwrap_or(0)
+}
+
+impl MetaManager {
+    /// Resolve `wants` and its requirements against the loaded meta data, without starting a
+    /// search or touching the library and asset directories except for checking which files
+    /// exist.
+    ///
+    /// Meta data which is not loaded yet is returned in [`ResolutionPlan::pending`], frontends
+    /// can load it and plan again to get the complete plan.
+    pub fn plan(&self, wants: Wants) -> Result<ResolutionPlan> {
+        self.plan_all(std::slice::from_ref(&wants))
+    }
+
+    /// Resolve several components together like [`MetaManager::plan`], e.g. Minecraft, a mod
+    /// loader and a newer LWJGL.
+    ///
+    /// The versions of `wants` take precedence over the versions other components suggest.
+    /// Fails with [`Error::DuplicateUid`] if a component is wanted in different versions and
+    /// with [`Error::RequirementConflict`] if a component requires a version the plan does not
+    /// use, unless [`VersionPolicy::FirstRequested`] is set.
+    pub fn plan_all(&self, wants: &[Wants]) -> Result<ResolutionPlan> {
+        let mut plan = ResolutionPlan::default();
+
+        let index = match &self.index {
+            Some(index) => index,
+            None => {
+                plan.pending.push(self.index_request());
+                return Ok(plan);
+            }
+        };
+
+        let os = OS::get();
+        let mut versions: HashMap<String, String> = HashMap::new();
+        let mut seen_files = HashSet::new();
+        let roots: HashSet<&str> = wants.iter().map(|w| w.uid.as_str()).collect();
+        let mut queue: VecDeque<(Wants, bool)> = wants.iter().map(|w| (w.clone(), true)).collect();
+
+        while let Some((what, root)) = queue.pop_front() {
+            self.cancel.check()?;
+            if let Some(version) = versions.get(&what.uid) {
+                let in_range = what.range.iter().any(|r| r.matches(version));
+                // Requirements of a wanted component are checked once everything is resolved
+                let pinned = !root && roots.contains(what.uid.as_str());
+                if *version != what.version
+                    && !in_range
+                    && !pinned
+                    && self.version_policy == VersionPolicy::Fail
+                {
+                    return Err(Error::DuplicateUid {
+                        uid: what.uid,
+                        first: version.to_string(),
+                        second: what.version,
+                    });
+                }
+                continue;
+            }
+            if what.optional {
+                if let Some(missing) = self.missing().iter().find(|m| m.uid == what.uid) {
+                    plan.add_missing(missing.clone());
+                    continue;
+                }
+            }
+
+            let package = match index.get_uid(&what.uid) {
+                Ok(package) => package,
+                Err(e) if what.optional => {
+                    plan.add_missing(MissingComponent::new(&what, &e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            versions.insert(what.uid.clone(), what.version.clone());
+            let package_index = match &package.index {
+                Some(package_index) => package_index,
+                None => {
+                    plan.pending
+                        .push(DownloadRequest::new_package_index(&self.base_url, package));
+                    continue;
+                }
+            };
+
+            let version = match package_index.select_version(&what) {
+                Ok(version) => version,
+                Err(e) if what.optional => {
+                    versions.remove(&what.uid);
+                    plan.add_missing(MissingComponent::new(&what, &e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            versions.insert(what.uid.clone(), version.version.clone());
+            plan.add_requirements(&package.uid, &version.requires);
+            let requires = version.requires.iter().cloned();
+            queue.extend(requires.map(|req| (Wants::from(req), false)));
+            plan.components
+                .push((package.uid.to_string(), version.version.to_string()));
+
+            let manifest = match &version.manifest {
+                Some(manifest) => manifest,
+                None => {
+                    plan.pending.push(DownloadRequest::new_package_manifest(
+                        &self.base_url,
+                        &package.uid,
+                        version,
+                    ));
+                    continue;
+                }
+            };
+            plan.add_requirements(&package.uid, &manifest.requires);
+            let requires = manifest.requires.iter().cloned();
+            queue.extend(requires.map(|req| (Wants::from(req), false)));
+
+            let libraries = manifest
+                .libraries
+                .iter()
+                .filter(|lib| lib.required_for(&os))
+                .chain(&manifest.main_jar);
+            for lib in libraries {
+                let download = lib.select_for(&os).ok_or(Error::LibraryNotSupported)?;
+                let path = lib.path_at_for(&self.library_path, &os);
+                if !seen_files.insert(path.clone()) {
+                    continue;
+                }
+
+                plan.libraries.push(PlannedLibrary {
+                    library: lib.clone(),
+                    present: path.is_file(),
+                    path,
+                    size: u64::try_from(download.size).unwrap_or(0),
+                });
+            }
+
+            if let Some(info) = &manifest.asset_index {
+                if plan.asset_indexes.iter().any(|i| i.id == info.id) {
+                    continue;
+                }
+                match &info.cache {
+                    Some(asset_index) => plan.assets.extend(asset_index.objects.values().cloned()),
+                    None => plan.pending.push(DownloadRequest::AssetIndex {
+                        info: info.clone(),
+                        uid: manifest.uid.to_string(),
+                        version: manifest.version.to_string(),
+                        path: manifest
+                            .assets_path_at(&self.assets_path)
+                            .ok_or(Error::MetaNotFound)?,
+                    }),
+                }
+                plan.asset_indexes.push(info.clone());
+            }
+        }
+
+        if self.version_policy == VersionPolicy::Fail {
+            for (from, req) in &plan.requirements {
+                let version = match versions.get(&req.uid) {
+                    Some(version) => version,
+                    None => continue,
+                };
+                let overridden = self.lwjgl.iter().any(|l| l.overrides(&req.uid));
+                if !overridden && !req.accepts(version) {
+                    return Err(Error::RequirementConflict {
+                        uid: req.uid.to_string(),
+                        version: version.to_string(),
+                        reason: format!(
+                            "{} {} needs {}",
+                            from,
+                            versions.get(from).map(String::as_str).unwrap_or("?"),
+                            super::constraint(req)
+                        ),
+                    });
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+}
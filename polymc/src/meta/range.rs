@@ -0,0 +1,202 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+/// Versions accepted by a [`super::manifest::Requirement`], e.g. `>=0.14 <0.15` or `1.18.*`.
+///
+/// Comparators are separated by spaces or commas and all have to match. A comparator is a
+/// version prefixed with `=`, `!=`, `>`, `>=`, `<` or `<=`, without prefix it has to be equal.
+/// A trailing `.*` matches all versions starting with the given one.
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde_with::SerializeDisplay, serde_with::DeserializeFromStr,
+)]
+pub struct VersionRange {
+    comparators: Vec<(Op, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Prefix,
+}
+
+impl VersionRange {
+    /// Whether `version` is in the range.
+    pub fn matches(&self, version: &str) -> bool {
+        self.comparators.iter().all(|(op, v)| {
+            let ord = compare_versions(version, v);
+            match op {
+                Op::Eq => ord == Ordering::Equal,
+                Op::Ne => ord != Ordering::Equal,
+                Op::Gt => ord == Ordering::Greater,
+                Op::Ge => ord != Ordering::Less,
+                Op::Lt => ord == Ordering::Less,
+                Op::Le => ord != Ordering::Greater,
+                Op::Prefix => match version.strip_prefix(v.as_str()) {
+                    Some(rest) => rest.is_empty() || rest.starts_with(&['.', '-'][..]),
+                    None => false,
+                },
+            }
+        })
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts: Vec<String> = Vec::new();
+        for part in s.split(|c: char| c == ',' || c.is_whitespace()) {
+            match parts.last_mut() {
+                // An operator separated from its version, e.g. `>= 1.0`
+                Some(last) if last.chars().all(|c| "<>=!".contains(c)) => last.push_str(part),
+                _ if part.is_empty() => {}
+                _ => parts.push(part.to_string()),
+            }
+        }
+
+        let mut comparators = Vec::new();
+        for part in &parts {
+            let part = part.as_str();
+            let (op, version) = [
+                (">=", Op::Ge),
+                ("<=", Op::Le),
+                ("!=", Op::Ne),
+                (">", Op::Gt),
+                ("<", Op::Lt),
+                ("=", Op::Eq),
+            ]
+            .iter()
+            .find_map(|(prefix, op)| Some((*op, part.strip_prefix(prefix)?)))
+            .unwrap_or((Op::Eq, part));
+            let (op, version) = match version.strip_suffix(".*") {
+                Some(prefix) if op == Op::Eq => (Op::Prefix, prefix),
+                _ => (op, version),
+            };
+            if version.is_empty() || version.contains('*') {
+                return Err(Error::InvalidVersionRange(s.to_string()));
+            }
+            comparators.push((op, version.to_string()));
+        }
+
+        if comparators.is_empty() {
+            return Err(Error::InvalidVersionRange(s.to_string()));
+        }
+        Ok(Self { comparators })
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self
+            .comparators
+            .iter()
+            .map(|(op, v)| match op {
+                Op::Eq => format!("={}", v),
+                Op::Ne => format!("!={}", v),
+                Op::Gt => format!(">{}", v),
+                Op::Ge => format!(">={}", v),
+                Op::Lt => format!("<{}", v),
+                Op::Le => format!("<={}", v),
+                Op::Prefix => format!("{}.*", v),
+            })
+            .collect();
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// Compare versions by their parts separated by `.` and `+`, numerically where both parts are
+/// numbers. A version with more parts is newer, e.g. `1.18.1` is newer than `1.18`.
+///
+/// Anything after the first `-` is a pre-release, which is older than the release without it,
+/// e.g. `1.18-pre1` is older than `1.18`. Pre-releases of the same release are compared by their
+/// parts like releases.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a, a_pre) = split_pre_release(a);
+    let (b, b_pre) = split_pre_release(b);
+    compare_parts(a, b).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(a), Some(b)) => compare_parts(a, b),
+    })
+}
+
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    match version.split_once('-') {
+        Some((release, pre)) => (release, Some(pre)),
+        None => (version, None),
+    }
+}
+
+fn compare_parts(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.split(&['.', '-', '+'][..])
+            .map(ToString::to_string)
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    for (a, b) in a.iter().zip(&b) {
+        let ord = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_range() {
+        let range: VersionRange = ">=0.14.2, <0.15".parse().unwrap();
+        assert!(range.matches("0.14.2"));
+        assert!(range.matches("0.14.10"));
+        assert!(!range.matches("0.14.1"));
+        assert!(!range.matches("0.15"));
+        assert_eq!(range.to_string(), ">=0.14.2 <0.15");
+
+        let range: VersionRange = "1.18.*".parse().unwrap();
+        assert!(range.matches("1.18"));
+        assert!(range.matches("1.18.2"));
+        assert!(!range.matches("1.180"));
+        assert!(!range.matches("1.19"));
+
+        let range: VersionRange = "1.0 != 1.1".parse().unwrap();
+        assert!(range.matches("1.0"));
+        assert!("".parse::<VersionRange>().is_err());
+        assert!(">=".parse::<VersionRange>().is_err());
+        assert!(">=1.*".parse::<VersionRange>().is_err());
+
+        assert_eq!(compare_versions("1.18.10", "1.18.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.18", "1.18.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0-pre1", "1.0-pre2"), Ordering::Less);
+    }
+
+    #[test]
+    fn pre_releases() {
+        assert_eq!(compare_versions("1.18-pre1", "1.18"), Ordering::Less);
+        assert_eq!(compare_versions("1.18", "1.18-rc1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.18-pre1", "1.18-pre2"), Ordering::Less);
+        assert_eq!(compare_versions("1.18-pre2", "1.18-rc1"), Ordering::Less);
+        assert_eq!(compare_versions("1.18-pre1", "1.17.1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.18.1-pre1", "1.18"), Ordering::Greater);
+
+        let range: VersionRange = ">=1.18".parse().unwrap();
+        assert!(!range.matches("1.18-pre1"));
+        assert!(range.matches("1.18.1-rc1"));
+    }
+}
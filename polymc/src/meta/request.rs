@@ -3,12 +3,12 @@ use crate::meta::{Asset, AssetIndexInfo, MetaIndexPackage, PackageVersion};
 use std::ffi::{CStr, CString};
 use std::fmt::{Display, Formatter};
 use std::os::raw::c_char;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::manifest::LibraryDownload;
+use super::manifest::{ExtractOptions, LibraryDownload};
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum FileType {
     /// Index of Indexes in the meta directory
     MetaIndex,
@@ -74,10 +74,32 @@ impl Display for FileType {
     }
 }
 
+/// Order in which the downloads are needed, the downloader should finish requests of a higher
+/// priority (lower value) first.
+///
+/// Everything before [`DownloadPriority::Asset`] is needed to start the game, so a frontend can
+/// launch once only assets are left and keep downloading them while the game is running.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadPriority {
+    /// Meta index, package indexes and version manifests
+    Meta,
+    /// Main jar of a component (e.g. the Minecraft client)
+    MainJar,
+    /// Other libraries and the logging configuration
+    Library,
+    /// Asset index
+    AssetIndex,
+    /// Asset file
+    Asset,
+}
+
 #[derive(Debug, Clone)]
 pub enum DownloadRequest {
     MetaIndex {
         url: String,
+        /// Pinned hash of the meta index, if configured.
+        hash: Option<Sha256Sum>,
     },
     Index {
         url: String,
@@ -93,6 +115,10 @@ pub enum DownloadRequest {
     Library {
         path: String,
         download: LibraryDownload,
+        /// Main jar of a component instead of a library it uses.
+        main_jar: bool,
+        /// How to extract the jar if it holds natives.
+        natives: Option<ExtractOptions>,
     },
     AssetIndex {
         uid: String,
@@ -109,8 +135,8 @@ pub enum DownloadRequest {
 }
 
 impl DownloadRequest {
-    pub fn new_meta_index(url: String) -> Self {
-        Self::MetaIndex { url }
+    pub fn new_meta_index(url: String, hash: Option<Sha256Sum>) -> Self {
+        Self::MetaIndex { url, hash }
     }
 
     pub fn new_package_index(base_url: &str, package: &MetaIndexPackage) -> Self {
@@ -134,6 +160,27 @@ impl DownloadRequest {
         Self::Library {
             download,
             path: path.display().to_string(),
+            main_jar: false,
+            natives: None,
+        }
+    }
+
+    /// Request of a jar holding natives, extracted with `extract` before the game starts.
+    pub fn new_natives(download: LibraryDownload, path: PathBuf, extract: ExtractOptions) -> Self {
+        Self::Library {
+            download,
+            path: path.display().to_string(),
+            main_jar: false,
+            natives: Some(extract),
+        }
+    }
+
+    pub fn new_main_jar(download: LibraryDownload, path: PathBuf) -> Self {
+        Self::Library {
+            download,
+            path: path.display().to_string(),
+            main_jar: true,
+            natives: None,
         }
     }
 
@@ -149,17 +196,39 @@ impl DownloadRequest {
         }
     }
 
+    /// When the request is needed, see [`DownloadPriority`].
+    #[export_name = "download_request_priority"]
+    pub extern "C" fn priority(&self) -> DownloadPriority {
+        match self {
+            Self::MetaIndex { .. } | Self::Index { .. } | Self::Manifest { .. } => {
+                DownloadPriority::Meta
+            }
+            Self::Library { main_jar: true, .. } => DownloadPriority::MainJar,
+            Self::Library { .. } => DownloadPriority::Library,
+            Self::AssetIndex { .. } => DownloadPriority::AssetIndex,
+            Self::Asset { .. } => DownloadPriority::Asset,
+        }
+    }
+
     #[export_name = "download_request_hash_size"]
     pub extern "C" fn hash_size(&self) -> usize {
-        self.request_type().hash_size()
+        match self {
+            Self::MetaIndex { hash: Some(_), .. } => ring::digest::SHA256_OUTPUT_LEN,
+            Self::Library { download, .. } if download.sha1.is_none() => 0,
+            _ => self.request_type().hash_size(),
+        }
     }
 
     pub fn get_hash(&self) -> &[u8] {
         match self {
-            Self::MetaIndex { .. } => &[],
+            Self::MetaIndex { hash, .. } => hash.as_ref().map(|h| &h.as_ref()[..]).unwrap_or(&[]),
             Self::Index { hash, .. } => hash.as_ref(),
             Self::Manifest { hash, .. } => hash.as_ref(),
-            Self::Library { download, .. } => download.sha1.as_ref(),
+            Self::Library { download, .. } => download
+                .sha1
+                .as_ref()
+                .map(|h| &h.as_ref()[..])
+                .unwrap_or(&[]),
             Self::AssetIndex { info, .. } => info.sha1.as_ref(),
             Self::Asset { asset, .. } => asset.hash.as_ref(),
         }
@@ -186,18 +255,40 @@ impl DownloadRequest {
     }
 
     pub fn get_hash_algo(&self) -> Option<&'static ring::digest::Algorithm> {
-        self.request_type().get_hash_algo()
+        match self {
+            Self::MetaIndex { hash: Some(_), .. } => Some(&ring::digest::SHA256),
+            _ => self.request_type().get_hash_algo(),
+        }
+    }
+
+    /// Size in bytes of the file to download, if the meta data declares it.
+    pub fn get_size(&self) -> Option<u64> {
+        let size = match self {
+            Self::Library { download, .. } => download.size,
+            Self::AssetIndex { info, .. } => info.size,
+            Self::Asset { asset, .. } => asset.size,
+            _ => return None,
+        };
+
+        u64::try_from(size).ok()
+    }
+
+    /// Size in bytes of the file to download, 0 if unknown.
+    #[export_name = "download_request_size"]
+    pub extern "C" fn size(&self) -> u64 {
+        self.get_size().unwrap_or(0)
     }
 
     /// Get the hash of the file to download.
-    /// If the type is MetaIndex `NULL` will be returned
+    /// If the type is MetaIndex without a pinned hash `NULL` will be returned
     #[cfg(feature = "ctypes")]
     #[doc(hidden)]
     #[export_name = "download_request_get_hash"]
     pub extern "C" fn get_hash_c(&self) -> *const u8 {
-        match self {
-            Self::MetaIndex { .. } => core::ptr::null(),
-            _ => self.get_hash().as_ptr(),
+        if self.has_hash() {
+            self.get_hash().as_ptr()
+        } else {
+            core::ptr::null()
         }
     }
 
@@ -235,6 +326,29 @@ impl DownloadRequest {
         }
     }
 
+    /// How to extract the jar if the request is for natives.
+    pub fn natives(&self) -> Option<&ExtractOptions> {
+        match self {
+            Self::Library { natives, .. } => natives.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Path of a meta file in the meta directory `meta_dir`, `None` for other files.
+    ///
+    /// Mirrors the layout of the meta server, e.g. `net.minecraft/1.18.2.json`.
+    pub fn meta_path_at<P: AsRef<Path>>(&self, meta_dir: P) -> Option<PathBuf> {
+        let meta_dir = meta_dir.as_ref();
+        match self {
+            Self::MetaIndex { .. } => Some(meta_dir.join("index.json")),
+            Self::Index { uid, .. } => Some(meta_dir.join(uid).join("index.json")),
+            Self::Manifest { uid, version, .. } => {
+                Some(meta_dir.join(uid).join(format!("{}.json", version)))
+            }
+            _ => None,
+        }
+    }
+
     /// If the type is Library, this returns the expected path to save the file under.
     /// The returned pointer has to be freed with [`free_str`] and not with free.
     #[cfg(feature = "ctypes")]
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// Meta servers tried when the user did not choose one, in order of preference.
+///
+/// The fastest of them is used, so they must all serve the same meta data: only list mirrors of
+/// one source here, never servers of different projects which sign and maintain their meta
+/// separately.
+pub const DEFAULT_META_SERVERS: &[&str] = &["https://meta.prismlauncher.org/v1"];
+
+/// Whether and how fast a meta server answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerProbe {
+    pub url: String,
+    /// Time until the server answered the request for its index, `None` if it failed.
+    pub latency: Option<Duration>,
+}
+
+impl ServerProbe {
+    pub fn new<S: Into<String>>(url: S, latency: Option<Duration>) -> Self {
+        Self {
+            url: url.into(),
+            latency,
+        }
+    }
+}
+
+/// The healthy server answering fastest, the one listed first if several are equally fast.
+pub fn fastest_server(probes: &[ServerProbe]) -> Option<&ServerProbe> {
+    probes
+        .iter()
+        .filter_map(|probe| Some((probe.latency?, probe)))
+        .min_by_key(|(latency, _)| *latency)
+        .map(|(_, probe)| probe)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fastest() {
+        let ms = Duration::from_millis;
+        let probes = [
+            ServerProbe::new("https://down.example.com", None),
+            ServerProbe::new("https://slow.example.com", Some(ms(300))),
+            ServerProbe::new("https://fast.example.com", Some(ms(20))),
+            ServerProbe::new("https://fast2.example.com", Some(ms(20))),
+        ];
+        assert_eq!(
+            fastest_server(&probes).unwrap().url,
+            "https://fast.example.com"
+        );
+        assert_eq!(fastest_server(&probes[..1]), None);
+        assert_eq!(fastest_server(&[]), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::meta::manifest::Sha256Sum;
+use crate::meta::DownloadRequest;
+use crate::{Error, Result};
+
+/// Name of the file describing a snapshot, next to the meta files in the snapshot directory.
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Frozen copy of the meta server, for reproducible builds.
+///
+/// A snapshot directory has the layout of the meta server (see
+/// [`DownloadRequest::meta_path_at`]) and holds the meta index, and the package indexes and all
+/// manifests of the packages in [`MetaSnapshot::uids`]. A [`super::MetaManager`] with a snapshot
+/// set resolves from it only, see [`super::MetaManager::set_snapshot`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaSnapshot {
+    /// Meta server the snapshot was taken from.
+    pub base_url: String,
+    /// Hash of the meta index in the snapshot, pinned when resolving from it.
+    pub index_sha256: Sha256Sum,
+    /// Packages in the snapshot.
+    pub uids: Vec<String>,
+}
+
+impl MetaSnapshot {
+    /// Describe a snapshot of `uids` taken from `base_url`, with the meta index `index`.
+    pub fn new(base_url: &str, index: &[u8], uids: Vec<String>) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, index);
+        Self {
+            base_url: base_url.to_string(),
+            index_sha256: hex::encode(digest.as_ref())
+                .parse()
+                .expect("sha256 digest has the size of a Sha256Sum"),
+            uids,
+        }
+    }
+
+    /// Load the description of the snapshot directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref().join(SNAPSHOT_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .open(&path)
+            .map_err(|_| Error::InvalidSnapshot(format!("{} is missing", path.display())))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(dir.as_ref().join(SNAPSHOT_FILE))?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+
+    /// Read the meta file of `request` from the snapshot directory `dir`.
+    ///
+    /// Fails with [`Error::InvalidSnapshot`] if the file is missing or does not match the hash of
+    /// the request.
+    pub fn read_at<P: AsRef<Path>>(dir: P, request: &DownloadRequest) -> Result<Vec<u8>> {
+        let path = request.meta_path_at(dir).ok_or_else(|| {
+            Error::InvalidSnapshot(format!("{} is no meta file", request.get_url()))
+        })?;
+        let data = std::fs::read(&path)
+            .map_err(|_| Error::InvalidSnapshot(format!("{} is missing", path.display())))?;
+
+        if request.has_hash() {
+            let digest = ring::digest::digest(request.get_hash_algo().unwrap(), &data);
+            if digest.as_ref() != request.get_hash() {
+                return Err(Error::InvalidSnapshot(format!(
+                    "{} does not match the index",
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::{MetaManager, Wants};
+    use crate::test_util::TempDir;
+
+    fn sha256(data: &str) -> String {
+        hex::encode(ring::digest::digest(&ring::digest::SHA256, data.as_bytes()).as_ref())
+    }
+
+    #[test]
+    fn resolve_from_snapshot() {
+        let dir = TempDir::new("snapshot");
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+
+        let manifest = r#"{"name": "a", "uid": "a", "version": "1.0", "order": 0,
+            "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release", "libraries": []}"#;
+        let package_index = format!(
+            r#"{{"formatVersion": 1, "name": "a", "uid": "a", "versions": [
+                {{"releaseTime": "2022-01-01T00:00:00+00:00", "sha256": "{}", "type": "release", "version": "1.0"}}
+            ]}}"#,
+            sha256(manifest)
+        );
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [{{"name": "a", "sha256": "{}", "uid": "a"}}]}}"#,
+            sha256(&package_index)
+        );
+        std::fs::write(dir.join("index.json"), &index).unwrap();
+        std::fs::write(dir.join("a/index.json"), &package_index).unwrap();
+        std::fs::write(dir.join("a/1.0.json"), manifest).unwrap();
+
+        let mut manager = MetaManager::new("lib", "assets", "https://example.com");
+        assert!(matches!(
+            manager.set_snapshot(Some(&dir)),
+            Err(Error::InvalidSnapshot(_))
+        ));
+        MetaSnapshot::new(
+            "https://example.com",
+            index.as_bytes(),
+            vec!["a".to_string()],
+        )
+        .save(&dir)
+        .unwrap();
+        manager.set_snapshot(Some(&dir)).unwrap();
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        let search = manager.continue_search().unwrap();
+        assert!(search.is_ready());
+        assert!(search.manifests.contains_key("a"));
+
+        // Modified files are refused
+        std::fs::write(
+            dir.join("a/1.0.json"),
+            manifest.replace(r#""order": 0"#, r#""order": 1"#),
+        )
+        .unwrap();
+        let mut manager = MetaManager::new("lib", "assets", "https://example.com");
+        manager.set_snapshot(Some(&dir)).unwrap();
+        manager.search(Wants::new("a", "1.0")).unwrap();
+        assert!(matches!(
+            manager.continue_search(),
+            Err(Error::InvalidSnapshot(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use crate::i18n::Phase;
+use crate::meta::{AssetDiff, DownloadRequest, FileType};
+
+/// Files of one component.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ComponentStats {
+    /// Files which were already there with the right hash.
+    pub cached: usize,
+    /// Files which were downloaded.
+    pub downloaded: usize,
+    /// Bytes of the downloaded files.
+    pub bytes: u64,
+}
+
+impl ComponentStats {
+    fn add(&mut self, other: &ComponentStats) {
+        self.cached += other.cached;
+        self.downloaded += other.downloaded;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Key the meta index is counted under in [`DownloadStats::components`], it belongs to no
+/// component.
+pub const META_INDEX_STATS_KEY: &str = "index.json";
+
+/// Report of a resolution run, how much came from the cache and where the time went.
+///
+/// The library counts the files it verified, the frontend reports its downloads with
+/// [`DownloadStats::record_download`] and [`DownloadStats::record_cached`], usually through
+/// [`crate::meta::MetaManager::stats_mut`].
+#[derive(Debug, Clone, Default)]
+pub struct DownloadStats {
+    /// Files by the uid of their component, the meta index is counted under
+    /// [`META_INDEX_STATS_KEY`] and libraries of an unknown component under an empty uid.
+    pub components: BTreeMap<String, ComponentStats>,
+    /// Time spent in each phase, only resolving, verifying and downloading are timed.
+    pub times: Vec<(Phase, Duration)>,
+    /// Asset objects per uid shared with the asset index the instance was resolved with before,
+    /// see [`crate::meta::MetaManager::set_previous_asset_index`].
+    pub asset_diffs: BTreeMap<String, AssetDiff>,
+    /// Kinds of files per component whose cached files were counted already.
+    verified: HashSet<(String, FileType)>,
+    /// Component of requested libraries by path, library requests don't know their component.
+    libraries: HashMap<String, String>,
+}
+
+impl DownloadStats {
+    /// Count the `cached` files of `file_type` of `uid` found at the first verification, later
+    /// verifications also see the files downloaded meanwhile.
+    pub(crate) fn record_verified(&mut self, uid: &str, file_type: FileType, cached: usize) {
+        if self.verified.insert((uid.to_string(), file_type)) {
+            self.component(uid).cached += cached;
+        }
+    }
+
+    /// Record how the asset index of `uid` differs from the previous one.
+    pub(crate) fn record_asset_diff(&mut self, uid: &str, diff: AssetDiff) {
+        self.asset_diffs.insert(uid.to_string(), diff);
+    }
+
+    /// Remember that the library of `request` belongs to `uid`.
+    pub(crate) fn track_library(&mut self, request: &DownloadRequest, uid: &str) {
+        if let Some(path) = request.get_path() {
+            self.libraries.insert(path.to_string(), uid.to_string());
+        }
+    }
+
+    /// Count the file of `request` as downloaded with `bytes`.
+    pub fn record_download(&mut self, request: &DownloadRequest, bytes: u64) {
+        let uid = self.uid_of(request);
+        let component = self.component(&uid);
+        component.downloaded += 1;
+        component.bytes += bytes;
+    }
+
+    /// Count the file of `request` as found in the cache by the frontend.
+    pub fn record_cached(&mut self, request: &DownloadRequest) {
+        let uid = self.uid_of(request);
+        self.component(&uid).cached += 1;
+    }
+
+    /// Add `time` spent in `phase`.
+    pub fn add_time(&mut self, phase: Phase, time: Duration) {
+        match self.times.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += time,
+            None => self.times.push((phase, time)),
+        }
+    }
+
+    /// Time spent in `phase`.
+    pub fn time(&self, phase: Phase) -> Duration {
+        self.times
+            .iter()
+            .find(|(p, _)| *p == phase)
+            .map(|(_, time)| *time)
+            .unwrap_or_default()
+    }
+
+    /// Files of all components together.
+    pub fn total(&self) -> ComponentStats {
+        let mut total = ComponentStats::default();
+        for component in self.components.values() {
+            total.add(component);
+        }
+        total
+    }
+
+    fn component(&mut self, uid: &str) -> &mut ComponentStats {
+        self.components.entry(uid.to_string()).or_default()
+    }
+
+    fn uid_of(&self, request: &DownloadRequest) -> String {
+        match request {
+            DownloadRequest::MetaIndex { .. } => META_INDEX_STATS_KEY.to_string(),
+            DownloadRequest::Index { uid, .. }
+            | DownloadRequest::Manifest { uid, .. }
+            | DownloadRequest::AssetIndex { uid, .. }
+            | DownloadRequest::Asset { uid, .. } => uid.to_string(),
+            DownloadRequest::Library { path, .. } => {
+                self.libraries.get(path).cloned().unwrap_or_default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::manifest::LibraryDownload;
+
+    #[test]
+    fn count_per_component() {
+        let mut stats = DownloadStats::default();
+        stats.record_verified("net.minecraft", FileType::Library, 3);
+        // Downloaded files are found by the next verification
+        stats.record_verified("net.minecraft", FileType::Library, 5);
+
+        let library = DownloadRequest::new_library(
+            LibraryDownload {
+                sha1: None,
+                size: 10,
+                url: "https://libraries.example.com/a.jar".to_string(),
+            },
+            "/libraries/a.jar".into(),
+        );
+        stats.track_library(&library, "net.minecraft");
+        stats.record_download(&library, 10);
+        stats.record_cached(&DownloadRequest::new_meta_index(
+            "index.json".to_string(),
+            None,
+        ));
+
+        assert_eq!(
+            stats.components["net.minecraft"],
+            ComponentStats {
+                cached: 3,
+                downloaded: 1,
+                bytes: 10
+            }
+        );
+        // Libraries not tracked belong to no known component
+        stats.record_cached(&DownloadRequest::new_library(
+            LibraryDownload {
+                sha1: None,
+                size: 10,
+                url: "https://libraries.example.com/b.jar".to_string(),
+            },
+            "/libraries/b.jar".into(),
+        ));
+        assert_eq!(stats.components[META_INDEX_STATS_KEY].cached, 1);
+        assert_eq!(stats.components[""].cached, 1);
+        assert_eq!(stats.total().cached, 5);
+
+        stats.add_time(Phase::Download, Duration::from_secs(1));
+        stats.add_time(Phase::Download, Duration::from_secs(2));
+        assert_eq!(stats.time(Phase::Download), Duration::from_secs(3));
+        assert_eq!(stats.time(Phase::Verify), Duration::ZERO);
+    }
+}
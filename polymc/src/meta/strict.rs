@@ -0,0 +1,75 @@
+//! Validation of meta files against the structs they are parsed into.
+//!
+//! Serde ignores unknown fields by default so launchers keep working when the meta format
+//! gets extended. Meta server maintainers can use this to find fields the library does not know.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Error, Result};
+
+/// Parse `data` into `T`, failing with [`Error::MetaUnknownFields`] if it contains fields `T`
+/// does not know.
+pub fn parse_strict<T: DeserializeOwned + Serialize>(data: &[u8]) -> Result<T> {
+    let original: Value = serde_json::from_slice(data)?;
+    let parsed: T = serde_json::from_value(original.clone())?;
+
+    let mut unknown = Vec::new();
+    unknown_fields(&original, &serde_json::to_value(&parsed)?, "", &mut unknown);
+    if unknown.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(Error::MetaUnknownFields(unknown))
+    }
+}
+
+/// Collect the paths of all object keys in `original` which are missing in `parsed`.
+fn unknown_fields(original: &Value, parsed: &Value, path: &str, unknown: &mut Vec<String>) {
+    match (original, parsed) {
+        (Value::Object(original), Value::Object(parsed)) => {
+            for (key, value) in original {
+                let path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match parsed.get(key) {
+                    Some(parsed) => unknown_fields(value, parsed, &path, unknown),
+                    None => unknown.push(path),
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(parsed)) => {
+            for (i, (original, parsed)) in original.iter().zip(parsed).enumerate() {
+                unknown_fields(original, parsed, &format!("{}[{}]", path, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::MetaIndex;
+
+    #[test]
+    fn unknown_fields() {
+        let hash = "0".repeat(64);
+        let index = format!(
+            r#"{{"formatVersion": 1, "packages": [{{"name": "a", "sha256": "{hash}", "uid": "a"}}]}}"#
+        );
+        parse_strict::<MetaIndex>(index.as_bytes()).unwrap();
+
+        let index = format!(
+            r#"{{"formatVersion": 1, "extra": 1, "packages": [{{"name": "a", "sha256": "{hash}", "uid": "a", "recommended": []}}]}}"#
+        );
+        match parse_strict::<MetaIndex>(index.as_bytes()) {
+            Err(Error::MetaUnknownFields(fields)) => {
+                assert_eq!(fields, vec!["extra", "packages[0].recommended"])
+            }
+            _ => panic!("unknown fields not detected"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Sharing the library and asset stores with other launchers on the local network.
+//!
+//! A peer serves its stores over HTTP, e.g. with `plmc store serve`, at the paths given by
+//! [`peer_path`]. Files from peers are only used if the meta data declares their hash, so a
+//! peer can't hand out anything else than the origin would.
+//!
+//! Peers sharing their stores with the local network answer a [`DISCOVERY_QUERY`] broadcast to
+//! [`DISCOVERY_PORT`] over UDP with a [`discovery_reply`] naming the port they serve on.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::meta::DownloadRequest;
+
+/// UDP port peers listen on for discovery queries.
+pub const DISCOVERY_PORT: u16 = 25580;
+
+/// Datagram asking the peers on the local network for the port they serve on.
+pub const DISCOVERY_QUERY: &[u8] = b"polymc-peer?";
+
+const DISCOVERY_REPLY: &str = "polymc-peer ";
+
+/// Answer to a [`DISCOVERY_QUERY`] of a peer serving on `port`.
+pub fn discovery_reply(port: u16) -> Vec<u8> {
+    format!("{}{}", DISCOVERY_REPLY, port).into_bytes()
+}
+
+/// Port of the peer that sent `data`, `None` if it isn't a [`discovery_reply`].
+pub fn parse_discovery_reply(data: &[u8]) -> Option<u16> {
+    std::str::from_utf8(data)
+        .ok()?
+        .strip_prefix(DISCOVERY_REPLY)?
+        .parse()
+        .ok()
+}
+
+/// Path of the file of `request` on a peer, relative to its url.
+///
+/// Returns `None` for meta data and files without a hash, see [`crate::store_relative_path`].
+pub fn peer_path(
+    request: &DownloadRequest,
+    libraries_dir: &Path,
+    assets_dir: &Path,
+) -> Option<String> {
+    if !request.has_hash() {
+        return None;
+    }
+    store_relative_path(request, libraries_dir, assets_dir)
+}
+
+/// Path of the file of `request` in a directory holding the stores next to each other.
+///
+/// Files in the library directory are at `libraries/<path in the library directory>` and files
+/// in the assets directory, like asset indexes and logging configurations, at
+/// `assets/<path in the assets directory>`. Returns `None` for meta data and files outside of
+/// the stores.
+pub fn store_relative_path(
+    request: &DownloadRequest,
+    libraries_dir: &Path,
+    assets_dir: &Path,
+) -> Option<String> {
+    let path = Path::new(request.get_path()?);
+    let (prefix, relative) = match path.strip_prefix(libraries_dir) {
+        Ok(relative) => ("libraries", relative),
+        Err(_) => ("assets", path.strip_prefix(assets_dir).ok()?),
+    };
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    Some(format!("{}/{}", prefix, parts.join("/")))
+}
+
+/// File in the stores served for the peer path `path`, the reverse of [`peer_path`].
+///
+/// Returns `None` for paths outside of the stores.
+pub fn store_path(path: &str, libraries_dir: &Path, assets_dir: &Path) -> Option<PathBuf> {
+    let path = path.trim_start_matches('/');
+    let (prefix, relative) = path.split_once('/')?;
+    let store = match prefix {
+        "libraries" => libraries_dir,
+        "assets" => assets_dir,
+        _ => return None,
+    };
+    let mut ret = store.to_path_buf();
+    for part in relative.split('/') {
+        // Drive prefixes and separators of other platforms could leave the store
+        if part.contains(':') || part.contains('\\') {
+            return None;
+        }
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(part)), None) => ret.push(part),
+            _ => return None,
+        }
+    }
+
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::manifest::LibraryDownload;
+
+    #[test]
+    fn peer_paths() {
+        let (libraries, assets) = (Path::new("/store/lib"), Path::new("/store/assets"));
+        let library = |sha1: Option<&str>| {
+            DownloadRequest::new_library(
+                LibraryDownload {
+                    sha1: sha1.map(|h| h.parse().unwrap()),
+                    size: 1,
+                    url: "https://libraries.example.com/org/example/lib.jar".to_string(),
+                },
+                libraries.join("org/example/lib.jar"),
+            )
+        };
+
+        let hashed = library(Some("da39a3ee5e6b4b0d3255bfef95601890afd80709"));
+        let path = peer_path(&hashed, libraries, assets).unwrap();
+        assert_eq!(path, "libraries/org/example/lib.jar");
+        assert_eq!(
+            store_path(&format!("/{}", path), libraries, assets),
+            Some(libraries.join("org/example/lib.jar"))
+        );
+        // Unverifiable files are only taken from the origin
+        assert_eq!(peer_path(&library(None), libraries, assets), None);
+
+        assert_eq!(store_path("/libraries/../secret", libraries, assets), None);
+        assert_eq!(store_path("/meta/index.json", libraries, assets), None);
+        assert_eq!(store_path("/assets//etc/passwd", libraries, assets), None);
+        assert_eq!(store_path("/assets/./index.json", libraries, assets), None);
+        assert_eq!(store_path("/libraries/C:/secret", libraries, assets), None);
+        assert_eq!(store_path("/libraries/a\\..\\..", libraries, assets), None);
+    }
+
+    #[test]
+    fn discovery() {
+        assert_eq!(parse_discovery_reply(&discovery_reply(25580)), Some(25580));
+        assert_eq!(parse_discovery_reply(DISCOVERY_QUERY), None);
+        assert_eq!(parse_discovery_reply(b"polymc-peer 99999"), None);
+    }
+}
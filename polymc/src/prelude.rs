@@ -0,0 +1,18 @@
+//! The high-level API of the library, for `use polymc::prelude::*`.
+//!
+//! These are the types frontends need to resolve, download and start the game, and they are
+//! kept stable: the deeper module paths hold the building blocks they are made of, which may
+//! still change. The library does no network requests itself, frontends download through their
+//! [`Fetch`] implementation or the [`DownloadRequest`]s of a [`MetaManager`] search.
+
+pub use crate::auth::{Auth, AuthProfile};
+pub use crate::cancel::CancellationToken;
+pub use crate::instance::{Instance, InstanceGameConfig, InstanceInfo};
+pub use crate::java_wrapper::{Java, RunningInstance};
+pub use crate::launch::{launch, prepare, Fetch, FetchFuture, LaunchOptions};
+pub use crate::launch_command::LaunchCommandBuilder;
+pub use crate::meta::{
+    DownloadPriority, DownloadRequest, FileType, MetaManager, SearchResult, VersionPolicy, Wants,
+};
+pub use crate::runtime::JavaRuntime;
+pub use crate::{Error, Result};
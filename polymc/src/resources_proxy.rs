@@ -0,0 +1,242 @@
+//! Serving the resources of versions before 1.6 from the asset store.
+//!
+//! These versions download their sounds and music from Amazon S3 and minecraft.net, which stopped
+//! serving them. The frontend runs a local HTTP proxy answering with [`ResourceProxy::respond`]
+//! and starts the game with [`ResourceProxy::jvm_args`], so the game gets the files from the
+//! asset index instead. Other requests, like skins or logins, are passed on by the frontend.
+
+use std::path::PathBuf;
+
+use crate::meta::{Asset, AssetIndex};
+
+/// Host of the S3 bucket listing resources as XML.
+const S3_HOST: &str = "s3.amazonaws.com";
+const S3_PREFIX: &str = "/MinecraftResources/";
+
+/// Hosts listing resources as text for versions before 1.0.
+const LEGACY_HOSTS: &[&str] = &["www.minecraft.net", "minecraft.net"];
+const LEGACY_PREFIX: &str = "/resources/";
+
+/// Answer of the proxy to a request of the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyResponse {
+    /// Content of the asset stored at `path`.
+    File {
+        path: PathBuf,
+        size: u64,
+    },
+    /// Listing of all resources.
+    Listing {
+        content_type: &'static str,
+        body: String,
+    },
+    /// Resource missing from the asset index.
+    NotFound,
+    /// Not a request for resources, to be forwarded to the host.
+    Forward,
+}
+
+/// Proxy resolving requests for resources to the assets of an asset index.
+#[derive(Debug, Clone)]
+pub struct ResourceProxy {
+    /// Assets by name, sorted.
+    assets: Vec<(String, Asset)>,
+    assets_path: String,
+}
+
+impl ResourceProxy {
+    /// Proxy for the assets of `index` stored in the asset directory `assets_path`.
+    pub fn new(index: &AssetIndex, assets_path: &str) -> Self {
+        let mut assets: Vec<_> = index
+            .objects
+            .iter()
+            .map(|(name, asset)| (name.clone(), asset.clone()))
+            .collect();
+        assets.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self {
+            assets,
+            assets_path: assets_path.to_string(),
+        }
+    }
+
+    /// JVM arguments sending the HTTP requests of the game through the proxy on `port`.
+    pub fn jvm_args(port: u16) -> Vec<String> {
+        vec![
+            "-Dhttp.proxyHost=127.0.0.1".to_string(),
+            format!("-Dhttp.proxyPort={}", port),
+        ]
+    }
+
+    /// Answer a request for `path` on `host`, other requests than for resources are forwarded.
+    pub fn respond(&self, host: &str, path: &str) -> ProxyResponse {
+        let s3 = host.eq_ignore_ascii_case(S3_HOST);
+        let prefix = if s3 {
+            S3_PREFIX
+        } else if LEGACY_HOSTS.iter().any(|h| host.eq_ignore_ascii_case(h)) {
+            LEGACY_PREFIX
+        } else {
+            return ProxyResponse::Forward;
+        };
+
+        let name = match path.strip_prefix(prefix) {
+            Some(name) => name,
+            None if path == prefix.trim_end_matches('/') => "",
+            // Skins share the host with the resources
+            None => return ProxyResponse::Forward,
+        };
+        if name.is_empty() {
+            return if s3 {
+                ProxyResponse::Listing {
+                    content_type: "application/xml",
+                    body: self.s3_listing(),
+                }
+            } else {
+                ProxyResponse::Listing {
+                    content_type: "text/plain",
+                    body: self.legacy_listing(),
+                }
+            };
+        }
+
+        let name = percent_decode(name);
+        match self.assets.binary_search_by(|(n, _)| n.as_str().cmp(&name)) {
+            Ok(i) => {
+                let asset = &self.assets[i].1;
+                ProxyResponse::File {
+                    path: asset.path_at(&self.assets_path).into(),
+                    size: asset.size.max(0) as u64,
+                }
+            }
+            Err(_) => ProxyResponse::NotFound,
+        }
+    }
+
+    /// Bucket listing in the format of S3, read by versions from 1.0 to 1.5.
+    fn s3_listing(&self) -> String {
+        let mut ret = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?><ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>MinecraftResources</Name><IsTruncated>false</IsTruncated>"#,
+        );
+        for (name, asset) in &self.assets {
+            ret.push_str(&format!(
+                r#"<Contents><Key>{}</Key><LastModified>2013-01-01T00:00:00.000Z</LastModified><ETag>"{}"</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>"#,
+                xml_escape(name),
+                asset.hash,
+                asset.size
+            ));
+        }
+        ret.push_str("</ListBucketResult>");
+        ret
+    }
+
+    /// Listing of `name,size,modified` lines, read by versions before 1.0.
+    fn legacy_listing(&self) -> String {
+        self.assets
+            .iter()
+            .map(|(name, asset)| format!("{},{},0\n", name, asset.size))
+            .collect()
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                ret.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                ret.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&ret).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn resource_proxy() {
+        let dir = TempDir::new("resources");
+        let assets = dir.join("assets").display().to_string();
+        // sha1 of "sound"
+        let index: AssetIndex = r#"{"map_to_resources": true, "objects": {
+            "sound/random/click one.ogg": {
+                "hash": "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e", "size": 5
+            }
+        }}"#
+        .parse()
+        .unwrap();
+        assert!(index.map_to_resources);
+        let asset = &index.objects["sound/random/click one.ogg"];
+        let stored = asset.path_at(&assets);
+        std::fs::create_dir_all(std::path::Path::new(&stored).parent().unwrap()).unwrap();
+        std::fs::write(&stored, "sound").unwrap();
+
+        index
+            .map_to_resources_at(&assets, dir.join("game"))
+            .unwrap();
+        let mapped = dir.join("game/resources/sound/random/click one.ogg");
+        assert_eq!(std::fs::read_to_string(&mapped).unwrap(), "sound");
+
+        let proxy = ResourceProxy::new(&index, &assets);
+        let file = ProxyResponse::File {
+            path: stored.into(),
+            size: 5,
+        };
+        assert_eq!(
+            proxy.respond(
+                "s3.amazonaws.com",
+                "/MinecraftResources/sound/random/click%20one.ogg"
+            ),
+            file
+        );
+        assert_eq!(
+            proxy.respond("www.minecraft.net", "/resources/sound/random/click one.ogg"),
+            file
+        );
+        match proxy.respond("s3.amazonaws.com", "/MinecraftResources/") {
+            ProxyResponse::Listing { body, .. } => {
+                assert!(body.contains("<Key>sound/random/click one.ogg</Key>"));
+                assert!(body.contains("<Size>5</Size>"));
+            }
+            other => panic!("no listing: {:?}", other),
+        }
+        assert_eq!(
+            proxy.respond("www.minecraft.net", "/resources/"),
+            ProxyResponse::Listing {
+                content_type: "text/plain",
+                body: "sound/random/click one.ogg,5,0\n".to_string(),
+            }
+        );
+        assert_eq!(
+            proxy.respond("s3.amazonaws.com", "/MinecraftResources/missing.ogg"),
+            ProxyResponse::NotFound
+        );
+        assert_eq!(
+            proxy.respond("example.com", "/resources/"),
+            ProxyResponse::Forward
+        );
+        assert_eq!(
+            proxy.respond("s3.amazonaws.com", "/MinecraftSkins/player.png"),
+            ProxyResponse::Forward
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Discovery of installed Java runtimes and selection of one matching the needs of an instance.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use tracing::*;
+
+use crate::java_wrapper::Java;
+use crate::{Error, Result};
+
+#[cfg(target_family = "windows")]
+const JAVA_EXECUTABLE: &str = "java.exe";
+#[cfg(not(target_family = "windows"))]
+const JAVA_EXECUTABLE: &str = "java";
+
+/// Directories which usually contain one runtime per subdirectory.
+#[cfg(target_os = "linux")]
+const SYSTEM_RUNTIME_DIRS: &[&str] = &["/usr/lib/jvm", "/usr/lib64/jvm", "/opt/java"];
+#[cfg(target_os = "macos")]
+const SYSTEM_RUNTIME_DIRS: &[&str] = &["/Library/Java/JavaVirtualMachines"];
+#[cfg(target_family = "windows")]
+const SYSTEM_RUNTIME_DIRS: &[&str] = &[
+    "C:\\Program Files\\Java",
+    "C:\\Program Files\\Eclipse Adoptium",
+];
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_family = "windows")))]
+const SYSTEM_RUNTIME_DIRS: &[&str] = &[];
+
+/// An installed runtime and its major version.
+#[derive(Debug, Clone)]
+pub struct JavaRuntime {
+    pub java: Java,
+    pub major: u32,
+}
+
+/// Major version of a Java version string, e.g. 8 for `1.8.0_292` or 17 for `17.0.2`.
+pub(crate) fn parse_major(version: &str) -> Option<u32> {
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|p| !p.is_empty());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Major version from the output of `java -version`.
+pub(crate) fn parse_version_output(output: &str) -> Option<u32> {
+    let line = output.lines().find(|l| l.contains(" version "))?;
+    let version = line.split('"').nth(1)?;
+    parse_major(version)
+}
+
+/// Path of the java executable in a runtime directory (`JAVA_HOME`).
+fn executable_in(home: &Path) -> Option<PathBuf> {
+    [
+        home.join("bin"),
+        // macOS bundles
+        home.join("Contents").join("Home").join("bin"),
+    ]
+    .into_iter()
+    .map(|bin| bin.join(JAVA_EXECUTABLE))
+    .find(|java| java.is_file())
+}
+
+/// Absolute path of the executable `program` is started as, searching `PATH` for bare names
+/// like `java`.
+pub fn which(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 || program.is_absolute() {
+        let path = std::env::current_dir().ok()?.join(program);
+        return Some(path).filter(|path| path.is_file());
+    }
+
+    let mut name = program.as_os_str().to_os_string();
+    if program.extension().is_none() {
+        name.push(std::env::consts::EXE_SUFFIX);
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+/// Find installed runtimes in the usual locations of the platform, `JAVA_HOME`, `PATH` and in
+/// the subdirectories of `dirs` (e.g. runtimes managed by the launcher).
+///
+/// Runtimes which fail to report their version are skipped.
+pub fn discover<P: AsRef<Path>>(dirs: &[P]) -> Vec<JavaRuntime> {
+    let mut candidates = Vec::new();
+
+    let roots = dirs
+        .iter()
+        .map(|d| d.as_ref().to_path_buf())
+        .chain(SYSTEM_RUNTIME_DIRS.iter().map(PathBuf::from));
+    for root in roots {
+        if let Ok(entries) = std::fs::read_dir(&root) {
+            let mut homes: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+            homes.sort();
+            candidates.extend(homes.iter().filter_map(|home| executable_in(home)));
+        }
+    }
+
+    if let Some(home) = std::env::var_os("JAVA_HOME") {
+        candidates.extend(executable_in(Path::new(&home)));
+    }
+    if let Some(path) = std::env::var_os("PATH") {
+        candidates.extend(
+            std::env::split_paths(&path)
+                .map(|dir| dir.join(JAVA_EXECUTABLE))
+                .filter(|java| java.is_file()),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    let mut ret = Vec::new();
+    for candidate in candidates {
+        let canonical = std::fs::canonicalize(&candidate).unwrap_or_else(|_| candidate.clone());
+        if !seen.insert(canonical) {
+            continue;
+        }
+
+        let java = Java::new(&candidate);
+        match java.major_version() {
+            Ok(major) => ret.push(JavaRuntime { java, major }),
+            Err(e) => debug!("skipping {}: {}", candidate.display(), e),
+        }
+    }
+
+    ret
+}
+
+/// Pick the runtime for the required major version.
+///
+/// An exact match is preferred, otherwise the oldest newer runtime is used.
+pub(crate) fn select(runtimes: &[JavaRuntime], major: u32) -> Option<&JavaRuntime> {
+    runtimes.iter().find(|r| r.major == major).or_else(|| {
+        runtimes
+            .iter()
+            .filter(|r| r.major > major)
+            .min_by_key(|r| r.major)
+    })
+}
+
+/// Discover the installed runtimes and pick one for the required major version.
+///
+/// Fails with [`Error::NoCompatibleJava`] if none is installed.
+pub fn find<P: AsRef<Path>>(dirs: &[P], major: u32) -> Result<Java> {
+    let runtimes = discover(dirs);
+    let runtime = select(&runtimes, major).ok_or(Error::NoCompatibleJava(major))?;
+    debug!(
+        "using Java {} at {}",
+        runtime.major,
+        runtime.java.path().display()
+    );
+
+    Ok(runtime.java.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn select_runtime() {
+        assert_eq!(parse_major("1.8.0_292"), Some(8));
+        assert_eq!(parse_major("17.0.2"), Some(17));
+        assert_eq!(parse_major("21-ea"), Some(21));
+        assert_eq!(
+            parse_version_output(
+                "openjdk version \"17.0.2\" 2022-01-18\nOpenJDK Runtime Environment"
+            ),
+            Some(17)
+        );
+
+        let runtime = |major| JavaRuntime {
+            java: Java::new(&format!("/java/{}", major)),
+            major,
+        };
+        let runtimes = [runtime(8), runtime(21), runtime(17)];
+        assert_eq!(select(&runtimes, 8).unwrap().major, 8);
+        assert_eq!(select(&runtimes, 16).unwrap().major, 17);
+        assert!(select(&runtimes, 22).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn which_executable() {
+        assert_eq!(which(Path::new("sh")).unwrap().file_name().unwrap(), "sh");
+        assert_eq!(which(Path::new("/bin/sh")), Some(PathBuf::from("/bin/sh")));
+        assert_eq!(which(Path::new("polymc-no-such-program")), None);
+        assert_eq!(which(Path::new("./no/such/java")), None);
+    }
+
+    #[test]
+    fn no_compatible_java() {
+        let e = find::<&Path>(&[], 1000).unwrap_err();
+        assert!(matches!(e, Error::NoCompatibleJava(1000)));
+        // Frontends using the C API see it as a missing file
+        assert_eq!(e.as_c_error(), libc::ENOENT);
+    }
+}
@@ -0,0 +1,303 @@
+//! Archives of the whole launcher state, to move it to another machine with a single file.
+//!
+//! An archive is a zip holding directories of state, e.g. the config and the instances, each
+//! under its own name. The shared stores of libraries, assets and meta data are left out, the
+//! other machine downloads them again.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+
+use tracing::*;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::instance::is_session_file;
+use crate::{Error, Result};
+
+/// Directory of state stored in an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDir {
+    /// Name of the directory in the archive.
+    pub name: String,
+    pub path: PathBuf,
+    /// Depth of the game directories in it, whose logs and other session files are left out: 0
+    /// for a game directory, 1 for a directory of instances.
+    pub instance_depth: Option<usize>,
+    /// Files left out, relative to the directory.
+    pub exclude: Vec<PathBuf>,
+}
+
+impl StateDir {
+    pub fn new<S: Into<String>, P: Into<PathBuf>>(name: S, path: P) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            instance_depth: None,
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Directory of game directories at `depth`, see [`StateDir::instance_depth`].
+    pub fn instances<S: Into<String>, P: Into<PathBuf>>(name: S, path: P, depth: usize) -> Self {
+        Self {
+            instance_depth: Some(depth),
+            ..Self::new(name, path)
+        }
+    }
+
+    fn includes(&self, relative: &Path) -> bool {
+        if self.exclude.iter().any(|e| e == relative) {
+            return false;
+        }
+        let session_file = self
+            .instance_depth
+            .and_then(|depth| relative.components().nth(depth))
+            .map(|name| is_session_file(&name.as_os_str().to_string_lossy()))
+            .unwrap_or(false);
+        !session_file
+    }
+}
+
+/// Write the files of `dirs` as zip into `writer`, skipping directories which don't exist.
+///
+/// Returns the number of files written.
+pub fn export_state<W: Write + Seek>(dirs: &[StateDir], writer: W) -> Result<usize> {
+    let mut zip = ZipWriter::new(writer);
+    let mut count = 0;
+    for dir in dirs {
+        if !dir.path.is_dir() {
+            debug!("not exporting {}, it does not exist", dir.path.display());
+            continue;
+        }
+        count += add_dir(&mut zip, dir, Path::new(""))?;
+    }
+    zip.finish()?;
+    Ok(count)
+}
+
+fn add_dir<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    dir: &StateDir,
+    relative: &Path,
+) -> Result<usize> {
+    let mut count = 0;
+    let mut entries = std::fs::read_dir(dir.path.join(relative))?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let relative = relative.join(entry.file_name());
+        if !dir.includes(&relative) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            count += add_dir(zip, dir, &relative)?;
+            continue;
+        }
+        // Links to files are stored as the file, links to directories could loop
+        let metadata = match std::fs::metadata(entry.path()) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                warn!(
+                    "not exporting {}, it links to a directory",
+                    entry.path().display()
+                );
+                continue;
+            }
+            Err(e) if file_type.is_symlink() => {
+                warn!(
+                    "not exporting {}, its link is broken: {}",
+                    entry.path().display(),
+                    e
+                );
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut options = FileOptions::default();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(metadata.permissions().mode() & 0o777);
+        }
+        zip.start_file(archive_name(&dir.name, &relative), options)?;
+        std::io::copy(&mut File::open(entry.path())?, zip)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn archive_name(name: &str, relative: &Path) -> String {
+    let mut ret = name.to_string();
+    for part in relative.components() {
+        ret.push('/');
+        ret.push_str(&part.as_os_str().to_string_lossy());
+    }
+    ret
+}
+
+/// Extract an archive written by [`export_state`] into `dirs`.
+///
+/// Fails with [`Error::InvalidStateArchive`] for files outside of `dirs`, and without changing
+/// anything if a file exists already unless `overwrite` is set. Returns the number of files
+/// extracted.
+pub fn import_state<R: Read + Seek>(
+    reader: R,
+    dirs: &[StateDir],
+    overwrite: bool,
+) -> Result<usize> {
+    let mut zip = ZipArchive::new(reader)?;
+
+    // Check everything before extracting anything
+    let mut targets = Vec::new();
+    for i in 0..zip.len() {
+        let file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = target(file.name(), dirs)?;
+        if !overwrite && path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )
+            .into());
+        }
+        targets.push((i, path));
+    }
+
+    for (i, path) in &targets {
+        let mut file = zip.by_index(*i)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(path)?;
+        std::io::copy(&mut file, &mut out)?;
+        #[cfg(unix)]
+        if let Some(mode) = file.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            out.set_permissions(std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+    Ok(targets.len())
+}
+
+/// Path to extract the archive entry `name` to.
+fn target(name: &str, dirs: &[StateDir]) -> Result<PathBuf> {
+    let invalid = || Error::InvalidStateArchive(format!("unexpected file {}", name));
+    let (dir_name, relative) = name.split_once('/').ok_or_else(invalid)?;
+    let dir = dirs
+        .iter()
+        .find(|d| d.name == dir_name)
+        .ok_or_else(invalid)?;
+    let relative = Path::new(relative);
+    let safe = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if !safe || relative.as_os_str().is_empty() {
+        return Err(invalid());
+    }
+    Ok(dir.path.join(relative))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::io::Cursor;
+
+    #[test]
+    fn export_import() {
+        let dir = TempDir::new("state");
+        let from = dir.join("from");
+        for file in [
+            "config/config.json",
+            "config/accounts.json",
+            "instances/a/instance.json",
+            "instances/a/saves/world/level.dat",
+            "instances/a/logs/latest.log",
+        ] {
+            let path = from.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+        let dirs = |root: &Path| {
+            let mut config = StateDir::new("config", root.join("config"));
+            config.exclude.push("accounts.json".into());
+            vec![
+                config,
+                StateDir::instances("instances", root.join("instances"), 1),
+                StateDir::new("missing", root.join("missing")),
+            ]
+        };
+
+        let mut archive = Cursor::new(Vec::new());
+        assert_eq!(export_state(&dirs(&from), &mut archive).unwrap(), 3);
+
+        let to = dir.join("to");
+        archive.set_position(0);
+        assert_eq!(import_state(&mut archive, &dirs(&to), false).unwrap(), 3);
+        assert!(to.join("instances/a/saves/world/level.dat").is_file());
+        assert!(!to.join("instances/a/logs").exists());
+        assert!(!to.join("config/accounts.json").exists());
+
+        // Existing files are kept
+        archive.set_position(0);
+        assert!(import_state(&mut archive, &dirs(&to), false).is_err());
+        archive.set_position(0);
+        assert_eq!(import_state(&mut archive, &dirs(&to), true).unwrap(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("state-links");
+        let from = dir.join("from");
+        let other = dir.join("other");
+        std::fs::create_dir_all(other.join("saves")).unwrap();
+        std::fs::write(other.join("options.txt"), "options").unwrap();
+        std::fs::create_dir_all(&from).unwrap();
+        symlink(other.join("options.txt"), from.join("options.txt")).unwrap();
+        symlink(other.join("saves"), from.join("saves")).unwrap();
+        symlink(other.join("missing"), from.join("broken")).unwrap();
+        // The directory itself is followed
+        let linked = dir.join("linked");
+        symlink(&from, &linked).unwrap();
+
+        let mut archive = Cursor::new(Vec::new());
+        let dirs = [StateDir::new("game", &linked)];
+        assert_eq!(export_state(&dirs, &mut archive).unwrap(), 1);
+
+        let to = dir.join("to");
+        archive.set_position(0);
+        import_state(&mut archive, &[StateDir::new("game", &to)], false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(to.join("options.txt")).unwrap(),
+            "options"
+        );
+    }
+
+    #[test]
+    fn reject_outside() {
+        let dirs = [StateDir::new("config", "/config")];
+        assert_eq!(
+            target("config/a/b.json", &dirs).unwrap(),
+            Path::new("/config/a/b.json")
+        );
+        for name in [
+            "config/../etc/passwd",
+            "other/a.json",
+            "config//etc/passwd",
+            "a.json",
+        ] {
+            assert!(
+                matches!(target(name, &dirs), Err(Error::InvalidStateArchive(_))),
+                "{}",
+                name
+            );
+        }
+    }
+}
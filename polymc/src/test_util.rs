@@ -0,0 +1,76 @@
+//! Helpers shared by the unit tests.
+
+use std::ffi::OsStr;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::meta::manifest::Manifest;
+
+/// Path for the files of a test, removed when it is dropped.
+///
+/// Only the path is reserved, the directory is created by the test if it needs one.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Path named after `name`, unique to the test process and call so tests running in
+    /// parallel do not share it.
+    pub(crate) fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "polymc-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        remove(&path);
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<OsStr> for TempDir {
+    fn as_ref(&self) -> &OsStr {
+        self.0.as_os_str()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        remove(&self.0);
+    }
+}
+
+/// Manifest of the component `uid` in version 1.0, with the JSON members `fields` added.
+pub(crate) fn manifest(uid: &str, order: i64, fields: &str) -> Manifest {
+    let separator = if fields.is_empty() { "" } else { "," };
+    format!(
+        r#"{{"name": "{uid}", "uid": "{uid}", "version": "1.0", "order": {order},
+            "releaseTime": "2022-01-01T00:00:00+00:00", "type": "release",
+            "libraries": []{separator} {fields}
+        }}"#
+    )
+    .parse()
+    .unwrap()
+}
+
+fn remove(path: &Path) {
+    if path.is_dir() {
+        let _ = std::fs::remove_dir_all(path);
+    } else {
+        let _ = std::fs::remove_file(path);
+    }
+}
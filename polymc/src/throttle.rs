@@ -0,0 +1,158 @@
+//! Backing off from servers which rate limit the downloads.
+//!
+//! The meta server and the Mojang CDNs answer with `429 Too Many Requests` (or
+//! `503 Service Unavailable`) and a `Retry-After` header when a launcher downloads too fast.
+//! Frontends wait as told by [`Backoff::delay`] and give up with [`Error::Throttled`] once the
+//! server keeps throttling.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Error;
+
+/// Abbreviated month names of HTTP dates.
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// How often and how long to wait for a throttling server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    /// Retries of a request before giving up.
+    pub max_retries: u32,
+    /// Wait before the first retry if the server does not say, doubled for every retry.
+    pub initial_wait: Duration,
+    /// Longest wait for a single retry, servers asking for more are given up on.
+    pub max_wait: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_wait: Duration::from_secs(1),
+            max_wait: Duration::from_secs(120),
+        }
+    }
+}
+
+impl Backoff {
+    /// Time to wait before retry number `attempt`, counted from 0, if the server asked to wait
+    /// `retry_after`.
+    ///
+    /// Returns `None` once the request should not be retried anymore.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        let wait = retry_after.unwrap_or_else(|| {
+            self.initial_wait
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(self.max_wait)
+        });
+
+        Some(wait).filter(|wait| *wait <= self.max_wait)
+    }
+
+    /// Error for `url` after the server kept throttling, with the wait it asked for last.
+    pub fn give_up(&self, url: &str, retry_after: Option<Duration>) -> Error {
+        Error::Throttled {
+            url: url.to_string(),
+            retry_after: retry_after.map(|wait| wait.as_secs()),
+        }
+    }
+}
+
+/// Whether the HTTP `status` asks to retry later.
+pub fn is_throttled(status: u16, has_retry_after: bool) -> bool {
+    status == 429 || (status == 503 && has_retry_after)
+}
+
+/// Parse the value of a `Retry-After` header, in seconds or as an HTTP date relative to `now`.
+///
+/// Dates in the past mean retrying right away.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Parse an IMF-fixdate like `Sun, 06 Nov 1994 08:49:37 GMT`, the format servers have to send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let (_weekday, rest) = value.split_once(", ")?;
+    let mut parts = rest.split(' ');
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next() != Some("GMT") || year < 1970 || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Days from 1970-01-01 to the date, for years since 1970.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Count years from March, so the leap day is at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        // 1994-11-06 08:49:37
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            parse_retry_after("Sunday, 06-Nov-94 08:49:37 GMT", now),
+            None
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn backoff() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(0, None), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.delay(2, None), Some(Duration::from_secs(4)));
+        assert_eq!(
+            backoff.delay(0, Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
+        // Waiting longer than allowed or too often is sustained throttling
+        assert_eq!(backoff.delay(0, Some(Duration::from_secs(3600))), None);
+        assert_eq!(backoff.delay(5, None), None);
+
+        assert!(is_throttled(429, false));
+        assert!(is_throttled(503, true));
+        assert!(!is_throttled(503, false));
+    }
+}
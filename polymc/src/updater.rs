@@ -0,0 +1,260 @@
+//! Updating the binaries of a frontend from its releases.
+//!
+//! The frontend fetches the release description from [`DEFAULT_RELEASES_URL`] or its own url,
+//! which is in the format of the GitHub releases API, and downloads the asset selected by
+//! [`Release::asset_for`] together with its checksum and signature. [`verify_release`] checks
+//! the download before [`replace_binary`] swaps it in.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::meta::manifest::Sha256Sum;
+use crate::{Error, Result};
+
+/// Latest release of the project on GitHub.
+pub const DEFAULT_RELEASES_URL: &str =
+    "https://api.github.com/repos/korewaChino/libpolymc/releases/latest";
+
+/// Suffix of the asset holding the hex sha256 of another asset.
+pub const CHECKSUM_SUFFIX: &str = ".sha256";
+/// Suffix of the asset holding the ed25519 signature of another asset.
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Hex ed25519 key the releases are signed with, embedded by the release builds from the
+/// `PLMC_RELEASE_PUBLIC_KEY` environment variable.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("PLMC_RELEASE_PUBLIC_KEY");
+
+/// Release in the format of the GitHub releases API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+crate::meta::from_str_json!(Release);
+
+impl Release {
+    /// Version of the release, the tag without a leading `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Whether the release is newer than `current`, comparing dot separated numbers.
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        let parse = |v: &str| -> Vec<u64> {
+            v.trim_start_matches('v')
+                .split(&['.', '-', '+'][..])
+                .map_while(|n| n.parse().ok())
+                .collect()
+        };
+        parse(self.version()) > parse(current)
+    }
+
+    /// Asset of `binary` for `target` (see [`current_target`]), named `binary-target` with an
+    /// optional `.exe` extension.
+    pub fn asset_for(&self, binary: &str, target: &str) -> Option<&ReleaseAsset> {
+        let name = format!("{}-{}", binary, target);
+        self.assets
+            .iter()
+            .find(|a| a.name == name || a.name.strip_suffix(".exe") == Some(&name))
+    }
+
+    /// Asset holding the checksum of `asset`.
+    pub fn checksum_for(&self, asset: &ReleaseAsset) -> Option<&ReleaseAsset> {
+        self.find(&format!("{}{}", asset.name, CHECKSUM_SUFFIX))
+    }
+
+    /// Asset holding the signature of `asset`.
+    pub fn signature_for(&self, asset: &ReleaseAsset) -> Option<&ReleaseAsset> {
+        self.find(&format!("{}{}", asset.name, SIGNATURE_SUFFIX))
+    }
+
+    fn find(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Target the binaries of the running platform are released for, e.g. `x86_64-linux`.
+pub fn current_target() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Parse a checksum asset, the hex sha256 optionally followed by the file name like the
+/// output of `sha256sum`.
+pub fn parse_checksum(data: &[u8]) -> Result<Sha256Sum> {
+    let data = std::str::from_utf8(data)?;
+    data.split_whitespace()
+        .next()
+        .ok_or_else(|| Error::UpdateInvalid("empty checksum".to_string()))?
+        .parse()
+}
+
+/// Verify a downloaded binary against its checksum and its ed25519 `signature` by `public_key`.
+///
+/// Fails with [`Error::UpdateInvalid`] if the binary does not match.
+pub fn verify_release(
+    data: &[u8],
+    checksum: &Sha256Sum,
+    public_key: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    verify_checksum(data, checksum)?;
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(data, signature)
+        .map_err(|_| Error::UpdateInvalid("the signature does not match".to_string()))
+}
+
+/// Verify a downloaded binary against its checksum only, for releases the user chose to
+/// install unsigned.
+///
+/// Fails with [`Error::UpdateInvalid`] if the binary does not match.
+pub fn verify_checksum(data: &[u8], checksum: &Sha256Sum) -> Result<()> {
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    if digest.as_ref() != checksum.as_ref() {
+        return Err(Error::UpdateInvalid(
+            "the checksum does not match".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Replace the binary at `path` with `data`, keeping its permissions.
+///
+/// The new binary is written next to the old one and renamed over it, so `path` is never left
+/// half written. Windows does not allow replacing a running binary, there the old one is moved
+/// aside to `path.old` first and moved back if the new one can't be moved in place.
+pub fn replace_binary<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    let new = sibling(path, "new");
+    let result = write_binary(&new, path, data).and_then(|_| {
+        if cfg!(windows) {
+            swap_binary(&new, path)
+        } else {
+            Ok(std::fs::rename(&new, path)?)
+        }
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&new);
+    }
+    result
+}
+
+/// Write `data` to `new` with the permissions of the binary at `path`.
+fn write_binary(new: &Path, path: &Path, data: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(new)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::set_permissions(new, std::fs::metadata(path)?.permissions())?;
+    Ok(())
+}
+
+/// Move `new` to `path` through `path.old`, restoring the old binary if that fails.
+fn swap_binary(new: &Path, path: &Path) -> Result<()> {
+    let old = sibling(path, "old");
+    // Left over from the last update, it isn't running anymore
+    let _ = std::fs::remove_file(&old);
+    std::fs::rename(path, &old)?;
+    if let Err(e) = std::fs::rename(new, path) {
+        std::fs::rename(&old, path)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// `path` with `suffix` appended to the file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn self_update() {
+        let release: Release = r#"{"tag_name": "v0.2.0", "assets": [
+            {"name": "plmc-x86_64-linux", "browser_download_url": "https://example.com/plmc"},
+            {"name": "plmc-x86_64-linux.sha256", "browser_download_url": "https://example.com/sha"},
+            {"name": "plmc-x86_64-windows.exe", "browser_download_url": "https://example.com/exe"}
+        ]}"#
+        .parse()
+        .unwrap();
+        assert!(release.is_newer_than("0.1.0"));
+        assert!(release.is_newer_than("0.1.9"));
+        assert!(!release.is_newer_than("0.2.0"));
+        assert!(!release.is_newer_than("0.10.0"));
+
+        let asset = release.asset_for("plmc", "x86_64-linux").unwrap();
+        assert_eq!(
+            release.checksum_for(asset).unwrap().name,
+            "plmc-x86_64-linux.sha256"
+        );
+        assert!(release.signature_for(asset).is_none());
+        assert!(release.asset_for("plmc", "x86_64-windows").is_some());
+        assert!(release.asset_for("plmc", "aarch64-macos").is_none());
+
+        let data = b"new binary";
+        let checksum = parse_checksum(
+            format!(
+                "{}  plmc-x86_64-linux\n",
+                hex::encode(ring::digest::digest(&ring::digest::SHA256, data).as_ref())
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = key.public_key().as_ref();
+        let signature = key.sign(data);
+        verify_release(data, &checksum, public_key, signature.as_ref()).unwrap();
+        verify_checksum(data, &checksum).unwrap();
+        assert!(matches!(
+            verify_checksum(b"tampered", &checksum),
+            Err(Error::UpdateInvalid(_))
+        ));
+        assert!(matches!(
+            verify_release(data, &checksum, public_key, key.sign(b"other").as_ref()),
+            Err(Error::UpdateInvalid(_))
+        ));
+        assert!(matches!(
+            verify_release(data, &checksum, &[0; 32], signature.as_ref()),
+            Err(Error::UpdateInvalid(_))
+        ));
+
+        let dir = TempDir::new("update");
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("plmc");
+        std::fs::write(&binary, b"old binary").unwrap();
+        replace_binary(&binary, data).unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), data);
+        assert!(!dir.join("plmc.new").exists());
+
+        swap_binary(&dir.join("plmc.new"), &binary).unwrap_err();
+        assert_eq!(std::fs::read(&binary).unwrap(), data);
+        std::fs::write(dir.join("plmc.new"), b"newer binary").unwrap();
+        swap_binary(&dir.join("plmc.new"), &binary).unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), b"newer binary");
+        assert_eq!(std::fs::read(dir.join("plmc.old")).unwrap(), data);
+    }
+}
@@ -0,0 +1,144 @@
+//! Detection of games hanging before their window opens.
+//!
+//! The library does not read the output of the game itself, the frontend passes everything it
+//! reads to [`StartupWatchdog::observe`] and waits with
+//! [`crate::java_wrapper::RunningInstance::wait_for_startup`].
+
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// Time a game gets to open its window by default, modded instances take a while.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Lines logged by the game when it creates its window, by LWJGL 3 and LWJGL 2 versions.
+pub const DEFAULT_STARTUP_MARKERS: &[&str] = &["Backend library: LWJGL", "LWJGL Version: "];
+
+/// Bytes of stderr kept for [`Error::LaunchStalled`].
+const STDERR_TAIL_LEN: usize = 16 * 1024;
+
+/// Watches the output of a starting game for a sign that it is up.
+#[derive(Debug, Clone)]
+pub struct StartupWatchdog {
+    /// Time to wait for one of the markers.
+    pub timeout: Duration,
+    /// Text in the output showing the game started, any line on stdout if empty.
+    pub markers: Vec<String>,
+    /// Kill the game once it stalled.
+    pub kill: bool,
+    since: Instant,
+    started: bool,
+    stderr: String,
+}
+
+impl StartupWatchdog {
+    /// Watchdog waiting `timeout` for one of the [`DEFAULT_STARTUP_MARKERS`].
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            markers: DEFAULT_STARTUP_MARKERS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            kill: false,
+            since: Instant::now(),
+            started: false,
+            stderr: String::new(),
+        }
+    }
+
+    /// Restart the timeout, e.g. for a restarted game.
+    pub fn reset(&mut self) {
+        self.since = Instant::now();
+        self.started = false;
+        self.stderr.clear();
+    }
+
+    /// Look at output of the game, `stderr` if it was written to stderr.
+    ///
+    /// `text` may be any chunk of the output, markers split across chunks are missed.
+    pub fn observe(&mut self, text: &str, stderr: bool) {
+        if self.started {
+            return;
+        }
+
+        if stderr {
+            self.stderr.push_str(text);
+            if self.stderr.len() > STDERR_TAIL_LEN {
+                let mut start = self.stderr.len() - STDERR_TAIL_LEN;
+                while !self.stderr.is_char_boundary(start) {
+                    start += 1;
+                }
+                self.stderr.drain(..start);
+            }
+        }
+
+        self.started = if self.markers.is_empty() {
+            !stderr && !text.trim().is_empty()
+        } else {
+            self.markers.iter().any(|m| text.contains(m.as_str()))
+        };
+    }
+
+    /// Whether the game showed it started.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    /// Fails with [`Error::LaunchStalled`] if the game did not start within the timeout at `now`.
+    pub fn check(&self, now: Instant) -> Result<()> {
+        let waited = now.saturating_duration_since(self.since);
+        if self.started || waited < self.timeout {
+            return Ok(());
+        }
+
+        Err(Error::LaunchStalled {
+            waited: waited.as_secs(),
+            stderr: self.stderr.clone(),
+        })
+    }
+}
+
+impl Default for StartupWatchdog {
+    fn default() -> Self {
+        Self::new(DEFAULT_STARTUP_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn startup_watchdog() {
+        let mut watchdog = StartupWatchdog::new(Duration::from_secs(10));
+        let later = Instant::now() + Duration::from_secs(11);
+
+        watchdog.observe("[main/INFO]: Setting user: Player\n", false);
+        watchdog.observe("Exception in thread \"main\" UnsatisfiedLinkError\n", true);
+        assert!(!watchdog.started());
+        watchdog.check(Instant::now()).unwrap();
+        match watchdog.check(later) {
+            Err(Error::LaunchStalled { waited, stderr }) => {
+                assert!(waited >= 10);
+                assert!(stderr.contains("UnsatisfiedLinkError"));
+            }
+            other => panic!("not stalled: {:?}", other),
+        }
+
+        watchdog.observe(
+            "[Render thread/INFO]: Backend library: LWJGL version 3.2.2\n",
+            false,
+        );
+        assert!(watchdog.started());
+        watchdog.check(later).unwrap();
+
+        // Any line on stdout without markers
+        watchdog.reset();
+        watchdog.markers.clear();
+        watchdog.observe("warning\n", true);
+        assert!(!watchdog.started());
+        watchdog.observe("[main/INFO]: Setting user: Player\n", false);
+        assert!(watchdog.started());
+    }
+}